@@ -0,0 +1,115 @@
+//! Benchmarks of the fitting algorithm and the serializers, built from the `data` scenarios.
+//!
+//! Run with `cargo bench --features bench-utils`; set `FLUENT_DATA_BENCH_CI` for reduced sizes.
+
+use criterion::{
+    criterion_group, criterion_main, BatchSize, Bencher, BenchmarkId, Criterion, Throughput,
+};
+use fluent_data::{
+    data::{self, FitScenario, Sizes},
+    serializer::{
+        ColumnarJsonSerializer, EnvelopeSerializer, JsonSerializer, ModelSerializer,
+        MsgpackSerializer,
+    },
+    space,
+};
+
+const SEED: u64 = 42;
+
+type Serializer = dyn ModelSerializer<Vec<f64>>;
+
+/// Maximum number of candidates of the indexed neighborhood search.
+const BUDGET: usize = 64;
+
+/// Builds a scenario with the Euclidean distance.
+fn new_scenario(balls: usize, dim: usize, points: usize) -> FitScenario {
+    FitScenario::new(
+        space::euclid_dist,
+        space::real_combine,
+        balls,
+        dim,
+        points,
+        SEED,
+    )
+}
+
+/// Fits the points of the scenario into a fresh model at each iteration, so that weights do not grow across iterations.
+fn bench_fit(b: &mut Bencher, scenario: &FitScenario) {
+    b.iter_batched(
+        || scenario.new_model(),
+        |mut model| scenario.fit(&mut model),
+        BatchSize::LargeInput,
+    )
+}
+
+/// Fit throughput vs the number of balls of the model.
+fn fit_balls(c: &mut Criterion) {
+    let sizes = Sizes::from_env();
+    let mut group = c.benchmark_group("fit_balls");
+    group.throughput(Throughput::Elements(sizes.points as u64));
+    for &balls in sizes.ball_counts.iter() {
+        let scenario = new_scenario(balls, 2, sizes.points);
+        group.bench_function(BenchmarkId::from_parameter(balls), |b| {
+            bench_fit(b, &scenario)
+        });
+    }
+    group.finish();
+}
+
+/// Fit throughput vs the dimension of the points.
+fn fit_dims(c: &mut Criterion) {
+    let sizes = Sizes::from_env();
+    let mut group = c.benchmark_group("fit_dims");
+    group.throughput(Throughput::Elements(sizes.points as u64));
+    for &dim in sizes.dims.iter() {
+        let scenario = new_scenario(sizes.sweep_balls, dim, sizes.points);
+        group.bench_function(BenchmarkId::from_parameter(dim), |b| {
+            bench_fit(b, &scenario)
+        });
+    }
+    group.finish();
+}
+
+/// Cost of an emission for each serializer.
+fn serializers(c: &mut Criterion) {
+    let sizes = Sizes::from_env();
+    let model = data::model_with_balls(&data::centers(sizes.sweep_balls, 2, SEED));
+    let serializers: Vec<(&str, Box<Serializer>)> = vec![
+        ("json", Box::new(JsonSerializer)),
+        ("columnar", Box::new(ColumnarJsonSerializer)),
+        ("msgpack", Box::new(MsgpackSerializer)),
+        ("envelope", Box::new(EnvelopeSerializer::new())),
+    ];
+    let mut group = c.benchmark_group("serializer");
+    for (name, serializer) in serializers.iter() {
+        let mut output = String::new();
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                output.clear();
+                serializer.serialize(&model, &mut output).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Fit throughput with a brute-force neighborhood search vs a search restricted by the graph of balls.
+fn neighborhood(c: &mut Criterion) {
+    let sizes = Sizes::from_env();
+    let mut group = c.benchmark_group("neighborhood");
+    group.throughput(Throughput::Elements(sizes.points as u64));
+    for &balls in sizes.ball_counts.iter() {
+        let brute_force = new_scenario(balls, 2, sizes.points);
+        group.bench_function(BenchmarkId::new("brute_force", balls), |b| {
+            bench_fit(b, &brute_force)
+        });
+        let indexed = new_scenario(balls, 2, sizes.points).with_budget(BUDGET);
+        group.bench_function(BenchmarkId::new("indexed", balls), |b| {
+            bench_fit(b, &indexed)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fit_balls, fit_dims, serializers, neighborhood);
+criterion_main!(benches);
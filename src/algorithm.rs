@@ -1,15 +1,114 @@
 //! The [Algo] struct implements the algorithm that fits a set of balls model from data point streams.
+//!
+//! The algorithm parameters can be tuned with an [AlgoConfig];
+//! the [ab_run] function fits the same stream with several configurations side by side.
 
-use std::{marker::PhantomData, ops::DerefMut};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{hash_map::DefaultHasher, HashMap},
+    error::Error,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    mem,
+    ops::DerefMut,
+    panic::{catch_unwind, AssertUnwindSafe},
+    rc::Rc,
+    str::FromStr,
+};
 
-use crate::model::{Ball, BallNode, GetNeighbors, Model};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    model::{log_add, Ball, BallNode, GetNeighbors, Model, Normalization, OutOfRange},
+    space::RealPoint,
+    streamer::{panic_reason, RunReport, Streamer},
+};
 
 const EXTRA_THRESHOLD: f64 = 25.;
-const INTRA_THRESHOLD: f64 = 16.;
+const INTRA_THRESHOLD: f64 = 4.;
 const MERGE_THRESHOLD: f64 = 1.;
 const DECAY_FACTOR: f64 = 0.95;
 const DECAY_THRESHOLD: f64 = 1E-2;
 const MAX_NEIGHBORS: usize = 2;
+const AUTO_MAX_CLUSTERS: usize = 8;
+const AUTO_KMEANS_ROUNDS: usize = 10;
+const AUTO_PERCENTILE: f64 = 0.95;
+
+/// Defines how the radius of a ball is computed from the points it includes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RadiusStrategy {
+    /// The radius is the root mean square of the distances from the included points to the ball center.
+    RootMeanSquare,
+    /// The radius is `k` times the standard deviation of the distances from the included points to the ball center.
+    /// The root mean square is used until the ball includes enough points to estimate the deviation.
+    StdDev(f64),
+}
+
+/// Tunable parameters of the algorithm.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlgoConfig {
+    /// Name used to tag the models fitted with this configuration.
+    pub name: String,
+    /// Factor applied to the weight of balls which do not include the incoming point.
+    pub decay: f64,
+    /// A point belongs to a ball if its distance to the center is less than `threshold` times the radius,
+    /// otherwise a new ball is created.
+    pub threshold: f64,
+    /// Balls which weight decays below this floor are removed.
+    /// The floor cannot be lower than the smallest positive normal number, so weights never become subnormal.
+    pub floor: f64,
+    /// Seed of the random number generator used by stochastic components;
+    /// when `None` the generator is seeded from entropy.
+    pub seed: Option<u64>,
+}
+
+impl Default for AlgoConfig {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            decay: DECAY_FACTOR,
+            threshold: INTRA_THRESHOLD,
+            floor: DECAY_THRESHOLD,
+            seed: None,
+        }
+    }
+}
+
+/// Parses a comma separated list of `key=value` pairs, e.g. `name=candidate,decay=0.9,threshold=3,floor=0.001,seed=42`.
+/// Missing keys take their default value.
+impl FromStr for AlgoConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = AlgoConfig::default();
+        for pair in s.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, found {}", pair))?;
+            let parse = |v: &str| {
+                v.parse::<f64>()
+                    .map_err(|e| format!("invalid value for {}: {}", key, e))
+            };
+            match key.trim() {
+                "name" => config.name = String::from(value.trim()),
+                "decay" => config.decay = parse(value.trim())?,
+                "threshold" => config.threshold = parse(value.trim())?,
+                "floor" => config.floor = parse(value.trim())?,
+                "seed" => {
+                    let seed = value
+                        .trim()
+                        .parse::<u64>()
+                        .map_err(|e| format!("invalid value for {}: {}", key, e))?;
+                    config.seed = Some(seed);
+                }
+                _ => return Err(format!("unknown key {}", key)),
+            }
+        }
+        Ok(config)
+    }
+}
 
 /// Fits incoming points to a set of balls model.
 ///
@@ -40,9 +139,239 @@ const MAX_NEIGHBORS: usize = 2;
 pub struct Algo<Point: PartialEq + 'static> {
     dist: Box<dyn Fn(&Point, &Point) -> f64>,
     combine: Box<dyn Fn(&Point, f64, &Point, f64) -> Point>,
+    radius_strategy: RadiusStrategy,
+    config: AlgoConfig,
+    window: Option<Window<Point>>,
+    budget: Option<usize>,
+    max_step: Option<f64>,
+    min_radius: Option<f64>,
+    auto_threshold: Option<AutoThreshold<Point>>,
+    panic_guard: Option<PanicGuard<Point>>,
+    normalizer: Option<Normalizer<Point>>,
+    velocity: Option<VelocityTracker<Point>>,
+    drift: Option<DriftTracker<Point>>,
+    /// Running totals of merged and created balls.
+    merges: Cell<u64>,
+    splits: Cell<u64>,
+    valid_region: Option<RegionCheck<Point>>,
+    rejected: Cell<u64>,
+    /// Index of the ball updated or created by the last fitted point.
+    last_updated: Cell<Option<usize>>,
+    samples: Option<BallSamples<Point>>,
+    center_refresh: Option<u64>,
+    sketch: Option<RegionSketch<Point>>,
     phantom: PhantomData<Point>,
 }
 
+/// The state accumulated by an [Algo] while fitting points, which is not part of the model,
+/// see [Algo::save_state].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AlgoState<Point> {
+    /// See [Algo::merge_count].
+    pub merges: u64,
+    /// See [Algo::split_count].
+    pub splits: u64,
+    /// The points buffered during the warm-up of [Algo::with_auto_threshold].
+    pub warmup: Vec<Point>,
+    /// See [Algo::selected_threshold].
+    pub selected_threshold: Option<f64>,
+    /// The centers and weights of the balls when the drift was last measured, when [Algo::with_drift] is enabled.
+    pub drift_snapshot: Vec<(Point, f64)>,
+    /// See [Algo::drift_since_last].
+    pub drift: f64,
+    /// The points buffered during the warm-up of [Algo::with_normalization].
+    #[serde(default)]
+    pub normalization_warmup: Vec<Point>,
+    /// The reservoirs of [Algo::with_global_sample_budget]: for each ball id, in ascending order,
+    /// the number of points offered to the ball and its sample.
+    #[serde(default)]
+    pub samples: Vec<(u64, u64, Vec<Point>)>,
+}
+
+/// How a point would be fitted, see [Algo::preview_fit].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FitPreview {
+    /// The ball which would include the point or a new ball.
+    pub target: FitTarget,
+    /// The distance the center of the including ball would move by, zero when a new ball would be created.
+    pub center_shift: f64,
+    /// The radius of the including ball once updated, or of the new ball.
+    pub radius: f64,
+}
+
+/// The ball a point would be fitted to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FitTarget {
+    /// The ball at the given index in [Model::iter_balls] order.
+    Ball(usize),
+    /// A new ball would be created.
+    NewBall,
+}
+
+/// Warm-up state of the automatic threshold selection, see [Algo::with_auto_threshold].
+struct AutoThreshold<Point> {
+    warmup: usize,
+    buffer: RefCell<Vec<Point>>,
+    selected: Cell<Option<f64>>,
+}
+
+/// A clustering of the warm-up points.
+struct Clustering<Point> {
+    centroids: Vec<Point>,
+    /// The cluster of each point.
+    clusters: Vec<usize>,
+    /// Sum of the squared distances from the points to their centroid.
+    cost: f64,
+}
+
+/// Warm-up state of the normalization, see [Algo::with_normalization].
+struct Normalizer<Point: PartialEq> {
+    buffer: RefCell<Vec<Point>>,
+    normalize: NormalizeFn<Point>,
+}
+
+/// Normalizes a point before it is fitted, or buffers it until the normalization is learnt.
+/// Returns the points to fit at the end of the warm-up, like [Algo::warm_up].
+type NormalizeFn<Point> =
+    Box<dyn Fn(&mut Model<Point>, &mut Vec<Point>, Point) -> Result<Option<Vec<Point>>, Point>>;
+
+/// Updates the velocity of a ball given its previous center, or initializes it for a new ball,
+/// see [Algo::with_velocity].
+type VelocityTracker<Point> = Box<dyn Fn(&mut Ball<Point>, Option<&Point>)>;
+
+/// Tells whether a point lies in the valid region, see [Algo::with_valid_region].
+type RegionCheck<Point> = Box<dyn Fn(&Point) -> bool>;
+
+/// The model as it was when the drift was last measured, see [Algo::with_drift].
+struct DriftTracker<Point> {
+    clone: fn(&Point) -> Point,
+    /// The centers and weights of the balls.
+    snapshot: RefCell<Vec<(Point, f64)>>,
+    /// The last measured drift.
+    score: Cell<f64>,
+}
+
+/// Computes the grid cell of a point, see [Algo::with_region_sketch].
+type CellKey<Point> = Box<dyn Fn(&Point) -> Vec<i64>>;
+
+/// Count-min sketch of the fitted points by grid cell, see [Algo::with_region_sketch].
+struct RegionSketch<Point> {
+    cell: CellKey<Point>,
+    /// One row of counters per hash function.
+    counts: RefCell<Vec<Vec<u64>>>,
+}
+
+impl<Point> RegionSketch<Point> {
+    /// The counter of the cell in each row.
+    fn slots<'a>(&'a self, cell: &'a [i64]) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let width = self.counts.borrow()[0].len();
+        (0..self.counts.borrow().len()).map(move |row| {
+            let mut hasher = DefaultHasher::new();
+            (row, cell).hash(&mut hasher);
+            (row, (hasher.finish() % width as u64) as usize)
+        })
+    }
+}
+
+/// Per-ball reservoir samples of the fitted points, see [Algo::with_global_sample_budget].
+struct BallSamples<Point> {
+    clone: fn(&Point) -> Point,
+    /// Maximum number of points retained across all balls.
+    total: usize,
+    /// The number of points offered to each ball and its sample, by ball id.
+    reservoirs: RefCell<HashMap<u64, (u64, Vec<Point>)>>,
+    capacities: RefCell<Option<Capacities>>,
+    /// Seeded from the algorithm configuration on the first offer.
+    rng: RefCell<Option<StdRng>>,
+}
+
+/// The capacity of each reservoir, by ball id, computed for the given [Model::ball_set_version].
+type Capacities = ((u64, usize), HashMap<u64, usize>);
+
+/// Shares `total` sample points between the balls in proportion to their weight, by ball id,
+/// or evenly when no ball has a weight yet.
+/// Shares are rounded down, then the remainder goes to the balls with the largest fractional shares.
+fn sample_capacities<Point: PartialEq + 'static>(
+    model: &Model<Point>,
+    total: usize,
+) -> HashMap<u64, usize> {
+    let total_weight: f64 = model.iter_balls().map(|b| b.weight).sum();
+    let count = model.iter_balls().count() as f64;
+    let shares: Vec<(u64, f64)> = model
+        .iter_balls()
+        .map(|b| {
+            let share = if total_weight > 0. {
+                total as f64 * b.weight / total_weight
+            } else {
+                total as f64 / count
+            };
+            (b.id, share)
+        })
+        .collect();
+    let mut capacities: HashMap<u64, usize> = shares
+        .iter()
+        .map(|(id, share)| (*id, share.floor() as usize))
+        .collect();
+    let remainder = total.saturating_sub(capacities.values().sum());
+    let mut fractions = shares;
+    fractions.sort_by(|(_, s1), (_, s2)| s2.fract().total_cmp(&s1.fract()));
+    for (id, _) in fractions.into_iter().take(remainder) {
+        *capacities.get_mut(&id).unwrap() += 1;
+    }
+    capacities
+}
+
+/// Rollback state of the panic guard, see [Algo::with_panic_guard].
+struct PanicGuard<Point: PartialEq> {
+    snapshot: fn(&Ball<Point>) -> Ball<Point>,
+    /// The user function being called, if any.
+    calling: Rc<Cell<Option<UserFn>>>,
+    /// The ball changed by the current point, as it was before.
+    touched: RefCell<Option<(BallNode<Point>, Ball<Point>)>>,
+    /// Whether the current point created a ball.
+    created: Cell<bool>,
+}
+
+/// Errors raised when fitting a point, see [Algo::try_fit].
+#[derive(Clone, Debug, PartialEq)]
+pub enum FitError {
+    /// A user function panicked, the point was skipped.
+    UserFnPanicked {
+        which: UserFn,
+        /// The panic message, if it is a string.
+        payload_message: String,
+    },
+    /// The point is outside of the valid region, it was not fitted, see [Algo::with_valid_region].
+    OutOfRegion,
+}
+
+impl Display for FitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FitError::UserFnPanicked {
+                which,
+                payload_message,
+            } => write!(f, "{:?} function panicked: {}", which, payload_message),
+            FitError::OutOfRegion => write!(f, "point outside of the valid region"),
+        }
+    }
+}
+
+impl Error for FitError {}
+
+/// The user functions of an [Algo].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UserFn {
+    Dist,
+    Combine,
+}
+
+/// Sliding window parameters, see [Algo::sliding_window].
+struct Window<Point> {
+    size: usize,
+    clone: fn(&Point) -> Point,
+}
+
 impl<Point: PartialEq + 'static> Algo<Point> {
     /// Creates a new algorithm for the given distance and combination functions.
     pub fn new<Dist, Combine>(dist: Dist, combine: Combine) -> Self
@@ -53,19 +382,786 @@ impl<Point: PartialEq + 'static> Algo<Point> {
         Self {
             dist: Box::new(dist),
             combine: Box::new(combine),
+            radius_strategy: RadiusStrategy::RootMeanSquare,
+            config: AlgoConfig::default(),
+            window: None,
+            budget: None,
+            max_step: None,
+            min_radius: None,
+            auto_threshold: None,
+            panic_guard: None,
+            normalizer: None,
+            velocity: None,
+            drift: None,
+            merges: Cell::new(0),
+            splits: Cell::new(0),
+            valid_region: None,
+            rejected: Cell::new(0),
+            last_updated: Cell::new(None),
+            samples: None,
+            center_refresh: None,
+            sketch: None,
             phantom: PhantomData,
         }
     }
 
+    /// Changes the algorithm parameters.
+    /// ```
+    /// use fluent_data::{Algo, algorithm::AlgoConfig, space};
+    ///
+    /// let config = "decay=0.9,threshold=3".parse::<AlgoConfig>().unwrap();
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+    /// ```
+    pub fn with_config(mut self, config: AlgoConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// The algorithm parameters.
+    pub fn config(&self) -> &AlgoConfig {
+        &self.config
+    }
+
+    /// Builds an algorithm for other distance and combination functions with the same parameters,
+    /// radius strategy, budget, maximum step, minimum radius and counters, e.g. to fit a model rebuilt by [Model::rebuild_with].
+    /// The samples of [Algo::with_global_sample_budget] are kept as well.
+    /// The other options that hold functions or points, like [Algo::with_panic_guard] or [Algo::with_auto_threshold],
+    /// are not carried over and must be enabled again.
+    /// ```
+    /// use fluent_data::{Algo, algorithm::AlgoConfig, space};
+    ///
+    /// let config = "decay=0.9".parse::<AlgoConfig>().unwrap();
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+    /// let scaled = algo.rebuild_with(|p1: &Vec<f64>, p2: &Vec<f64>| 4. * space::euclid_dist(p1, p2), space::real_combine);
+    /// assert_eq!(0.9, scaled.config().decay);
+    /// ```
+    pub fn rebuild_with<Dist, Combine>(&self, dist: Dist, combine: Combine) -> Self
+    where
+        Dist: Fn(&Point, &Point) -> f64 + 'static,
+        Combine: Fn(&Point, f64, &Point, f64) -> Point + 'static,
+    {
+        let mut algo = Self::new(dist, combine)
+            .with_config(self.config.clone())
+            .with_radius_strategy(self.radius_strategy);
+        algo.budget = self.budget;
+        algo.max_step = self.max_step;
+        algo.min_radius = self.min_radius;
+        algo.merges.set(self.merges.get());
+        algo.splits.set(self.splits.get());
+        algo.samples = self.samples.as_ref().map(|samples| BallSamples {
+            clone: samples.clone,
+            total: samples.total,
+            reservoirs: RefCell::new(
+                samples
+                    .reservoirs
+                    .borrow()
+                    .iter()
+                    .map(|(id, (seen, points))| {
+                        (*id, (*seen, points.iter().map(samples.clone).collect()))
+                    })
+                    .collect(),
+            ),
+            capacities: RefCell::new(None),
+            rng: RefCell::new(samples.rng.borrow().clone()),
+        });
+        algo
+    }
+
+    /// Bounds the cost of fitting a point when the model is large:
+    /// the search for the balls the point belongs to evaluates at most `max_candidates` balls.
+    ///
+    /// When the model has more balls than the budget, candidates are the ball found for the previous point,
+    /// its neighbors in the model graph, then balls taken in turn, thus the fit trades exactness for a bounded latency.
+    /// The number of truncated searches is given by [Model::truncated_searches].
+    /// ```
+    /// use fluent_data::{Algo, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_budget(64);
+    /// ```
+    pub fn with_budget(mut self, max_candidates: usize) -> Self {
+        assert!(
+            max_candidates > 0,
+            "the budget must allow at least one candidate"
+        );
+        self.budget = Some(max_candidates);
+        self
+    }
+
+    /// Bounds the distance a center moves when a point is included in its ball, to damp jitter on noisy streams.
+    ///
+    /// Without bound, the center moves towards the point by `1 / (w + 1)` of their distance where `w` is the ball weight,
+    /// thus the bound mostly applies to light balls and distant points.
+    /// The bounded center is computed by the combine function, as the weighted center of the former center
+    /// and the unbounded one. Centers of merged balls are not bounded.
+    ///
+    /// The step is the square root of the distance function, which is the square of a distance as for all the algorithm,
+    /// see [Algo]; thus `max_step` is in the units of the points, e.g. meters for [crate::space::haversine_dist].
+    /// With a distance function that is not squared, e.g. [crate::space::manhattan_dist],
+    /// the bound applies to the square root of the distance.
+    /// ```
+    /// use fluent_data::{Algo, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_max_center_step(0.5);
+    /// ```
+    pub fn with_max_center_step(mut self, max_step: f64) -> Self {
+        assert!(max_step > 0., "the center step bound must be positive");
+        self.max_step = Some(max_step);
+        self
+    }
+
+    /// Keeps the radius of every ball at least `min_radius`.
+    ///
+    /// A ball that included many identical points has a vanishing radius, thus it would not include any other point.
+    /// The floor keeps a capture region around such balls.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_min_radius(0.5);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// algo.fit(&mut model, vec![1.]);
+    /// algo.fit(&mut model, vec![1.1]);
+    /// assert_eq!(0.5, model.iter_balls().next().unwrap().radius());
+    /// ```
+    pub fn with_min_radius(mut self, min_radius: f64) -> Self {
+        assert!(min_radius > 0., "the minimum radius must be positive");
+        self.min_radius = Some(min_radius);
+        self
+    }
+
+    /// Raises the square of a radius to the floor set by [Algo::with_min_radius].
+    fn floor_radius(&self, radius: f64) -> f64 {
+        match self.min_radius {
+            Some(min_radius) => radius.max(min_radius * min_radius),
+            None => radius,
+        }
+    }
+
+    /// Selects the threshold from the first `warmup_points` points instead of [AlgoConfig::threshold].
+    ///
+    /// Warm-up points are buffered, not fitted, until the buffer is full. The buffer is then clustered
+    /// by k-means, with the number of clusters chosen where adding a cluster no longer halves the cost,
+    /// and the threshold is set to the 95th percentile of the distances from the points to their centroid,
+    /// divided by the radius of their cluster. The threshold is then locked in, see [Algo::selected_threshold],
+    /// and the buffered points are fitted.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_auto_threshold(100);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for i in 0..100 {
+    ///     assert!(algo.selected_threshold().is_none());
+    ///     algo.fit(&mut model, vec![(i % 10) as f64 + 100. * (i % 2) as f64]);
+    /// }
+    /// assert!(algo.selected_threshold().is_some());
+    /// ```
+    pub fn with_auto_threshold(mut self, warmup_points: usize) -> Self {
+        assert!(warmup_points > 1, "the warm-up needs at least two points");
+        self.auto_threshold = Some(AutoThreshold {
+            warmup: warmup_points,
+            buffer: RefCell::new(Vec::with_capacity(warmup_points)),
+            selected: Cell::new(None),
+        });
+        self
+    }
+
+    /// The threshold selected at the end of the warm-up, see [Algo::with_auto_threshold].
+    pub fn selected_threshold(&self) -> Option<f64> {
+        self.auto_threshold.as_ref().and_then(|a| a.selected.get())
+    }
+
+    /// The algorithm parameters in use, i.e. with the selected threshold once it is locked in.
+    pub fn effective_config(&self) -> AlgoConfig {
+        let mut config = self.config.clone();
+        if let Some(threshold) = self.selected_threshold() {
+            config.threshold = threshold;
+        }
+        config
+    }
+
+    /// Buffers the point during the warm-up of the automatic threshold selection.
+    /// At the end of the warm-up, selects the threshold and returns the buffered points which remain to be fitted.
+    /// Returns `Err(point)` if the point must be fitted now.
+    fn warm_up(&self, point: Point) -> Result<Option<Vec<Point>>, Point> {
+        let auto = match &self.auto_threshold {
+            Some(auto) if auto.selected.get().is_none() => auto,
+            _ => return Err(point),
+        };
+        let mut buffer = auto.buffer.borrow_mut();
+        buffer.push(point);
+        if buffer.len() < auto.warmup {
+            return Ok(None);
+        }
+        let points = mem::take(&mut *buffer);
+        let threshold = self
+            .select_threshold(&points)
+            .unwrap_or(self.config.threshold);
+        auto.selected.set(Some(threshold));
+        Ok(Some(points))
+    }
+
+    /// Clusters the points and computes the percentile of the normalized distances to the centroids.
+    fn select_threshold(&self, points: &[Point]) -> Option<f64> {
+        let mut clustering = self.kmeans(points, 1);
+        for k in 2..=AUTO_MAX_CLUSTERS.min(points.len()) {
+            let next = self.kmeans(points, k);
+            if next.cost > clustering.cost / 2. {
+                break;
+            }
+            clustering = next;
+        }
+        let dists: Vec<f64> = points
+            .iter()
+            .zip(&clustering.clusters)
+            .map(|(p, &c)| (self.dist)(p, &clustering.centroids[c]))
+            .collect();
+        let mut sums = vec![(0., 0.); clustering.centroids.len()];
+        for (&c, d) in clustering.clusters.iter().zip(&dists) {
+            sums[c].0 += d;
+            sums[c].1 += 1.;
+        }
+        let mut normalized: Vec<f64> = clustering
+            .clusters
+            .iter()
+            .zip(&dists)
+            .map(|(&c, d)| (d * sums[c].1 / sums[c].0).sqrt())
+            .filter(|d| d.is_finite())
+            .collect();
+        normalized.sort_by(|a, b| a.total_cmp(b));
+        let rank = (AUTO_PERCENTILE * normalized.len() as f64).ceil() as usize;
+        normalized.get(rank.saturating_sub(1)).copied()
+    }
+
+    /// Clusters the points by k-means, initialized with the farthest point heuristic.
+    fn kmeans(&self, points: &[Point], k: usize) -> Clustering<Point> {
+        // the weighted center of a point with itself is a copy of the point
+        let copy = |p: &Point| (self.combine)(p, 1., p, 1.);
+        let mut centroids = vec![copy(&points[0])];
+        let mut nearest: Vec<f64> = points
+            .iter()
+            .map(|p| (self.dist)(p, &centroids[0]))
+            .collect();
+        while centroids.len() < k {
+            let (farthest, _) = nearest
+                .iter()
+                .enumerate()
+                .max_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+                .unwrap();
+            centroids.push(copy(&points[farthest]));
+            let centroid = centroids.last().unwrap();
+            for (p, d) in points.iter().zip(nearest.iter_mut()) {
+                *d = d.min((self.dist)(p, centroid));
+            }
+        }
+        let mut clusters = vec![0; points.len()];
+        for _ in 0..AUTO_KMEANS_ROUNDS {
+            for (p, c) in points.iter().zip(clusters.iter_mut()) {
+                *c = (0..k)
+                    .min_by(|&i, &j| {
+                        (self.dist)(p, &centroids[i]).total_cmp(&(self.dist)(p, &centroids[j]))
+                    })
+                    .unwrap();
+            }
+            let mut means: Vec<(Option<Point>, f64)> = (0..k).map(|_| (None, 0.)).collect();
+            for (p, &c) in points.iter().zip(&clusters) {
+                let (mean, count) = &mut means[c];
+                *mean = Some(match mean {
+                    Some(mean) => (self.combine)(mean, *count, p, 1.),
+                    None => copy(p),
+                });
+                *count += 1.;
+            }
+            for (centroid, (mean, _)) in centroids.iter_mut().zip(means) {
+                if let Some(mean) = mean {
+                    *centroid = mean;
+                }
+            }
+        }
+        let cost = points
+            .iter()
+            .zip(&clusters)
+            .map(|(p, &c)| (self.dist)(p, &centroids[c]))
+            .sum();
+        Clustering {
+            centroids,
+            clusters,
+            cost,
+        }
+    }
+
+    /// Seeds every stochastic component so that runs are reproducible:
+    /// the same seed, configuration and input produce byte-identical models.
+    ///
+    /// The fit itself is deterministic; randomness is only consumed by
+    /// [adaptive sampling](crate::Streamer::with_adaptive_sampling)
+    /// and the [global reservoir](crate::Streamer::with_global_reservoir).
+    /// Without a seed, the generator is seeded from entropy.
+    /// ```
+    /// use fluent_data::{Algo, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_seed(42);
+    /// assert_eq!(Some(42), algo.config().seed);
+    /// ```
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    /// A new random number generator, seeded from the configuration or from entropy.
+    pub(crate) fn rng(&self) -> StdRng {
+        match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    /// Changes the way ball radii are computed, see [RadiusStrategy].
+    /// ```
+    /// use fluent_data::{Algo, algorithm::RadiusStrategy, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine)
+    ///     .with_radius_strategy(RadiusStrategy::StdDev(2.));
+    /// ```
+    pub fn with_radius_strategy(mut self, radius_strategy: RadiusStrategy) -> Self {
+        self.radius_strategy = radius_strategy;
+        self
+    }
+
     /// Fits the incoming points to the given mixture model.
+    ///
+    /// With the [Algo::with_panic_guard], points that make a user function panic are skipped, see [Algo::try_fit].
     pub fn fit<'a>(&'a self, model: &'a mut Model<Point>, point: Point) {
-        let neighborhood = model.get_neighborhood(&point);
+        let _ = self.try_fit(model, point);
+    }
+
+    /// Fits the incoming points to the given mixture model.
+    ///
+    /// With the [Algo::with_panic_guard], a point that makes the distance or the combination function panic
+    /// is skipped and a [FitError] is returned; the model is left as if the point was not received.
+    /// Without the guard, the panic is propagated.
+    /// A point outside of the region of [Algo::with_valid_region] is skipped with [FitError::OutOfRegion].
+    pub fn try_fit(&self, model: &mut Model<Point>, point: Point) -> Result<(), FitError> {
+        self.try_fit_uncertain(model, point, 0., None)
+    }
+
+    /// Fits an incoming point which position is uncertain to the given mixture model.
+    ///
+    /// `sigma` is the standard deviation of the point position, in the unit of the ball radii.
+    /// The point is included into a ball or creates a new one as any other point, but when it is included,
+    /// it moves the center less and widens the radius more than a certain point would.
+    /// A zero `sigma` fits the point as [Algo::fit] does.
+    ///
+    /// When points are replayed by [Algo::sliding_window], their uncertainty is not kept.
+    /// ```
+    /// use fluent_data::{Algo, Model, model::Ball, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 3.)]);
+    /// algo.fit_with_uncertainty(&mut model, vec![1.], 2.);
+    /// let ball = model.iter_balls().next().unwrap();
+    /// assert!(ball.center()[0] < 0.25);
+    /// assert!(ball.radius() > 1.);
+    /// ```
+    pub fn fit_with_uncertainty(&self, model: &mut Model<Point>, point: Point, sigma: f64) {
+        let _ = self.try_fit_uncertain(model, point, sigma, None);
+    }
+
+    /// The drift of the model between the last two measures, i.e. between the last two emitted models
+    /// when the algorithm runs in a [crate::Streamer], see [Algo::with_drift].
+    /// Zero when drift tracking is not enabled or before two measures.
+    pub fn drift_since_last(&self) -> f64 {
+        self.drift.as_ref().map_or(0., |drift| drift.score.get())
+    }
+
+    /// Measures the drift of the model since the last measure and keeps it for [Algo::drift_since_last],
+    /// see [Algo::with_drift]. The streamer measures it each time it emits a model.
+    /// Does nothing when drift tracking is not enabled.
+    pub fn measure_drift(&self, model: &Model<Point>) {
+        if let Some(drift) = &self.drift {
+            let current: Vec<_> = model
+                .iter_balls()
+                .map(|b| ((drift.clone)(&b.center), b.weight))
+                .collect();
+            let previous = drift.snapshot.replace(current);
+            drift
+                .score
+                .set(drift_score(&self.dist, &previous, &drift.snapshot.borrow()));
+        }
+    }
+
+    /// The number of times two balls were merged into one since the algorithm was created.
+    /// A high merge rate tells balls are created too eagerly, see [AlgoConfig::threshold].
+    pub fn merge_count(&self) -> u64 {
+        self.merges.get()
+    }
+
+    /// The number of balls created for points too far from the existing balls since the algorithm was created.
+    pub fn split_count(&self) -> u64 {
+        self.splits.get()
+    }
+
+    /// The number of points dropped because they were outside the valid region, see [Algo::with_valid_region].
+    pub fn rejected_out_of_region(&self) -> u64 {
+        self.rejected.get()
+    }
+
+    /// The index of the ball updated or created by the last point fitted with this algorithm,
+    /// in the [Model::iter_balls] order of the model as it was right after the fit.
+    /// Returns `None` before the first fit or when the last point was not fitted, see [Model::last_fitted_ball].
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for point in [vec![0.], vec![1.], vec![100.], vec![0.5]] {
+    ///     algo.fit(&mut model, point);
+    /// }
+    /// assert_eq!(Some(0), algo.last_updated_ball());
+    /// ```
+    pub fn last_updated_ball(&self) -> Option<usize> {
+        self.last_updated.get()
+    }
+
+    /// The approximate number of points fitted in the given grid cell, see [Algo::with_region_sketch].
+    /// The estimate is never lower than the actual count. Returns 0 when regions are not counted.
+    pub fn region_frequency(&self, cell: &[i64]) -> u64 {
+        match &self.sketch {
+            Some(sketch) => {
+                let counts = sketch.counts.borrow();
+                sketch
+                    .slots(cell)
+                    .map(|(row, slot)| counts[row][slot])
+                    .min()
+                    .unwrap_or(0)
+            }
+            None => 0,
+        }
+    }
+
+    /// Combines the point into the ball at `index`, in the [Model::iter_balls] order, rather than into its nearest ball,
+    /// e.g. when the cluster of the point is known from a label.
+    ///
+    /// The point is fitted as [Algo::try_fit] does, except that it is included into the given ball
+    /// however far it is: the center, the radius, the distance statistics and the velocity of the ball are updated,
+    /// the other balls decay and the point is kept in the sliding window, the samples and the region counts.
+    /// The point skips the [Algo::with_auto_threshold] warm-up.
+    /// Returns an error if the index is out of range, if the ball is frozen, see [Model::freeze_ball],
+    /// if the [Algo::with_normalization] warm-up is not over, or if the point is not fitted, see [Algo::try_fit].
+    /// ```
+    /// use fluent_data::{Algo, Model, model::Ball, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let balls = vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![10.], 1., 1.)];
+    /// let mut model = Model::load(space::euclid_dist, balls);
+    /// algo.assign_to(&mut model, 1, vec![2.]).unwrap();
+    /// assert!(algo.assign_to(&mut model, 2, vec![2.]).is_err());
+    /// let ball = model.iter_balls().nth(1).unwrap();
+    /// assert_eq!(&vec![6.], ball.center());
+    /// assert_eq!(2., ball.weight());
+    /// ```
+    pub fn assign_to(
+        &self,
+        model: &mut Model<Point>,
+        index: usize,
+        point: Point,
+    ) -> Result<(), Box<dyn Error>> {
+        let vertex = model.vertex(index)?.clone();
+        if vertex.deref_data().frozen {
+            return Err(format!("ball {} is frozen", index).into());
+        }
+        if self.normalizer.is_some() && model.normalization.is_none() {
+            return Err("the normalization warm-up is not over".into());
+        }
+        self.try_fit_uncertain(model, point, 0., Some(&vertex))?;
+        Ok(())
+    }
+
+    /// Fits a point with the given position standard deviation, see [Algo::try_fit],
+    /// into the `target` ball if any, see [Algo::assign_to].
+    fn try_fit_uncertain(
+        &self,
+        model: &mut Model<Point>,
+        point: Point,
+        sigma: f64,
+        target: Option<&BallNode<Point>>,
+    ) -> Result<(), FitError> {
+        if let Some(is_valid) = &self.valid_region {
+            if !is_valid(&point) {
+                model.record_fitted(None);
+                self.last_updated.set(None);
+                self.rejected.set(self.rejected.get() + 1);
+                return Err(FitError::OutOfRegion);
+            }
+        }
+        let copy = self.samples.as_ref().map(|samples| (samples.clone)(&point));
+        let cell = self.sketch.as_ref().map(|sketch| (sketch.cell)(&point));
+        let fitted = self.fit_uncertain(model, point, sigma, target);
+        self.last_updated.set(model.last_fitted_ball());
+        if let (Some(point), true) = (copy, fitted.is_ok()) {
+            self.sample(model, point);
+        }
+        if let (Some(cell), true) = (cell, fitted.is_ok()) {
+            self.count_region(&cell);
+        }
+        fitted
+    }
+
+    /// Increments the counters of the cell of a fitted point, see [Algo::with_region_sketch].
+    fn count_region(&self, cell: &[i64]) {
+        if let Some(sketch) = &self.sketch {
+            let slots: Vec<_> = sketch.slots(cell).collect();
+            let mut counts = sketch.counts.borrow_mut();
+            for (row, slot) in slots {
+                counts[row][slot] += 1;
+            }
+        }
+    }
+
+    /// Offers a fitted point to the reservoir of its ball, then shrinks the reservoirs to their share of the budget,
+    /// see [Algo::with_global_sample_budget]. The center of the ball is recomputed when a refresh is due,
+    /// see [Algo::with_center_refresh].
+    fn sample(&self, model: &mut Model<Point>, point: Point) {
+        let samples = match &self.samples {
+            Some(samples) => samples,
+            None => return,
+        };
+        let mut rng = samples.rng.borrow_mut();
+        let rng = rng.get_or_insert_with(|| self.rng());
+        let mut reservoirs = samples.reservoirs.borrow_mut();
+        let mut cached = samples.capacities.borrow_mut();
+        let version = model.ball_set_version();
+        if cached.as_ref().is_none_or(|(cached, _)| *cached != version) {
+            let capacities = sample_capacities(model, samples.total);
+            reservoirs.retain(|id, _| capacities.contains_key(id));
+            for (id, (_, points)) in reservoirs.iter_mut() {
+                while points.len() > capacities[id] {
+                    points.swap_remove(rng.gen_range(0..points.len()));
+                }
+            }
+            *cached = Some((version, capacities));
+        }
+        let capacities = &cached.as_ref().unwrap().1;
+        let mut refresh = None;
+        if let Some((index, ball)) = model
+            .last_fitted_ball()
+            .and_then(|index| Some((index, model.iter_balls().nth(index)?)))
+        {
+            let (seen, points) = reservoirs.entry(ball.id).or_default();
+            *seen += 1;
+            if points.len() < capacities[&ball.id] {
+                points.push(point);
+            } else {
+                let index = rng.gen_range(0..*seen) as usize;
+                if index < points.len() {
+                    points[index] = point;
+                }
+            }
+            if let Some(every) = self.center_refresh {
+                if *seen % every == 0 && !points.is_empty() {
+                    refresh = Some((index, ball.id));
+                }
+            }
+        }
+        if let Some((index, id)) = refresh {
+            let (center, _) = self.pairwise_mean(&reservoirs[&id].1, samples.clone);
+            if let Some(mut ball) = model.balls_mut().nth(index) {
+                ball.center = center;
+            }
+        }
+    }
+
+    /// The mean of a non-empty set of points and its weight, combining halves of the set recursively,
+    /// thus rounding errors grow with the logarithm of the number of points instead of the number of points.
+    fn pairwise_mean(&self, points: &[Point], clone: fn(&Point) -> Point) -> (Point, f64) {
+        if points.len() == 1 {
+            return (clone(&points[0]), 1.);
+        }
+        let (left, right) = points.split_at(points.len() / 2);
+        let (left, left_weight) = self.pairwise_mean(left, clone);
+        let (right, right_weight) = self.pairwise_mean(right, clone);
+        let mean = (self.combine)(&left, left_weight, &right, right_weight);
+        (mean, left_weight + right_weight)
+    }
+
+    /// Fits a point with the given position standard deviation.
+    fn fit_uncertain(
+        &self,
+        model: &mut Model<Point>,
+        point: Point,
+        sigma: f64,
+        target: Option<&BallNode<Point>>,
+    ) -> Result<(), FitError> {
+        model.record_fitted(None);
+        let point = match &self.normalizer {
+            Some(normalizer) => {
+                match (normalizer.normalize)(model, &mut normalizer.buffer.borrow_mut(), point) {
+                    Ok(normalized) => {
+                        for point in normalized.into_iter().flatten() {
+                            self.fit_normalized(model, point, 0., None)?;
+                        }
+                        return Ok(());
+                    }
+                    Err(point) => point,
+                }
+            }
+            None => point,
+        };
+        self.fit_normalized(model, point, sigma, target)
+    }
+
+    /// Fits a point which is normalized if normalization is enabled.
+    fn fit_normalized(
+        &self,
+        model: &mut Model<Point>,
+        point: Point,
+        sigma: f64,
+        target: Option<&BallNode<Point>>,
+    ) -> Result<(), FitError> {
+        let point = match target {
+            Some(_) => point,
+            None => match self.warm_up(point) {
+                Ok(warmed_up) => {
+                    for point in warmed_up.into_iter().flatten() {
+                        let _ = self.fit_normalized(model, point, 0., None);
+                    }
+                    return Ok(());
+                }
+                Err(point) => point,
+            },
+        };
+        if let Some(window) = &self.window {
+            if model.recent.len() == window.size {
+                let oldest = model.recent.pop_front().expect("the window is not empty");
+                if self.panic_guard.is_some() {
+                    // the ball is left unchanged when a user function panics
+                    let _ = catch_unwind(AssertUnwindSafe(|| self.unfit(model, &oldest)));
+                } else {
+                    self.unfit(model, &oldest);
+                }
+            }
+            let kept = (window.clone)(&point);
+            let fitted = self.fit_point(model, point, sigma, target);
+            if fitted.is_ok() {
+                model.recent.push_back(kept);
+            }
+            return fitted;
+        }
+        self.fit_point(model, point, sigma, target)
+    }
+
+    /// Removes a point which falls out of the sliding window from the ball which covers it, if any,
+    /// reverting the running means of the center, the radius and the distance statistics.
+    /// The ball is removed when its weight falls below the floor. Frozen balls are left untouched.
+    fn unfit(&self, model: &mut Model<Point>, point: &Point) {
+        let vertex = match model.get_neighborhood(point).into_iter().next() {
+            Some(vertex) => vertex,
+            None => return,
+        };
+        let mut ball = vertex.deref_data_mut();
+        // a point that is not covered by its closest ball was already removed with its ball
+        if ball.frozen || (self.dist)(&ball.center, point) >= self.intra_threshold() * ball.radius {
+            return;
+        }
+        let weight = ball.weight - 1.;
+        if weight <= self.floor() {
+            model.count_removed(&ball);
+            drop(ball);
+            model.graph.retain(|v| !v.ptr_eq(&vertex));
+            return;
+        }
+        let center = (self.combine)(&ball.center, ball.weight, point, -1.);
+        let d = (self.dist)(&center, point);
+        let x = d.sqrt();
+        let dist_mean = (ball.dist_mean * ball.weight - x) / weight;
+        let dist_var = ((ball.dist_var * ball.weight - (x - dist_mean) * (x - ball.dist_mean))
+            / weight)
+            .max(0.);
+        let radius = self
+            .std_dev_radius(dist_var)
+            .unwrap_or((ball.radius * ball.weight - d) / weight);
+        if radius > 0. {
+            ball.radius = self.floor_radius(radius);
+        }
+        ball.center = center;
+        (ball.dist_mean, ball.dist_var) = (dist_mean, dist_var);
+        ball.weight = weight;
+        debug_check_finite(&ball);
+    }
+
+    /// Fits a point to the model, regardless of the sliding window,
+    /// rolling the model back if a user function panics when the panic guard is enabled.
+    fn fit_point(
+        &self,
+        model: &mut Model<Point>,
+        point: Point,
+        sigma: f64,
+        target: Option<&BallNode<Point>>,
+    ) -> Result<(), FitError> {
+        let guard = match &self.panic_guard {
+            Some(guard) => guard,
+            None => {
+                self.fit_unguarded(model, point, sigma, target);
+                return Ok(());
+            }
+        };
+        let fitted = catch_unwind(AssertUnwindSafe(|| {
+            self.fit_unguarded(model, point, sigma, target)
+        }));
+        let touched = guard.touched.take();
+        let created = guard.created.take();
+        let which = guard.calling.take();
+        let panic = match fitted {
+            Ok(()) => return Ok(()),
+            Err(panic) => panic,
+        };
+        if let Some((vertex, ball)) = touched {
+            *vertex.deref_data_mut() = ball;
+        }
+        if created {
+            model.graph.pop();
+        }
+        Err(FitError::UserFnPanicked {
+            // the model distance is the only user function not wrapped by the guard
+            which: which.unwrap_or(UserFn::Dist),
+            payload_message: panic_reason(panic),
+        })
+    }
+
+    /// Fits a point to the model, into the `target` ball if any.
+    fn fit_unguarded(
+        &self,
+        model: &mut Model<Point>,
+        point: Point,
+        sigma: f64,
+        target: Option<&BallNode<Point>>,
+    ) {
+        // the target may have been removed when the oldest point left the sliding window
+        if let Some(vertex) = target.filter(|vertex| model.graph.iter().any(|v| v.ptr_eq(vertex))) {
+            let mut ball = vertex.deref_data_mut();
+            self.save(vertex, &ball);
+            let d = (self.dist)(&ball.center, &point);
+            self.include(&mut ball, point, d, sigma);
+            drop(ball);
+            model.record_recent(vertex);
+            model.record_fitted(Some(vertex));
+            model.invalidate_neighbors();
+            self.decay(model, vertex.clone());
+            return;
+        }
+        let neighborhood = match self.budget {
+            Some(budget) => model.get_neighborhood_within(&point, budget),
+            None => model.get_neighborhood(&point),
+        };
         match neighborhood.first() {
             None => {
-                self.init(model, point);
+                let vertex = self.init(model, point);
+                model.record_recent(&vertex);
+                model.record_fitted(Some(&vertex));
             }
             Some(candidate) => {
-                let (vertex, maybe_neighbor) = self.update(model, candidate, point, &neighborhood);
+                let (vertex, maybe_neighbor) =
+                    self.update(model, candidate, point, sigma, &neighborhood);
+                model.record_recent(&vertex);
+                model.record_fitted(Some(&vertex));
                 if let Some(maybe_neighbor) = maybe_neighbor {
                     self.update_local_graph(candidate, maybe_neighbor);
                 };
@@ -74,18 +1170,114 @@ impl<Point: PartialEq + 'static> Algo<Point> {
         }
     }
 
+    /// Predicts how the point would be fitted, without changing the model.
+    ///
+    /// The prediction covers the inclusion of the point into its closest ball or the creation of a new ball;
+    /// the merge of the updated ball with a neighbor that may follow is not predicted.
+    /// The closest ball is searched among all balls, regardless of [Algo::with_budget],
+    /// and the point is assumed to be fitted, regardless of [Algo::with_auto_threshold] warm-up.
+    /// ```
+    /// use fluent_data::{algorithm::FitTarget, Algo, Model, model::Ball, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 3.)]);
+    /// let preview = algo.preview_fit(&model, &vec![2.]);
+    /// assert_eq!(FitTarget::Ball(0), preview.target);
+    /// assert_eq!(0.5, preview.center_shift);
+    /// assert_eq!(f64::sqrt(1.75), preview.radius);
+    /// assert_eq!(FitTarget::NewBall, algo.preview_fit(&model, &vec![10.]).target);
+    /// ```
+    pub fn preview_fit(&self, model: &Model<Point>, point: &Point) -> FitPreview {
+        let candidate = match model.get_neighborhood(point).into_iter().next() {
+            None => {
+                return FitPreview {
+                    target: FitTarget::NewBall,
+                    center_shift: 0.,
+                    radius: f64::INFINITY,
+                }
+            }
+            Some(candidate) => candidate,
+        };
+        let closest = candidate.deref_data();
+        let d = (self.dist)(&closest.center, point);
+        if d >= self.intra_threshold() * closest.radius {
+            return FitPreview {
+                target: FitTarget::NewBall,
+                center_shift: 0.,
+                radius: self.floor_radius(d / EXTRA_THRESHOLD).sqrt(),
+            };
+        }
+        let index = model.graph.iter().position(|v| v.ptr_eq(&candidate));
+        let target = FitTarget::Ball(index.expect("the closest ball belongs to the model"));
+        if closest.frozen {
+            return FitPreview {
+                target,
+                center_shift: 0.,
+                radius: closest.radius(),
+            };
+        }
+        let center = self.update_mu(&closest, (self.combine)(point, 1., point, 1.), 1.);
+        let (_, dist_var) = self.update_dist_stats(&closest, d);
+        FitPreview {
+            target,
+            center_shift: (self.dist)(&closest.center, &center).sqrt(),
+            radius: self.update_sigma(&closest, dist_var, d).sqrt(),
+        }
+    }
+
+    /// Tells whether the point would be merged into an existing ball rather than create a new one.
+    /// The check uses the model neighborhood cache, thus it is cheaper than fitting.
+    pub(crate) fn is_covered(&self, model: &Model<Point>, point: &Point) -> bool {
+        model.is_covered(point, self.intra_threshold())
+    }
+
+    /// Applies `steps` decay steps at once to all balls, as if they did not include any of the last `steps` points.
+    /// Balls which weight falls below the floor are removed.
+    ///
+    /// The decayed weights are computed in closed form, in logarithmic scale, thus without
+    /// iterating steps and without underflow.
+    /// ```
+    /// use fluent_data::{Algo, Model, model::Ball, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 10.)]);
+    /// algo.fast_forward(&mut model, 10);
+    /// assert!((model.iter_balls().next().unwrap().weight() - 10. * 0.95f64.powi(10)).abs() < 1E-9);
+    /// algo.fast_forward(&mut model, 1_000_000);
+    /// assert_eq!(0, model.iter_balls().count());
+    /// ```
+    pub fn fast_forward(&self, model: &mut Model<Point>, steps: u64) {
+        model.decay_weights(self.config.decay.ln() * steps as f64, self.floor());
+    }
+
+    /// The weight floor under which balls are removed.
+    fn floor(&self) -> f64 {
+        self.config.floor.max(f64::MIN_POSITIVE)
+    }
+
+    /// The threshold on the square of the normalized distance under which a point belongs to a ball.
+    fn intra_threshold(&self) -> f64 {
+        let threshold = self.selected_threshold().unwrap_or(self.config.threshold);
+        threshold * threshold
+    }
+
     /// Initializes the model for the first incoming point.
     /// It creates a first balls with an infinite radius and a zero weight.
     /// The second point will be merged into this ball and the radius updated
-    /// to the distance between the two points.
+    /// to the distance between the two points. Points identical to the first one
+    /// are merged too but the radius is not known until a distinct point arrives.
+    /// The center goes through the combination function, like all centers, e.g. to be quantized.
     fn init(&self, model: &mut Model<Point>, point: Point) -> BallNode<Point> {
-        let ball = Ball::new(point, f64::INFINITY, 0.);
+        let center = (self.combine)(&point, 1., &point, 1.);
+        let mut ball = Ball::new(center, f64::INFINITY, 0.);
+        ball.assigned = 1;
+        self.track_velocity(&mut ball, None);
         model.add_ball(ball, vec![])
     }
 
     /// Updates the model for all points after the first.
     /// If the new point is "far" from its neighbors, a new ball is created
-    /// otherwise it is merged into the closest one.
+    /// otherwise it is merged into the closest one, unless it is frozen.
     /// In both case the radius is calculated or updated using
     /// the distance between the point and its closest ball.
     fn update(
@@ -93,40 +1285,138 @@ impl<Point: PartialEq + 'static> Algo<Point> {
         model: &mut Model<Point>,
         vertex: &BallNode<Point>,
         point: Point,
+        sigma: f64,
         neighborhood: &Vec<BallNode<Point>>,
     ) -> (BallNode<Point>, Option<BallNode<Point>>) {
         let mut closest = vertex.deref_data_mut();
+        self.save(vertex, &closest);
         let d = (self.dist)(&closest.center, &point);
-        if d < INTRA_THRESHOLD * closest.radius {
-            self.update_ball(&mut closest, point, d);
+        if d < self.intra_threshold() * closest.radius {
+            self.include(&mut closest, point, d, sigma);
             (vertex.clone(), neighborhood.get(1).map(|v| v.clone()))
         } else {
             let ball = self.split_ball(point, d, &closest);
             let vertex = model.add_ball(ball, neighborhood.get_neighbors());
+            if let Some(guard) = &self.panic_guard {
+                guard.created.set(true);
+            }
             (vertex.clone(), Some(vertex))
         }
     }
 
+    /// Includes the point at the given distance into the ball, which is updated unless it is frozen.
+    fn include(
+        &self,
+        ball: &mut impl DerefMut<Target = Ball<Point>>,
+        point: Point,
+        dist: f64,
+        sigma: f64,
+    ) {
+        ball.included += 1;
+        if dist <= ball.radius {
+            ball.inside += 1;
+        }
+        if ball.frozen {
+            ball.assigned += 1;
+        } else {
+            self.update_ball(ball, point, dist, sigma);
+        }
+    }
+
+    /// Saves the ball that the point may change, to restore it if a user function panics.
+    fn save(&self, vertex: &BallNode<Point>, ball: &Ball<Point>) {
+        if let Some(guard) = &self.panic_guard {
+            guard
+                .touched
+                .replace(Some((vertex.clone(), (guard.snapshot)(ball))));
+        }
+    }
+
     /// Updates the ball when the given point is merged.
     /// The center is updated to the weighted center of point ansd the ball.
     /// The radius is updated using the distance between the point and the ball center.
-    fn update_ball(&self, ball: &mut impl DerefMut<Target = Ball<Point>>, point: Point, dist: f64) {
-        ball.center = self.update_mu(ball, point);
-        ball.radius = self.update_sigma(ball, dist);
+    ///
+    /// The position of the point has the standard deviation `sigma`:
+    /// the point weighs less in the center, by the ratio of the ball variance to the total variance,
+    /// and its variance adds to the distance used for the radius.
+    fn update_ball(
+        &self,
+        ball: &mut impl DerefMut<Target = Ball<Point>>,
+        point: Point,
+        dist: f64,
+        sigma: f64,
+    ) {
+        let variance = sigma * sigma;
+        let point_weight = if variance > 0. && ball.radius.is_finite() {
+            ball.radius / (ball.radius + variance)
+        } else {
+            1.
+        };
+        let dist = dist + variance;
+        let center = self.update_mu(ball, point, point_weight);
+        let previous = mem::replace(&mut ball.center, center);
+        self.track_velocity(ball, Some(&previous));
+        (ball.dist_mean, ball.dist_var) = self.update_dist_stats(ball, dist);
+        ball.radius = self.update_sigma(ball, ball.dist_var, dist);
         ball.weight += 1.;
+        ball.assigned += 1;
+        debug_check_finite(ball);
+    }
+
+    /// Computes the weighted mean and variance of the distances from the included points to the ball center
+    /// once a point at the given distance is included.
+    fn update_dist_stats(&self, ball: &Ball<Point>, dist: f64) -> (f64, f64) {
+        let x = dist.sqrt();
+        let delta = x - ball.dist_mean;
+        let mean = ball.dist_mean + delta / (ball.weight + 1.);
+        let var = (ball.dist_var * ball.weight + delta * (x - mean)) / (ball.weight + 1.);
+        (mean, var)
     }
 
     /// Updates the ball center to the weighted center of point ansd the ball.
-    fn update_mu(&self, ball: &impl DerefMut<Target = Ball<Point>>, point: Point) -> Point {
-        (self.combine)(&ball.center, ball.weight, &point, 1.)
+    fn update_mu(&self, ball: &Ball<Point>, point: Point, point_weight: f64) -> Point {
+        let center = (self.combine)(&ball.center, ball.weight, &point, point_weight);
+        match self.max_step {
+            Some(max_step) => self.clamp_step(&ball.center, center, max_step),
+            None => center,
+        }
+    }
+
+    /// Moves the center back towards its former position if it moved further than `max_step`.
+    /// The distance function gives the square of the step.
+    fn clamp_step(&self, from: &Point, to: Point, max_step: f64) -> Point {
+        let step = (self.dist)(from, &to).sqrt();
+        if step <= max_step {
+            return to;
+        }
+        let t = max_step / step;
+        (self.combine)(from, 1. - t, &to, t)
     }
 
-    /// Updates the ball radius using the distance between the point and the ball center.
-    fn update_sigma(&self, ball: &impl DerefMut<Target = Ball<Point>>, dist: f64) -> f64 {
-        if ball.weight == 0. {
-            dist
+    /// Updates the ball radius using the distance between the point and the ball center
+    /// and the updated variance of the distances.
+    fn update_sigma(&self, ball: &Ball<Point>, dist_var: f64, dist: f64) -> f64 {
+        let radius = if let Some(radius) = self.std_dev_radius(dist_var) {
+            radius
+        } else if ball.weight == 0. || ball.radius.is_infinite() {
+            // the radius is not known until a point distinct from the center is included
+            if dist > 0. {
+                dist
+            } else {
+                ball.radius
+            }
         } else {
-            (ball.radius * ball.weight + dist) / (ball.weight + 1.)
+            ball.radius + (dist - ball.radius) / (ball.weight + 1.)
+        };
+        self.floor_radius(radius)
+    }
+
+    /// Computes the square of the radius as a multiple of the distance standard deviation
+    /// when this strategy is selected and the deviation is known.
+    fn std_dev_radius(&self, dist_var: f64) -> Option<f64> {
+        match self.radius_strategy {
+            RadiusStrategy::StdDev(k) if dist_var > 0. => Some(k * k * dist_var),
+            _ => None,
         }
     }
 
@@ -138,9 +1428,21 @@ impl<Point: PartialEq + 'static> Algo<Point> {
         d: f64,
         neighbor: &impl DerefMut<Target = Ball<Point>>,
     ) -> Ball<Point> {
-        let radius = d / EXTRA_THRESHOLD;
+        let radius = self.floor_radius(d / EXTRA_THRESHOLD);
         let center = (self.combine)(&neighbor.center, -1., &point, 5.);
-        Ball::new(center, radius, 1.)
+        let mut ball = Ball::new(center, radius, 1.);
+        ball.assigned = 1;
+        self.track_velocity(&mut ball, None);
+        debug_check_finite(&ball);
+        self.splits.set(self.splits.get() + 1);
+        ball
+    }
+
+    /// Updates the velocity of the ball if it is tracked, see [Algo::with_velocity].
+    fn track_velocity(&self, ball: &mut Ball<Point>, previous: Option<&Point>) {
+        if let Some(track) = &self.velocity {
+            track(ball, previous);
+        }
     }
 
     /// Updates the neighborhood of a ball with the candidate ball if it is closer than its current neighbors.
@@ -200,12 +1502,41 @@ impl<Point: PartialEq + 'static> Algo<Point> {
         neighborhood
     }
 
+    /// Merges the balls which overlap enough, as the algorithm does when a point is fitted,
+    /// until no pair of balls should be merged, e.g. after [Model::merge].
+    /// ```
+    /// use fluent_data::{Algo, Model, model::Ball, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 4., 1.)]);
+    /// model.merge(&Model::load(space::euclid_dist, vec![Ball::new(vec![1.], 4., 1.)]));
+    /// algo.merge_overlapping(&mut model);
+    /// assert_eq!(1, model.iter_balls().count());
+    /// ```
+    pub fn merge_overlapping(&self, model: &mut Model<Point>) {
+        let overlapping = |graph: &[BallNode<Point>]| {
+            (0..graph.len()).find_map(|i| {
+                (i + 1..graph.len()).find_map(|j| match self.should_merge(&graph[i], &graph[j]) {
+                    (true, d) => Some((i, j, d)),
+                    _ => None,
+                })
+            })
+        };
+        while let Some((i, j, d)) = overlapping(&model.graph) {
+            self.merge_balls(&model.graph[i], &model.graph[j], d);
+            model.graph.remove(j);
+        }
+        model.rebuild_neighbors();
+    }
+
     /// Decides if two balls are close enough to merge.
     fn should_merge(&self, first: &BallNode<Point>, second: &BallNode<Point>) -> (bool, f64) {
         let current_data = first.deref_data();
         let neighbor_data = second.deref_data();
         let d = (self.dist)(&current_data.center, &neighbor_data.center);
-        let should_merge = d < (current_data.radius + neighbor_data.radius) * MERGE_THRESHOLD;
+        let should_merge = !current_data.frozen
+            && !neighbor_data.frozen
+            && current_data.overlaps(&neighbor_data, d, MERGE_THRESHOLD);
         (should_merge, d)
     }
 
@@ -221,31 +1552,765 @@ impl<Point: PartialEq + 'static> Algo<Point> {
             &neighbor_data.center,
             neighbor_data.weight,
         );
-        current_data.radius = d
-            + (current_data.radius * current_data.weight
-                + neighbor_data.radius * neighbor_data.weight)
-                / (current_data.weight + neighbor_data.weight);
-        current_data.weight = current_data.weight + neighbor_data.weight;
+        let weight = current_data.weight + neighbor_data.weight;
+        current_data.velocity = merge_velocities(&current_data, &neighbor_data);
+        self.merge_dist_stats(&mut current_data, &neighbor_data);
+        current_data.radius =
+            self.floor_radius(self.std_dev_radius(current_data.dist_var).unwrap_or(
+                d + current_data.radius
+                    + (neighbor_data.radius - current_data.radius)
+                        * (neighbor_data.weight / weight),
+            ));
+        current_data.weight = weight;
+        current_data.assigned += neighbor_data.assigned;
+        current_data.included += neighbor_data.included;
+        current_data.inside += neighbor_data.inside;
+        current_data.recent = log_add(current_data.recent, neighbor_data.recent);
         neighbor_data.weight = 0.;
+        neighbor_data.assigned = 0;
+        debug_check_finite(&current_data);
+        self.merges.set(self.merges.get() + 1);
+    }
+
+    /// Pools the distance statistics of two balls that are merged.
+    fn merge_dist_stats(
+        &self,
+        ball: &mut impl DerefMut<Target = Ball<Point>>,
+        other: &impl DerefMut<Target = Ball<Point>>,
+    ) {
+        let weight = ball.weight + other.weight;
+        let mean = (ball.dist_mean * ball.weight + other.dist_mean * other.weight) / weight;
+        let spread = |b: &Ball<Point>| b.weight * (b.dist_var + (b.dist_mean - mean).powi(2));
+        ball.dist_var = (spread(ball) + spread(other)) / weight;
+        ball.dist_mean = mean;
     }
 
     /// Decrease the weight of all balls by applying decay factor.
-    /// Remove balls which weight is too low.
+    /// Remove balls which weight is too low. Frozen balls are left untouched.
     fn decay(&self, model: &mut Model<Point>, vertex: BallNode<Point>) {
-        model.graph.retain(|v| {
+        let mut graph = mem::take(&mut model.graph);
+        graph.retain(|v| {
+            if v.deref_data().frozen {
+                return true;
+            }
             if v.deref_data().ne(&vertex.deref_data()) {
-                v.deref_data_mut().weight *= DECAY_FACTOR;
+                v.deref_data_mut().weight *= self.config.decay;
             }
-            v.deref_data().weight > DECAY_THRESHOLD
-        })
+            let keep = v.deref_data().weight > self.floor();
+            if !keep {
+                model.count_removed(&v.deref_data());
+            }
+            keep
+        });
+        model.graph = graph;
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use approx_eq::assert_approx_eq;
-
-    use crate::algorithm::*;
+/// Averages the velocities of two balls that are merged, weighted by the ball weights.
+fn merge_velocities<Point: PartialEq>(ball: &Ball<Point>, other: &Ball<Point>) -> Option<Vec<f64>> {
+    match (&ball.velocity, &other.velocity) {
+        (Some(v1), Some(v2)) => {
+            let weight = ball.weight + other.weight;
+            let merged = v1
+                .iter()
+                .zip(v2)
+                .map(|(x1, x2)| (x1 * ball.weight + x2 * other.weight) / weight)
+                .collect();
+            Some(merged)
+        }
+        (velocity, None) | (None, velocity) => velocity.clone(),
+    }
+}
+
+/// Checks in debug builds that the ball radius and weight did not overflow or became undefined.
+/// The radius may be infinite only while the ball included no point distinct from its center.
+fn debug_check_finite<Point: PartialEq>(ball: &Ball<Point>) {
+    debug_assert!(ball.weight.is_finite(), "non finite weight {}", ball.weight);
+    debug_assert!(
+        ball.radius.is_finite() || ball.weight == 0. || ball.dist_mean == 0.,
+        "non finite radius {} with weight {}",
+        ball.radius,
+        ball.weight
+    );
+}
+
+impl<Point: PartialEq + Clone + 'static> Algo<Point> {
+    /// Makes the model reflect the last `size` points instead of relying on decay only.
+    ///
+    /// The last points are kept in the model; when a point falls out of the window
+    /// it is removed from the ball which covers it: the center, radius and distance statistics of the ball
+    /// are reverted and its weight decreases by one, the ball is removed when its weight falls below the floor.
+    /// Balls are not rebuilt, thus the model depends on the order the points were fitted in,
+    /// and points fitted before the window was full are removed from the balls which cover them then.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).sliding_window(2);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// algo.fit(&mut model, vec![100.]);
+    /// algo.fit(&mut model, vec![1.]);
+    /// algo.fit(&mut model, vec![2.]);
+    /// assert_eq!(&vec![2.], model.iter_balls().next().unwrap().center());
+    /// ```
+    pub fn sliding_window(mut self, size: usize) -> Self {
+        assert!(size > 0, "sliding window size must be positive");
+        self.window = Some(Window {
+            size,
+            clone: Point::clone,
+        });
+        self
+    }
+
+    /// Measures the drift of the model each time it is emitted, see [Algo::measure_drift] and [Algo::drift_since_last].
+    ///
+    /// The drift compares the model with the model at the previous measure, as centers and weights:
+    ///  - the center movement is the mean distance from each center to the nearest previous center, weighted by the ball weight,
+    ///    as given by the distance of the algorithm, e.g. a square distance for [crate::space::euclid_dist],
+    ///  - the weight redistribution is the share of the total weight that moved from one ball to another,
+    ///    each ball being matched to the nearest previous ball; it is in `[0, 1]`.
+    ///
+    /// The drift is the sum of both: small when points are included in existing balls
+    /// and large when the weight moves toward new places, e.g. after a regime shift.
+    /// Each measure costs a copy of the centers and a distance per pair of balls.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_drift();
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for x in [0., 1., 0., 1.] {
+    ///     algo.fit(&mut model, vec![x]);
+    /// }
+    /// algo.measure_drift(&model);
+    /// algo.fit(&mut model, vec![0.5]);
+    /// algo.measure_drift(&model);
+    /// let steady = algo.drift_since_last();
+    /// algo.fit(&mut model, vec![100.]);
+    /// algo.measure_drift(&model);
+    /// assert!(algo.drift_since_last() > 10. * steady);
+    /// ```
+    pub fn with_drift(mut self) -> Self {
+        self.drift = Some(DriftTracker {
+            clone: Point::clone,
+            snapshot: RefCell::new(vec![]),
+            score: Cell::new(0.),
+        });
+        self
+    }
+
+    /// Keeps a uniform random sample of the points included in each ball, at most `total` points across all balls.
+    ///
+    /// The budget is shared between balls in proportion to their weight, thus the reservoirs shrink as balls are created
+    /// and the heaviest balls retain the most points. The samples of balls that are merged or removed are dropped.
+    /// The shares are computed again, with a pass over the balls, only when a ball is created or removed,
+    /// and the whole budget is spread, the balls with the largest fractional shares getting the rounding remainder.
+    /// The samples are given by [Algo::ball_samples]; they are kept by [Algo::save_state] and [Algo::rebuild_with].
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_global_sample_budget(4);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for x in [0., 1., 0., 1., 0., 1.] {
+    ///     algo.fit(&mut model, vec![x]);
+    /// }
+    /// assert_eq!(4, algo.ball_samples(&model)[0].len());
+    /// ```
+    pub fn with_global_sample_budget(mut self, total: usize) -> Self {
+        self.samples = Some(BallSamples {
+            clone: Point::clone,
+            total,
+            reservoirs: RefCell::new(HashMap::new()),
+            capacities: RefCell::new(None),
+            rng: RefCell::new(None),
+        });
+        self
+    }
+
+    /// Recomputes the center of a ball from its sampled points every `every` points included in the ball,
+    /// to correct the rounding errors accumulated by the incremental updates of the center.
+    ///
+    /// The center becomes the mean of the sample, computed by the combination function on halves of the sample recursively;
+    /// this requires samples to be kept, see [Algo::with_global_sample_budget], otherwise centers are never refreshed.
+    /// Since samples are uniform over all the points of the ball, the refreshed center does not reflect the decay,
+    /// see [AlgoConfig::decay]. Samples hold the points as they are fitted, thus refresh should not be used with normalization.
+    /// ```
+    /// use fluent_data::{Algo, Model, model::Ball, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine)
+    ///     .with_global_sample_budget(100)
+    ///     .with_center_refresh(3);
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 100., 1.)]);
+    /// for x in [1., 2., 3.] {
+    ///     algo.fit(&mut model, vec![x]);
+    /// }
+    /// assert_eq!(&vec![2.], model.iter_balls().next().unwrap().center());
+    /// ```
+    pub fn with_center_refresh(mut self, every: u64) -> Self {
+        assert!(every > 0, "the refresh period must be positive");
+        self.center_refresh = Some(every);
+        self
+    }
+
+    /// The points sampled in each ball of the model, in the [Model::iter_balls] order,
+    /// see [Algo::with_global_sample_budget]. Empty if samples are not kept.
+    pub fn ball_samples(&self, model: &Model<Point>) -> Vec<Vec<Point>> {
+        let samples = match &self.samples {
+            Some(samples) => samples,
+            None => return vec![],
+        };
+        let reservoirs = samples.reservoirs.borrow();
+        model
+            .iter_balls()
+            .map(|b| {
+                reservoirs
+                    .get(&b.id)
+                    .map_or(vec![], |(_, points)| points.clone())
+            })
+            .collect()
+    }
+
+    /// Skips the points that make the distance or the combination function panic instead of propagating the panic,
+    /// see [Algo::try_fit].
+    ///
+    /// The ball changed by the point is saved before each fit and restored when a panic occurs,
+    /// thus the functions need not be unwind safe.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let combine = |p1: &Vec<f64>, w1: f64, p2: &Vec<f64>, w2: f64| {
+    ///     assert!(p2[0].is_finite(), "not finite");
+    ///     space::real_combine(p1, w1, p2, w2)
+    /// };
+    /// let algo = Algo::new(space::euclid_dist, combine).with_panic_guard();
+    /// let mut model = Model::new(space::euclid_dist);
+    /// algo.fit(&mut model, vec![0.]);
+    /// algo.fit(&mut model, vec![1.]);
+    /// assert!(algo.try_fit(&mut model, vec![f64::NAN]).is_err());
+    /// assert_eq!(&vec![1.], model.iter_balls().next().unwrap().center());
+    /// ```
+    pub fn with_panic_guard(mut self) -> Self {
+        let calling = Rc::new(Cell::new(None));
+        let dist = self.dist;
+        let dist_calling = Rc::clone(&calling);
+        self.dist = Box::new(move |p1, p2| {
+            dist_calling.set(Some(UserFn::Dist));
+            let d = dist(p1, p2);
+            dist_calling.set(None);
+            d
+        });
+        let combine = self.combine;
+        let combine_calling = Rc::clone(&calling);
+        self.combine = Box::new(move |p1, w1, p2, w2| {
+            combine_calling.set(Some(UserFn::Combine));
+            let center = combine(p1, w1, p2, w2);
+            combine_calling.set(None);
+            center
+        });
+        self.panic_guard = Some(PanicGuard {
+            snapshot: Ball::clone,
+            calling,
+            touched: RefCell::new(None),
+            created: Cell::new(false),
+        });
+        self
+    }
+
+    /// Captures the state accumulated while fitting points, so that a restarted algorithm resumes exactly
+    /// when it is given this state with [Algo::load_state] and the model is restored, e.g. from a checkpoint.
+    /// The state is serializable with serde.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// algo.fit(&mut model, vec![1.]);
+    /// algo.fit(&mut model, vec![100.]);
+    /// let state = serde_json::to_string(&algo.save_state()).unwrap();
+    /// let restarted = Algo::new(space::euclid_dist, space::real_combine);
+    /// restarted.load_state(serde_json::from_str(&state).unwrap()).unwrap();
+    /// assert_eq!(algo.split_count(), restarted.split_count());
+    /// ```
+    pub fn save_state(&self) -> AlgoState<Point> {
+        let (warmup, selected_threshold) = match &self.auto_threshold {
+            Some(auto) => (auto.buffer.borrow().clone(), auto.selected.get()),
+            None => (vec![], None),
+        };
+        let (drift_snapshot, drift) = match &self.drift {
+            Some(drift) => (drift.snapshot.borrow().clone(), drift.score.get()),
+            None => (vec![], 0.),
+        };
+        let normalization_warmup = match &self.normalizer {
+            Some(normalizer) => normalizer.buffer.borrow().clone(),
+            None => vec![],
+        };
+        let mut samples: Vec<(u64, u64, Vec<Point>)> = match &self.samples {
+            Some(samples) => samples
+                .reservoirs
+                .borrow()
+                .iter()
+                .map(|(id, (seen, points))| (*id, *seen, points.clone()))
+                .collect(),
+            None => vec![],
+        };
+        samples.sort_by_key(|(id, _, _)| *id);
+        AlgoState {
+            merges: self.merges.get(),
+            splits: self.splits.get(),
+            warmup,
+            selected_threshold,
+            drift_snapshot,
+            drift,
+            normalization_warmup,
+            samples,
+        }
+    }
+
+    /// Restores the state captured by [Algo::save_state].
+    /// Fails if the state carries warm-up, drift, normalization or sample data while the corresponding option is not enabled.
+    pub fn load_state(&self, state: AlgoState<Point>) -> Result<(), Box<dyn Error>> {
+        match &self.auto_threshold {
+            Some(auto) => {
+                *auto.buffer.borrow_mut() = state.warmup;
+                auto.selected.set(state.selected_threshold);
+            }
+            None if !state.warmup.is_empty() || state.selected_threshold.is_some() => {
+                return Err(
+                    "the state has a warm-up but the automatic threshold is not enabled".into(),
+                )
+            }
+            None => {}
+        }
+        match &self.drift {
+            Some(drift) => {
+                *drift.snapshot.borrow_mut() = state.drift_snapshot;
+                drift.score.set(state.drift);
+            }
+            None if !state.drift_snapshot.is_empty() => {
+                return Err("the state has a drift snapshot but drift is not enabled".into())
+            }
+            None => {}
+        }
+        match &self.normalizer {
+            Some(normalizer) => *normalizer.buffer.borrow_mut() = state.normalization_warmup,
+            None if !state.normalization_warmup.is_empty() => {
+                return Err(
+                    "the state has a normalization warm-up but normalization is not enabled".into(),
+                )
+            }
+            None => {}
+        }
+        match &self.samples {
+            Some(samples) => {
+                *samples.reservoirs.borrow_mut() = state
+                    .samples
+                    .into_iter()
+                    .map(|(id, seen, points)| (id, (seen, points)))
+                    .collect();
+                *samples.capacities.borrow_mut() = None;
+            }
+            None if !state.samples.is_empty() => {
+                return Err("the state has samples but the sample budget is not enabled".into())
+            }
+            None => {}
+        }
+        self.merges.set(state.merges);
+        self.splits.set(state.splits);
+        Ok(())
+    }
+}
+
+impl Algo<RealPoint> {
+    /// Checks that each result of the combination function lies at the expected position
+    /// along the segment between the combined points, i.e. that it behaves like a weighted mean:
+    /// `combine(a, w1, b, w2) = a + w2 / (w1 + w2) * (b - a)`.
+    ///
+    /// The algorithm panics when the check fails.
+    /// ```should_panic
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let skewed = |p1: &Vec<f64>, _: f64, p2: &Vec<f64>, _: f64| space::real_combine(p1, 1., p2, 1.);
+    /// let algo = Algo::new(space::euclid_dist, skewed).with_strict_combine();
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for point in [vec![0.], vec![1.], vec![2.]] {
+    ///     algo.fit(&mut model, point);
+    /// }
+    /// ```
+    pub fn with_strict_combine(mut self) -> Self {
+        let combine = self.combine;
+        self.combine = Box::new(move |p1, w1, p2, w2| {
+            let center = combine(p1, w1, p2, w2);
+            check_combine(p1, w1, p2, w2, &center);
+            center
+        });
+        self
+    }
+
+    /// Drops the points outside of the hyper-rectangle with the given bounds for each dimension, bounds included,
+    /// instead of fitting them. Points which dimension differs from the bounds are dropped as well.
+    /// Dropped points leave the model unchanged, make [Algo::try_fit] return [FitError::OutOfRegion]
+    /// and are counted by [Algo::rejected_out_of_region]; unlike [OutOfRange::Clamp], they are not moved into the region.
+    /// ```
+    /// use fluent_data::{algorithm::FitError, Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_valid_region(vec![0.], vec![10.]);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// assert_eq!(Err(FitError::OutOfRegion), algo.try_fit(&mut model, vec![11.]));
+    /// assert_eq!(0, model.iter_balls().count());
+    /// assert_eq!(1, algo.rejected_out_of_region());
+    /// ```
+    pub fn with_valid_region(mut self, min: Vec<f64>, max: Vec<f64>) -> Self {
+        assert_eq!(
+            min.len(),
+            max.len(),
+            "the region needs the same number of lower and upper bounds"
+        );
+        self.valid_region = Some(Box::new(move |point: &RealPoint| {
+            point.len() == min.len()
+                && point
+                    .iter()
+                    .zip(min.iter().zip(max.iter()))
+                    .all(|(x, (min, max))| min <= x && x <= max)
+        }));
+        self
+    }
+
+    /// Learns the bounds of each dimension from the first `warmup_points` points,
+    /// then normalizes all points into `[0, 1]` before they are fitted, see [Normalization].
+    ///
+    /// Warm-up points are buffered, not fitted, until the buffer is full. The normalization is then
+    /// stored in the model, see [Model::normalization], and the buffered points are fitted.
+    /// A model which already has a normalization, e.g. restored by [Model::with_normalization], skips the warm-up.
+    /// Later points outside of the bounds are clamped or passed through depending on `out_of_range`.
+    /// Queries of the model take points in original units and outputs are written in original units,
+    /// except the radius which stays in normalized units and comes with its semi-axes in original units as `"radii"`.
+    /// ```
+    /// use fluent_data::{Algo, Model, model::OutOfRange, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_normalization(10, OutOfRange::Clamp);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for i in 0..10 {
+    ///     algo.fit(&mut model, vec![1000. + 10. * i as f64]);
+    /// }
+    /// assert_eq!(vec![1.], model.normalize_point(&[1090.]));
+    /// ```
+    pub fn with_normalization(mut self, warmup_points: usize, out_of_range: OutOfRange) -> Self {
+        assert!(warmup_points > 0, "the warm-up needs at least one point");
+        self.normalizer = Some(Normalizer {
+            buffer: RefCell::new(Vec::with_capacity(warmup_points)),
+            normalize: Box::new(move |model, buffer, point| {
+                if let Some(normalization) = &model.normalization {
+                    return Err(normalization.normalize(&point));
+                }
+                buffer.push(point);
+                if buffer.len() < warmup_points {
+                    return Ok(None);
+                }
+                let points = mem::take(buffer);
+                let normalization = Normalization::learn(&points, out_of_range);
+                let normalized = points.iter().map(|p| normalization.normalize(p)).collect();
+                model.set_normalization(normalization);
+                Ok(Some(normalized))
+            }),
+        });
+        self
+    }
+
+    /// Counts the fitted points by cell of a grid of the given `cell_size`, in bounded memory,
+    /// for streams with too many distinct regions to count them exactly.
+    ///
+    /// A cell is identified by the indexes `floor(x / cell_size)` of a point on each dimension,
+    /// and its count is estimated by [Algo::region_frequency] with a count-min sketch of `depth` rows
+    /// of `width` counters. The estimate of a cell is never lower than its count; for `N` fitted points,
+    /// it exceeds the count by more than `e.N / width` with a probability lower than `e^-depth`.
+    /// E.g. a width of 2719 and a depth of 5 overestimate by at most 0.1% of `N` for 99.3% of the cells.
+    /// Points are counted before they are normalized, see [Algo::with_normalization].
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_region_sketch(10., 2719, 5);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for x in [1., 2., 15., 3.] {
+    ///     algo.fit(&mut model, vec![x, 0.]);
+    /// }
+    /// assert_eq!(3, algo.region_frequency(&[0, 0]));
+    /// assert_eq!(1, algo.region_frequency(&[1, 0]));
+    /// ```
+    pub fn with_region_sketch(mut self, cell_size: f64, width: usize, depth: usize) -> Self {
+        assert!(cell_size > 0., "the cells must have a positive size");
+        assert!(
+            width > 0 && depth > 0,
+            "the sketch needs at least one counter"
+        );
+        self.sketch = Some(RegionSketch {
+            cell: Box::new(move |point: &RealPoint| {
+                point
+                    .iter()
+                    .map(|x| (x / cell_size).floor() as i64)
+                    .collect()
+            }),
+            counts: RefCell::new(vec![vec![0; width]; depth]),
+        });
+        self
+    }
+
+    /// Tracks the velocity of each ball, i.e. the displacement of its center per included point,
+    /// smoothed by an exponential moving average with the given `smoothing` factor in `]0, 1]`.
+    ///
+    /// New balls start at zero velocity and merged balls get the weighted mean of their velocities.
+    /// The velocity is available with [Ball::velocity] and is serialized as `"velocity"`.
+    /// The center of a ball lags behind a moving cluster as its weight grows;
+    /// the velocity converges to the cluster velocity when the weights are bounded by decay, see [Algo::fast_forward].
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_velocity(0.1);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for i in 0..10 {
+    ///     algo.fit(&mut model, vec![i as f64 / 10.]);
+    /// }
+    /// assert!(model.iter_balls().next().unwrap().velocity().unwrap()[0] > 0.);
+    /// ```
+    pub fn with_velocity(mut self, smoothing: f64) -> Self {
+        assert!(
+            smoothing > 0. && smoothing <= 1.,
+            "the smoothing factor must be in ]0, 1]"
+        );
+        self.velocity = Some(Box::new(move |ball, previous| {
+            let velocity = ball
+                .velocity
+                .get_or_insert_with(|| vec![0.; ball.center.len()]);
+            if let Some(previous) = previous {
+                for ((v, x), p) in velocity.iter_mut().zip(&ball.center).zip(previous) {
+                    *v += smoothing * (x - p - *v);
+                }
+            }
+        }));
+        self
+    }
+}
+
+/// The drift from the `previous` centers and weights to the `current` ones, see [Algo::with_drift].
+fn drift_score<Point>(
+    dist: &dyn Fn(&Point, &Point) -> f64,
+    previous: &[(Point, f64)],
+    current: &[(Point, f64)],
+) -> f64 {
+    let previous_total: f64 = previous.iter().map(|(_, w)| w).sum();
+    let current_total: f64 = current.iter().map(|(_, w)| w).sum();
+    if previous_total <= 0. || current_total <= 0. {
+        return 0.;
+    }
+    let mut matched = vec![0.; previous.len()];
+    let mut movement = 0.;
+    for (center, weight) in current {
+        let nearest = previous
+            .iter()
+            .map(|(c, _)| dist(center, c))
+            .enumerate()
+            .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+        if let Some((index, d)) = nearest {
+            let share = weight / current_total;
+            movement += share * d;
+            matched[index] += share;
+        }
+    }
+    let redistribution: f64 = previous
+        .iter()
+        .zip(matched)
+        .map(|((_, w), share)| (w / previous_total - share).abs())
+        .sum();
+    movement + redistribution / 2.
+}
+
+/// Panics if the `center` is not the weighted center of `p1 x w1` and `p2 x w2`.
+fn check_combine(p1: &RealPoint, w1: f64, p2: &RealPoint, w2: f64, center: &RealPoint) {
+    let t = w2 / (w1 + w2);
+    let scale = (w1.abs() + w2.abs()) / (w1 + w2).abs();
+    let valid = center.len() == p1.len()
+        && p1.iter().zip(p2).zip(center).all(|((x1, x2), c)| {
+            let expected = x1 + t * (x2 - x1);
+            let tolerance = 1E-9 * scale * (1. + x1.abs() + x2.abs());
+            (c - expected).abs() <= tolerance
+        });
+    assert!(
+        valid,
+        "combine function is not a weighted mean: combine({:?}, {}, {:?}, {}) returned {:?}",
+        p1, w1, p2, w2, center
+    );
+}
+
+/// Fits the points produced by the streamer into one model for each configuration.
+///
+/// Each incoming point is fitted into every model and a combined output is written,
+/// a json array with an object for each configuration: `{"name": ..., "model": [...]}`.
+/// The returned report contains the average anomaly score of each model, see [Model::anomaly_score].
+/// Fails when `configs` is empty.
+/// ```
+/// use fluent_data::{algorithm::{self, AlgoConfig}, space, streamer, Streamer};
+///
+/// let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))].into_iter();
+/// let streamer = Streamer::new(points, |_| Ok(()));
+/// let challenger = "name=challenger,decay=0.9".parse::<AlgoConfig>().unwrap();
+/// let configs = vec![AlgoConfig::default(), challenger];
+/// let report = algorithm::ab_run(streamer, configs, space::euclid_dist, space::real_combine).unwrap();
+/// assert_eq!("challenger", report.models[1].name);
+/// ```
+pub fn ab_run<Point, In, Out, Dist, Combine>(
+    streamer: Streamer<In, Out>,
+    configs: Vec<AlgoConfig>,
+    dist: Dist,
+    combine: Combine,
+) -> Result<RunReport, Box<dyn Error>>
+where
+    Point: PartialEq + Clone + Serialize + DeserializeOwned + 'static,
+    In: Iterator<Item = Result<String, Box<dyn Error>>>,
+    Out: FnMut(String) -> Result<(), Box<dyn Error>>,
+    Dist: Fn(&Point, &Point) -> f64 + Clone + 'static,
+    Combine: Fn(&Point, f64, &Point, f64) -> Point + Clone + 'static,
+{
+    let runs = configs
+        .into_iter()
+        .map(|config| {
+            let algo = Algo::new(dist.clone(), combine.clone()).with_config(config);
+            let model = Model::new(dist.clone());
+            (algo, model)
+        })
+        .collect();
+    Streamer::run_ab(streamer, runs)
+}
+
+/// Picks the threshold, see [AlgoConfig::threshold], that best models the `holdout` points
+/// once the `train` points are fitted, among the `candidates`.
+///
+/// Each candidate is scored by the inertia of the held-out points over their coverage:
+/// the inertia is the mean distance from a point to the nearest ball center, as given by `dist`,
+/// and the coverage is the fraction of the points which lie within the radius of the ball that most probably includes them,
+/// see [Model::is_within_radius]. The inertia is multiplied by the number of balls so that splitting a ball
+/// pays off only when it reduces the inertia in a greater proportion. The score is infinite when no point is covered.
+/// The candidate with the lowest score is returned, the first one in case of a tie.
+/// Returns an error if there is no candidate.
+/// ```
+/// use fluent_data::{algorithm, space};
+///
+/// let train: Vec<Vec<f64>> = (0..60).map(|i| vec![(i % 3) as f64 * 100. + (i % 7) as f64]).collect();
+/// let holdout: Vec<Vec<f64>> = (0..30).map(|i| vec![(i % 3) as f64 * 100. + (i % 5) as f64]).collect();
+/// let threshold = algorithm::tune_split_threshold(&train, &holdout, &[2., 4., 1000.], space::euclid_dist, space::real_combine);
+/// assert!(threshold.unwrap() < 1000.);
+/// ```
+pub fn tune_split_threshold<Point, Dist, Combine>(
+    train: &[Point],
+    holdout: &[Point],
+    candidates: &[f64],
+    dist: Dist,
+    combine: Combine,
+) -> Result<f64, Box<dyn Error>>
+where
+    Point: PartialEq + Clone + 'static,
+    Dist: Fn(&Point, &Point) -> f64 + Clone + 'static,
+    Combine: Fn(&Point, f64, &Point, f64) -> Point + Clone + 'static,
+{
+    let mut best: Option<(f64, f64)> = None;
+    for &threshold in candidates {
+        let config = AlgoConfig {
+            threshold,
+            ..AlgoConfig::default()
+        };
+        let algo = Algo::new(dist.clone(), combine.clone()).with_config(config);
+        let mut model = Model::new(dist.clone());
+        for point in train {
+            algo.fit(&mut model, point.clone());
+        }
+        let score = holdout_score(&model, &dist, holdout);
+        if best.is_none_or(|(_, best_score)| score < best_score) {
+            best = Some((threshold, score));
+        }
+    }
+    best.map(|(threshold, _)| threshold)
+        .ok_or_else(|| "no candidate threshold".into())
+}
+
+/// Scores held-out points by their inertia over their coverage, see [tune_split_threshold].
+fn holdout_score<Point: PartialEq + 'static>(
+    model: &Model<Point>,
+    dist: impl Fn(&Point, &Point) -> f64,
+    holdout: &[Point],
+) -> f64 {
+    let mut covered = 0;
+    let mut inertia = 0.;
+    for point in holdout {
+        if model.is_within_radius(point) == Some(true) {
+            covered += 1;
+        }
+        inertia += model
+            .iter_balls()
+            .map(|b| dist(point, b.center()))
+            .fold(f64::INFINITY, f64::min);
+    }
+    if covered == 0 {
+        return f64::INFINITY;
+    }
+    inertia * model.iter_balls().count() as f64 / covered as f64
+}
+
+/// Fits a batch of points by splitting it into `shards` contiguous shards that are fitted in parallel,
+/// then merges the models of the shards with [Model::merge] and [Algo::merge_overlapping].
+/// Requires the `rayon` feature.
+///
+/// The algorithm and the empty model of each shard, and of the merged model, are built by `new`,
+/// because they cannot be shared between threads.
+/// The result differs slightly from fitting the points one after the other: the balls of the shards are combined
+/// when they overlap, and their weights are the sums of the weights in each shard, which were decayed separately.
+/// The first ball of a shard which radius is not known yet, i.e. a shard of a single point, is left out.
+/// ```
+/// use fluent_data::{algorithm, space, Algo, Model};
+///
+/// let points: Vec<Vec<f64>> = (0..400).map(|i| vec![(i / 10 % 2) as f64 * 100. + (i % 3) as f64]).collect();
+/// let new = || (Algo::new(space::euclid_dist, space::real_combine), Model::new(space::euclid_dist));
+/// let model = algorithm::fit_batch_parallel(&points, 4, new);
+/// assert!(model.iter_balls().count() >= 2);
+/// ```
+#[cfg(feature = "rayon")]
+pub fn fit_batch_parallel<Point, New>(points: &[Point], shards: usize, new: New) -> Model<Point>
+where
+    Point: PartialEq + Clone + Send + Sync + 'static,
+    New: Fn() -> (Algo<Point>, Model<Point>) + Sync,
+{
+    use rayon::prelude::*;
+
+    let shard_len = points.len().div_ceil(shards.max(1)).max(1);
+    // models are not `Send`, their balls are
+    let shard_balls: Vec<Vec<Ball<Point>>> = points
+        .par_chunks(shard_len)
+        .map(|shard| {
+            let (algo, mut model) = new();
+            for point in shard {
+                algo.fit(&mut model, point.clone());
+            }
+            model
+                .iter_balls()
+                .filter(|b| b.radius.is_finite())
+                .map(|b| b.clone())
+                .collect()
+        })
+        .collect();
+    let (algo, mut model) = new();
+    for balls in shard_balls {
+        let (_, mut shard) = new();
+        for ball in balls {
+            shard.add_ball(ball, vec![]);
+        }
+        model.merge(&shard);
+    }
+    algo.merge_overlapping(&mut model);
+    model
+}
+
+#[cfg(test)]
+mod tests {
+    use approx_eq::assert_approx_eq;
+    use rand::{rngs::StdRng, SeedableRng};
+    use rand_distr::{Distribution, Normal};
+
+    use crate::algorithm::*;
     use crate::space;
 
     #[test]
@@ -268,6 +2333,24 @@ mod tests {
         assert_eq!(1., first.weight);
     }
 
+    #[test]
+    fn test_identical_points() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        for _ in 0..3 {
+            algo.fit(&mut model, vec![5., -1.]);
+        }
+        let first = model.iter_balls().next().unwrap().clone();
+        assert_eq!(1, model.iter_balls().count());
+        assert_eq!(f64::INFINITY, first.radius);
+        assert_eq!(2., first.weight);
+        algo.fit(&mut model, vec![1., 1.]);
+        assert_eq!(1, model.iter_balls().count());
+        let first = model.iter_balls().next().unwrap().clone();
+        assert_eq!(20., first.radius);
+        assert_eq!(3., first.weight);
+    }
+
     #[test]
     fn test_new() {
         let (dataset, model) = build_model(3);
@@ -355,6 +2438,491 @@ mod tests {
         assert!(n1.next().is_none());
     }
 
+    #[test]
+    fn test_merge_count() {
+        let dataset = build_sample();
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut merged = false;
+        for point in dataset.iter().take(8) {
+            let (balls, merges) = (model.iter_balls().count(), algo.merge_count());
+            algo.fit(&mut model, point.clone());
+            if model.iter_balls().count() < balls {
+                assert_eq!(merges + 1, algo.merge_count());
+                merged = true;
+            }
+            let expected = 1 + algo.split_count() - algo.merge_count();
+            assert_eq!(expected as usize, model.iter_balls().count());
+        }
+        assert!(merged);
+    }
+
+    #[test]
+    fn test_last_updated_ball() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let balls = vec![Ball::new(vec![0.], 1., 5.), Ball::new(vec![10.], 1., 5.)];
+        let mut model = Model::load(space::euclid_dist, balls);
+        assert_eq!(None, algo.last_updated_ball());
+        algo.fit(&mut model, vec![9.]);
+        assert_eq!(Some(1), algo.last_updated_ball());
+        let ball = model.iter_balls().nth(1).unwrap().clone();
+        assert!(ball.center[0] < 10.);
+        assert_eq!(6., ball.weight);
+        algo.fit(&mut model, vec![0.5]);
+        assert_eq!(Some(0), algo.last_updated_ball());
+        algo.fit(&mut model, vec![100.]);
+        assert_eq!(Some(2), algo.last_updated_ball());
+        assert_eq!(1., model.iter_balls().nth(2).unwrap().weight);
+    }
+
+    #[test]
+    fn test_global_sample_budget() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let normal = Normal::new(0., 1.).unwrap();
+        let config = AlgoConfig {
+            decay: 1.,
+            ..AlgoConfig::default()
+        };
+        let algo = Algo::new(space::euclid_dist, space::real_combine)
+            .with_config(config)
+            .with_seed(5)
+            .with_global_sample_budget(50);
+        let mut model = Model::new(space::euclid_dist);
+        for cluster in 0..40 {
+            for _ in 0..10 {
+                algo.fit(
+                    &mut model,
+                    vec![100. * cluster as f64 + normal.sample(&mut rng)],
+                );
+                let samples = algo.ball_samples(&model);
+                assert_eq!(model.iter_balls().count(), samples.len());
+                assert!(samples.iter().map(|s| s.len()).sum::<usize>() <= 50);
+            }
+        }
+        assert!(model.iter_balls().count() >= 20);
+        // the whole budget is shared, although each ball gets less than one point
+        let capacities = algo.samples.as_ref().unwrap().capacities.borrow();
+        assert_eq!(50, capacities.as_ref().unwrap().1.values().sum::<usize>());
+        let samples = algo.ball_samples(&model);
+        let total: usize = samples.iter().map(|s| s.len()).sum();
+        assert!(total > 10);
+        for (ball, sample) in model.iter_balls().zip(samples.iter()) {
+            assert!(sample.iter().all(|p| (p[0] - ball.center[0]).abs() < 50.));
+        }
+        let unsampled = Algo::new(space::euclid_dist, space::real_combine);
+        assert!(unsampled.ball_samples(&model).is_empty());
+        let rebuilt = algo.rebuild_with(space::euclid_dist, space::real_combine);
+        assert_eq!(samples, rebuilt.ball_samples(&model));
+        let state = algo.save_state();
+        let resumed =
+            Algo::new(space::euclid_dist, space::real_combine).with_global_sample_budget(50);
+        resumed.load_state(state.clone()).unwrap();
+        assert_eq!(samples, resumed.ball_samples(&model));
+        assert!(unsampled.load_state(state).is_err());
+    }
+
+    #[test]
+    fn test_region_sketch() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (width, depth) = (100, 4);
+        let algo =
+            Algo::new(space::euclid_dist, space::real_combine).with_region_sketch(1., width, depth);
+        let mut model = Model::new(space::euclid_dist);
+        // a few heavy cells among many cells of a single point
+        let heavy = [(0, 0, 500), (3, -2, 300), (-7, 5, 200), (10, 10, 50)];
+        for &(i, j, count) in heavy.iter() {
+            for _ in 0..count {
+                let (x, y): (f64, f64) = (rng.gen(), rng.gen());
+                algo.fit(&mut model, vec![i as f64 + x, j as f64 + y]);
+            }
+        }
+        for k in 0..1000 {
+            algo.fit(&mut model, vec![1000. + k as f64 + 0.5, -1000.5]);
+        }
+        let total = 2050.;
+        let bound = std::f64::consts::E * total / width as f64;
+        for &(i, j, count) in heavy.iter() {
+            let estimate = algo.region_frequency(&[i, j]);
+            assert!(estimate >= count);
+            assert!(((estimate - count) as f64) <= bound);
+        }
+        let singles: Vec<u64> = (0..1000)
+            .map(|k| algo.region_frequency(&[1000 + k, -1001]))
+            .collect();
+        assert!(singles.iter().all(|&e| e >= 1));
+        let errors = singles.iter().filter(|&&e| (e - 1) as f64 > bound).count();
+        assert!((errors as f64) < 1000. * (-(depth as f64)).exp());
+        assert!((algo.region_frequency(&[-50, -50]) as f64) <= bound);
+        let unsketched = Algo::new(space::euclid_dist, space::real_combine);
+        assert_eq!(0, unsketched.region_frequency(&[0, 0]));
+    }
+
+    #[test]
+    fn test_center_refresh() {
+        // rounds centers to single precision, thus incremental updates smaller than the rounding are lost
+        let combine = |p1: &Vec<f64>, w1: f64, p2: &Vec<f64>, w2: f64| {
+            let center = space::real_combine(p1, w1, p2, w2);
+            center.iter().map(|x| *x as f32 as f64).collect::<Vec<_>>()
+        };
+        let offset = 1E6;
+        let normal = Normal::new(offset, 1.).unwrap();
+        let (mut refreshed_error, mut incremental_error) = (0., 0.);
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let refreshing = Algo::new(space::euclid_dist, combine)
+                .with_seed(seed)
+                .with_global_sample_budget(200)
+                .with_center_refresh(50);
+            let incremental = Algo::new(space::euclid_dist, combine);
+            let ball = Ball::new(vec![offset], 100., 1.);
+            let mut refreshed = Model::load(space::euclid_dist, vec![ball.clone()]);
+            let mut updated = Model::load(space::euclid_dist, vec![ball]);
+            for _ in 0..1000 {
+                let point = vec![normal.sample(&mut rng)];
+                refreshing.fit(&mut refreshed, point.clone());
+                incremental.fit(&mut updated, point);
+            }
+            let samples = &refreshing.ball_samples(&refreshed)[0];
+            let center = refreshed.iter_balls().next().unwrap().center[0];
+            let mean = samples.iter().map(|p| p[0] - offset).sum::<f64>() / samples.len() as f64;
+            assert!((mean - (center - offset)).abs() < 0.1);
+            refreshed_error += (center - offset).abs();
+            incremental_error += (updated.iter_balls().next().unwrap().center[0] - offset).abs();
+        }
+        assert!(refreshed_error < incremental_error / 2.);
+    }
+
+    #[test]
+    fn test_save_state() {
+        use crate::serializer;
+
+        let mut rng = StdRng::seed_from_u64(11);
+        let normal = Normal::new(0., 1.).unwrap();
+        let dataset: Vec<Vec<f64>> = (0..400)
+            .map(|i| vec![20. * ((i / 10) % 3) as f64 + normal.sample(&mut rng)])
+            .collect();
+        let new_algo = || {
+            Algo::new(space::euclid_dist, space::real_combine)
+                .with_auto_threshold(100)
+                .with_drift()
+        };
+        let algo = new_algo();
+        let mut model = Model::new(space::euclid_dist);
+        for point in dataset.iter() {
+            algo.fit(&mut model, point.clone());
+        }
+        for restart in [40, 250] {
+            let first = new_algo();
+            let mut first_model = Model::new(space::euclid_dist);
+            for point in dataset[..restart].iter() {
+                first.fit(&mut first_model, point.clone());
+            }
+            let state = serde_json::to_string(&first.save_state()).unwrap();
+            let mut checkpoint = String::new();
+            serializer::checkpoint_columns(&first_model, &mut checkpoint).unwrap();
+            let resumed = new_algo();
+            resumed
+                .load_state(serde_json::from_str(&state).unwrap())
+                .unwrap();
+            let balls = serializer::parse_columnar(&checkpoint).unwrap();
+            let mut resumed_model = Model::restore(space::euclid_dist, balls);
+            for point in dataset[restart..].iter() {
+                resumed.fit(&mut resumed_model, point.clone());
+            }
+            assert_eq!(algo.save_state(), resumed.save_state());
+            assert_eq!(
+                model.iter_balls().count(),
+                resumed_model.iter_balls().count()
+            );
+            for (ball, resumed_ball) in model.iter_balls().zip(resumed_model.iter_balls()) {
+                assert_eq!(ball.center, resumed_ball.center);
+                assert_eq!(ball.weight, resumed_ball.weight);
+                // the checkpoint holds the radius, not its square
+                assert_approx_eq!(ball.radius, resumed_ball.radius, 1E-12);
+                assert_approx_eq!(ball.dist_mean, resumed_ball.dist_mean, 1E-12);
+                assert_approx_eq!(ball.dist_var, resumed_ball.dist_var, 1E-12);
+                assert_eq!(ball.assigned, resumed_ball.assigned);
+                assert_eq!(ball.recent, resumed_ball.recent);
+                assert_eq!(ball.frozen, resumed_ball.frozen);
+            }
+        }
+        assert!(algo.merge_count() > 0);
+        assert!(algo.selected_threshold().is_some());
+        let plain = Algo::new(space::euclid_dist, space::real_combine);
+        assert!(plain.load_state(algo.save_state()).is_err());
+
+        // a state saved during the warm-up of the normalization keeps the buffered points
+        let new_algo = || {
+            Algo::new(space::euclid_dist, space::real_combine)
+                .with_normalization(20, OutOfRange::Clamp)
+        };
+        let algo = new_algo();
+        let mut model = Model::new(space::euclid_dist);
+        for point in dataset[..40].iter() {
+            algo.fit(&mut model, point.clone());
+        }
+        let first = new_algo();
+        for point in dataset[..10].iter() {
+            first.fit(&mut Model::new(space::euclid_dist), point.clone());
+        }
+        let state = first.save_state();
+        assert_eq!(10, state.normalization_warmup.len());
+        let resumed = new_algo();
+        resumed.load_state(state.clone()).unwrap();
+        let mut resumed_model = Model::new(space::euclid_dist);
+        for point in dataset[10..40].iter() {
+            resumed.fit(&mut resumed_model, point.clone());
+        }
+        assert_eq!(model.normalization(), resumed_model.normalization());
+        assert_eq!(model.fingerprint(), resumed_model.fingerprint());
+        assert!(plain.load_state(state).is_err());
+    }
+
+    #[test]
+    fn test_valid_region() {
+        let dataset = build_sample();
+        let in_region = |p: &Vec<f64>| p.iter().all(|x| (-10. ..=20.).contains(x));
+        let algo = Algo::new(space::euclid_dist, space::real_combine)
+            .with_valid_region(vec![-10., -10.], vec![20., 20.]);
+        let mut model = Model::new(space::euclid_dist);
+        let expected_algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut expected = Model::new(space::euclid_dist);
+        for point in dataset.iter() {
+            algo.fit(&mut model, point.clone());
+            if in_region(point) {
+                expected_algo.fit(&mut expected, point.clone());
+            }
+        }
+        assert_eq!(
+            Err(FitError::OutOfRegion),
+            algo.try_fit(&mut model, vec![0.])
+        );
+        let rejected = dataset.iter().filter(|p| !in_region(p)).count();
+        assert!(rejected > 0 && rejected < dataset.len());
+        assert_eq!(rejected as u64 + 1, algo.rejected_out_of_region());
+        let balls = |model: &Model<Vec<f64>>| -> Vec<Ball<Vec<f64>>> {
+            model.iter_balls().map(|b| b.clone()).collect()
+        };
+        assert_eq!(balls(&expected), balls(&model));
+    }
+
+    #[test]
+    fn test_std_dev_radius() {
+        let normal = Normal::new(0., 1.).unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        let dataset: Vec<Vec<f64>> = (0..5000).map(|_| vec![normal.sample(&mut rng)]).collect();
+        let algo = Algo::new(space::euclid_dist, space::real_combine)
+            .with_radius_strategy(RadiusStrategy::StdDev(2.));
+        let mut model = Model::new(space::euclid_dist);
+        for point in dataset.iter() {
+            algo.fit(&mut model, point.clone());
+        }
+        let ball = model
+            .iter_balls()
+            .max_by(|b1, b2| b1.weight.total_cmp(&b2.weight))
+            .unwrap();
+        let dists: Vec<f64> = dataset
+            .iter()
+            .map(|p| (p[0] - ball.center[0]).abs())
+            .collect();
+        let mean = dists.iter().sum::<f64>() / dists.len() as f64;
+        let var = dists.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / dists.len() as f64;
+        assert_approx_eq!(2. * var.sqrt(), ball.radius(), 5E-2);
+    }
+
+    #[test]
+    fn test_min_radius() {
+        let fit = |algo: Algo<Vec<f64>>| {
+            let mut model = Model::new(space::euclid_dist);
+            algo.fit(&mut model, vec![0., 0.]);
+            for _ in 0..1000 {
+                algo.fit(&mut model, vec![1., 2.]);
+            }
+            algo.fit(&mut model, vec![1.5, 2.]);
+            model
+        };
+        let collapsed = fit(Algo::new(space::euclid_dist, space::real_combine));
+        assert!(collapsed.iter_balls().count() > 1);
+        let floored = fit(Algo::new(space::euclid_dist, space::real_combine).with_min_radius(0.5));
+        assert_eq!(1, floored.iter_balls().count());
+        let ball = floored.iter_balls().next().unwrap();
+        assert_eq!(1001., ball.weight());
+        assert!(ball.radius() >= 0.5);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: AlgoConfig = "name=candidate,decay=0.9,threshold=3".parse().unwrap();
+        assert_eq!("candidate", config.name);
+        assert_eq!(0.9, config.decay);
+        assert_eq!(3., config.threshold);
+        let config: AlgoConfig = "threshold=3".parse().unwrap();
+        assert_eq!(DECAY_FACTOR, config.decay);
+        assert!("decay".parse::<AlgoConfig>().is_err());
+        assert!("speed=1".parse::<AlgoConfig>().is_err());
+        let config: AlgoConfig = "seed=42".parse().unwrap();
+        assert_eq!(Some(42), config.seed);
+        assert!("seed=-1".parse::<AlgoConfig>().is_err());
+    }
+
+    #[test]
+    fn test_ab_run() {
+        let dataset = build_sample();
+        let points = dataset
+            .iter()
+            .map(|p| Ok(serde_json::to_string(p).unwrap()));
+        let mut outputs: Vec<String> = vec![];
+        let write = |s| {
+            outputs.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write);
+        let challenger: AlgoConfig = "name=challenger,decay=0.5,threshold=3".parse().unwrap();
+        let configs = vec![AlgoConfig::default(), challenger];
+        let report = ab_run(streamer, configs, space::euclid_dist, space::real_combine).unwrap();
+        assert_eq!(dataset.len(), outputs.len());
+        let last: serde_json::Value = serde_json::from_str(outputs.last().unwrap()).unwrap();
+        assert_eq!("default", last[0]["name"]);
+        assert_eq!("challenger", last[1]["name"]);
+        assert!(!last[0]["model"].as_array().unwrap().is_empty());
+        assert!(!last[1]["model"].as_array().unwrap().is_empty());
+        assert_eq!(2, report.models.len());
+        for model in report.models {
+            assert_eq!(dataset.len() - 1, model.scored);
+            assert!(model.mean_anomaly_score > 0.);
+        }
+    }
+
+    #[test]
+    fn test_ab_run_without_config() {
+        let points = vec![Ok(String::from("[1.0]"))].into_iter();
+        let streamer = Streamer::new(points, |_| Ok(()));
+        let result = ab_run(streamer, vec![], space::euclid_dist, space::real_combine);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_strict_combine() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_strict_combine();
+        let mut model = Model::new(space::euclid_dist);
+        for point in build_sample() {
+            algo.fit(&mut model, point);
+        }
+        assert_eq!(3, model.graph.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "combine function is not a weighted mean")]
+    fn test_strict_combine_skewed() {
+        let skewed = |p1: &Vec<f64>, w1: f64, p2: &Vec<f64>, w2: f64| {
+            space::real_combine(p1, w1, p2, 2. * w2)
+        };
+        let algo = Algo::new(space::euclid_dist, skewed).with_strict_combine();
+        let mut model = Model::new(space::euclid_dist);
+        for point in build_sample() {
+            algo.fit(&mut model, point);
+        }
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let (_dataset, mut model) = build_model(8);
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let weights: Vec<f64> = model.iter_balls().map(|b| b.weight).collect();
+        algo.fast_forward(&mut model, 20);
+        let decayed: Vec<f64> = model.iter_balls().map(|b| b.weight).collect();
+        assert_eq!(weights.len(), decayed.len());
+        for (w, d) in weights.iter().zip(decayed) {
+            assert_approx_eq!(w * DECAY_FACTOR.powi(20), d);
+        }
+    }
+
+    #[test]
+    fn test_fast_forward_long_horizon() {
+        let config: AlgoConfig = "floor=1E-300".parse().unwrap();
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+        let mut model = Model::load(
+            space::euclid_dist,
+            vec![Ball::new(vec![0.], 1., 1E300), Ball::new(vec![9.], 1., 1.)],
+        );
+        for _ in 0..10 {
+            algo.fast_forward(&mut model, 1_000);
+            for ball in model.iter_balls() {
+                assert!(ball.weight.is_normal());
+            }
+        }
+        assert_eq!(2, model.iter_balls().count());
+        algo.fast_forward(&mut model, 5_000);
+        assert_eq!(1, model.iter_balls().count());
+        algo.fast_forward(&mut model, 10_000_000);
+        assert_eq!(0, model.iter_balls().count());
+    }
+
+    #[test]
+    fn test_zero_floor() {
+        let config: AlgoConfig = "floor=0".parse().unwrap();
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+        let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+        algo.fast_forward(&mut model, 14_000);
+        assert!(model.iter_balls().all(|b| b.weight.is_normal()));
+        algo.fast_forward(&mut model, 100);
+        assert_eq!(0, model.iter_balls().count());
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let normal = Normal::new(0., 1.).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let points: Vec<RealPoint> = (0..150)
+            .map(|i| {
+                let offset = if i < 50 { -50. } else { 50. };
+                vec![offset + normal.sample(&mut rng)]
+            })
+            .collect();
+        let config: AlgoConfig = "decay=1".parse().unwrap();
+        let algo = Algo::new(space::euclid_dist, space::real_combine)
+            .with_config(config)
+            .sliding_window(100);
+        let mut model = Model::new(space::euclid_dist);
+        for point in points[..100].iter() {
+            algo.fit(&mut model, point.clone());
+        }
+        assert!(model.iter_balls().any(|b| b.center[0] < 0.));
+        let ids: Vec<u64> = model
+            .iter_balls()
+            .filter(|b| b.center[0] > 0.)
+            .map(|b| b.id())
+            .collect();
+        let (merges, splits) = (algo.merge_count(), algo.split_count());
+        for point in points[100..].iter() {
+            algo.fit(&mut model, point.clone());
+        }
+        assert_eq!(100, model.recent.len());
+        assert!(model.iter_balls().all(|b| b.center[0] > 0.));
+        let weight: f64 = model.iter_balls().map(|b| b.weight()).sum();
+        assert_eq!(100., weight);
+        // balls are updated in place when points fall out of the window
+        assert_eq!(ids, model.iter_balls().map(|b| b.id()).collect::<Vec<_>>());
+        assert_eq!(merges, algo.merge_count());
+        assert_eq!(splits, algo.split_count());
+    }
+
+    #[test]
+    fn test_assignments_sliding_window() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).sliding_window(10);
+        let mut model = Model::new(space::euclid_dist);
+        for i in 0..100 {
+            algo.fit(&mut model, vec![(i * 37 % 101) as f64]);
+            if i % 7 == 6 {
+                let assignments = model.take_assignments();
+                assert_eq!(
+                    7,
+                    assignments.balls.iter().sum::<u64>() + assignments.removed
+                );
+            }
+        }
+    }
+
     fn build_model(count: usize) -> (Vec<Vec<f64>>, Model<Vec<f64>>) {
         let dataset = build_sample();
         let algo = Algo::new(space::euclid_dist, space::real_combine);
@@ -365,6 +2933,482 @@ mod tests {
         (dataset, model)
     }
 
+    #[test]
+    fn test_frozen_ball() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let reference = Ball::new(vec![0.], 1., 10.).with_frozen(true);
+        let mut model = Model::load(space::euclid_dist, vec![reference.clone()]);
+        for i in 0..100 {
+            algo.fit(&mut model, vec![i as f64 / 100.]);
+        }
+        let ball = model.iter_balls().next().unwrap().clone();
+        assert!(ball.is_frozen());
+        assert_eq!(reference.center, ball.center);
+        assert_eq!(reference.radius, ball.radius);
+        assert_eq!(reference.weight, ball.weight);
+        assert_eq!(100, ball.assigned);
+        model.unfreeze_ball(0).unwrap();
+        algo.fit(&mut model, vec![0.5]);
+        assert_ne!(reference.center, model.iter_balls().next().unwrap().center);
+    }
+
+    #[test]
+    fn test_assign_to() {
+        let balls = vec![
+            Ball::new(vec![0., 0.], 4., 3.),
+            Ball::new(vec![10., 0.], 4., 3.),
+        ];
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::load(space::euclid_dist, balls.clone());
+        let point = vec![1., 0.];
+        assert_eq!(Some((0, 0.25)), model.classify(&point));
+        algo.assign_to(&mut model, 1, point.clone()).unwrap();
+        assert_eq!(Some(1), algo.last_updated_ball());
+        let assigned: Vec<_> = model.iter_balls().map(|b| b.clone()).collect();
+        assert_eq!(vec![0., 0.], assigned[0].center);
+        assert_eq!(3. * DECAY_FACTOR, assigned[0].weight);
+        assert_eq!(vec![7.75, 0.], assigned[1].center);
+        assert_eq!(4., assigned[1].weight);
+        assert_eq!(4. + (81. - 4.) / 4., assigned[1].radius);
+        assert_eq!(2. + 7. / 4., assigned[1].dist_mean);
+        assert_eq!((1, 0), (assigned[1].included, assigned[1].inside));
+        assert_eq!(vec![0, 1], model.take_assignments().balls);
+        // the ball is updated as a fitted point would update it
+        let algo = Algo::new(space::euclid_dist, space::real_combine)
+            .with_radius_strategy(RadiusStrategy::StdDev(2.))
+            .with_min_radius(10.);
+        let mut model = Model::load(space::euclid_dist, balls);
+        algo.assign_to(&mut model, 1, point.clone()).unwrap();
+        assert_eq!(100., model.iter_balls().nth(1).unwrap().radius);
+        assert!(algo.assign_to(&mut model, 2, point.clone()).is_err());
+        model.freeze_ball(0).unwrap();
+        assert!(algo.assign_to(&mut model, 0, point.clone()).is_err());
+        let algo = Algo::new(space::euclid_dist, space::real_combine).sliding_window(2);
+        algo.assign_to(&mut model, 1, point).unwrap();
+        assert_eq!(1, model.recent.len());
+    }
+
+    #[test]
+    fn test_recent_weight_share() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let data = vec![Ball::new(vec![0.], 1., 50.), Ball::new(vec![100.], 1., 1.)];
+        let mut model = Model::load(space::euclid_dist, data)
+            .with_recent_window(std::time::Duration::from_millis(10));
+        for _ in 0..20 {
+            algo.fit(&mut model, vec![0.1]);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        for _ in 0..3 {
+            algo.fit(&mut model, vec![100.1]);
+        }
+        let weights: Vec<f64> = model.iter_balls().map(|b| b.weight).collect();
+        assert!(weights[0] > 10. * weights[1]);
+        let shares = model.recent_weight_share();
+        assert!(shares[1] > 0.99);
+        assert_approx_eq!(1., shares.iter().sum::<f64>());
+    }
+
+    #[test]
+    fn test_auto_threshold() {
+        const SEPARATION: f64 = 20.;
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_auto_threshold(300);
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(8);
+        let normal = Normal::new(0., 1.).unwrap();
+        let centers = [[0., 0.], [SEPARATION, 0.], [0., SEPARATION]];
+        for i in 0..1000 {
+            let center = centers[(i / 20) % 3];
+            let point = vec![
+                center[0] + normal.sample(&mut rng),
+                center[1] + normal.sample(&mut rng),
+            ];
+            algo.fit(&mut model, point);
+        }
+        let threshold = algo.selected_threshold().unwrap();
+        assert_eq!(threshold, algo.effective_config().threshold);
+        // radius of a 2D standard normal cluster
+        let radius = f64::sqrt(2.);
+        assert!(radius < threshold * radius);
+        assert!(threshold * radius < SEPARATION / 2.);
+        assert!(model.iter_balls().filter(|b| b.weight > 1.).count() <= 6);
+    }
+
+    #[test]
+    fn test_max_center_step() {
+        let data = || vec![Ball::new(vec![0., 0.], 100., 1.)];
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::load(space::euclid_dist, data());
+        algo.fit(&mut model, vec![6., 8.]);
+        assert_eq!(vec![3., 4.], model.iter_balls().next().unwrap().center);
+        let algo = algo.with_max_center_step(1.);
+        let mut model = Model::load(space::euclid_dist, data());
+        algo.fit(&mut model, vec![6., 8.]);
+        let center = model.iter_balls().next().unwrap().center.clone();
+        assert!(space::euclid_dist(&vec![0., 0.], &center).sqrt() <= 1. + 1E-12);
+        assert_approx_eq!(0.6, center[0]);
+        assert_approx_eq!(0.8, center[1]);
+    }
+
+    #[test]
+    fn test_preview_fit() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(7);
+        let normal = Normal::new(0., 1.).unwrap();
+        let (mut included, mut created) = (0, 0);
+        for i in 0..300 {
+            let offset = (i % 3) as f64 * 20.;
+            let point = vec![offset + normal.sample(&mut rng), normal.sample(&mut rng)];
+            let preview = algo.preview_fit(&model, &point);
+            let before: Vec<Ball<Vec<f64>>> = model.iter_balls().map(|b| b.clone()).collect();
+            algo.fit(&mut model, point);
+            let after: Vec<Ball<Vec<f64>>> = model.iter_balls().map(|b| b.clone()).collect();
+            match preview.target {
+                // the ball was neither merged nor removed
+                FitTarget::Ball(index) if after.len() == before.len() => {
+                    let shift = space::euclid_dist(&before[index].center, &after[index].center);
+                    assert_approx_eq!(preview.center_shift, shift.sqrt());
+                    assert_approx_eq!(preview.radius, after[index].radius());
+                    included += 1;
+                }
+                FitTarget::NewBall if after.len() == before.len() + 1 => {
+                    let radius = |b: &Ball<Vec<f64>>| b.radius().min(f64::MAX);
+                    let expected = preview.radius.min(f64::MAX);
+                    assert!(after.iter().any(|b| (radius(b) - expected).abs() < 1E-9));
+                    created += 1;
+                }
+                FitTarget::Ball(_) => assert!(after.len() < before.len()),
+                FitTarget::NewBall => assert!(after.len() <= before.len()),
+            }
+        }
+        assert!(included > 100);
+        assert!(created > 0);
+    }
+
+    #[test]
+    fn test_fit_with_uncertainty() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let fitted = |sigma| {
+            let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0., 0.], 1., 10.)]);
+            algo.fit_with_uncertainty(&mut model, vec![1., 0.], sigma);
+            let ball = model.iter_balls().next().unwrap().clone();
+            ball
+        };
+        let certain = fitted(0.);
+        let low = fitted(0.1);
+        let high = fitted(2.);
+        assert_eq!(11., high.weight());
+        assert!(high.radius() > low.radius());
+        assert!(high.center()[0] < low.center()[0]);
+        assert_approx_eq!(certain.center()[0], 1. / 11.);
+        assert!(low.center()[0] > 0.9 / 11.);
+        assert!((low.radius() - certain.radius()).abs() < 0.01);
+        assert!(high.radius() > 1.1 * certain.radius());
+    }
+
+    #[test]
+    fn test_panic_guard_rollback() {
+        // panics when balls are merged, after the closest ball was updated
+        let combine = |p1: &Vec<f64>, w1: f64, p2: &Vec<f64>, w2: f64| {
+            assert!(w2 == 1. || w2 == 5., "merge");
+            space::real_combine(p1, w1, p2, w2)
+        };
+        let algo = Algo::new(space::euclid_dist, combine).with_panic_guard();
+        let mut model = Model::new(space::euclid_dist);
+        let mut failures = 0;
+        for point in build_sample().into_iter().take(8) {
+            let before: Vec<Ball<Vec<f64>>> = model.iter_balls().map(|b| b.clone()).collect();
+            if let Err(error) = algo.try_fit(&mut model, point) {
+                assert_eq!(
+                    FitError::UserFnPanicked {
+                        which: UserFn::Combine,
+                        payload_message: String::from("merge")
+                    },
+                    error
+                );
+                let after: Vec<Ball<Vec<f64>>> = model.iter_balls().map(|b| b.clone()).collect();
+                assert_eq!(before, after);
+                failures += 1;
+            }
+        }
+        assert!(failures > 0);
+    }
+
+    #[test]
+    fn test_frozen_ball_never_merged() {
+        let config: AlgoConfig = "decay=0.5".parse().unwrap();
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+        let mut model = Model::new(space::euclid_dist);
+        for x in [0., 1., 0.5, 10.] {
+            algo.fit(&mut model, vec![x]);
+        }
+        model.freeze_ball(0).unwrap();
+        let reference = model.iter_balls().next().unwrap().clone();
+        for i in 0..200 {
+            algo.fit(&mut model, vec![10. - i as f64 / 20.]);
+        }
+        let unchanged = |b: &Ball<Vec<f64>>| {
+            b.center == reference.center
+                && b.radius == reference.radius
+                && b.weight == reference.weight
+        };
+        assert!(model.iter_balls().any(|b| b.frozen && unchanged(&b)));
+    }
+
+    #[test]
+    fn test_budget() {
+        let config: AlgoConfig = "decay=0.999,threshold=2".parse().unwrap();
+        let full = Algo::new(space::euclid_dist, space::real_combine).with_config(config.clone());
+        let budgeted = Algo::new(space::euclid_dist, space::real_combine)
+            .with_config(config)
+            .with_budget(BUDGET);
+        let mut full_model = Model::new(space::euclid_dist);
+        let mut budgeted_model = Model::new(space::euclid_dist);
+        for point in build_grid_sample(20000, 1) {
+            full.fit(&mut full_model, point.clone());
+            budgeted.fit(&mut budgeted_model, point);
+            assert!(budgeted_model.evaluated_candidates() <= BUDGET);
+        }
+        // mean distance from held out points to the closest center
+        let score = |model: &Model<Vec<f64>>| {
+            let held_out = build_grid_sample(5000, 2);
+            held_out
+                .iter()
+                .map(|p| {
+                    model
+                        .iter_balls()
+                        .map(|b| space::euclid_dist(p, b.center()).sqrt())
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .sum::<f64>()
+                / held_out.len() as f64
+        };
+        assert!(budgeted_model.truncated_searches() > 0);
+        assert!(score(&budgeted_model) < 3. * score(&full_model));
+    }
+
+    const BUDGET: usize = 8;
+
+    /// Points drawn around the nodes of a 10x10 grid, visited in turn by bursts of 50 points.
+    fn build_grid_sample(count: usize, seed: u64) -> Vec<Vec<f64>> {
+        let normal = Normal::new(0., 1.).unwrap();
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|i| {
+                let node = (i / 50 * 37) % 100;
+                let x = (node % 10) as f64 * 100. + normal.sample(&mut rng);
+                let y = (node / 10) as f64 * 100. + normal.sample(&mut rng);
+                vec![x, y]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_normalization() {
+        use crate::{
+            model::OutOfRange,
+            serializer::{self, ColumnarJsonSerializer, JsonSerializer, ModelSerializer},
+        };
+
+        let algo = Algo::new(space::euclid_dist, space::real_combine)
+            .with_normalization(60, OutOfRange::Clamp);
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(3);
+        let normal = Normal::new(0., 1.).unwrap();
+        let clusters = [(1000., 0.002), (2000., 0.008)];
+        // the warm-up points come from one cluster, then the other
+        let mut sample = |i: usize| {
+            let (x, y) = clusters[if i < 60 { i / 30 } else { i % 2 }];
+            vec![
+                x + 10. * normal.sample(&mut rng),
+                y + 1E-4 * normal.sample(&mut rng),
+            ]
+        };
+        for i in 0..400 {
+            algo.fit(&mut model, sample(i));
+        }
+        let normalization = model.normalization().unwrap();
+        assert!(normalization.min[0] < 1000. && normalization.max[1] > 0.008);
+        let mut output = String::new();
+        JsonSerializer.serialize(&model, &mut output).unwrap();
+        let balls: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+        for (x, y) in clusters {
+            assert!(balls.iter().any(|ball| {
+                let center: Vec<f64> = serde_json::from_value(ball["center"].clone()).unwrap();
+                (center[0] - x).abs() < 10. && (center[1] - y).abs() < 1E-4
+            }));
+        }
+
+        let mut output = String::new();
+        ColumnarJsonSerializer
+            .serialize(&model, &mut output)
+            .unwrap();
+        let restored = serializer::parse_columnar_normalization(&output)
+            .unwrap()
+            .unwrap();
+        let balls = serializer::parse_columnar(&output).unwrap();
+        let mut reloaded = Model::load(space::euclid_dist, balls).with_normalization(restored);
+        let point = sample(0);
+        let normalized = reloaded.normalize_point(&point);
+        for (x, y) in model.normalize_point(&point).iter().zip(normalized) {
+            assert_approx_eq!(*x, y, 1E-12);
+        }
+        algo.fit(&mut model, point.clone());
+        Algo::new(space::euclid_dist, space::real_combine)
+            .with_normalization(60, OutOfRange::Clamp)
+            .fit(&mut reloaded, point);
+        for (ball, reloaded) in model.iter_balls().zip(reloaded.iter_balls()) {
+            for (x, y) in ball.center().iter().zip(reloaded.center()) {
+                assert_approx_eq!(*x, *y, 1E-9);
+            }
+            assert_approx_eq!(ball.weight(), reloaded.weight());
+        }
+        assert_eq!(model.iter_balls().count(), reloaded.iter_balls().count());
+    }
+
+    #[test]
+    fn test_tune_split_threshold() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let normal = Normal::new(0., 1.).unwrap();
+        let centers = [[0., 0.], [20., 0.], [0., 20.]];
+        // points come by runs of ten from each cluster
+        let mut sample = |count: usize| -> Vec<Vec<f64>> {
+            (0..count)
+                .map(|i| {
+                    let [x, y] = centers[i / 10 % 3];
+                    vec![x + normal.sample(&mut rng), y + normal.sample(&mut rng)]
+                })
+                .collect()
+        };
+        let (train, holdout) = (sample(300), sample(90));
+        let candidates = [0.5, 1., 2., 4., 8., 100.];
+        let threshold = tune_split_threshold(
+            &train,
+            &holdout,
+            &candidates,
+            space::euclid_dist,
+            space::real_combine,
+        )
+        .unwrap();
+        let config = AlgoConfig {
+            threshold,
+            ..AlgoConfig::default()
+        };
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+        let mut model = Model::new(space::euclid_dist);
+        for point in train {
+            algo.fit(&mut model, point);
+        }
+        assert_eq!(3, model.iter_balls().count());
+        let candidates: [f64; 0] = [];
+        assert!(tune_split_threshold(
+            &holdout,
+            &holdout,
+            &candidates,
+            space::euclid_dist,
+            space::real_combine
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_velocity() {
+        use crate::serializer::{JsonSerializer, ModelSerializer};
+
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_velocity(0.05);
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(5);
+        let normal = Normal::new(0., 0.1).unwrap();
+        let speed = [0.05, -0.02];
+        for t in 0..2000 {
+            let point = speed
+                .iter()
+                .map(|v| v * t as f64 + normal.sample(&mut rng))
+                .collect();
+            algo.fit(&mut model, point);
+            // the first ball has a zero weight until the second point
+            if t > 0 {
+                algo.fast_forward(&mut model, 1);
+            }
+        }
+        let ball = model
+            .iter_balls()
+            .max_by(|b1, b2| b1.weight().total_cmp(&b2.weight()))
+            .unwrap();
+        let velocity = ball.velocity().unwrap();
+        assert_approx_eq!(speed[0], velocity[0], 0.1);
+        assert_approx_eq!(speed[1], velocity[1], 0.1);
+        let mut output = String::new();
+        JsonSerializer.serialize(&model, &mut output).unwrap();
+        assert!(output.contains(r#""velocity":["#));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_fit_batch_parallel() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let normal = Normal::new(0., 1.).unwrap();
+        let centers = [[0., 0.], [30., 0.], [0., 30.]];
+        // runs of points per cluster, otherwise the first ball includes all clusters
+        let points: Vec<Vec<f64>> = (0..3000)
+            .map(|i| {
+                let center = centers[i / 10 % 3];
+                center.iter().map(|c| c + normal.sample(&mut rng)).collect()
+            })
+            .collect();
+        let new = || {
+            let algo = Algo::new(space::euclid_dist, space::real_combine);
+            (algo, Model::new(space::euclid_dist))
+        };
+        let (algo, mut sequential) = new();
+        for point in points.iter() {
+            algo.fit(&mut sequential, point.clone());
+        }
+        let parallel = fit_batch_parallel(&points, 4, new);
+        let clusters = |model: &Model<Vec<f64>>| {
+            let total: f64 = model.iter_balls().map(|b| b.weight).sum();
+            let mut centers: Vec<Vec<f64>> = model
+                .iter_balls()
+                .filter(|b| b.weight > 0.05 * total)
+                .map(|b| b.center.clone())
+                .collect();
+            centers.sort_by(|c1, c2| (c1[0] - c1[1]).total_cmp(&(c2[0] - c2[1])));
+            centers
+        };
+        let (sequential, parallel) = (clusters(&sequential), clusters(&parallel));
+        assert_eq!(3, sequential.len());
+        assert_eq!(sequential.len(), parallel.len());
+        for (s, p) in sequential.iter().zip(parallel.iter()) {
+            assert!(space::euclid_dist(s, p).sqrt() < 2., "{:?} vs {:?}", s, p);
+        }
+    }
+
+    #[test]
+    fn test_drift() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_drift();
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(7);
+        let normal = Normal::new(0., 1.).unwrap();
+        let mut drifts = vec![];
+        for t in 0..300 {
+            let offset = if t < 200 { 0. } else { 50. };
+            algo.fit(&mut model, vec![offset + normal.sample(&mut rng)]);
+            algo.measure_drift(&model);
+            drifts.push(algo.drift_since_last());
+        }
+        assert_eq!(0., drifts[0]);
+        let stationary = drifts[100..200].iter().cloned().fold(0., f64::max);
+        let spike = drifts[200];
+        assert!(stationary < 0.5, "stationary drift {}", stationary);
+        assert!(spike > 5. * stationary, "spike {} vs {}", spike, stationary);
+        let settled = drifts[280..].iter().cloned().fold(0., f64::max);
+        assert!(settled < spike / 5., "settled {} vs {}", settled, spike);
+        assert_eq!(
+            0.,
+            Algo::new(space::euclid_dist, space::real_combine).drift_since_last()
+        );
+    }
+
     fn build_sample() -> Vec<Vec<f64>> {
         vec![
             vec![5., -1.],
@@ -0,0 +1,182 @@
+//! Clients of the models emitted by a [crate::Streamer].
+//!
+//! The [DeltaDecoder] rebuilds full models from the [Envelope] messages written by
+//! [crate::serializer::DeltaSerializer] or [crate::serializer::EnvelopeSerializer].
+
+use std::error::Error;
+
+use serde_json::Value;
+
+use crate::message::Envelope;
+
+/// Rebuilds full models from the frames written by [crate::serializer::DeltaSerializer].
+///
+/// When a delta frame does not apply to the last decoded frame, e.g. because a frame was lost,
+/// the decoder drops frames until the next snapshot.
+/// ```
+/// use fluent_data::{client::DeltaDecoder, Model, model::Ball, serializer::{DeltaSerializer, ModelSerializer}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![1.], 4., 3.)]);
+/// let delta = DeltaSerializer::new(10);
+/// let mut decoder = DeltaDecoder::new();
+/// for _ in 0..2 {
+///     let mut frame = String::new();
+///     delta.serialize(&model, &mut frame).unwrap();
+///     let balls = decoder.decode(&frame).unwrap().unwrap();
+///     assert_eq!(3.0, balls[0]["weight"]);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct DeltaDecoder {
+    seq: Option<u64>,
+    balls: Vec<Value>,
+}
+
+impl DeltaDecoder {
+    /// Builds a decoder which awaits a first snapshot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tells whether the decoder is up to date, i.e. it is not awaiting a snapshot.
+    pub fn is_synchronized(&self) -> bool {
+        self.seq.is_some()
+    }
+
+    /// Decodes a message and returns the balls of the full model, with the same layout as [crate::serializer::JsonSerializer],
+    /// or `None` while awaiting a snapshot after a gap. Heartbeats return the current model.
+    /// A composite message which body embeds a `model` is decoded like a snapshot, other composite messages are skipped.
+    /// Returns an error if the message is not an [Envelope], if it is an error message, or if the delta is inconsistent.
+    pub fn decode(&mut self, frame: &str) -> Result<Option<&[Value]>, Box<dyn Error>> {
+        match serde_json::from_str(frame)? {
+            Envelope::Snapshot { seq, balls, .. } => {
+                self.balls = balls;
+                self.seq = Some(seq);
+            }
+            Envelope::Delta {
+                seq,
+                base_seq,
+                changed,
+                removed,
+            } => {
+                if self.seq != Some(base_seq) {
+                    self.seq = None;
+                    return Ok(None);
+                }
+                if let Err(reason) = self.apply(removed, changed) {
+                    self.seq = None;
+                    return Err(reason);
+                }
+                self.seq = Some(seq);
+            }
+            Envelope::Composite { seq, body, .. } => {
+                if let Some(Value::Array(balls)) = body.get("model") {
+                    self.balls = balls.clone();
+                    self.seq = Some(seq);
+                }
+            }
+            Envelope::Heartbeat { .. } => {}
+            Envelope::Error { error } => return Err(error.into()),
+        }
+        Ok(self.seq.map(|_| &self.balls[..]))
+    }
+
+    /// Removes then updates balls, removed indices refer to the previous model and changed indices to the new one.
+    fn apply(
+        &mut self,
+        mut removed: Vec<usize>,
+        changed: Vec<(usize, Value)>,
+    ) -> Result<(), Box<dyn Error>> {
+        removed.sort_unstable();
+        for index in removed.into_iter().rev() {
+            if index >= self.balls.len() {
+                return Err(format!("removed ball {} does not exist", index).into());
+            }
+            self.balls.remove(index);
+        }
+        for (index, ball) in changed {
+            match index.cmp(&self.balls.len()) {
+                std::cmp::Ordering::Less => self.balls[index] = ball,
+                std::cmp::Ordering::Equal => self.balls.push(ball),
+                std::cmp::Ordering::Greater => {
+                    return Err(format!("changed ball {} leaves a gap", index).into())
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::{
+        client::*,
+        serializer::{DeltaSerializer, JsonSerializer, ModelSerializer},
+        space, Algo, Model,
+    };
+
+    #[test]
+    fn test_decode() {
+        let (frames, models) = build_frames(30, 10);
+        let mut decoder = DeltaDecoder::new();
+        for (frame, model) in frames.iter().zip(models.iter()) {
+            let balls = decoder.decode(frame).unwrap().unwrap();
+            assert_eq!(model, balls);
+        }
+    }
+
+    #[test]
+    fn test_decode_lost_delta() {
+        let (frames, models) = build_frames(30, 10);
+        let mut decoder = DeltaDecoder::new();
+        for (seq, (frame, model)) in frames.iter().zip(models.iter()).enumerate() {
+            if seq == 13 {
+                continue;
+            }
+            let balls = decoder.decode(frame).unwrap().map(|b| b.to_vec());
+            if (14..20).contains(&seq) {
+                assert!(balls.is_none());
+                assert!(!decoder.is_synchronized());
+            } else {
+                assert_eq!(Some(model), balls.as_ref());
+            }
+        }
+        assert!(decoder.is_synchronized());
+    }
+
+    #[test]
+    fn test_decode_before_snapshot() {
+        let (frames, _) = build_frames(3, 10);
+        let mut decoder = DeltaDecoder::new();
+        assert!(decoder.decode(&frames[1]).unwrap().is_none());
+        assert!(decoder
+            .decode(r#"{"type":"heartbeat","seq":2,"ts":0}"#)
+            .unwrap()
+            .is_none());
+        assert!(decoder.decode(r#"{"seq":1}"#).is_err());
+        assert!(decoder
+            .decode(r#"{"type":"error","error":"oops"}"#)
+            .is_err());
+    }
+
+    /// Fits points and returns the delta frames with the corresponding full models.
+    fn build_frames(count: usize, snapshot_every: usize) -> (Vec<String>, Vec<Vec<Value>>) {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let delta = DeltaSerializer::new(snapshot_every);
+        let mut frames = vec![];
+        let mut models = vec![];
+        for i in 0..count {
+            algo.fit(&mut model, vec![(i * 37 % 11) as f64]);
+            let mut frame = String::new();
+            delta.serialize(&model, &mut frame).unwrap();
+            frames.push(frame);
+            let mut full = String::new();
+            JsonSerializer.serialize(&model, &mut full).unwrap();
+            models.push(serde_json::from_str(&full).unwrap());
+        }
+        (frames, models)
+    }
+}
@@ -0,0 +1,226 @@
+//! Synthetic data generators and benchmark scenarios.
+//!
+//! The benchmarks of the crate are built from these scenarios, see the `benches` directory;
+//! they can be reused to benchmark other distances the same way. Requires the `bench-utils` feature.
+//! ```
+//! use fluent_data::{data::{self, FitScenario}, space};
+//!
+//! let scenario = FitScenario::new(space::euclid_dist, space::real_combine, 10, 2, 100, 42);
+//! assert_eq!(10, scenario.fit(&mut scenario.new_model()));
+//! let model = data::model_with_balls(&data::centers(5, 3, 42));
+//! assert_eq!(5, model.iter_balls().count());
+//! ```
+
+use std::env;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+
+use crate::{
+    algorithm::AlgoConfig,
+    model::{Ball, Model},
+    space, Algo,
+};
+
+/// Standard deviation of the points around the center of their cluster, on each coordinate.
+pub const SPREAD: f64 = 1.;
+
+/// Number of consecutive points drawn from the same cluster.
+/// Interleaved points of distinct clusters would rather be fitted into a single large ball.
+pub const BURST: usize = 10;
+
+/// Distance between clusters, in cluster radii, roughly.
+const SEPARATION: f64 = 20.;
+
+/// Sizes of the benchmark scenarios.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sizes {
+    /// Number of balls of the models for the throughput scenarios.
+    pub ball_counts: Vec<usize>,
+    /// Dimensions of the points for the dimensionality sweep.
+    pub dims: Vec<usize>,
+    /// Number of balls of the models for the dimensionality sweep.
+    pub sweep_balls: usize,
+    /// Number of points fitted by each scenario run.
+    pub points: usize,
+}
+
+impl Sizes {
+    /// The sizes of the full benchmark suite.
+    pub fn full() -> Self {
+        Self {
+            ball_counts: vec![10, 100, 1000, 10000],
+            dims: vec![2, 8, 32, 128, 512, 1024],
+            sweep_balls: 100,
+            points: 1000,
+        }
+    }
+
+    /// Reduced sizes that check all scenarios run end-to-end quickly, e.g. on CI.
+    pub fn ci() -> Self {
+        Self {
+            ball_counts: vec![10, 100],
+            dims: vec![2, 16],
+            sweep_balls: 10,
+            points: 50,
+        }
+    }
+
+    /// The reduced sizes when the `FLUENT_DATA_BENCH_CI` environment variable is set, the full ones otherwise.
+    pub fn from_env() -> Self {
+        match env::var_os("FLUENT_DATA_BENCH_CI") {
+            Some(_) => Self::ci(),
+            None => Self::full(),
+        }
+    }
+}
+
+/// Draws the centers of `count` clusters in R^`dim`, far enough from each other that their points
+/// are not fitted in the same ball.
+pub fn centers(count: usize, dim: usize, seed: u64) -> Vec<Vec<f64>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let side = SEPARATION * SPREAD * (dim as f64).sqrt() * (count as f64).powf(1. / dim as f64);
+    (0..count)
+        .map(|_| (0..dim).map(|_| rng.gen_range(0. ..side)).collect())
+        .collect()
+}
+
+/// Draws `count` points around the given centers, by bursts of [BURST] points from a cluster chosen at random.
+pub fn clustered_points(centers: &[Vec<f64>], count: usize, seed: u64) -> Vec<Vec<f64>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let noise = Normal::new(0., SPREAD).unwrap();
+    let mut points = Vec::with_capacity(count);
+    while points.len() < count {
+        let center = &centers[rng.gen_range(0..centers.len())];
+        for _ in 0..BURST.min(count - points.len()) {
+            points.push(center.iter().map(|c| c + noise.sample(&mut rng)).collect());
+        }
+    }
+    points
+}
+
+/// Builds a model with a ball around each of the given centers, as if their clusters were already fitted.
+pub fn model_with_balls(centers: &[Vec<f64>]) -> Model<Vec<f64>> {
+    Model::load(space::euclid_dist, balls_around(centers))
+}
+
+/// Builds a ball around each of the given centers, see [model_with_balls].
+fn balls_around(centers: &[Vec<f64>]) -> Vec<Ball<Vec<f64>>> {
+    centers
+        .iter()
+        .map(|center| {
+            Ball::new(
+                center.clone(),
+                center.len() as f64 * SPREAD * SPREAD,
+                BURST as f64,
+            )
+        })
+        .collect()
+}
+
+/// Fits points drawn around the balls of a model, see [FitScenario::fit].
+///
+/// Each run should start from a fresh model, see [FitScenario::new_model], so that runs measure the same state.
+/// Weights do not decay, thus a run keeps the number of balls of the model,
+/// unless the neighborhood search is restricted by [FitScenario::with_budget] and misses the balls of some points.
+pub struct FitScenario {
+    pub algo: Algo<Vec<f64>>,
+    pub centers: Vec<Vec<f64>>,
+    pub points: Vec<Vec<f64>>,
+    new_model: ModelBuilder,
+}
+
+/// Builds a model with a ball around each of the given centers, see [FitScenario::new_model].
+type ModelBuilder = Box<dyn Fn(&[Vec<f64>]) -> Model<Vec<f64>>>;
+
+impl FitScenario {
+    /// Builds a scenario which fits `points` points of dimension `dim` into a model of `balls` balls,
+    /// with the given distance and combination functions, e.g. [space::euclid_dist] and [space::real_combine].
+    pub fn new<Dist, Combine>(
+        dist: Dist,
+        combine: Combine,
+        balls: usize,
+        dim: usize,
+        points: usize,
+        seed: u64,
+    ) -> Self
+    where
+        Dist: Fn(&Vec<f64>, &Vec<f64>) -> f64 + Clone + 'static,
+        Combine: Fn(&Vec<f64>, f64, &Vec<f64>, f64) -> Vec<f64> + 'static,
+    {
+        let centers = centers(balls, dim, seed);
+        let config = AlgoConfig {
+            decay: 1.,
+            ..AlgoConfig::default()
+        };
+        Self {
+            algo: Algo::new(dist.clone(), combine).with_config(config),
+            points: clustered_points(&centers, points, seed.wrapping_add(1)),
+            centers,
+            new_model: Box::new(move |centers| Model::load(dist.clone(), balls_around(centers))),
+        }
+    }
+
+    /// Builds a model with a ball around each center of the scenario, as if their clusters were already fitted.
+    pub fn new_model(&self) -> Model<Vec<f64>> {
+        (self.new_model)(&self.centers)
+    }
+
+    /// Searches the neighborhood of the points among at most `max_candidates` balls, see [Algo::with_budget].
+    pub fn with_budget(mut self, max_candidates: usize) -> Self {
+        self.algo = self.algo.with_budget(max_candidates);
+        self
+    }
+
+    /// Fits all points into the model and returns its number of balls.
+    pub fn fit(&self, model: &mut Model<Vec<f64>>) -> usize {
+        for point in self.points.iter() {
+            self.algo.fit(model, point.clone());
+        }
+        model.iter_balls().count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::*;
+
+    #[test]
+    fn test_generators() {
+        let centers = centers(4, 3, 7);
+        assert_eq!(4, centers.len());
+        assert!(centers.iter().all(|c| c.len() == 3));
+        let points = clustered_points(&centers, 25, 7);
+        assert_eq!(25, points.len());
+        let nearest = |p: &Vec<f64>| {
+            (0..centers.len())
+                .min_by(|&i, &j| {
+                    let di = space::euclid_dist(p, &centers[i]);
+                    let dj = space::euclid_dist(p, &centers[j]);
+                    di.partial_cmp(&dj).unwrap()
+                })
+                .unwrap()
+        };
+        for burst in points.chunks(BURST) {
+            assert!(burst.iter().all(|p| nearest(p) == nearest(&burst[0])));
+        }
+        assert_eq!(points, clustered_points(&centers, 25, 7));
+    }
+
+    #[test]
+    fn test_fit_scenario() {
+        let scenario = FitScenario::new(space::euclid_dist, space::real_combine, 20, 4, 200, 3);
+        let mut model = scenario.new_model();
+        assert_eq!(20, scenario.fit(&mut model));
+        assert_eq!(20, scenario.fit(&mut model));
+        assert_eq!(
+            scenario.new_model().fingerprint(),
+            scenario.new_model().fingerprint()
+        );
+        let indexed =
+            FitScenario::new(space::euclid_dist, space::real_combine, 20, 4, 200, 3).with_budget(8);
+        let mut model = indexed.new_model();
+        indexed.fit(&mut model);
+        assert!(model.truncated_searches() > 0);
+    }
+}
@@ -28,6 +28,13 @@ pub struct Neighbor<Data: PartialEq> {
     target: Weak<RefCell<Node<Data>>>,
 }
 
+impl<Data: PartialEq> Neighbor<Data> {
+    /// Get the target vertex if it still exists.
+    pub fn as_vertex(&self) -> Option<Vertex<Data>> {
+        self.target.upgrade().map(|n| Vertex { node: n })
+    }
+}
+
 /// Vertex internal structure, shared by vertices and neighbors thanks to a smart pointer.
 struct Node<Data: PartialEq> {
     data: Data,
@@ -59,6 +66,11 @@ impl<Data: PartialEq> Vertex<Data> {
         }
     }
 
+    /// Tells whether both vertices are the same, without comparing their data.
+    pub fn ptr_eq(&self, other: &Vertex<Data>) -> bool {
+        Rc::ptr_eq(&self.node, &other.node)
+    }
+
     /// Get an iterator over the vertices that are neighbor of this vertex.
     pub fn iter_neighbors(&self) -> impl Iterator<Item = Vertex<Data>> + '_ {
         NeighborIterator::new(Ref::map(self.node.borrow(), |n| &n.neighbors))
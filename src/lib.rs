@@ -187,14 +187,21 @@
 //!    - reads R^n points from standard input and writes models to standard output,
 //!  - `fluent_data --service`
 //!    - starts a server, receives R^n points from websockets and dispatch models to websockets,
+//!  - `fluent_data --challenger decay=0.9,threshold=3`
+//!    - fits a challenger configuration side by side with the default one and writes both models tagged by name,
 //!  - `fluent_data --help`
 //!    - display the executable usage documentation.
 //!    
 //! See the project [README on crates.io](https://crates.io/crates/fluent_data) for more information.
 
 pub mod algorithm;
+pub mod client;
+#[cfg(feature = "bench-utils")]
+pub mod data;
+pub mod message;
 pub mod model;
 pub mod neighborhood;
+pub mod serializer;
 pub mod service;
 pub mod space;
 pub mod streamer;
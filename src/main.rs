@@ -1,7 +1,15 @@
-use std::error::Error;
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-use clap::Parser;
-use fluent_data::{service, space, streamer};
+use clap::{Parser, Subcommand};
+use fluent_data::algorithm::{self, AlgoConfig};
+use fluent_data::{model, serializer, service, space, streamer};
+use fluent_data::{service::ServiceConfig, streamer::History};
 use fluent_data::{Algo, Model, Streamer};
 
 #[derive(Parser, Debug)]
@@ -10,36 +18,137 @@ struct Args {
     /// starts in service mode.
     #[clap(short, long, value_parser)]
     service: bool,
+
+    /// runs a challenger configuration side by side, e.g. `decay=0.9,threshold=3`.
+    #[clap(long, value_parser)]
+    challenger: Option<String>,
+
+    /// fits the points of a file before the live points, e.g. to warm up the service model.
+    #[clap(short, long, value_parser, conflicts_with = "challenger")]
+    input: Option<PathBuf>,
+
+    /// stops with an error on a line of the standard input or the input file longer than this number of bytes.
+    #[clap(long, value_parser)]
+    max_line: Option<usize>,
+
+    /// accepts points that start with a string id, e.g. `["a",1.0,2.5]`, and reports the ball of the last point with each model.
+    #[clap(long, value_parser)]
+    point_ids: bool,
+
+    /// applies the service settings of a JSON file, e.g. `{"emit_every":10}`, and again each time it is modified.
+    #[clap(long, value_parser, requires = "service")]
+    config: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// fits the points of the standard input, then writes the balls centers and weights to the standard output.
+    Export {
+        /// `csv`, or `npy` when built with the `npy` feature.
+        #[clap(long, value_parser, default_value = "csv")]
+        format: String,
+    },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let (algo, mut model) = get_algo_model();
-    let streamer = get_streamer(&args);
-    Streamer::run(streamer, algo, &mut model)?;
+    if let Some(Command::Export { format }) = &args.command {
+        let (algo, mut model) = get_algo_model();
+        if let Some(input) = &args.input {
+            warm_up(input, args.max_line, &mut model)?;
+        }
+        let streamer = Streamer::new(get_stdin(&args), |_| Ok(())).with_manual_emit();
+        Streamer::run(streamer, algo, &mut model)?;
+        export(&model, format)?;
+    } else if let Some(challenger) = &args.challenger {
+        let streamer = get_streamer(&args)?;
+        let configs = get_ab_configs(challenger)?;
+        algorithm::ab_run(streamer, configs, space::euclid_dist, space::real_combine)?;
+    } else {
+        let (algo, mut model) = get_algo_model();
+        if let Some(input) = &args.input {
+            warm_up(input, args.max_line, &mut model)?;
+        }
+        let streamer = get_streamer(&args)?;
+        // records that are not points are reported and skipped, they must not stop the service
+        Streamer::run_with_drop_sink(streamer, algo, &mut model, |record, reason| {
+            eprintln!("dropped {:?}: {}", reason, record)
+        })?;
+    }
     Ok(())
 }
 
+/// Fits the points of the input file, without writing models.
+fn warm_up(
+    input: &Path,
+    max_line: Option<usize>,
+    model: &mut Model<Vec<f64>>,
+) -> Result<(), Box<dyn Error>> {
+    let points: Box<dyn Iterator<Item = Result<String, Box<dyn Error>>>> = match max_line {
+        Some(max_len) => Box::new(streamer::lines(BufReader::new(File::open(input)?), max_len)),
+        None => Box::new(streamer::file(input)?),
+    };
+    let streamer = Streamer::new(points, |_| Ok(())).with_manual_emit();
+    let (algo, _) = get_algo_model();
+    Streamer::run(streamer, algo, model)
+}
+
+/// Writes the balls centers and weights to the standard output.
+fn export(model: &Model<Vec<f64>>, format: &str) -> Result<(), Box<dyn Error>> {
+    let points = model::to_weighted_points(model);
+    let output = match format {
+        "csv" => serializer::weighted_points_csv(&points).into_bytes(),
+        #[cfg(feature = "npy")]
+        "npy" => serializer::weighted_points_npy(&points)?,
+        _ => return Err(format!("unknown export format {}", format).into()),
+    };
+    io::stdout().write_all(&output)?;
+    Ok(())
+}
+
+/// Reads points from the standard input.
+fn get_stdin(args: &Args) -> Box<dyn Iterator<Item = Result<String, Box<dyn Error>>>> {
+    match args.max_line {
+        Some(max_len) => Box::new(streamer::lines(io::stdin().lock(), max_len)),
+        None => Box::new(streamer::stdio().0),
+    }
+}
+
 type BoxedInOut = (
     Box<dyn Iterator<Item = Result<String, Box<dyn Error>>>>,
     Box<dyn FnMut(String) -> Result<(), Box<dyn Error>>>,
 );
 
-fn get_streamer(
-    args: &Args,
-) -> Streamer<
+type BoxedStreamer = Streamer<
     Box<dyn Iterator<Item = Result<String, Box<dyn Error>>>>,
     Box<dyn FnMut(String) -> Result<(), Box<dyn Error>>>,
-> {
-    let (points, write): BoxedInOut = if args.service {
-        let (points, write) = service::backend();
-        (Box::new(points), Box::new(write))
+>;
+
+fn get_streamer(args: &Args) -> Result<BoxedStreamer, Box<dyn Error>> {
+    let streamer = if args.service {
+        let config = Arc::new(Mutex::new(ServiceConfig::default()));
+        let history = Arc::new(Mutex::new(History::new(
+            config.lock().unwrap().history_capacity,
+        )));
+        if let Some(path) = &args.config {
+            service::watch_config(path.clone(), config.clone(), history.clone())?;
+        }
+        let (points, write) = service::backend_with_config(history, config.clone());
+        Streamer::new(points, write).with_service_config(config)
     } else {
-        let (points, write) = streamer::stdio();
-        (Box::new(points), Box::new(write))
+        let (_, write) = streamer::stdio();
+        let (points, write): BoxedInOut = (get_stdin(args), Box::new(write));
+        Streamer::new(points, write)
     };
-    let streamer = Streamer::new(points, write);
-    streamer
+    let streamer = streamer.with_batches();
+    if args.point_ids {
+        Ok(streamer.with_point_ids())
+    } else {
+        Ok(streamer)
+    }
 }
 
 fn get_algo_model() -> (Algo<Vec<f64>>, Model<Vec<f64>>) {
@@ -47,3 +156,12 @@ fn get_algo_model() -> (Algo<Vec<f64>>, Model<Vec<f64>>) {
     let model = Model::new(space::euclid_dist);
     (algo, model)
 }
+
+fn get_ab_configs(challenger: &str) -> Result<Vec<AlgoConfig>, Box<dyn Error>> {
+    let champion = AlgoConfig {
+        name: String::from("champion"),
+        ..Default::default()
+    };
+    let challenger = format!("name=challenger,{}", challenger).parse()?;
+    Ok(vec![champion, challenger])
+}
@@ -0,0 +1,176 @@
+//! The [Envelope] is the typed wire format of the messages emitted by a [crate::Streamer] and the service.
+//!
+//! Messages are JSON objects tagged by a `type` field:
+//!  - `{"type":"snapshot","seq":<seq>,"ts":<ms>,"balls":[<ball>,...]}` carries a full model,
+//!    with `"restarted":true` when it is the first message after the emitter restarted from a checkpoint,
+//!  - `{"type":"delta","seq":<seq>,"base_seq":<seq>,"changed":[[<index>,<ball>],...],"removed":[<index>,...]}`
+//!    carries the balls that changed since the message `base_seq`, see [crate::serializer::DeltaSerializer],
+//!  - `{"type":"composite","seq":<seq>,"ts":<ms>,"body":<body>}` carries a body that embeds models,
+//!    e.g. with the assignments of the points or the models of an A/B run,
+//!  - `{"type":"heartbeat","seq":<seq>,"ts":<ms>}` tells the stream is alive while no model is emitted,
+//!  - `{"type":"error","error":<reason>}` reports a record that was rejected.
+//!
+//! Balls have the layout of [crate::serializer::JsonSerializer]; they can be decoded as [BallMessage]
+//! or kept as JSON values. Legacy consumers that expect bare arrays of balls are served by
+//! [crate::serializer::EnvelopeSerializer::legacy].
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A message emitted by a [crate::Streamer] or the service, generic over the ball representation.
+/// ```
+/// use fluent_data::message::{BallMessage, Envelope};
+///
+/// let message: Envelope<BallMessage<Vec<f64>>> = serde_json::from_str(
+///     r#"{"type":"snapshot","seq":3,"ts":0,"balls":[{"center":[1.0],"radius":2.0,"weight":3.0}]}"#,
+/// ).unwrap();
+/// if let Envelope::Snapshot { balls, .. } = message {
+///     assert_eq!(Some(2.), balls[0].radius);
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Envelope<P> {
+    /// A full model.
+    Snapshot {
+        seq: u64,
+        /// Emission time, in milliseconds since the UNIX epoch.
+        ts: u64,
+        balls: Vec<P>,
+        /// Whether the emitter restarted just before this message, thus a gap in sequence numbers is not a data loss.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        restarted: bool,
+    },
+    /// The balls that changed since message `base_seq`.
+    /// `removed` indices refer to the previous model and `changed` indices to the new one.
+    Delta {
+        seq: u64,
+        base_seq: u64,
+        changed: Vec<(usize, P)>,
+        removed: Vec<usize>,
+    },
+    /// A body that embeds models, e.g. a model with the assignments of the points `{"model":[...],"assignments":{...}}`,
+    /// see [crate::serializer::ModelSerializer::frame]. Balls in the body have the same layout as in snapshots.
+    Composite {
+        seq: u64,
+        /// Emission time, in milliseconds since the UNIX epoch.
+        ts: u64,
+        body: Value,
+        /// Whether the emitter restarted just before this message, thus a gap in sequence numbers is not a data loss.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        restarted: bool,
+    },
+    /// Tells the stream is alive.
+    /// `seq` is the sequence number of the next message, thus a client can tell whether it missed messages.
+    Heartbeat {
+        seq: u64,
+        /// Emission time, in milliseconds since the UNIX epoch.
+        ts: u64,
+    },
+    /// A record was rejected.
+    Error { error: String },
+}
+
+impl<P> Envelope<P> {
+    /// Builds an error message.
+    pub fn error(reason: impl ToString) -> Self {
+        Envelope::Error {
+            error: reason.to_string(),
+        }
+    }
+}
+
+/// A ball as emitted in messages.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BallMessage<Point> {
+    pub center: Point,
+    /// The radius, `None` for the first ball which radius is not known yet.
+    pub radius: Option<f64>,
+    pub weight: f64,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub frozen: bool,
+}
+
+/// The current time in milliseconds since the UNIX epoch, for [Envelope] timestamps.
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::message::*;
+
+    #[test]
+    fn test_round_trip() {
+        let ball = BallMessage {
+            center: vec![1., -2.5],
+            radius: Some(0.5),
+            weight: 3.,
+            frozen: false,
+        };
+        let first = BallMessage {
+            center: vec![0., 0.],
+            radius: None,
+            weight: 0.,
+            frozen: true,
+        };
+        let messages = vec![
+            Envelope::Snapshot {
+                seq: 4,
+                ts: 1_700_000_000_000,
+                balls: vec![ball.clone(), first.clone()],
+                restarted: false,
+            },
+            Envelope::Delta {
+                seq: 5,
+                base_seq: 4,
+                changed: vec![(1, first)],
+                removed: vec![2, 3],
+            },
+            Envelope::Composite {
+                seq: 6,
+                ts: 1_700_000_000_200,
+                body: json!({"model": [], "assignments": {"removed": 0}}),
+                restarted: true,
+            },
+            Envelope::Heartbeat {
+                seq: 7,
+                ts: 1_700_000_000_500,
+            },
+            Envelope::error("line 2, offset 0: expected value"),
+        ];
+        let expected = [
+            json!({"type": "snapshot", "seq": 4, "ts": 1_700_000_000_000u64, "balls": [
+                {"center": [1.0, -2.5], "radius": 0.5, "weight": 3.0},
+                {"center": [0.0, 0.0], "radius": null, "weight": 0.0, "frozen": true},
+            ]}),
+            json!({"type": "delta", "seq": 5, "base_seq": 4, "changed": [
+                [1, {"center": [0.0, 0.0], "radius": null, "weight": 0.0, "frozen": true}],
+            ], "removed": [2, 3]}),
+            json!({"type": "composite", "seq": 6, "ts": 1_700_000_000_200u64, "restarted": true,
+                "body": {"model": [], "assignments": {"removed": 0}}}),
+            json!({"type": "heartbeat", "seq": 7, "ts": 1_700_000_000_500u64}),
+            json!({"type": "error", "error": "line 2, offset 0: expected value"}),
+        ];
+        for (message, expected) in messages.into_iter().zip(expected) {
+            let json = serde_json::to_string(&message).unwrap();
+            assert_eq!(expected, serde_json::from_str::<Value>(&json).unwrap());
+            let parsed: Envelope<BallMessage<Vec<f64>>> = serde_json::from_str(&json).unwrap();
+            assert_eq!(message, parsed);
+            let untyped: Envelope<Value> = serde_json::from_str(&json).unwrap();
+            assert_eq!(expected, serde_json::to_value(&untyped).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        assert!(serde_json::from_str::<Envelope<Value>>(r#"{"type":"ping"}"#).is_err());
+        assert!(serde_json::from_str::<Envelope<Value>>(r#"[{"center":[1.0]}]"#).is_err());
+    }
+}
@@ -3,31 +3,118 @@
 //! The model can be loaded with existing balls by the [Model::load] method.
 //! It can also be used to predict the balls that most probably contains a given point
 //! by using the [Model::predict] method.
-use std::ops::Deref;
+//! The [Model::fast_forward] method decays the model after some time elapsed without data.
+//! For 2D real points, the [to_heatmap] function rasterizes the mixture density for visualization
+//! and the [to_svg] function draws the balls.
+//! For real points, the [covered_measure] function estimates the space covered by the balls.
+//! The [dendrogram] function agglomerates the balls into a tree for hierarchical interpretation.
+//! Real points may be normalized into the unit cube before fitting, see [Normalization].
+//! A [ModelSnapshot] is a read-only copy of a model which can be queried from several threads, see [Model::snapshot_with].
+use std::{
+    cell::{Cell, RefCell},
+    collections::{hash_map::DefaultHasher, VecDeque},
+    error::Error,
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{
     graph::{Neighbor, Vertex},
-    neighborhood::{GetNeighborhood, Neighborhood},
+    neighborhood::{GetNeighborhood, GetNeighborhoodBounded, Neighborhood},
+    space::{self, Quantization, QuantizedPoint},
 };
 
 /// A ball in the set of balls model.
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Ball<Point: PartialEq> {
+    /// Creation rank of the ball in its model, see [Ball::id].
+    pub(crate) id: u64,
     pub(crate) center: Point,
     pub(crate) radius: f64,
     pub(crate) weight: f64,
+    pub(crate) dist_mean: f64,
+    pub(crate) dist_var: f64,
+    pub(crate) assigned: u64,
+    pub(crate) frozen: bool,
+    /// Logarithm of the recent weight, see [Model::recent_weight_share].
+    pub(crate) recent: f64,
+    /// Smoothed displacement of the center, see [crate::Algo::with_velocity].
+    pub(crate) velocity: Option<Vec<f64>>,
+    /// Number of points included in the ball after its creation, and among them the number of points
+    /// which were within its radius, see [Model::radius_coverage].
+    pub(crate) included: u64,
+    pub(crate) inside: u64,
+}
+
+/// Balls are equal when they have the same data, whatever their id.
+impl<Point: PartialEq> PartialEq for Ball<Point> {
+    fn eq(&self, other: &Self) -> bool {
+        self.center == other.center
+            && self.radius == other.radius
+            && self.weight == other.weight
+            && self.dist_mean == other.dist_mean
+            && self.dist_var == other.dist_var
+            && self.assigned == other.assigned
+            && self.frozen == other.frozen
+            && self.recent == other.recent
+            && self.velocity == other.velocity
+            && self.included == other.included
+            && self.inside == other.inside
+    }
 }
 
 impl<Point: PartialEq> Ball<Point> {
     /// Builds a new ball.
     pub fn new(center: Point, radius: f64, weight: f64) -> Self {
+        let dist_mean = if radius.is_finite() {
+            radius.sqrt()
+        } else {
+            0.
+        };
         Ball {
+            id: 0,
             center,
             radius,
             weight,
+            dist_mean,
+            dist_var: 0.,
+            assigned: 0,
+            frozen: false,
+            recent: f64::NEG_INFINITY,
+            velocity: None,
+            included: 0,
+            inside: 0,
         }
     }
 
+    /// Ball identifier, unique in its model: balls get increasing ids as they are added to the model,
+    /// a ball keeps its id when it is updated and a merged ball keeps the id of the ball which includes the point.
+    /// Balls which are not in a model have id 0, except the balls read by [crate::serializer::parse_columnar].
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Tells whether the point lies within the radius of the ball, boundary included,
+    /// given the square of the distance between two points.
+    /// Balls which radius is not known yet contain all points.
+    /// ```
+    /// use fluent_data::{model::Ball, space};
+    ///
+    /// let ball = Ball::new(vec![0.], 4., 1.);
+    /// assert!(ball.contains(&vec![2.], space::euclid_dist));
+    /// assert!(!ball.contains(&vec![2.5], space::euclid_dist));
+    /// ```
+    pub fn contains(&self, point: &Point, dist: impl Fn(&Point, &Point) -> f64) -> bool {
+        dist(point, &self.center) <= self.radius
+    }
+
     /// Ball center.
     pub fn center(&self) -> &Point {
         &self.center
@@ -42,26 +129,288 @@ impl<Point: PartialEq> Ball<Point> {
     pub fn weight(&self) -> f64 {
         self.weight
     }
+
+    /// Smoothed displacement of the center per included point, if tracked, see [crate::Algo::with_velocity].
+    pub fn velocity(&self) -> Option<&[f64]> {
+        self.velocity.as_deref()
+    }
+
+    /// Tells whether this ball is frozen, see [Model::freeze_ball].
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Sets the frozen flag, e.g. to restore a frozen ball with [Model::load].
+    pub fn with_frozen(mut self, frozen: bool) -> Self {
+        self.frozen = frozen;
+        self
+    }
+
+    /// Moves the center, see [Model::balls_mut].
+    pub fn set_center(&mut self, center: Point) {
+        self.center = center;
+    }
+
+    /// Changes the radius, see [Model::balls_mut].
+    pub fn set_radius(&mut self, radius: f64) {
+        self.radius = radius * radius;
+    }
+
+    /// Changes the weight, see [Model::balls_mut].
+    pub fn set_weight(&mut self, weight: f64) {
+        self.weight = weight;
+    }
+
+    /// Sets the frozen flag, see [Model::balls_mut].
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Tells whether this ball overlaps the other one given the square of the distance between their centers.
+    /// Balls overlap when this distance is less than `threshold` times the sum of their squared radii.
+    pub(crate) fn overlaps(&self, other: &Ball<Point>, center_dist: f64, threshold: f64) -> bool {
+        center_dist < (self.radius + other.radius) * threshold
+    }
+}
+
+/// Time based weight decay: ball weights are multiplied by `factor` every `period`
+/// and balls which weight falls below `floor` are removed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecayConfig {
+    /// The factor applied to weights once per period, in `]0, 1]`.
+    pub factor: f64,
+    /// The period of the decay, it must not be zero.
+    pub period: Duration,
+    /// The weight below which balls are removed, frozen balls are kept.
+    pub floor: f64,
+}
+
+/// Per dimension bounds that map real points into the unit cube before they are fitted,
+/// see [crate::Algo::with_normalization].
+///
+/// The balls of a normalized model are in normalized units, their centers are written in original units
+/// by the serializers, their radii are kept in normalized units.
+/// ```
+/// use fluent_data::model::{Normalization, OutOfRange};
+///
+/// let normalization = Normalization::new(vec![0., 10.], vec![2., 30.], OutOfRange::Clamp);
+/// assert_eq!(vec![0.5, 1.], normalization.normalize(&[1., 50.]));
+/// assert_eq!(vec![1., 30.], normalization.denormalize(&[0.5, 1.]));
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Normalization {
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+    pub out_of_range: OutOfRange,
+}
+
+/// What [Normalization] does with coordinates outside of the learned bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutOfRange {
+    /// Coordinates are clamped into `[0, 1]`.
+    Clamp,
+    /// Coordinates are scaled like the others and may fall outside of `[0, 1]`.
+    PassThrough,
+}
+
+impl Normalization {
+    /// Builds a normalization with the given bounds for each dimension.
+    pub fn new(min: Vec<f64>, max: Vec<f64>, out_of_range: OutOfRange) -> Self {
+        Normalization {
+            min,
+            max,
+            out_of_range,
+        }
+    }
+
+    /// Learns the bounds of each dimension from the given points.
+    pub fn learn<'a>(
+        points: impl IntoIterator<Item = &'a Vec<f64>>,
+        out_of_range: OutOfRange,
+    ) -> Self {
+        let (mut min, mut max) = (Vec::<f64>::new(), Vec::<f64>::new());
+        for point in points {
+            if min.len() < point.len() {
+                min.resize(point.len(), f64::INFINITY);
+                max.resize(point.len(), f64::NEG_INFINITY);
+            }
+            for (i, x) in point.iter().enumerate() {
+                min[i] = min[i].min(*x);
+                max[i] = max[i].max(*x);
+            }
+        }
+        Normalization::new(min, max, out_of_range)
+    }
+
+    /// Maps a point in original units into the unit cube.
+    /// Dimensions without bounds or which bounds are equal are only shifted.
+    pub fn normalize(&self, point: &[f64]) -> Vec<f64> {
+        let mut normalized = self.scale(point);
+        if self.out_of_range == OutOfRange::Clamp {
+            normalized.iter_mut().for_each(|y| *y = y.clamp(0., 1.));
+        }
+        normalized
+    }
+
+    /// Maps a point in original units to normalized units, without clamping.
+    pub(crate) fn scale(&self, point: &[f64]) -> Vec<f64> {
+        point
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let (min, range) = self.bounds(i);
+                (x - min) / range
+            })
+            .collect()
+    }
+
+    /// Maps a point in normalized units back to original units.
+    pub fn denormalize(&self, point: &[f64]) -> Vec<f64> {
+        point
+            .iter()
+            .enumerate()
+            .map(|(i, y)| {
+                let (min, range) = self.bounds(i);
+                min + y * range
+            })
+            .collect()
+    }
+
+    /// Maps a displacement in normalized units back to original units, i.e. without the shift.
+    fn denormalize_offset(&self, offset: &[f64]) -> Vec<f64> {
+        offset
+            .iter()
+            .enumerate()
+            .map(|(i, y)| y * self.bounds(i).1)
+            .collect()
+    }
+
+    /// Maps a serialized ball in normalized units back to original units; all the outputs go through it.
+    ///
+    /// The center is denormalized and the velocity is scaled. A ball is isotropic in normalized units only,
+    /// thus `"radius"` stays in normalized units and `"radii"` gives its semi-axis along each dimension in original units.
+    pub(crate) fn denormalize_ball(&self, ball: &mut Map<String, Value>) {
+        let center = ball
+            .get("center")
+            .and_then(|c| serde_json::from_value::<Vec<f64>>(c.clone()).ok());
+        if let Some(center) = center {
+            let radii = ball
+                .get("radius")
+                .and_then(Value::as_f64)
+                .map(|radius| self.denormalize_offset(&vec![radius; center.len()]));
+            ball.insert("center".into(), Value::from(self.denormalize(&center)));
+            if let Some(radii) = radii {
+                ball.insert("radii".into(), Value::from(radii));
+            }
+        }
+        let velocity = ball
+            .get("velocity")
+            .and_then(|v| serde_json::from_value::<Vec<f64>>(v.clone()).ok());
+        if let Some(velocity) = velocity {
+            ball.insert(
+                "velocity".into(),
+                Value::from(self.denormalize_offset(&velocity)),
+            );
+        }
+    }
+
+    /// The lower bound and the extent of a dimension, the extent is never zero.
+    fn bounds(&self, dim: usize) -> (f64, f64) {
+        match (self.min.get(dim), self.max.get(dim)) {
+            (Some(min), Some(max)) if min.is_finite() && max > min => (*min, max - min),
+            (Some(min), _) if min.is_finite() => (*min, 1.),
+            _ => (0., 1.),
+        }
+    }
 }
 
+/// Number of points assigned to each ball since the counts were last taken, see [Model::take_assignments].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Assignments {
+    /// Points assigned to each ball, in the [Model::iter_balls] order.
+    /// The counts of merged balls are combined.
+    pub balls: Vec<u64>,
+    /// Points that were assigned to balls which have been removed since.
+    pub removed: u64,
+}
+
+/// Maps a point to another point of the same space, e.g. to normalize it.
+/// It is shared with the snapshots of the model, see [Model::snapshot_with].
+type PointMap<Point> = Arc<dyn Fn(&Point) -> Point + Send + Sync>;
+
+/// The distance from a point to a ball, which may stop early above a bound, see [Model::with_bounded_dist].
+type BoundedDist<Point> = Box<dyn Fn(&Point, &Ball<Point>, f64) -> f64>;
+
 /// A graph node which represents a ball.
 pub(crate) type BallNode<Point> = Vertex<Ball<Point>>;
 
 /// A set of balls model.
 pub struct Model<Point: PartialEq> {
     pub(crate) dist: Box<dyn Fn(&Point, &Ball<Point>) -> f64>,
+    /// The distance to a ball that may stop early, see [Model::with_bounded_dist].
+    bounded_dist: Option<BoundedDist<Point>>,
+    space_dist: Rc<dyn Fn(&Point, &Point) -> f64>,
     pub(crate) graph: Vec<BallNode<Point>>,
+    cache: RefCell<Option<Neighbor<Ball<Point>>>>,
+    pub(crate) recent: VecDeque<Point>,
+    removed: Cell<u64>,
+    search: SearchStats,
+    recent_window: Option<(Duration, Instant)>,
+    fitted: RefCell<Option<Neighbor<Ball<Point>>>>,
+    pub(crate) normalization: Option<Normalization>,
+    /// Maps queried points in original units into the units of the balls, set with the normalization.
+    to_model_units: Option<PointMap<Point>>,
+    /// Whether the balls were changed through [Model::balls_mut] since the neighbors were computed.
+    stale_neighbors: Cell<bool>,
+    /// The id of the next ball added to the model.
+    next_id: u64,
+}
+
+/// Counters of the budgeted neighborhood searches, see [Model::truncated_searches].
+#[derive(Default)]
+struct SearchStats {
+    /// Number of searches restricted to a subset of the balls.
+    truncated: Cell<u64>,
+    /// Number of candidates evaluated by the last search.
+    evaluated: Cell<usize>,
+    /// Position of the next balls scanned when the graph walk does not fill the budget.
+    scan: Cell<usize>,
 }
 
 impl<Point: PartialEq + 'static> Model<Point> {
     /// Build a new model.
+    ///
+    /// The distance may be a boxed trait object when it is chosen at runtime.
+    /// ```
+    /// use fluent_data::{Model, space};
+    ///
+    /// let dist: Box<dyn Fn(&Vec<f64>, &Vec<f64>) -> f64> = Box::new(space::euclid_dist);
+    /// let model = Model::new(dist);
+    /// ```
     pub fn new<Dist>(space_dist: Dist) -> Self
     where
         Dist: Fn(&Point, &Point) -> f64 + 'static,
     {
+        let space_dist: Rc<dyn Fn(&Point, &Point) -> f64> = Rc::new(space_dist);
+        let normalized = Rc::clone(&space_dist);
         Self {
-            dist: Box::new(Model::normalize(space_dist)),
+            dist: Box::new(Model::normalize(move |p1: &Point, p2: &Point| {
+                normalized(p1, p2)
+            })),
+            bounded_dist: None,
+            space_dist,
             graph: vec![],
+            cache: RefCell::new(None),
+            recent: VecDeque::new(),
+            removed: Cell::new(0),
+            search: SearchStats::default(),
+            recent_window: None,
+            fitted: RefCell::new(None),
+            normalization: None,
+            to_model_units: None,
+            stale_neighbors: Cell::new(false),
+            next_id: 0,
         }
     }
 
@@ -86,13 +435,69 @@ impl<Point: PartialEq + 'static> Model<Point> {
         for ball in data {
             model.add_ball(ball, vec![]);
         }
-        for vertex in model.graph.iter() {
-            let neighborhood = model
+        model.rebuild_neighbors();
+        model
+    }
+
+    /// Same as [Model::load] but the balls keep their id, e.g. balls restored from a checkpoint
+    /// by [crate::serializer::parse_columnar], so that consumers match them across a restart.
+    /// Balls added afterwards get ids above the largest one. Balls must be given in ascending id order.
+    pub fn restore<Dist>(space_dist: Dist, data: Vec<Ball<Point>>) -> Self
+    where
+        Dist: Fn(&Point, &Point) -> f64 + 'static,
+    {
+        let mut model = Self::new(space_dist);
+        let mut next_id = 0;
+        for ball in data {
+            next_id = next_id.max(ball.id + 1);
+            model.next_id = ball.id;
+            model.add_ball(ball, vec![]);
+        }
+        model.next_id = next_id;
+        model.rebuild_neighbors();
+        model
+    }
+
+    /// Searches the balls a point belongs to with a distance which computation stops early,
+    /// e.g. [space::euclid_dist_bounded] for a model built with [space::euclid_dist],
+    /// see [GetNeighborhoodBounded](crate::neighborhood::GetNeighborhoodBounded).
+    ///
+    /// `bounded_dist` must return the distance of the model when it is lower than the given bound,
+    /// and any distance not lower than the bound otherwise; fits and queries are then unchanged, only faster.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, neighborhood::Neighborhood, space};
+    ///
+    /// let balls = vec![Ball::new(vec![0., 0.], 1., 1.), Ball::new(vec![10., 10.], 1., 1.)];
+    /// let model = Model::load(space::euclid_dist, balls).with_bounded_dist(space::euclid_dist_bounded);
+    /// match model.predict(&vec![9., 9.]) {
+    ///     Neighborhood::Two(nearest, _) => assert_eq!(&vec![10., 10.], nearest.coord().center()),
+    ///     _ => panic!(),
+    /// };
+    /// ```
+    pub fn with_bounded_dist<Bounded>(mut self, bounded_dist: Bounded) -> Self
+    where
+        Bounded: Fn(&Point, &Point, f64) -> f64 + 'static,
+    {
+        self.bounded_dist = Some(Box::new(move |point, ball, bound| {
+            bounded_dist(point, &ball.center, bound * ball.radius) / ball.radius
+        }));
+        self
+    }
+
+    /// Changes whenever a ball is added or removed, e.g. to cache values computed over the set of balls.
+    pub(crate) fn ball_set_version(&self) -> (u64, usize) {
+        (self.next_id, self.graph.len())
+    }
+
+    /// Computes the neighbors of all balls from scratch.
+    pub(crate) fn rebuild_neighbors(&self) {
+        for vertex in self.graph.iter() {
+            let neighborhood = self
                 .graph
                 .iter()
                 .filter(|v| v.ne(&vertex))
                 .get_neighborhood(&vertex.deref_data().center, |v1, v2| {
-                    (model.dist)(v1, &v2.deref_data())
+                    (self.dist)(v1, &v2.deref_data())
                 });
             let neighbors = {
                 let mut neighbors = vec![];
@@ -110,7 +515,45 @@ impl<Point: PartialEq + 'static> Model<Point> {
             };
             vertex.set_neighbors(neighbors.iter().map(|v| v.as_neighbor()).collect());
         }
-        model
+        self.cache.replace(None);
+        self.stale_neighbors.set(false);
+    }
+
+    /// Marks the neighbors as stale after a ball was changed, e.g. by [crate::Algo::assign_to].
+    pub(crate) fn invalidate_neighbors(&self) {
+        self.stale_neighbors.set(true);
+    }
+
+    /// Recomputes the neighbors of all balls if they were changed through [Model::balls_mut].
+    fn refresh_neighbors(&self) {
+        if self.stale_neighbors.get() {
+            self.rebuild_neighbors();
+        }
+    }
+
+    /// Builds a model from `(center, weight)` pairs, e.g. clusters adjusted by an external tool, see [to_weighted_points].
+    /// All balls get the `default_radius`.
+    /// ```
+    /// use fluent_data::{Model, space};
+    ///
+    /// let model = Model::from_weighted_points(vec![(vec![0.], 2.), (vec![10.], 1.)], space::euclid_dist, 1.5);
+    /// let ball = model.iter_balls().next().unwrap();
+    /// assert_eq!((1.5, 2.), (ball.radius(), ball.weight()));
+    /// ```
+    pub fn from_weighted_points<Dist>(
+        points: Vec<(Point, f64)>,
+        space_dist: Dist,
+        default_radius: f64,
+    ) -> Self
+    where
+        Dist: Fn(&Point, &Point) -> f64 + 'static,
+    {
+        let radius = default_radius * default_radius;
+        let data = points
+            .into_iter()
+            .map(|(center, weight)| Ball::new(center, radius, weight))
+            .collect();
+        Self::load(space_dist, data)
     }
 
     /// Normalize the given distance function by dividing by the radius.
@@ -123,11 +566,103 @@ impl<Point: PartialEq + 'static> Model<Point> {
 
     /// Get the vertices associated to balls which the given point most probably belongs to.
     pub(crate) fn get_neighborhood(&self, point: &Point) -> Vec<BallNode<Point>> {
+        self.refresh_neighbors();
+        self.get_neighborhood_among(self.graph.iter(), point)
+    }
+
+    /// Get the vertices associated to balls which the given point most probably belongs to,
+    /// evaluating at most `budget` candidates.
+    ///
+    /// When the model has more balls than the budget, the candidates are collected by walking the graph
+    /// from the ball found by the previous search, then by scanning the balls in turn; the result may not be exact.
+    pub(crate) fn get_neighborhood_within(
+        &self,
+        point: &Point,
+        budget: usize,
+    ) -> Vec<BallNode<Point>> {
+        self.refresh_neighbors();
+        let neighbors = if self.graph.len() <= budget {
+            self.search.evaluated.set(self.graph.len());
+            self.get_neighborhood(point)
+        } else {
+            self.search.truncated.set(self.search.truncated.get() + 1);
+            let candidates = self.collect_candidates(budget);
+            self.search.evaluated.set(candidates.len());
+            self.get_neighborhood_among(candidates.iter(), point)
+        };
+        *self.cache.borrow_mut() = neighbors.first().map(|v| v.as_neighbor());
+        neighbors
+    }
+
+    /// Collects at most `budget` distinct vertices, starting with the ball found by the previous search
+    /// and the last added ball, then their neighbors in the graph, then balls taken in turn from the model.
+    fn collect_candidates(&self, budget: usize) -> Vec<BallNode<Point>> {
+        let mut candidates: Vec<BallNode<Point>> = vec![];
+        let push = |candidates: &mut Vec<BallNode<Point>>, vertex: BallNode<Point>| {
+            if candidates.len() < budget && !candidates.iter().any(|c| c.ptr_eq(&vertex)) {
+                candidates.push(vertex);
+            }
+        };
+        let cached = self.cache.borrow().as_ref().and_then(|n| n.as_vertex());
+        for vertex in cached.into_iter().chain(self.graph.last().cloned()) {
+            push(&mut candidates, vertex);
+        }
+        let mut next = 0;
+        while next < candidates.len() && candidates.len() < budget {
+            let neighbors: Vec<BallNode<Point>> = candidates[next].iter_neighbors().collect();
+            for neighbor in neighbors {
+                push(&mut candidates, neighbor);
+            }
+            next += 1;
+        }
+        let len = self.graph.len();
+        let mut scan = self.search.scan.get();
+        for _ in 0..len {
+            if candidates.len() >= budget {
+                break;
+            }
+            push(&mut candidates, self.graph[scan % len].clone());
+            scan = (scan + 1) % len;
+        }
+        self.search.scan.set(scan);
+        candidates
+    }
+
+    /// Number of neighborhood searches that were restricted to a subset of the balls
+    /// because the model exceeded the algorithm budget, see [Algo::with_budget](crate::Algo::with_budget).
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_budget(2);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for x in [0., 100., 1000., 10000., 5.] {
+    ///     algo.fit(&mut model, vec![x]);
+    /// }
+    /// assert!(model.truncated_searches() > 0);
+    /// ```
+    pub fn truncated_searches(&self) -> u64 {
+        self.search.truncated.get()
+    }
+
+    /// Number of candidates evaluated by the last budgeted neighborhood search.
+    #[cfg(test)]
+    pub(crate) fn evaluated_candidates(&self) -> usize {
+        self.search.evaluated.get()
+    }
+
+    /// Get the vertices associated to the balls among `candidates` which the given point most probably belongs to.
+    fn get_neighborhood_among<'a>(
+        &self,
+        mut candidates: impl Iterator<Item = &'a BallNode<Point>>,
+        point: &Point,
+    ) -> Vec<BallNode<Point>> {
         let mut neighbors = vec![];
-        let neighborhood = self
-            .graph
-            .iter()
-            .get_neighborhood(point, |p, m| (self.dist)(p, &*m.deref_data()));
+        let neighborhood = match &self.bounded_dist {
+            Some(bounded_dist) => candidates.get_neighborhood_bounded(point, |p, m, bound| {
+                bounded_dist(p, &*m.deref_data(), bound)
+            }),
+            None => candidates.get_neighborhood(point, |p, m| (self.dist)(p, &*m.deref_data())),
+        };
 
         match neighborhood {
             Neighborhood::Two(n1, n2) => {
@@ -142,26 +677,392 @@ impl<Point: PartialEq + 'static> Model<Point> {
         neighbors
     }
 
+    /// Tells whether the given point lies in some ball, i.e. its square distance to the ball center
+    /// is less than `threshold` times the square of the ball radius.
+    /// The ball found by the previous call and its neighbors are checked first,
+    /// a full neighborhood search is done only when none of them covers the point.
+    pub(crate) fn is_covered(&self, point: &Point, threshold: f64) -> bool {
+        self.refresh_neighbors();
+        let covers = |v: &BallNode<Point>| (self.dist)(point, &v.deref_data()) < threshold;
+        let cached = self.cache.borrow().as_ref().and_then(|n| n.as_vertex());
+        if let Some(vertex) = cached {
+            if covers(&vertex) || vertex.iter_neighbors().any(|v| covers(&v)) {
+                return true;
+            }
+        }
+        match self.get_neighborhood(point).first() {
+            Some(closest) => {
+                *self.cache.borrow_mut() = Some(closest.as_neighbor());
+                covers(closest)
+            }
+            None => false,
+        }
+    }
+
+    /// The normalization applied to points before they are fitted, if any, see [crate::Algo::with_normalization].
+    pub fn normalization(&self) -> Option<&Normalization> {
+        self.normalization.as_ref()
+    }
+
+    /// Removes all balls from the model.
+    pub(crate) fn clear(&mut self) {
+        self.graph
+            .iter()
+            .for_each(|v| self.count_removed(&v.deref_data()));
+        self.graph.clear();
+        self.cache.replace(None);
+    }
+
+    /// Counts the points assigned to a ball which is removed.
+    pub(crate) fn count_removed(&self, ball: &Ball<Point>) {
+        self.removed.set(self.removed.get() + ball.assigned);
+    }
+
+    /// Records the ball which includes the last fitted point, see [Model::last_fitted_ball].
+    pub(crate) fn record_fitted(&self, vertex: Option<&BallNode<Point>>) {
+        self.fitted.replace(vertex.map(|v| v.as_neighbor()));
+    }
+
+    /// Gets the index of the ball which includes the last fitted point, in the [Model::iter_balls] order.
+    /// Returns `None` if no point was fitted yet or if the ball was removed since.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for point in [vec![0.], vec![1.], vec![100.]] {
+    ///     algo.fit(&mut model, point);
+    /// }
+    /// assert_eq!(Some(1), model.last_fitted_ball());
+    /// ```
+    pub fn last_fitted_ball(&self) -> Option<usize> {
+        let vertex = self.fitted.borrow().as_ref()?.as_vertex()?;
+        self.graph.iter().position(|v| v.ptr_eq(&vertex))
+    }
+
+    /// Tracks the weight of the points recently assigned to each ball, see [Model::recent_weight_share].
+    /// A point assigned `window` ago counts for `1/e` of a point assigned now.
+    pub fn with_recent_window(mut self, window: Duration) -> Self {
+        self.recent_window = Some((window, Instant::now()));
+        self
+    }
+
+    /// Adds a point assigned now to the recent weight of the ball, if recent weights are tracked.
+    ///
+    /// Recent weights are stored in logarithmic scale relatively to the time the tracking started,
+    /// thus they never need to be decayed: all balls would be decayed by the same factor.
+    pub(crate) fn record_recent(&self, vertex: &BallNode<Point>) {
+        if let Some((window, start)) = self.recent_window {
+            let log_weight = start.elapsed().as_secs_f64() / window.as_secs_f64();
+            let mut ball = vertex.deref_data_mut();
+            ball.recent = log_add(ball.recent, log_weight);
+        }
+    }
+
+    /// Gets each ball's share of the recent weight, in the [Model::iter_balls] order.
+    /// Unlike the ball weights, which decay with the number of incoming points,
+    /// the recent weights decay with time, see [Model::with_recent_window].
+    /// All shares are zero when recent weights are not tracked or no point was assigned yet.
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist).with_recent_window(Duration::from_secs(60));
+    /// for x in [1., 2., 1.5, 30.] {
+    ///     algo.fit(&mut model, vec![x]);
+    /// }
+    /// let shares = model.recent_weight_share();
+    /// assert!((shares.iter().sum::<f64>() - 1.).abs() < 1E-9);
+    /// ```
+    pub fn recent_weight_share(&self) -> Vec<f64> {
+        let log_weights: Vec<f64> = self.iter_balls().map(|b| b.recent).collect();
+        let max = log_weights
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if max == f64::NEG_INFINITY {
+            return vec![0.; log_weights.len()];
+        }
+        let weights: Vec<f64> = log_weights.iter().map(|w| (w - max).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        weights.iter().map(|w| w / total).collect()
+    }
+
+    /// Gets, for each ball in the [Model::iter_balls] order, the fraction of the points included in the ball
+    /// which were within its radius when they were included, see [Ball::contains].
+    ///
+    /// The expected fraction depends on the [RadiusStrategy](crate::algorithm::RadiusStrategy) and on the distribution of the points,
+    /// e.g. it is `1 - 1/e`, about 63%, for the root mean square radius of a normal cluster in R^2;
+    /// a ball which coverage is far from the expected one has a radius that does not fit its points.
+    /// The points that created the ball are not counted; the coverage is `NaN` for a ball which included no other point.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for x in [0., 2., 1., 1.5, 0.5, 5.] {
+    ///     algo.fit(&mut model, vec![x]);
+    /// }
+    /// let coverage = model.radius_coverage()[0];
+    /// assert!(0. < coverage && coverage < 1.);
+    /// ```
+    pub fn radius_coverage(&self) -> Vec<f64> {
+        self.iter_balls()
+            .map(|b| {
+                if b.included == 0 {
+                    f64::NAN
+                } else {
+                    b.inside as f64 / b.included as f64
+                }
+            })
+            .collect()
+    }
+
+    /// Gets the number of points assigned to each ball since the last call and resets the counts.
+    /// ```
+    /// use fluent_data::{Algo, Model, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// for x in [1., 2., 1.5, 30.] {
+    ///     algo.fit(&mut model, vec![x]);
+    /// }
+    /// let assignments = model.take_assignments();
+    /// assert_eq!(4, assignments.balls.iter().sum::<u64>() + assignments.removed);
+    /// assert_eq!(0, model.take_assignments().balls.iter().sum::<u64>());
+    /// ```
+    pub fn take_assignments(&self) -> Assignments {
+        let balls = self
+            .graph
+            .iter()
+            .map(|v| std::mem::take(&mut v.deref_data_mut().assigned))
+            .collect();
+        Assignments {
+            balls,
+            removed: self.removed.take(),
+        }
+    }
+
     /// Add a new ball or ball to the model.
     /// Balls neighbors are generally already known,
     /// thus in order to avoid unecessary calls to `Self.get_neighborhood` they are also passed.
+    /// The ball gets the next id and is the last in the [Model::iter_balls] order.
     pub(crate) fn add_ball(
         &mut self,
-        ball: Ball<Point>,
+        mut ball: Ball<Point>,
         neighbors: Vec<Neighbor<Ball<Point>>>,
     ) -> BallNode<Point> {
+        ball.id = self.next_id;
+        self.next_id += 1;
         let vertex = Vertex::new(ball);
         vertex.set_neighbors(neighbors);
         self.graph.push(vertex.clone());
         vertex
     }
 
-    /// Gets an iterator over the balls of this model.
+    /// Gets the indices of the balls that overlap the ball at `index`, in the [Model::iter_balls] order.
+    /// Balls overlap when the square of the distance between their centers is less than
+    /// `threshold` times the sum of their squared radii; balls are merged by the algorithm with a threshold of 1.
+    /// Returns an error if the index is out of range.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let data = vec![
+    ///     Ball::new(vec![0.], 4., 1.),
+    ///     Ball::new(vec![2.], 4., 1.),
+    ///     Ball::new(vec![9.], 4., 1.),
+    /// ];
+    /// let model = Model::load(space::euclid_dist, data);
+    /// assert_eq!(vec![1], model.overlapping_with(0, 1.).unwrap());
+    /// assert!(model.overlapping_with(3, 1.).is_err());
+    /// ```
+    pub fn overlapping_with(
+        &self,
+        index: usize,
+        threshold: f64,
+    ) -> Result<Vec<usize>, Box<dyn Error>> {
+        let ball = self.vertex(index)?.deref_data();
+        let overlapping = self
+            .iter_balls()
+            .enumerate()
+            .filter(|(i, other)| {
+                *i != index
+                    && ball.overlaps(
+                        other,
+                        (self.space_dist)(&ball.center, &other.center),
+                        threshold,
+                    )
+            })
+            .map(|(i, _)| i)
+            .collect();
+        Ok(overlapping)
+    }
+
+    /// Freezes the ball at `index`, in the [Model::iter_balls] order, e.g. to protect a reference cluster.
+    ///
+    /// A frozen ball still attracts points, which are counted in assignments,
+    /// but its center, radius and weight are not updated; it is never merged nor removed.
+    /// Returns an error if the index is out of range.
+    /// ```
+    /// use fluent_data::{Algo, Model, model::Ball, space};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+    /// model.freeze_ball(0).unwrap();
+    /// algo.fit(&mut model, vec![0.5]);
+    /// let ball = model.iter_balls().next().unwrap();
+    /// assert!(ball.is_frozen());
+    /// assert_eq!(&vec![0.], ball.center());
+    /// ```
+    pub fn freeze_ball(&self, index: usize) -> Result<(), Box<dyn Error>> {
+        self.vertex(index)?.deref_data_mut().frozen = true;
+        Ok(())
+    }
+
+    /// Unfreezes the ball at `index`, see [Model::freeze_ball].
+    /// Returns an error if the index is out of range.
+    pub fn unfreeze_ball(&self, index: usize) -> Result<(), Box<dyn Error>> {
+        self.vertex(index)?.deref_data_mut().frozen = false;
+        Ok(())
+    }
+
+    /// Gets the vertex at `index` or an error if the index is out of range.
+    pub(crate) fn vertex(&self, index: usize) -> Result<&BallNode<Point>, Box<dyn Error>> {
+        let vertex = self.graph.get(index).ok_or_else(|| {
+            format!(
+                "ball index {} out of range, model has {} balls",
+                index,
+                self.graph.len()
+            )
+        })?;
+        Ok(vertex)
+    }
+
+    /// Gets, for each ball, the square of the distance from its center to the closest other ball center,
+    /// in the [Model::iter_balls] order. A ball with a small separation is a merge candidate.
+    /// The separation of a lonely ball is infinite.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let data = vec![
+    ///     Ball::new(vec![0.], 1., 1.),
+    ///     Ball::new(vec![2.], 1., 1.),
+    ///     Ball::new(vec![7.], 1., 1.),
+    /// ];
+    /// let model = Model::load(space::euclid_dist, data);
+    /// assert_eq!(vec![4., 4., 25.], model.separation_profile());
+    /// ```
+    pub fn separation_profile(&self) -> Vec<f64> {
+        self.center_distances()
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, d)| *d)
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect()
+    }
+
+    /// Computes the matrix of the distances between ball centers.
+    fn center_distances(&self) -> Vec<Vec<f64>> {
+        let centers: Vec<_> = self.iter_balls().collect();
+        centers
+            .iter()
+            .map(|b1| {
+                centers
+                    .iter()
+                    .map(|b2| (self.space_dist)(&b1.center, &b2.center))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Decays all balls by the given elapsed time, as if no point was received meanwhile.
+    /// Balls which weight falls below the floor are removed.
+    /// A model restored from a checkpoint is fast-forwarded by [crate::streamer::restore_checkpoint].
+    ///
+    /// Panics if the decay period is zero or if the decay factor is not in `]0, 1]`.
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use fluent_data::{Model, model::{Ball, DecayConfig}, space};
+    ///
+    /// let decay = DecayConfig { factor: 0.5, period: Duration::from_secs(60), floor: 1E-2 };
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 8.)]);
+    /// model.fast_forward(Duration::from_secs(180), &decay);
+    /// assert!((model.iter_balls().next().unwrap().weight() - 1.).abs() < 1E-12);
+    /// model.fast_forward(Duration::from_secs(3600), &decay);
+    /// assert_eq!(0, model.iter_balls().count());
+    /// ```
+    pub fn fast_forward(&mut self, elapsed: Duration, decay: &DecayConfig) {
+        assert!(!decay.period.is_zero(), "decay period must not be zero");
+        assert!(
+            decay.factor > 0. && decay.factor <= 1.,
+            "decay factor must be in ]0, 1], got {}",
+            decay.factor
+        );
+        let steps = elapsed.as_secs_f64() / decay.period.as_secs_f64();
+        self.decay_weights(decay.factor.ln() * steps, decay.floor);
+    }
+
+    /// Multiplies all weights by `exp(log_decay)` and removes balls which weight falls below `floor`.
+    /// Weights are computed in logarithmic scale so that they never become subnormal.
+    pub(crate) fn decay_weights(&mut self, log_decay: f64, floor: f64) {
+        let log_floor = floor.max(f64::MIN_POSITIVE).ln();
+        let removed = &self.removed;
+        self.graph.retain(|v| {
+            let mut ball = v.deref_data_mut();
+            if ball.frozen {
+                return true;
+            }
+            let log_weight = ball.weight.ln() + log_decay;
+            if log_weight > log_floor {
+                ball.weight = log_weight.exp();
+                true
+            } else {
+                removed.set(removed.get() + ball.assigned);
+                false
+            }
+        })
+    }
+
+    /// Gets an iterator over the balls of this model, in ascending [Ball::id] order.
+    ///
+    /// This order is stable: removing balls does not reorder the others and new balls come last.
+    /// Serializers write balls in this order and indices of balls in this order are used across the crate.
     pub fn iter_balls(&self) -> impl Iterator<Item = impl Deref<Target = Ball<Point>> + '_> {
         self.graph.iter().map(|v| v.deref_data())
     }
 
+    /// Gets an iterator over the balls of this model that allows changing them in place,
+    /// e.g. to clamp the radii after a batch of points.
+    ///
+    /// The neighbors of the balls are computed from their centers and radii, changing them invalidates the neighbors:
+    /// they are recomputed for all balls before the next search, which costs a distance per pair of balls.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 100., 1.)]);
+    /// for mut ball in model.balls_mut() {
+    ///     let radius = ball.radius().min(2.);
+    ///     ball.set_radius(radius);
+    /// }
+    /// assert_eq!(2., model.iter_balls().next().unwrap().radius());
+    /// ```
+    pub fn balls_mut(&mut self) -> impl Iterator<Item = impl DerefMut<Target = Ball<Point>> + '_> {
+        self.stale_neighbors.set(true);
+        self.graph.iter().map(|v| v.deref_data_mut())
+    }
+
     /// Gets the balls that most probably include the given point.
+    /// Queried points are in original units, they are normalized like fitted points when the model is normalized,
+    /// see [Model::normalization]; this holds for all the queries of the model.
+    /// The neighborhood is [Neighborhood::None] for an empty model and [Neighborhood::One] for a model of a single ball.
     /// ```
     /// use fluent_data::{Model, model::Ball, space, neighborhood::{GetNeighborhood, Neighborhood}};
     ///
@@ -187,24 +1088,967 @@ impl<Point: PartialEq + 'static> Model<Point> {
         &self,
         point: &Point,
     ) -> Neighborhood<Ball<Point>, impl Deref<Target = Ball<Point>> + '_> {
-        self.iter_balls()
-            .get_neighborhood(point, |p, m| (self.dist)(p, m))
+        let normalized = self.model_units(point);
+        self.predict_normalized(normalized.as_ref().unwrap_or(point))
     }
-}
-
-pub(crate) trait GetNeighbors<Point: PartialEq> {
-    fn get_neighbors(&self) -> Vec<Neighbor<Ball<Point>>>;
-}
 
-impl<Point: PartialEq> GetNeighbors<Point> for Vec<BallNode<Point>> {
-    fn get_neighbors(&self) -> Vec<Neighbor<Ball<Point>>> {
+    /// Predicts the neighborhood of a point already in the units of the balls, see [Model::predict].
+    fn predict_normalized(
+        &self,
+        point: &Point,
+    ) -> Neighborhood<Ball<Point>, impl Deref<Target = Ball<Point>> + '_> {
+        match &self.bounded_dist {
+            Some(bounded_dist) => self
+                .iter_balls()
+                .get_neighborhood_bounded(point, |p, m, bound| bounded_dist(p, m, bound)),
+            None => self
+                .iter_balls()
+                .get_neighborhood(point, |p, m| (self.dist)(p, m)),
+        }
+    }
+
+    /// Maps a queried point into the units of the balls, `None` when the model is not normalized.
+    fn model_units(&self, point: &Point) -> Option<Point> {
+        self.to_model_units
+            .as_ref()
+            .map(|normalize| normalize(point))
+    }
+
+    /// Tells whether the point lies within the radius of the ball that most probably includes it, see [Ball::contains].
+    /// Returns `None` if the model is empty.
+    pub fn is_within_radius(&self, point: &Point) -> Option<bool> {
+        let normalized = self.model_units(point);
+        let point = normalized.as_ref().unwrap_or(point);
+        match self.predict_normalized(point) {
+            Neighborhood::One(n) | Neighborhood::Two(n, _) => Some(
+                n.coord()
+                    .contains(point, |p1, p2| (self.space_dist)(p1, p2)),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Gets, for each ball of the `fine` model, the index of the ball of this model
+    /// that most probably contains its center, see [Model::predict].
+    /// Indices refer to the [Model::iter_balls] order; they are `None` when this model is empty.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let coarse = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 4., 1.), Ball::new(vec![10.], 4., 1.)]);
+    /// let fine = Model::load(space::euclid_dist, vec![Ball::new(vec![9.], 1., 1.), Ball::new(vec![1.], 1., 1.)]);
+    /// assert_eq!(vec![Some(1), Some(0)], coarse.parents_of(&fine));
+    /// ```
+    pub fn parents_of(&self, fine: &Model<Point>) -> Vec<Option<usize>> {
+        fine.iter_balls()
+            .map(|fine_ball| {
+                self.iter_balls()
+                    .map(|ball| (self.dist)(&fine_ball.center, &ball))
+                    .enumerate()
+                    .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+                    .map(|(index, _)| index)
+            })
+            .collect()
+    }
+
+    /// Gets the anomaly score of the given point, i.e. the distance from the point to the center
+    /// of the ball that most probably includes it, divided by the radius of this ball.
+    /// Returns `None` if the model is empty.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 4., 1.)]);
+    /// assert_eq!(Some(1.5), model.anomaly_score(&vec![3.]));
+    /// ```
+    pub fn anomaly_score(&self, point: &Point) -> Option<f64> {
+        match self.predict(point) {
+            Neighborhood::Two(n, _) | Neighborhood::One(n) => Some(n.dist().sqrt()),
+            Neighborhood::None => None,
+        }
+    }
+
+    /// Gets the index of the ball that most probably includes the given point, in the [Model::iter_balls] order,
+    /// and the distance from the point to this ball, as given by [Model::predict].
+    /// Returns `None` if the model is empty.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 4., 1.), Ball::new(vec![10.], 1., 1.)]);
+    /// assert_eq!(Some((1, 4.)), model.classify(&vec![8.]));
+    /// ```
+    pub fn classify(&self, point: &Point) -> Option<(usize, f64)> {
+        let normalized = self.model_units(point);
+        let point = normalized.as_ref().unwrap_or(point);
+        self.iter_balls()
+            .map(|ball| (self.dist)(point, &ball))
+            .enumerate()
+            .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+    }
+
+    /// Classifies each point of a batch without fitting it, see [Model::classify].
+    /// The model is not changed, e.g. to score a held-out set.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 4., 1.), Ball::new(vec![10.], 1., 1.)]);
+    /// let classes = model.classify_batch(&[vec![1.], vec![9.]]);
+    /// assert_eq!(vec![Some(0), Some(1)], classes.iter().map(|c| c.map(|(i, _)| i)).collect::<Vec<_>>());
+    /// ```
+    pub fn classify_batch(&self, points: &[Point]) -> Vec<Option<(usize, f64)>> {
+        points.iter().map(|point| self.classify(point)).collect()
+    }
+
+    /// Gets the `k` balls which centers are the closest to the given point, as `(ball index, squared distance)` pairs
+    /// by increasing distance, ball indices in the [Model::iter_balls] order.
+    /// Distances are given by the space distance of the model, regardless of the ball radii, unlike [Model::classify].
+    /// All balls are returned if the model has fewer than `k` balls.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(
+    ///     space::euclid_dist,
+    ///     vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![5.], 1., 1.), Ball::new(vec![2.], 1., 1.)],
+    /// );
+    /// assert_eq!(vec![(2, 1.), (1, 4.)], model.k_nearest_balls(&vec![3.], 2));
+    /// ```
+    pub fn k_nearest_balls(&self, point: &Point, k: usize) -> Vec<(usize, f64)> {
+        let normalized = self.model_units(point);
+        let point = normalized.as_ref().unwrap_or(point);
+        let mut nearest: Vec<(usize, f64)> = self
+            .iter_balls()
+            .map(|ball| (self.space_dist)(point, &ball.center))
+            .enumerate()
+            .collect();
+        nearest.sort_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+        nearest.truncate(k);
+        nearest
+    }
+
+    /// Gets the maximum reach of the model from the given anchor point, i.e. the distance to the farthest ball edge:
+    /// the maximum over the balls of the distance from the anchor to the center plus the radius.
+    /// Distances are the square roots of the space distance of the model, like [Ball::radius].
+    /// The reach is infinite when a ball radius is not known yet, and `None` for an empty model.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 4., 1.), Ball::new(vec![5.], 1., 1.)]);
+    /// assert_eq!(Some(5.), model.max_reach(&vec![1.]));
+    /// assert_eq!(None, Model::new(space::euclid_dist).max_reach(&vec![1.]));
+    /// ```
+    pub fn max_reach(&self, anchor: &Point) -> Option<f64> {
+        let normalized = self.model_units(anchor);
+        let anchor = normalized.as_ref().unwrap_or(anchor);
+        self.iter_balls()
+            .map(|ball| (self.space_dist)(anchor, &ball.center).sqrt() + ball.radius.sqrt())
+            .max_by(f64::total_cmp)
+    }
+
+    /// Gets the membership probabilities of the given point over all balls, see [Model::soft_predict_nearest].
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(
+    ///     space::euclid_dist,
+    ///     vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![2.], 1., 1.)],
+    /// );
+    /// assert_eq!(vec![(0, 0.5), (1, 0.5)], model.soft_predict(&vec![1.], 1.));
+    /// ```
+    pub fn soft_predict(&self, point: &Point, temperature: f64) -> Vec<(usize, f64)> {
+        self.soft_predict_nearest(point, temperature, usize::MAX)
+    }
+
+    /// Gets the membership probabilities of the given point over the `k` balls that most probably include it,
+    /// as `(ball index, probability)` pairs by decreasing probability, ball indices in the [Model::iter_balls] order.
+    ///
+    /// Probabilities are the softmax of `-d²/temperature` where `d` is the distance from the point
+    /// to the ball center divided by the ball radius; they sum to 1.
+    /// As the temperature tends to 0, all the probability goes to the ball returned by [Model::predict].
+    /// Returns an empty vector if the model is empty.
+    pub fn soft_predict_nearest(
+        &self,
+        point: &Point,
+        temperature: f64,
+        k: usize,
+    ) -> Vec<(usize, f64)> {
+        let normalized = self.model_units(point);
+        let point = normalized.as_ref().unwrap_or(point);
+        let mut memberships: Vec<(usize, f64)> = self
+            .iter_balls()
+            .enumerate()
+            .map(|(i, b)| (i, (self.dist)(point, &b)))
+            .collect();
+        memberships.sort_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+        memberships.truncate(k);
+        let min = match memberships.first() {
+            Some((_, d)) => *d,
+            None => return memberships,
+        };
+        // shift by the smallest distance so that the largest term is exp(0) whatever the distances
+        for (_, d) in memberships.iter_mut() {
+            *d = if *d == min {
+                1.
+            } else {
+                (-(*d - min) / temperature).exp()
+            };
+        }
+        let total: f64 = memberships.iter().map(|(_, p)| p).sum();
+        memberships.iter_mut().for_each(|(_, p)| *p /= total);
+        memberships
+    }
+}
+
+impl Model<QuantizedPoint> {
+    /// Quantizes the centers of the balls, e.g. after [Model::load].
+    /// The centers computed afterwards are quantized by the [Quantization::combine] function of the algorithm.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space::{self, QuantizedPoint, Q8}};
+    ///
+    /// let data = vec![Ball::new(QuantizedPoint::Exact(vec![1., 2., 3.]), 1., 1.)];
+    /// let model = Model::load(space::quantized_dist, data).with_quantization(&Q8);
+    /// assert!(model.iter_balls().next().unwrap().center().max_error() > 0.);
+    /// ```
+    pub fn with_quantization(self, quantization: &Quantization) -> Self {
+        for vertex in self.graph.iter() {
+            let mut ball = vertex.deref_data_mut();
+            let coords: Vec<f64> = ball.center.coords().collect();
+            ball.center = quantization.quantize(&coords);
+        }
+        self
+    }
+
+    /// Number of bytes used by the centers of the balls.
+    pub fn center_bytes(&self) -> usize {
+        self.iter_balls().map(|b| b.center.size_in_bytes()).sum()
+    }
+}
+
+impl<Point: PartialEq + Clone + 'static> Model<Point> {
+    /// Adds the balls of the `other` model to this model, after its own balls.
+    ///
+    /// Balls are copied as they are: overlapping balls of both models are not combined,
+    /// see [crate::Algo::merge_overlapping].
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+    /// let other = Model::load(space::euclid_dist, vec![Ball::new(vec![10.], 1., 2.)]);
+    /// model.merge(&other);
+    /// assert_eq!(2, model.iter_balls().count());
+    /// ```
+    pub fn merge(&mut self, other: &Model<Point>) {
+        for ball in other.iter_balls() {
+            self.add_ball(ball.clone(), vec![]);
+        }
+        self.rebuild_neighbors();
+    }
+
+    /// Takes a read-only snapshot of the model which can be shared between threads, e.g. to serve queries
+    /// while the model keeps being fitted: the fitting thread publishes a new snapshot from time to time,
+    /// and readers query the snapshot they hold without locking.
+    ///
+    /// **The snapshot is given the distance again, and it must be the one the model was built with:**
+    /// the distance of the model cannot be shared between threads, and a different distance is not detected,
+    /// the snapshot then gives wrong answers to [ModelSnapshot::classify], [ModelSnapshot::log_likelihood]
+    /// and [ModelSnapshot::density_at]. The normalization, if any, is carried over:
+    /// the snapshot is queried in original units, like the model.
+    /// ```
+    /// use std::thread;
+    ///
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![10.], 1., 1.)]);
+    /// let snapshot = model.snapshot_with(space::euclid_dist);
+    /// let reader = thread::spawn(move || snapshot.classify(&vec![9.]));
+    /// assert_eq!(Some((1, 1.)), reader.join().unwrap());
+    /// ```
+    pub fn snapshot_with<Dist>(&self, space_dist: Dist) -> Arc<ModelSnapshot<Point>>
+    where
+        Dist: Fn(&Point, &Point) -> f64 + Send + Sync + 'static,
+    {
+        Arc::new(ModelSnapshot {
+            balls: self.iter_balls().map(|ball| ball.clone()).collect(),
+            space_dist: Box::new(space_dist),
+            to_model_units: self.to_model_units.clone(),
+        })
+    }
+
+    /// Builds a copy of the model for another distance, e.g. to migrate a running model to a standardized space.
+    /// When the points change too, the centers are mapped through `transform`; points fitted or predicted
+    /// afterwards are expected to be transformed the same way. See [crate::Algo::rebuild_with] for the algorithm.
+    ///
+    /// The square radius of each ball is multiplied by the largest stretch of its distances to the other centers,
+    /// i.e. the largest ratio of the new distance between the transformed centers to the old distance.
+    /// This is exact for a uniform scaling and conservative along the directions of the other balls
+    /// for other linear transforms. The radius is unchanged when there is no other ball.
+    /// The neighbors are computed under the new distance; the normalization, if any, is not carried over.
+    /// ```
+    /// use fluent_data::{model::{Ball, Model}, space};
+    ///
+    /// let balls = vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![10.], 4., 1.)];
+    /// let model = Model::load(space::euclid_dist, balls);
+    /// let double: fn(&Vec<f64>) -> Vec<f64> = |p| p.iter().map(|x| 2. * x).collect();
+    /// let rebuilt = model.rebuild_with(space::euclid_dist, Some(double));
+    /// let ball = rebuilt.iter_balls().nth(1).unwrap();
+    /// assert_eq!(&vec![20.], ball.center());
+    /// assert_eq!(4., ball.radius());
+    /// ```
+    pub fn rebuild_with<Dist>(
+        &self,
+        space_dist: Dist,
+        transform: Option<fn(&Point) -> Point>,
+    ) -> Model<Point>
+    where
+        Dist: Fn(&Point, &Point) -> f64 + 'static,
+    {
+        let map = |p: &Point| match transform {
+            Some(transform) => transform(p),
+            None => p.clone(),
+        };
+        let balls: Vec<Ball<Point>> = self.iter_balls().map(|b| b.clone()).collect();
+        let centers: Vec<Point> = balls.iter().map(|b| map(&b.center)).collect();
+        let rebuilt = balls
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut ball)| {
+                let stretch = centers
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .filter_map(|(j, center)| {
+                        let old =
+                            (self.space_dist)(&ball.center, &self.graph[j].deref_data().center);
+                        (old > 0.).then(|| space_dist(&centers[i], center) / old)
+                    })
+                    .reduce(f64::max)
+                    .unwrap_or(1.);
+                ball.center = centers[i].clone();
+                ball.radius *= stretch;
+                ball.dist_mean *= stretch.sqrt();
+                ball.dist_var *= stretch;
+                ball
+            })
+            .collect();
+        Model::load(space_dist, rebuilt)
+    }
+}
+
+impl Model<Vec<f64>> {
+    /// Restores the normalization of a model, e.g. after [Model::load].
+    /// The centers of the balls are expected in original units, as they are serialized, and are normalized;
+    /// points fitted afterwards are normalized the same way.
+    /// ```
+    /// use fluent_data::{Model, model::{Ball, Normalization, OutOfRange}, space};
+    ///
+    /// let normalization = Normalization::new(vec![0.], vec![10.], OutOfRange::Clamp);
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![5.], 0.01, 1.)])
+    ///     .with_normalization(normalization);
+    /// assert_eq!(&vec![0.5], model.iter_balls().next().unwrap().center());
+    /// assert_eq!(vec![0.2], model.normalize_point(&[2.]));
+    /// ```
+    pub fn with_normalization(mut self, normalization: Normalization) -> Self {
+        for vertex in self.graph.iter() {
+            let mut ball = vertex.deref_data_mut();
+            ball.center = normalization.scale(&ball.center);
+        }
+        self.set_normalization(normalization);
+        self
+    }
+
+    /// Sets the normalization of the model, queried points are normalized the same way.
+    pub(crate) fn set_normalization(&mut self, normalization: Normalization) {
+        let normalize = normalization.clone();
+        self.to_model_units = Some(Arc::new(move |point| normalize.normalize(point)));
+        self.normalization = Some(normalization);
+    }
+
+    /// Maps a point in original units into the units of the balls, as queries do, see [Model::predict].
+    /// The point is unchanged when the model is not normalized.
+    pub fn normalize_point(&self, point: &[f64]) -> Vec<f64> {
+        match &self.normalization {
+            Some(normalization) => normalization.normalize(point),
+            None => point.to_vec(),
+        }
+    }
+
+    /// Computes the logarithm of the mixture density at the given point, see [density_at] for the mixture.
+    /// Unlike the density, the log-likelihood does not underflow far from the balls, thus it can score anomalies.
+    /// Returns negative infinity when no ball has a known radius, e.g. for an empty model.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+    /// assert_eq!(-0.5 * (2. * std::f64::consts::PI).ln(), model.log_likelihood(&[0.]));
+    /// assert!(model.log_likelihood(&[1000.]) > f64::NEG_INFINITY);
+    /// ```
+    pub fn log_likelihood(&self, point: &[f64]) -> f64 {
+        mixture_log_likelihood(self.iter_balls(), &self.normalize_point(point))
+    }
+}
+
+/// Computes the logarithm of the mixture density of the balls at the given point, see [Model::log_likelihood].
+fn mixture_log_likelihood<B: Deref<Target = Ball<Vec<f64>>>>(
+    balls: impl Iterator<Item = B>,
+    point: &[f64],
+) -> f64 {
+    let dim = point.len() as f64;
+    let balls: Vec<_> = balls
+        .filter(|b| b.weight > 0. && b.radius.is_finite() && b.radius > 0.)
+        .collect();
+    let total: f64 = balls.iter().map(|b| b.weight).sum();
+    let terms: Vec<f64> = balls
+        .iter()
+        .map(|b| {
+            let variance = b.radius / dim;
+            let dist: f64 = point
+                .iter()
+                .zip(b.center.iter())
+                .map(|(x, c)| (x - c) * (x - c))
+                .sum();
+            (b.weight / total).ln()
+                - dim / 2. * (2. * std::f64::consts::PI * variance).ln()
+                - dist / (2. * variance)
+        })
+        .collect();
+    let max = terms.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + terms.iter().map(|t| (t - max).exp()).sum::<f64>().ln()
+}
+
+/// A distance which can be shared between threads.
+type SharedDist<Point> = Box<dyn Fn(&Point, &Point) -> f64 + Send + Sync>;
+
+/// A read-only copy of a model, which can be queried from several threads, see [Model::snapshot_with].
+pub struct ModelSnapshot<Point: PartialEq> {
+    balls: Vec<Ball<Point>>,
+    space_dist: SharedDist<Point>,
+    /// Maps queried points in original units into the units of the balls, see [Model::with_normalization].
+    to_model_units: Option<PointMap<Point>>,
+}
+
+impl<Point: PartialEq> ModelSnapshot<Point> {
+    /// Iterates over the balls, in the [Model::iter_balls] order of the model when the snapshot was taken.
+    pub fn iter_balls(&self) -> impl Iterator<Item = &Ball<Point>> {
+        self.balls.iter()
+    }
+
+    /// Gets the index of the ball that most probably includes the given point and the distance to it, see [Model::classify].
+    pub fn classify(&self, point: &Point) -> Option<(usize, f64)> {
+        let normalized = self
+            .to_model_units
+            .as_ref()
+            .map(|normalize| normalize(point));
+        let point = normalized.as_ref().unwrap_or(point);
+        self.balls
+            .iter()
+            .map(|ball| (self.space_dist)(point, &ball.center) / ball.radius)
+            .enumerate()
+            .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+    }
+}
+
+impl ModelSnapshot<Vec<f64>> {
+    /// Computes the logarithm of the mixture density at the given point, see [Model::log_likelihood].
+    pub fn log_likelihood(&self, point: &[f64]) -> f64 {
+        match &self.to_model_units {
+            Some(normalize) => {
+                mixture_log_likelihood(self.balls.iter(), &normalize(&point.to_vec()))
+            }
+            None => mixture_log_likelihood(self.balls.iter(), point),
+        }
+    }
+
+    /// Evaluates the density of the mixture of balls at the given point, see [density_at].
+    pub fn density_at(&self, point: &[f64]) -> f64 {
+        self.log_likelihood(point).exp()
+    }
+}
+
+impl<Point: PartialEq + Serialize + 'static> Model<Point> {
+    /// Computes a hash of the ball centers, radii and weights, that can be compared to tell
+    /// if two model snapshots are identical without comparing them deeply.
+    ///
+    /// Numbers are rounded to 10 significant digits before hashing so that floating point noise
+    /// does not change the fingerprint; the order of the balls does not matter.
+    /// Fingerprints are meant to be compared within the same program, they are not stable
+    /// across Rust versions.
+    /// ```
+    /// use fluent_data::{Model, model::Ball, space};
+    ///
+    /// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![1.], 1., 1.)]);
+    /// let same = Model::load(space::euclid_dist, vec![Ball::new(vec![1. + 1E-13], 1., 1.)]);
+    /// assert_eq!(model.fingerprint(), same.fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        self.iter_balls()
+            .map(|ball| {
+                let mut hasher = DefaultHasher::new();
+                match serde_json::to_value(&ball.center) {
+                    Ok(center) => hash_value(&center, &mut hasher),
+                    Err(_) => 0.hash(&mut hasher),
+                }
+                hash_number(ball.radius, &mut hasher);
+                hash_number(ball.weight, &mut hasher);
+                hasher.finish()
+            })
+            .fold(0, u64::wrapping_add)
+    }
+}
+
+/// Computes `ln(exp(a) + exp(b))` without overflow.
+pub(crate) fn log_add(a: f64, b: f64) -> f64 {
+    let (max, min) = if a > b { (a, b) } else { (b, a) };
+    if min == f64::NEG_INFINITY {
+        max
+    } else {
+        max + (min - max).exp().ln_1p()
+    }
+}
+
+/// Bounds of a 2D area, `[(x_min, x_max), (y_min, y_max)]`.
+pub type Bounds = [(f64, f64); 2];
+
+/// Evaluates the density of the mixture of balls at the given point.
+///
+/// Each ball is an isotropic normal distribution centered on the ball center, which mean square distance
+/// to the center is the square of the ball radius, weighted by the ball weight.
+/// Balls which radius is not known yet do not contribute; the density of an empty model is 0.
+/// ```
+/// use fluent_data::{model::{self, Ball, Model}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+/// let peak = 1. / (2. * std::f64::consts::PI).sqrt();
+/// assert!((model::density_at(&model, &[0.]) - peak).abs() < 1E-12);
+/// ```
+pub fn density_at(model: &Model<Vec<f64>>, point: &[f64]) -> f64 {
+    model.log_likelihood(point).exp()
+}
+
+/// Rasterizes the mixture density of a model of 2D points, see [density_at].
+///
+/// The area within `bounds` is divided into `resolution` × `resolution` cells and the density is evaluated at
+/// the center of each cell. The returned matrix is indexed by row then column: `grid[j][i]` is the intensity
+/// of the `i`-th cell along x in the `j`-th row along y, starting from `x_min` and `y_min`.
+/// ```
+/// use fluent_data::{model::{self, Ball, Model}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0., 0.], 1., 1.)]);
+/// let grid = model::to_heatmap(&model, [(-2., 2.), (-2., 2.)], 3);
+/// assert!(grid[1][1] > grid[0][0]);
+/// ```
+pub fn to_heatmap(model: &Model<Vec<f64>>, bounds: Bounds, resolution: usize) -> Vec<Vec<f64>> {
+    let [(x_min, x_max), (y_min, y_max)] = bounds;
+    let cell =
+        |min: f64, max: f64, i: usize| min + (max - min) * (i as f64 + 0.5) / resolution as f64;
+    (0..resolution)
+        .map(|j| {
+            let y = cell(y_min, y_max, j);
+            (0..resolution)
+                .map(|i| density_at(model, &[cell(x_min, x_max, i), y]))
+                .collect()
+        })
+        .collect()
+}
+
+/// Draws a model of 2D points as a standalone SVG image of `width` × `height` pixels which shows the area within `bounds`.
+///
+/// Each ball is a circle which opacity is its weight relative to the heaviest ball.
+/// The y axis points up and radii are scaled along x. Balls which radius is not known yet are not drawn.
+/// The bounds are in original units: the balls of a normalized model are drawn as ellipses, see [Normalization].
+/// Fails if a ball center is not 2D.
+/// ```
+/// use fluent_data::{model::{self, Ball, Model}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0., 0.], 1., 1.)]);
+/// let svg = model::to_svg(&model, 100, 100, [(-2., 2.), (-2., 2.)]).unwrap();
+/// assert!(svg.contains(r#"<circle cx="50" cy="50" r="25""#));
+/// ```
+pub fn to_svg(
+    model: &Model<Vec<f64>>,
+    width: u32,
+    height: u32,
+    bounds: Bounds,
+) -> Result<String, Box<dyn Error>> {
+    let [(x_min, x_max), (y_min, y_max)] = bounds;
+    let x_scale = width as f64 / (x_max - x_min);
+    let y_scale = height as f64 / (y_max - y_min);
+    let max_weight = model.iter_balls().map(|b| b.weight).fold(0., f64::max);
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        width, height
+    );
+    svg.push('\n');
+    for ball in crate::streamer::serialize_model(model) {
+        let center: Vec<f64> = serde_json::from_value(ball["center"].clone())?;
+        let (x, y) = match center[..] {
+            [x, y] => (x, y),
+            _ => {
+                return Err(format!("expected a 2D center, got {:?}", center).into());
+            }
+        };
+        let radius = match ball["radius"].as_f64() {
+            Some(radius) => radius,
+            None => continue,
+        };
+        let weight = ball["weight"].as_f64().unwrap_or_default();
+        let opacity = if max_weight > 0. {
+            weight / max_weight
+        } else {
+            1.
+        };
+        let (cx, cy) = ((x - x_min) * x_scale, (y_max - y) * y_scale);
+        let style = format!(
+            r#"fill="steelblue" fill-opacity="{}" stroke="steelblue""#,
+            opacity
+        );
+        match ball
+            .get("radii")
+            .map(|r| serde_json::from_value::<Vec<f64>>(r.clone()))
+        {
+            Some(radii) => {
+                let radii = radii?;
+                svg.push_str(&format!(
+                    r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" {}/>"#,
+                    cx,
+                    cy,
+                    radii[0] * x_scale,
+                    radii[1] * y_scale,
+                    style
+                ))
+            }
+            None => svg.push_str(&format!(
+                r#"<circle cx="{}" cy="{}" r="{}" {}/>"#,
+                cx,
+                cy,
+                radius * x_scale,
+                style
+            )),
+        }
+        svg.push('\n');
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// Estimates the measure of the space covered by the balls, i.e. the area of their union in 2D
+/// or its volume in higher dimensions.
+///
+/// This is a Monte Carlo estimate: `samples` points are drawn uniformly within the bounding box of the balls
+/// and the box volume is scaled by the fraction of points that lie inside a ball.
+/// Its relative error decreases as `1 / sqrt(samples)`. Balls which radius is not known yet are ignored.
+///
+/// Balls are treated as Euclidean balls in model units, whatever the distance the model was built with:
+/// the measure is meaningful for models built with [euclid_dist](space::euclid_dist), and it is expressed
+/// in normalized units when the model is normalized, see [Model::with_normalization].
+/// ```
+/// use fluent_data::{model::{self, Ball, Model}, space};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0., 0.], 1., 1.)]);
+/// let area = model::covered_measure(&model, 10_000, &mut StdRng::seed_from_u64(0));
+/// assert!((area - std::f64::consts::PI).abs() < 0.1);
+/// ```
+pub fn covered_measure(model: &Model<Vec<f64>>, samples: usize, rng: &mut impl Rng) -> f64 {
+    let balls: Vec<_> = model
+        .iter_balls()
+        .filter(|b| b.radius.is_finite())
+        .collect();
+    let dim = match balls.first() {
+        Some(ball) => ball.center.len(),
+        None => return 0.,
+    };
+    let bounds: Vec<(f64, f64)> = (0..dim)
+        .map(|i| {
+            balls
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), b| {
+                    let r = b.radius.sqrt();
+                    (min.min(b.center[i] - r), max.max(b.center[i] + r))
+                })
+        })
+        .collect();
+    let volume: f64 = bounds.iter().map(|(min, max)| max - min).product();
+    if samples == 0 || volume == 0. {
+        return 0.;
+    }
+    let mut point = vec![0.; dim];
+    let inside = (0..samples)
+        .filter(|_| {
+            for (x, (min, max)) in point.iter_mut().zip(bounds.iter()) {
+                *x = rng.gen_range(*min..=*max);
+            }
+            balls
+                .iter()
+                .any(|b| space::euclid_dist(&point, &b.center) < b.radius)
+        })
+        .count();
+    volume * inside as f64 / samples as f64
+}
+
+/// Measures how much each dimension drives the separation of the balls, e.g. to find the relevant input features.
+///
+/// The importance of a dimension is its share of the weighted variance of the ball centers,
+/// so that importances sum to 1. Balls are isotropic, thus the spread within the balls does not tell dimensions apart
+/// and is not taken into account: dimensions should be on comparable scales, see [Model::with_normalization].
+/// Balls which dimension differs from the first weighted ball's are ignored; importances are all zero
+/// when the centers do not spread.
+/// ```
+/// use fluent_data::{model::{self, Ball, Model}, space};
+///
+/// let balls = vec![Ball::new(vec![0., 0.], 1., 1.), Ball::new(vec![10., 1.], 1., 1.)];
+/// let importance = model::feature_importance(&Model::load(space::euclid_dist, balls));
+/// assert!(importance[0] > 0.99);
+/// ```
+pub fn feature_importance(model: &Model<Vec<f64>>) -> Vec<f64> {
+    let dim = match model.iter_balls().find(|b| b.weight > 0.) {
+        Some(ball) => ball.center.len(),
+        None => return vec![],
+    };
+    let balls: Vec<_> = model
+        .iter_balls()
+        .filter(|b| b.weight > 0. && b.center.len() == dim)
+        .collect();
+    let total: f64 = balls.iter().map(|b| b.weight).sum();
+    let variances: Vec<f64> = (0..dim)
+        .map(|i| {
+            let mean = balls.iter().map(|b| b.weight * b.center[i]).sum::<f64>() / total;
+            balls
+                .iter()
+                .map(|b| b.weight * (b.center[i] - mean).powi(2))
+                .sum::<f64>()
+                / total
+        })
+        .collect();
+    let sum: f64 = variances.iter().sum();
+    if sum == 0. {
+        return vec![0.; dim];
+    }
+    variances.iter().map(|v| v / sum).collect()
+}
+
+/// A tree of balls agglomerated by [dendrogram].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Dendrogram {
+    /// A ball, given by its index in [Model::iter_balls] order.
+    Leaf { ball: usize, weight: f64 },
+    /// Two clusters merged at the given height, the average distance between their balls.
+    Merge {
+        height: f64,
+        /// The sum of the weights of the balls.
+        weight: f64,
+        children: Box<[Dendrogram; 2]>,
+    },
+}
+
+impl Dendrogram {
+    /// The sum of the weights of the balls of the tree.
+    pub fn weight(&self) -> f64 {
+        match self {
+            Dendrogram::Leaf { weight, .. } | Dendrogram::Merge { weight, .. } => *weight,
+        }
+    }
+
+    /// The height at which the tree was merged, zero for a leaf.
+    pub fn height(&self) -> f64 {
+        match self {
+            Dendrogram::Leaf { .. } => 0.,
+            Dendrogram::Merge { height, .. } => *height,
+        }
+    }
+}
+
+/// Agglomerates the balls of the model into a tree by average linkage over their centers.
+///
+/// Starting with a cluster per ball, the two clusters with the smallest average distance are merged
+/// until a single tree remains. The average distance between two clusters is the mean of the distances between
+/// their centers, weighted by the product of the ball weights. Balls which weight is zero are left out;
+/// returns `None` when no ball is left.
+/// ```
+/// use fluent_data::{model::{self, Ball, Model}, space};
+///
+/// let balls = vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![2.], 1., 3.)];
+/// let model = Model::load(space::euclid_dist, balls);
+/// let tree = model::dendrogram(&model, space::euclid_dist).unwrap();
+/// assert_eq!(4., tree.height());
+/// assert_eq!(4., tree.weight());
+/// ```
+pub fn dendrogram<Point: PartialEq + 'static>(
+    model: &Model<Point>,
+    dist: impl Fn(&Point, &Point) -> f64,
+) -> Option<Dendrogram> {
+    let balls: Vec<_> = model
+        .iter_balls()
+        .enumerate()
+        .filter(|(_, b)| b.weight > 0.)
+        .collect();
+    let mut dists: Vec<Vec<f64>> = balls
+        .iter()
+        .map(|(_, b1)| {
+            balls
+                .iter()
+                .map(|(_, b2)| dist(&b1.center, &b2.center))
+                .collect()
+        })
+        .collect();
+    let mut clusters: Vec<Option<Dendrogram>> = balls
+        .iter()
+        .map(|(ball, b)| {
+            Some(Dendrogram::Leaf {
+                ball: *ball,
+                weight: b.weight,
+            })
+        })
+        .collect();
+    let alive = |clusters: &[Option<Dendrogram>]| -> Vec<usize> {
+        (0..clusters.len())
+            .filter(|&i| clusters[i].is_some())
+            .collect()
+    };
+    loop {
+        let indices = alive(&clusters);
+        let (i, j) = match indices
+            .iter()
+            .flat_map(|&i| {
+                indices
+                    .iter()
+                    .filter(move |&&j| j > i)
+                    .map(move |&j| (i, j))
+            })
+            .min_by(|&(i1, j1), &(i2, j2)| dists[i1][j1].total_cmp(&dists[i2][j2]))
+        {
+            Some(pair) => pair,
+            None => break,
+        };
+        let left = clusters[i].take().unwrap();
+        let right = clusters[j].take().unwrap();
+        let (w1, w2) = (left.weight(), right.weight());
+        // Lance-Williams update of the average distances to the merged cluster
+        for &k in indices.iter().filter(|&&k| k != i && k != j) {
+            let d = (w1 * dists[i][k] + w2 * dists[j][k]) / (w1 + w2);
+            dists[i][k] = d;
+            dists[k][i] = d;
+        }
+        clusters[i] = Some(Dendrogram::Merge {
+            height: dists[i][j],
+            weight: w1 + w2,
+            children: Box::new([left, right]),
+        });
+    }
+    clusters.into_iter().flatten().next()
+}
+
+/// `(center, weight)` pairs, see [to_weighted_points].
+pub type WeightedPoints = Vec<(Vec<f64>, f64)>;
+
+/// Converts the model into `(center, weight)` pairs, e.g. to re-cluster the balls with an external tool.
+/// Balls which weight is zero are left out.
+/// ```
+/// use fluent_data::{model::{self, Ball, Model}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![1., 2.], 4., 3.)]);
+/// assert_eq!(vec![(vec![1., 2.], 3.)], model::to_weighted_points(&model));
+/// ```
+pub fn to_weighted_points(model: &Model<Vec<f64>>) -> WeightedPoints {
+    model
+        .iter_balls()
+        .filter(|b| b.weight > 0.)
+        .map(|b| (b.center.clone(), b.weight))
+        .collect()
+}
+
+/// Hashes a serialized point, rounding numbers.
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    match value {
+        Value::Number(n) => match n.as_f64() {
+            Some(x) => hash_number(x, hasher),
+            None => n.to_string().hash(hasher),
+        },
+        Value::Array(values) => {
+            values.len().hash(hasher);
+            values.iter().for_each(|v| hash_value(v, hasher));
+        }
+        Value::Object(map) => map.iter().for_each(|(k, v)| {
+            k.hash(hasher);
+            hash_value(v, hasher);
+        }),
+        _ => value.to_string().hash(hasher),
+    }
+}
+
+/// Hashes a number rounded to 10 significant digits.
+fn hash_number(x: f64, hasher: &mut impl Hasher) {
+    format!("{:.9e}", x).hash(hasher);
+}
+
+pub(crate) trait GetNeighbors<Point: PartialEq> {
+    fn get_neighbors(&self) -> Vec<Neighbor<Ball<Point>>>;
+}
+
+impl<Point: PartialEq> GetNeighbors<Point> for Vec<BallNode<Point>> {
+    fn get_neighbors(&self) -> Vec<Neighbor<Ball<Point>>> {
         self.iter().map(|n| n.as_neighbor()).collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{model::*, space};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use rand_distr::{Distribution, Normal};
+
+    use crate::{model::*, space, Algo};
+
+    #[test]
+    fn test_quantized_model() {
+        const DIM: usize = 128;
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let quantization = space::Q8;
+        let quantized_algo = Algo::new(space::quantized_dist, quantization.combine());
+        let mut quantized_model = Model::new(space::quantized_dist);
+        let mut rng = StdRng::seed_from_u64(5);
+        let normal = Normal::new(0., 0.5).unwrap();
+        for _ in 0..1500 {
+            let cluster = rng.gen_range(0..3) as f64;
+            let point: Vec<f64> = (0..DIM)
+                .map(|i| 20. * cluster + (i % 7) as f64 + normal.sample(&mut rng))
+                .collect();
+            algo.fit(&mut model, point.clone());
+            quantized_algo.fit(&mut quantized_model, QuantizedPoint::Exact(point));
+        }
+        let centers: Vec<Vec<f64>> = model.iter_balls().map(|b| b.center.clone()).collect();
+        for ball in quantized_model.iter_balls() {
+            assert!(matches!(ball.center, QuantizedPoint::Q8 { .. }));
+            let center: Vec<f64> = ball.center.coords().collect();
+            let nearest = centers
+                .iter()
+                .min_by(|c1, c2| {
+                    let d1 = space::euclid_dist(c1, &center);
+                    let d2 = space::euclid_dist(c2, &center);
+                    d1.partial_cmp(&d2).unwrap()
+                })
+                .unwrap();
+            let error = nearest
+                .iter()
+                .zip(&center)
+                .map(|(x, y)| (x - y).abs())
+                .fold(0., f64::max);
+            assert!(error < 10. * ball.center.max_error());
+        }
+        let mut first = Model::new(space::quantized_dist);
+        quantized_algo.fit(&mut first, QuantizedPoint::Exact(vec![1.; DIM]));
+        let ball = first.iter_balls().next().unwrap().clone();
+        assert!(matches!(ball.center, QuantizedPoint::Q8 { .. }));
+        let exact_bytes: usize = model
+            .iter_balls()
+            .map(|b| std::mem::size_of::<Vec<f64>>() + b.center.capacity() * 8)
+            .sum();
+        assert_eq!(
+            model.iter_balls().count(),
+            quantized_model.iter_balls().count()
+        );
+        assert!(exact_bytes > 4 * quantized_model.center_bytes());
+    }
 
     #[test]
     fn test_build_norm_data() {
@@ -273,6 +2117,36 @@ mod tests {
         assert!(n3.next().unwrap().deref_data().eq(&data[1]));
     }
 
+    #[test]
+    fn test_balls_mut() {
+        let data = vec![
+            Ball::new(vec![4.], 3., 1.),
+            Ball::new(vec![5.], 2., 2.),
+            Ball::new(vec![3.], 3., 3.),
+        ];
+        let mut model = Model::load(space::euclid_dist, data);
+        for (i, mut ball) in model.balls_mut().enumerate() {
+            match i {
+                0 => ball.set_radius(5.),
+                2 => ball.set_center(vec![100.]),
+                _ => {}
+            }
+        }
+        assert_eq!(5., model.iter_balls().next().unwrap().radius());
+        assert_eq!(Some(0.4), model.anomaly_score(&vec![6.]));
+        model.get_neighborhood(&vec![6.]);
+        // the neighbors of the first ball were [3.] then [5.] before the center moved
+        let neighbors: Vec<Vec<f64>> = model.graph[0]
+            .iter_neighbors()
+            .map(|v| v.deref_data().center.clone())
+            .collect();
+        assert_eq!(vec![vec![5.], vec![100.]], neighbors);
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        algo.fit(&mut model, vec![101.]);
+        assert_eq!(3, model.iter_balls().count());
+        assert_eq!(5., model.iter_balls().next().unwrap().radius());
+    }
+
     fn build_model() -> (Model<Vec<f64>>, Ball<Vec<f64>>, Ball<Vec<f64>>) {
         let mut model = Model::new(space::euclid_dist);
         let n1 = Ball::new(vec![4.], f64::INFINITY, 0.);
@@ -284,6 +2158,35 @@ mod tests {
         (model, n1, n2)
     }
 
+    #[test]
+    fn test_bounded_dist() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let normal = Normal::new(0., 1.).unwrap();
+        let points: Vec<Vec<f64>> = (0..300)
+            .map(|i| {
+                let offset = 20. * ((i / 10) % 5) as f64;
+                (0..16).map(|_| offset + normal.sample(&mut rng)).collect()
+            })
+            .collect();
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut exact = Model::new(space::euclid_dist);
+        let mut bounded =
+            Model::new(space::euclid_dist).with_bounded_dist(space::euclid_dist_bounded);
+        for point in points.iter() {
+            algo.fit(&mut exact, point.clone());
+            algo.fit(&mut bounded, point.clone());
+        }
+        assert!(exact.iter_balls().count() > 1);
+        assert_eq!(exact.fingerprint(), bounded.fingerprint());
+        let nearest = |model: &Model<Vec<f64>>, point| match model.predict(point) {
+            Neighborhood::One(n) | Neighborhood::Two(n, _) => Some(n.coord().center.clone()),
+            Neighborhood::None => None,
+        };
+        for point in points.iter().step_by(7) {
+            assert_eq!(nearest(&exact, point), nearest(&bounded, point));
+        }
+    }
+
     #[test]
     fn test_predict() {
         let data = vec![
@@ -302,4 +2205,587 @@ mod tests {
             panic!()
         }
     }
+
+    #[test]
+    fn test_fast_forward() {
+        let decay = DecayConfig {
+            factor: 0.9,
+            period: Duration::from_millis(100),
+            floor: 1E-2,
+        };
+        let data = vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![5.], 1., 20.)];
+        let mut once = Model::load(space::euclid_dist, data.clone());
+        let mut stepped = Model::load(space::euclid_dist, data);
+        once.fast_forward(Duration::from_secs(3), &decay);
+        for _ in 0..30 {
+            stepped.fast_forward(Duration::from_millis(100), &decay);
+        }
+        let once: Vec<_> = once.iter_balls().map(|b| b.weight()).collect();
+        let stepped: Vec<_> = stepped.iter_balls().map(|b| b.weight()).collect();
+        assert_eq!(once.len(), stepped.len());
+        for (w1, w2) in once.iter().zip(stepped) {
+            assert!((w1 - w2).abs() < 1E-12);
+        }
+        assert!((20. * 0.9f64.powi(30) - once[1]).abs() < 1E-12);
+    }
+
+    #[test]
+    fn test_fast_forward_prune() {
+        let decay = DecayConfig {
+            factor: 0.5,
+            period: Duration::from_secs(1),
+            floor: 1E-2,
+        };
+        let data = vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![5.], 1., 100.)];
+        let mut model = Model::load(space::euclid_dist, data);
+        model.fast_forward(Duration::from_secs(8), &decay);
+        let weights: Vec<_> = model.iter_balls().map(|b| b.weight()).collect();
+        assert_eq!(1, weights.len());
+        assert!((100. / 256. - weights[0]).abs() < 1E-12);
+        model.fast_forward(Duration::from_secs(3600 * 24), &decay);
+        assert_eq!(0, model.iter_balls().count());
+    }
+
+    #[test]
+    #[should_panic(expected = "decay period must not be zero")]
+    fn test_fast_forward_zero_period() {
+        let decay = DecayConfig {
+            factor: 0.5,
+            period: Duration::ZERO,
+            floor: 1E-2,
+        };
+        let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+        model.fast_forward(Duration::from_secs(1), &decay);
+    }
+
+    #[test]
+    #[should_panic(expected = "decay factor must be in ]0, 1]")]
+    fn test_fast_forward_nan_factor() {
+        let decay = DecayConfig {
+            factor: f64::NAN,
+            period: Duration::from_secs(1),
+            floor: 1E-2,
+        };
+        let mut model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+        model.fast_forward(Duration::from_secs(1), &decay);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let data = vec![
+            Ball::new(vec![4., 1.], 3., 1.),
+            Ball::new(vec![5., 2.], 2., 2.),
+            Ball::new(vec![3., 3.], 3., 3.),
+        ];
+        let model = Model::load(space::euclid_dist, data.clone());
+        let reversed = Model::load(space::euclid_dist, data.iter().rev().cloned().collect());
+        assert_eq!(model.fingerprint(), reversed.fingerprint());
+        let mut changed = data.clone();
+        changed[1].weight = 2.5;
+        let changed = Model::load(space::euclid_dist, changed);
+        assert_ne!(model.fingerprint(), changed.fingerprint());
+        let mut moved = data;
+        moved[2].center[0] = 3.001;
+        let moved = Model::load(space::euclid_dist, moved);
+        assert_ne!(model.fingerprint(), moved.fingerprint());
+    }
+
+    #[test]
+    fn test_removed_assignments() {
+        let mut ball = Ball::new(vec![0.], 1., 1.);
+        ball.assigned = 3;
+        let mut model = Model::load(space::euclid_dist, vec![ball, Ball::new(vec![9.], 1., 8.)]);
+        model.decay_weights(0.5f64.ln(), 0.6);
+        let assignments = model.take_assignments();
+        assert_eq!(vec![0], assignments.balls);
+        assert_eq!(3, assignments.removed);
+    }
+
+    #[test]
+    fn test_overlapping_with() {
+        let data = vec![
+            Ball::new(vec![0., 0.], 9., 1.),
+            Ball::new(vec![3., 0.], 1., 1.),
+            Ball::new(vec![10., 0.], 1., 1.),
+            Ball::new(vec![0., -2.], 1., 1.),
+            Ball::new(vec![-3., -3.], 1., 1.),
+        ];
+        let model = Model::load(space::euclid_dist, data);
+        assert_eq!(vec![1, 3], model.overlapping_with(0, 1.).unwrap());
+        assert_eq!(vec![0], model.overlapping_with(1, 1.).unwrap());
+        assert!(model.overlapping_with(5, 1.).is_err());
+    }
+
+    #[test]
+    fn test_separation_profile() {
+        let data = vec![
+            Ball::new(vec![0., 0.], 1., 1.),
+            Ball::new(vec![3., 4.], 1., 1.),
+            Ball::new(vec![3., 5.], 1., 1.),
+            Ball::new(vec![-6., 0.], 1., 1.),
+        ];
+        let model = Model::load(space::euclid_dist, data);
+        assert_eq!(vec![25., 1., 1., 36.], model.separation_profile());
+        let lonely = Model::load(space::euclid_dist, vec![Ball::new(vec![0., 0.], 1., 1.)]);
+        assert_eq!(vec![f64::INFINITY], lonely.separation_profile());
+    }
+
+    #[test]
+    fn test_soft_predict() {
+        let data = vec![
+            Ball::new(vec![0.], 1., 1.),
+            Ball::new(vec![3.], 1., 1.),
+            Ball::new(vec![10.], 1., 1.),
+        ];
+        let model = Model::load(space::euclid_dist, data);
+        let memberships = model.soft_predict(&vec![1.], 2.);
+        assert_eq!(
+            vec![0, 1, 2],
+            memberships.iter().map(|m| m.0).collect::<Vec<_>>()
+        );
+        assert!((memberships.iter().map(|m| m.1).sum::<f64>() - 1.).abs() < 1E-12);
+        assert!(memberships[0].1 > memberships[1].1 && memberships[1].1 > memberships[2].1);
+        let nearest = model.soft_predict_nearest(&vec![1.], 2., 2);
+        assert_eq!(2, nearest.len());
+        assert!((nearest.iter().map(|m| m.1).sum::<f64>() - 1.).abs() < 1E-12);
+        let empty: Model<Vec<f64>> = Model::new(space::euclid_dist);
+        assert!(empty.soft_predict(&vec![1.], 2.).is_empty());
+    }
+
+    #[test]
+    fn test_log_likelihood() {
+        let balls = vec![
+            Ball::new(vec![0., 0.], 2., 3.),
+            Ball::new(vec![10., 5.], 8., 1.),
+        ];
+        let model = Model::load(space::euclid_dist, balls);
+        let center = model.log_likelihood(&[0., 0.]);
+        assert!(center > model.log_likelihood(&[5., 2.]));
+        assert!(center > model.log_likelihood(&[10., 5.]));
+        let far = model.log_likelihood(&[1000., -1000.]);
+        assert!(far.is_finite() && far < center);
+        for point in [[0., 0.], [3., 1.], [10., 4.]] {
+            let expected = density_at(&model, &point).ln();
+            assert!((model.log_likelihood(&point) - expected).abs() < 1E-9);
+        }
+        assert_eq!(
+            f64::NEG_INFINITY,
+            Model::new(space::euclid_dist).log_likelihood(&[0.])
+        );
+    }
+
+    #[test]
+    fn test_classify_batch() {
+        let balls = vec![
+            Ball::new(vec![0., 0.], 2., 3.),
+            Ball::new(vec![10., 5.], 8., 1.),
+            Ball::new(vec![-4., 6.], 1., 2.),
+        ];
+        let model = Model::load(space::euclid_dist, balls.clone());
+        let mut rng = StdRng::seed_from_u64(3);
+        let points: Vec<Vec<f64>> = (0..50)
+            .map(|_| vec![rng.gen_range(-10. ..15.), rng.gen_range(-5. ..10.)])
+            .collect();
+        let classes = model.classify_batch(&points);
+        assert_eq!(points.len(), classes.len());
+        for (point, class) in points.iter().zip(classes) {
+            assert_eq!(model.classify(point), class);
+            let (index, dist) = class.unwrap();
+            match model.predict(point) {
+                Neighborhood::Two(n, _) => {
+                    assert_eq!(&balls[index], n.coord());
+                    assert_eq!(n.dist(), dist);
+                }
+                _ => panic!(),
+            }
+        }
+        assert_eq!(
+            balls,
+            model.iter_balls().map(|b| b.clone()).collect::<Vec<_>>()
+        );
+        assert!(Model::new(space::euclid_dist)
+            .classify_batch(&points)
+            .iter()
+            .all(|c| c.is_none()));
+    }
+
+    #[test]
+    fn test_radius_coverage() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(4);
+        let normal = Normal::new(0., 2.).unwrap();
+        for _ in 0..5000 {
+            let point = vec![normal.sample(&mut rng), normal.sample(&mut rng)];
+            algo.fit(&mut model, point);
+        }
+        let coverage = model.radius_coverage();
+        assert_eq!(model.iter_balls().count(), coverage.len());
+        let heaviest = model
+            .iter_balls()
+            .enumerate()
+            .max_by(|(_, b1), (_, b2)| b1.weight.total_cmp(&b2.weight))
+            .map(|(index, _)| index)
+            .unwrap();
+        // the squared root mean square radius of a normal cluster in R^2 is the mean of a chi-squared distribution
+        // with 2 degrees of freedom, times the variance
+        let expected = 1. - (-1f64).exp();
+        assert!((expected - coverage[heaviest]).abs() < 0.05);
+        let loaded = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+        assert!(loaded.radius_coverage()[0].is_nan());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        use std::{
+            sync::{
+                atomic::{AtomicBool, Ordering},
+                RwLock,
+            },
+            thread,
+        };
+
+        let empty = Model::new(space::euclid_dist).snapshot_with(space::euclid_dist);
+        let current = Arc::new(RwLock::new(empty));
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let (current, done) = (Arc::clone(&current), Arc::clone(&done));
+                thread::spawn(move || {
+                    let mut last_count = 0;
+                    loop {
+                        // the last read starts after the last snapshot is published
+                        let finished = done.load(Ordering::Acquire);
+                        let snapshot = Arc::clone(&current.read().unwrap());
+                        let count = snapshot.iter_balls().count();
+                        assert!(count >= last_count);
+                        last_count = count;
+                        for (index, ball) in snapshot.iter_balls().enumerate() {
+                            assert_eq!(Some((index, 0.)), snapshot.classify(&ball.center));
+                            assert!(snapshot.density_at(&ball.center) > 0.);
+                        }
+                        assert_eq!(snapshot.classify(&vec![42.]), snapshot.classify(&vec![42.]));
+                        if finished {
+                            return last_count;
+                        }
+                    }
+                })
+            })
+            .collect();
+        let writer = {
+            let current = Arc::clone(&current);
+            thread::spawn(move || {
+                let config = crate::algorithm::AlgoConfig {
+                    decay: 1.,
+                    ..crate::algorithm::AlgoConfig::default()
+                };
+                let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+                let mut model = Model::new(space::euclid_dist);
+                let mut rng = StdRng::seed_from_u64(9);
+                let normal = Normal::new(0., 1.).unwrap();
+                for cluster in 0..10 {
+                    for _ in 0..20 {
+                        let point = vec![100. * cluster as f64 + normal.sample(&mut rng)];
+                        algo.fit(&mut model, point);
+                    }
+                    *current.write().unwrap() = model.snapshot_with(space::euclid_dist);
+                }
+            })
+        };
+        writer.join().unwrap();
+        done.store(true, Ordering::Release);
+        for reader in readers {
+            assert_eq!(10, reader.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_snapshot_normalized() {
+        let normalization = Normalization::new(vec![0.], vec![100.], OutOfRange::PassThrough);
+        let model = Model::load(
+            space::euclid_dist,
+            vec![
+                Ball::new(vec![20.], 0.01, 1.),
+                Ball::new(vec![80.], 0.01, 1.),
+            ],
+        )
+        .with_normalization(normalization);
+        let snapshot = model.snapshot_with(space::euclid_dist);
+        for x in [10., 45., 60., 90.] {
+            let point = vec![x];
+            let live = match model.predict(&point) {
+                Neighborhood::One(n) | Neighborhood::Two(n, _) => n.coord().center().clone(),
+                Neighborhood::None => unreachable!(),
+            };
+            let (index, _) = snapshot.classify(&point).unwrap();
+            assert_eq!(&live, snapshot.iter_balls().nth(index).unwrap().center());
+            assert_eq!(
+                model.log_likelihood(&point),
+                snapshot.log_likelihood(&point)
+            );
+        }
+        assert_eq!(Some((1, 0.)), snapshot.classify(&vec![80.]));
+        assert!(snapshot.density_at(&[20.]) > snapshot.density_at(&[50.]));
+    }
+
+    #[test]
+    fn test_k_nearest_balls() {
+        let balls = vec![
+            Ball::new(vec![0., 0.], 1., 1.),
+            Ball::new(vec![10., 0.], 100., 1.),
+            Ball::new(vec![3., 4.], 1., 1.),
+            Ball::new(vec![-1., -1.], 1., 1.),
+        ];
+        let model = Model::load(space::euclid_dist, balls);
+        let point = vec![1., 0.];
+        assert_eq!(
+            vec![(0, 1.), (3, 5.), (2, 20.)],
+            model.k_nearest_balls(&point, 3)
+        );
+        let all = model.k_nearest_balls(&point, 10);
+        assert_eq!(vec![(0, 1.), (3, 5.), (2, 20.), (1, 81.)], all);
+        assert_eq!(Some((1, 0.81)), model.classify(&point));
+        assert!(model.k_nearest_balls(&point, 0).is_empty());
+        assert!(Model::new(space::euclid_dist)
+            .k_nearest_balls(&point, 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_max_reach() {
+        let balls = vec![
+            Ball::new(vec![0., 0.], 1., 1.),
+            Ball::new(vec![3., 4.], 4., 1.),
+            Ball::new(vec![-6., 0.], 0.25, 1.),
+        ];
+        let model = Model::load(space::euclid_dist, balls);
+        // the edge of the second ball is 5 + 2 away, the edge of the third one 6 + 0.5
+        assert_eq!(Some(7.), model.max_reach(&vec![0., 0.]));
+        assert_eq!(Some(12.5), model.max_reach(&vec![6., 0.]));
+        let edge = model.max_reach(&vec![-6., 0.]).unwrap();
+        assert!((edge - (97_f64.sqrt() + 2.)).abs() < 1E-12);
+        let mut unknown = Model::new(space::euclid_dist);
+        Algo::new(space::euclid_dist, space::real_combine).fit(&mut unknown, vec![1., 1.]);
+        assert_eq!(Some(f64::INFINITY), unknown.max_reach(&vec![0., 0.]));
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let data = vec![
+            Ball::new(vec![-5., 0.], 1., 1.),
+            Ball::new(vec![5., 0.], 2., 2.),
+        ];
+        let model = Model::load(space::euclid_dist, data);
+        let grid = to_heatmap(&model, [(-10., 10.), (-10., 10.)], 20);
+        assert_eq!(20, grid.len());
+        assert!(grid.iter().all(|row| row.len() == 20));
+        // cells centered at (-4.5, 0.5) and (5.5, 0.5)
+        let near = [grid[10][5], grid[10][15]];
+        let edges = [
+            grid[0][0],
+            grid[0][19],
+            grid[19][0],
+            grid[19][19],
+            grid[10][10],
+        ];
+        for center in near {
+            assert!(edges.iter().all(|&edge| center > 10. * edge));
+        }
+        let cell_area = 1.;
+        let mass: f64 = grid.iter().flatten().sum::<f64>() * cell_area;
+        assert!((mass - 1.).abs() < 1E-2);
+    }
+
+    #[test]
+    fn test_svg() {
+        let data = vec![
+            Ball::new(vec![-5., 0.], f64::INFINITY, 0.),
+            Ball::new(vec![-5., 5.], 4., 1.),
+            Ball::new(vec![5., -5.], 1., 2.),
+        ];
+        let model = Model::load(space::euclid_dist, data);
+        let svg = to_svg(&model, 200, 100, [(-10., 10.), (-10., 10.)]).unwrap();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        let circles: Vec<_> = svg.lines().filter(|l| l.starts_with("<circle")).collect();
+        assert_eq!(2, circles.len());
+        assert!(
+            circles[0].contains(r#"cx="50" cy="25" r="20" fill="steelblue" fill-opacity="0.5""#)
+        );
+        assert!(circles[1].contains(r#"cx="150" cy="75" r="10" fill="steelblue" fill-opacity="1""#));
+        let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0.], 1., 1.)]);
+        assert!(to_svg(&model, 200, 100, [(-10., 10.), (-10., 10.)]).is_err());
+    }
+
+    #[test]
+    fn test_normalized_queries() {
+        let normalization = Normalization::new(vec![0., 0.], vec![10., 100.], OutOfRange::Clamp);
+        let data = vec![
+            Ball::new(vec![2., 20.], 0.01, 1.),
+            Ball::new(vec![8., 80.], 0.01, 1.),
+        ];
+        let model = Model::load(space::euclid_dist, data).with_normalization(normalization);
+        assert_eq!(Some(1), model.classify(&vec![7., 70.]).map(|(i, _)| i));
+        assert_eq!(
+            vec![0],
+            model
+                .k_nearest_balls(&vec![3., 30.], 1)
+                .into_iter()
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(true), model.is_within_radius(&vec![8., 80.]));
+        let reach = model.max_reach(&vec![2., 20.]).unwrap();
+        assert!((reach - 0.6 * 2f64.sqrt() - 0.1).abs() < 1E-12);
+        assert!(model.log_likelihood(&[2., 20.]) > model.log_likelihood(&[5., 50.]));
+        let svg = to_svg(&model, 100, 100, [(0., 10.), (0., 100.)]).unwrap();
+        assert!(svg.contains(r#"<ellipse cx="20" cy="80" rx="10" ry="10""#));
+    }
+
+    #[test]
+    fn test_soft_predict_hard_limit() {
+        let data = vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![3.], 1., 1.)];
+        let model = Model::load(space::euclid_dist, data);
+        for temperature in [1E-3, 1E-9, 0.] {
+            assert_eq!(
+                vec![(0, 1.), (1, 0.)],
+                model.soft_predict(&vec![1.], temperature)
+            );
+        }
+    }
+
+    #[test]
+    fn test_soft_predict_far_point() {
+        let data = vec![Ball::new(vec![0.], 1., 1.), Ball::new(vec![1E140], 1., 1.)];
+        let model = Model::load(space::euclid_dist, data);
+        let memberships = model.soft_predict(&vec![1E150], 1.);
+        assert_eq!(vec![(1, 1.), (0, 0.)], memberships);
+    }
+
+    #[test]
+    fn test_covered_measure() {
+        let model = Model::load(
+            space::euclid_dist,
+            vec![
+                Ball::new(vec![0., 0.], 1., 1.),
+                Ball::new(vec![10., 0.], 4., 1.),
+            ],
+        );
+        let mut rng = StdRng::seed_from_u64(0);
+        let area = covered_measure(&model, 100_000, &mut rng);
+        let expected = std::f64::consts::PI * (1. + 4.);
+        assert!((area - expected).abs() / expected < 0.03);
+        let empty = Model::new(space::euclid_dist);
+        assert_eq!(0., covered_measure(&empty, 100, &mut rng));
+    }
+
+    #[test]
+    fn test_rebuild_with() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(9);
+        let normal = Normal::new(0., 1.).unwrap();
+        let centers = [(0., 0.), (20., -10.), (-15., 25.)];
+        for run in 0..30 {
+            let (x, y) = centers[run % 3];
+            for _ in 0..10 {
+                let point = vec![x + normal.sample(&mut rng), y + normal.sample(&mut rng)];
+                algo.fit(&mut model, point);
+            }
+        }
+        let scale: fn(&Vec<f64>) -> Vec<f64> = |p| p.iter().map(|x| 3. * x).collect();
+        let rebuilt = model.rebuild_with(space::euclid_dist, Some(scale));
+        assert_eq!(model.iter_balls().count(), rebuilt.iter_balls().count());
+        let predicted = |model: &Model<Vec<f64>>, point: &Vec<f64>| match model.predict(point) {
+            Neighborhood::One(n) | Neighborhood::Two(n, _) => model
+                .iter_balls()
+                .position(|b| b.id() == n.coord().id())
+                .unwrap(),
+            _ => panic!(),
+        };
+        for _ in 0..1000 {
+            let point = vec![rng.gen_range(-30.0..40.0), rng.gen_range(-30.0..40.0)];
+            assert_eq!(
+                predicted(&model, &point),
+                predicted(&rebuilt, &scale(&point))
+            );
+        }
+        let dist_only = model.rebuild_with(|p1, p2| 9. * space::euclid_dist(p1, p2), None);
+        for (ball, scaled) in rebuilt.iter_balls().zip(dist_only.iter_balls()) {
+            assert!((ball.radius() - scaled.radius()).abs() <= 1E-12 * ball.radius());
+        }
+    }
+
+    #[test]
+    fn test_dendrogram() {
+        let model = Model::load(
+            space::euclid_dist,
+            vec![
+                Ball::new(vec![0., 0.], 1., 1.),
+                Ball::new(vec![10., 0.], 1., 2.),
+                Ball::new(vec![0., 1.], 1., 3.),
+                Ball::new(vec![10., 1.5], 1., 1.),
+                Ball::new(vec![5., 5.], 1., 0.),
+            ],
+        );
+        let tree = dendrogram(&model, space::euclid_dist).unwrap();
+        assert_eq!(7., tree.weight());
+        let pairs = match &tree {
+            Dendrogram::Merge { children, .. } => children,
+            _ => panic!("{:?}", tree),
+        };
+        let leaves = |tree: &Dendrogram| match tree {
+            Dendrogram::Merge {
+                height, children, ..
+            } => match &**children {
+                [Dendrogram::Leaf { ball: b1, .. }, Dendrogram::Leaf { ball: b2, .. }] => {
+                    (*b1, *b2, *height)
+                }
+                _ => panic!("{:?}", tree),
+            },
+            _ => panic!("{:?}", tree),
+        };
+        assert_eq!((0, 2, 1.), leaves(&pairs[0]));
+        assert_eq!((1, 3, 2.25), leaves(&pairs[1]));
+        assert!(tree.height() > pairs[0].height().max(pairs[1].height()));
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(tree, serde_json::from_str(&json).unwrap());
+        let empty: Model<Vec<f64>> = Model::new(space::euclid_dist);
+        assert_eq!(None, dendrogram(&empty, space::euclid_dist));
+    }
+
+    #[test]
+    fn test_feature_importance() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(3);
+        let normal = Normal::new(0., 1.).unwrap();
+        // clusters are separated along dimensions 2 and 7 only
+        let centers = [(0., 0.), (30., -20.), (-25., 30.)];
+        for run in 0..60 {
+            let (x, y) = centers[run % 3];
+            for _ in 0..10 {
+                let mut point: Vec<f64> = (0..10).map(|_| normal.sample(&mut rng)).collect();
+                point[2] += x;
+                point[7] += y;
+                algo.fit(&mut model, point);
+            }
+        }
+        let importance = feature_importance(&model);
+        assert_eq!(10, importance.len());
+        assert!((importance.iter().sum::<f64>() - 1.).abs() < 1E-12);
+        let mut ranks: Vec<usize> = (0..10).collect();
+        ranks.sort_by(|&i, &j| importance[j].total_cmp(&importance[i]));
+        ranks.truncate(2);
+        ranks.sort();
+        assert_eq!(vec![2, 7], ranks);
+        assert!(feature_importance(&Model::new(space::euclid_dist)).is_empty());
+        let balls = vec![
+            Ball::new(vec![0., 0.], 1., 1.),
+            Ball::new(vec![4.], 1., 1.),
+            Ball::new(vec![0., 2.], 1., 1.),
+        ];
+        let importance = feature_importance(&Model::load(space::euclid_dist, balls));
+        assert_eq!(vec![0., 1.], importance);
+    }
 }
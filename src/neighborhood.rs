@@ -1,6 +1,8 @@
 //! This module defines the neighborhood of some point in space.
 //!
-//! To get neighbors of a point, use [GetNeighborhood::get_neighborhood] method.
+//! To get neighbors of a point, use [GetNeighborhood::get_neighborhood] method,
+//! or [GetNeighborhoodDyn::get_neighborhood_dyn] when the distance is chosen at runtime,
+//! or [GetNeighborhoodBounded::get_neighborhood_bounded] when the distance can be abandoned early.
 
 use std::{mem::swap, ops::Deref};
 
@@ -81,6 +83,117 @@ where
     }
 }
 
+/// Defines a two nearest neighbors getter function which distance is a trait object.
+///
+/// This is useful when the distance is chosen at runtime, e.g. from a configuration.
+/// ```
+/// use fluent_data::{space, neighborhood::{GetNeighborhoodDyn, Neighborhood}};
+/// fn main() {
+///   let metric = "euclid";
+///   let dist: Box<dyn Fn(&Vec<f64>, &Vec<f64>) -> f64> = match metric {
+///       "euclid" => Box::new(space::euclid_dist),
+///       _ => Box::new(|p1: &Vec<f64>, p2: &Vec<f64>| (p1[0] - p2[0]).abs()),
+///   };
+///   let points = vec![vec![0.], vec![2.], vec![5.]];
+///   let neighborhood = points.iter().get_neighborhood_dyn(&vec![3.], dist.as_ref());
+///   if let Neighborhood::Two(n1, _) = neighborhood {
+///       assert_eq!(&points[1], n1.coord());
+///   } else {
+///       panic!()
+///   }
+/// }
+/// ```
+pub trait GetNeighborhoodDyn<Point, Model, RefModel>
+where
+    RefModel: Deref<Target = Model>,
+{
+    /// Get the two nearest neighbors, ordered by their distance from the given point.
+    fn get_neighborhood_dyn(
+        &mut self,
+        point: &Point,
+        dist: &dyn Fn(&Point, &Model) -> f64,
+    ) -> Neighborhood<Model, RefModel>;
+}
+
+/// Implementation of the dynamic two nearest neighbors getter for an iterator over a set of models.
+impl<Iter, Point, Model, RefModel> GetNeighborhoodDyn<Point, Model, RefModel> for Iter
+where
+    Iter: Iterator<Item = RefModel>,
+    RefModel: Deref<Target = Model>,
+{
+    fn get_neighborhood_dyn(
+        &mut self,
+        point: &Point,
+        dist: &dyn Fn(&Point, &Model) -> f64,
+    ) -> Neighborhood<Model, RefModel> {
+        self.get_neighborhood(point, dist)
+    }
+}
+
+/// Defines a two nearest neighbors getter function which distance computation may stop early.
+///
+/// The distance function gets a bound, the distance of the second nearest neighbor found so far,
+/// and may return any distance not lower than the bound as soon as it knows the exact distance is not lower,
+/// e.g. [space::euclid_dist_bounded](crate::space::euclid_dist_bounded). The neighborhood is the same as
+/// the one found by [GetNeighborhood::get_neighborhood] with the exact distance.
+/// ```
+/// use fluent_data::{space, neighborhood::{GetNeighborhood, GetNeighborhoodBounded}};
+///
+/// let points = vec![vec![0., 0.], vec![2., 1.], vec![5., 5.]];
+/// let point = vec![3., 1.];
+/// assert_eq!(
+///     points.iter().get_neighborhood(&point, space::euclid_dist),
+///     points.iter().get_neighborhood_bounded(&point, space::euclid_dist_bounded),
+/// );
+/// ```
+pub trait GetNeighborhoodBounded<Point, Model, RefModel, Dist>
+where
+    Dist: Fn(&Point, &Model, f64) -> f64,
+    RefModel: Deref<Target = Model>,
+{
+    /// Get the two nearest neighbors, ordered by their distance from the given point.
+    fn get_neighborhood_bounded(
+        &mut self,
+        point: &Point,
+        dist: Dist,
+    ) -> Neighborhood<Model, RefModel>;
+}
+
+/// Implementation of the bounded two nearest neighbors getter for an iterator over a set of models.
+impl<Iter, Point, Model, RefModel, Dist> GetNeighborhoodBounded<Point, Model, RefModel, Dist>
+    for Iter
+where
+    Iter: Iterator<Item = RefModel>,
+    RefModel: Deref<Target = Model>,
+    Dist: Fn(&Point, &Model, f64) -> f64,
+{
+    fn get_neighborhood_bounded(
+        &mut self,
+        point: &Point,
+        dist: Dist,
+    ) -> Neighborhood<Model, RefModel> {
+        self.fold(Neighborhood::None, |neighborhood, p| match neighborhood {
+            Neighborhood::None => {
+                let d = dist(point, &p, f64::INFINITY);
+                Neighborhood::One(NeighborDist(p, d))
+            }
+            Neighborhood::One(first) => {
+                let d = dist(point, &p, f64::INFINITY);
+                fold_others_2(first, NeighborDist(p, d), std::iter::empty())
+            }
+            Neighborhood::Two(first, second) => {
+                let d = dist(point, &p, second.1);
+                if d >= second.1 {
+                    Neighborhood::Two(first, second)
+                } else {
+                    let (first, second) = smallest(first, second, NeighborDist(p, d));
+                    Neighborhood::Two(first, second)
+                }
+            }
+        })
+    }
+}
+
 /// find neighbors given a (model, distance) couples iterator
 fn fold_0<Model, RefModel>(
     mut iter: impl Iterator<Item = NeighborDist<Model, RefModel>>,
@@ -185,6 +298,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_neighbors_chebyshev() {
+        let centers = [vec![3., 3.], vec![4., 0.], vec![0., -5.]];
+        let point = &vec![0., 0.];
+        let nn = centers.iter().get_neighborhood(point, space::euclid_dist);
+        assert_eq!(
+            Neighborhood::Two(
+                NeighborDist(&centers[1], 16.),
+                NeighborDist(&centers[0], 18.)
+            ),
+            nn
+        );
+        // the diagonal center is nearer within axis-aligned cubes
+        let nn = centers
+            .iter()
+            .get_neighborhood(point, space::chebyshev_dist);
+        assert_eq!(
+            Neighborhood::Two(
+                NeighborDist(&centers[0], 9.),
+                NeighborDist(&centers[1], 16.)
+            ),
+            nn
+        );
+    }
+
     #[test]
     fn test_neighbors_0_model() {
         let centers = vec![];
@@ -215,6 +353,47 @@ mod tests {
         );
     }
 
+    type Dist = Box<dyn Fn(&Vec<f64>, &Vec<f64>) -> f64>;
+
+    struct Metric {
+        dist: Dist,
+    }
+
+    fn get_metric(name: &str) -> Metric {
+        let dist: Dist = match name {
+            "euclid" => Box::new(space::euclid_dist),
+            _ => Box::new(|p1, p2| (p1[1] - p2[1]).abs() + 0.1 * (p1[0] - p2[0]).abs()),
+        };
+        Metric { dist }
+    }
+
+    #[test]
+    fn test_neighbors_dyn() {
+        let centers = [vec![1., 1.], vec![3.5, -1.6], vec![2.4, 4.], vec![-0.5, 1.]];
+        let point = &vec![0., -1.];
+        let metric = get_metric("euclid");
+        let nn = centers
+            .iter()
+            .get_neighborhood_dyn(point, metric.dist.as_ref());
+        assert_eq!(
+            Neighborhood::Two(
+                NeighborDist(&centers[3], 4.25),
+                NeighborDist(&centers[0], 5.)
+            ),
+            nn
+        );
+        let metric = get_metric("weighted");
+        let nn = centers
+            .iter()
+            .get_neighborhood_dyn(point, metric.dist.as_ref());
+        if let Neighborhood::Two(n1, n2) = nn {
+            assert_eq!(&centers[1], n1.coord());
+            assert_eq!(&centers[3], n2.coord());
+        } else {
+            panic!()
+        }
+    }
+
     #[test]
     fn test_smallest() {
         let p: Vec<f64> = vec![];
@@ -234,4 +413,33 @@ mod tests {
         let s = smallest(d1, d2, d3);
         assert_eq!((NeighborDist(&p, 4.), NeighborDist(&p, 7.)), s);
     }
+
+    #[test]
+    fn test_neighbors_bounded() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let centers: Vec<Vec<f64>> = (0..200)
+            .map(|_| (0..64).map(|_| rng.gen_range(-1. ..1.)).collect())
+            .collect();
+        for _ in 0..20 {
+            let point: Vec<f64> = (0..64).map(|_| rng.gen_range(-1. ..1.)).collect();
+            let nn = centers.iter().get_neighborhood(&point, space::euclid_dist);
+            let bounded = centers
+                .iter()
+                .get_neighborhood_bounded(&point, space::euclid_dist_bounded);
+            assert_eq!(nn, bounded);
+        }
+        for count in 0..3 {
+            let point = vec![0.; 64];
+            assert_eq!(
+                centers[..count]
+                    .iter()
+                    .get_neighborhood(&point, space::euclid_dist),
+                centers[..count]
+                    .iter()
+                    .get_neighborhood_bounded(&point, space::euclid_dist_bounded)
+            );
+        }
+    }
 }
@@ -0,0 +1,1215 @@
+//! The [ModelSerializer] trait turns a model into the string emitted by a [crate::Streamer].
+//!
+//! This module provides the following serializers:
+//!  - [JsonSerializer] writes compact JSON, this is the default,
+//!  - [PrettyJsonSerializer] writes indented JSON,
+//!  - [ColumnarJsonSerializer] writes compact JSON with parallel arrays of centers, radii and weights,
+//!  - [CsvSerializer] writes one line per ball with center coordinates, radius and weight,
+//!  - [GeoJsonSerializer] writes a feature collection of 2D centers,
+//!  - [MsgpackSerializer] writes base64 encoded MessagePack,
+//!  - [HeatmapSerializer] writes the density of 2D real points over a grid,
+//!  - [DeltaSerializer] writes periodic snapshots and the changed balls in between,
+//!  - [EnvelopeSerializer] writes snapshot messages, see [crate::message::Envelope].
+//!
+//! All serializers write the balls in the [Model::iter_balls] order, i.e. by ascending [Ball::id],
+//! which does not change when other balls are updated or removed; JSON objects are written with sorted keys.
+//!
+//! Use [crate::Streamer::with_serializer] to select a serializer, or a [Format] to choose it at runtime.
+//! Columnar output and the checkpoints written by [checkpoint_columns] can be read back with [parse_columnar].
+
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    io,
+    str::FromStr,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    message::{self, Envelope},
+    model::{self, Ball, Bounds, Model, Normalization, WeightedPoints},
+    streamer::serialize_model,
+};
+
+/// Turns a model into the string emitted by a [crate::Streamer].
+pub trait ModelSerializer<Point: PartialEq> {
+    /// Appends the serialized model to `output`.
+    /// The streamer clears and reuses the same buffer for all emissions.
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>>;
+
+    /// The sequence number of the last message written, for serializers that number their messages.
+    /// It is written in checkpoints, see [crate::Streamer::with_checkpoints].
+    fn last_seq(&self) -> Option<u64> {
+        None
+    }
+
+    /// Appends the model as it is embedded in a larger body, e.g. with the assignments of the points,
+    /// which is then framed by [ModelSerializer::frame]. Same as [ModelSerializer::serialize] by default.
+    fn serialize_embedded(
+        &self,
+        model: &Model<Point>,
+        output: &mut String,
+    ) -> Result<(), Box<dyn Error>> {
+        self.serialize(model, output)
+    }
+
+    /// Appends the message that carries a JSON body which embeds models, e.g. `{"model":[...],"assignments":{...}}`
+    /// or the models of an A/B run, for serializers that frame their messages. The body is appended as is by default.
+    fn frame(&self, body: &str, output: &mut String) -> Result<(), Box<dyn Error>> {
+        output.push_str(body);
+        Ok(())
+    }
+
+    /// Appends a message that tells the stream is alive, for serializers that frame their messages,
+    /// see [crate::Streamer::with_heartbeat]. Appends nothing by default.
+    fn heartbeat(&self, _output: &mut String) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Writes the model as a compact JSON array of balls.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonSerializer;
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for JsonSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(StringWriter(output), &serialize_model(model))?;
+        Ok(())
+    }
+}
+
+/// Writes the model as an indented JSON array of balls.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PrettyJsonSerializer;
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for PrettyJsonSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(StringWriter(output), &serialize_model(model))?;
+        Ok(())
+    }
+}
+
+/// Writes the model as a single JSON object with parallel arrays of centers, radii and weights,
+/// which is more compact than [JsonSerializer] for large models.
+/// ```
+/// use fluent_data::{Model, model::Ball, serializer::{self, ColumnarJsonSerializer, ModelSerializer}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![1., 2.], 4., 3.)]);
+/// let mut output = String::new();
+/// ColumnarJsonSerializer.serialize(&model, &mut output).unwrap();
+/// assert_eq!(r#"{"centers":[[1.0,2.0]],"radii":[2.0],"weights":[3.0]}"#, output);
+/// let balls: Vec<Ball<Vec<f64>>> = serializer::parse_columnar(&output).unwrap();
+/// assert_eq!(&vec![1., 2.], balls[0].center());
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ColumnarJsonSerializer;
+
+/// Layout of [ColumnarJsonSerializer] output.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Columns<Point> {
+    centers: Vec<Point>,
+    radii: Vec<Option<f64>>,
+    weights: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    normalization: Option<Normalization>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state: Option<BallStates>,
+}
+
+/// The state of the balls beyond their center, radius and weight, written in checkpoints
+/// so that a restored model keeps it, see [checkpoint_columns].
+#[derive(Serialize, Deserialize)]
+struct BallStates {
+    ids: Vec<u64>,
+    frozen: Vec<bool>,
+    dist_means: Vec<f64>,
+    dist_vars: Vec<f64>,
+    assigned: Vec<u64>,
+    /// Logarithms of the recent weights, `None` when the ball got no recent weight.
+    recent: Vec<Option<f64>>,
+    velocities: Vec<Option<Vec<f64>>>,
+    included: Vec<u64>,
+    inside: Vec<u64>,
+}
+
+impl BallStates {
+    fn new<Point: PartialEq + 'static>(model: &Model<Point>) -> Self {
+        let balls: Vec<_> = model.iter_balls().collect();
+        BallStates {
+            ids: balls.iter().map(|b| b.id).collect(),
+            frozen: balls.iter().map(|b| b.frozen).collect(),
+            dist_means: balls.iter().map(|b| b.dist_mean).collect(),
+            dist_vars: balls.iter().map(|b| b.dist_var).collect(),
+            assigned: balls.iter().map(|b| b.assigned).collect(),
+            recent: balls
+                .iter()
+                .map(|b| Some(b.recent).filter(|r| r.is_finite()))
+                .collect(),
+            velocities: balls.iter().map(|b| b.velocity.clone()).collect(),
+            included: balls.iter().map(|b| b.included).collect(),
+            inside: balls.iter().map(|b| b.inside).collect(),
+        }
+    }
+
+    /// Restores the state of the balls, which must be as many as the states.
+    fn restore<Point: PartialEq>(self, balls: &mut [Ball<Point>]) -> Result<(), Box<dyn Error>> {
+        let len = balls.len();
+        if [
+            self.ids.len(),
+            self.frozen.len(),
+            self.dist_means.len(),
+            self.dist_vars.len(),
+            self.assigned.len(),
+            self.recent.len(),
+            self.velocities.len(),
+            self.included.len(),
+            self.inside.len(),
+        ]
+        .iter()
+        .any(|l| *l != len)
+        {
+            return Err("ball states do not have the same length as the columns".into());
+        }
+        let mut velocities = self.velocities.into_iter();
+        for (i, ball) in balls.iter_mut().enumerate() {
+            ball.id = self.ids[i];
+            ball.frozen = self.frozen[i];
+            ball.dist_mean = self.dist_means[i];
+            ball.dist_var = self.dist_vars[i];
+            ball.assigned = self.assigned[i];
+            ball.recent = self.recent[i].unwrap_or(f64::NEG_INFINITY);
+            ball.velocity = velocities.next().flatten();
+            ball.included = self.included[i];
+            ball.inside = self.inside[i];
+        }
+        Ok(())
+    }
+}
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for ColumnarJsonSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(StringWriter(output), &columns(model)?)?;
+        Ok(())
+    }
+}
+
+/// Writes the model like [ColumnarJsonSerializer] with the state of the balls, e.g. their ids, dispersion and
+/// velocity, in a `state` object, so that the model restored with [parse_columnar] and [Model::restore]
+/// resumes where it stopped, see [crate::Streamer::with_checkpoints].
+/// ```
+/// use fluent_data::{Model, model::Ball, serializer, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![1.], 4., 3.), Ball::new(vec![9.], 4., 3.)]);
+/// let mut output = String::new();
+/// serializer::checkpoint_columns(&model, &mut output).unwrap();
+/// let restored = Model::restore(space::euclid_dist, serializer::parse_columnar(&output).unwrap());
+/// let ids: Vec<u64> = restored.iter_balls().map(|b| b.id()).collect();
+/// assert_eq!(vec![0, 1], ids);
+/// ```
+pub fn checkpoint_columns<Point: PartialEq + Serialize + 'static>(
+    model: &Model<Point>,
+    output: &mut String,
+) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer(StringWriter(output), &state_columns(model)?)?;
+    Ok(())
+}
+
+/// Gets the columns of [checkpoint_columns] output.
+pub(crate) fn state_columns<Point: PartialEq + Serialize + 'static>(
+    model: &Model<Point>,
+) -> Result<Columns<Value>, Box<dyn Error>> {
+    Ok(Columns {
+        state: Some(BallStates::new(model)),
+        ..columns(model)?
+    })
+}
+
+/// Gets the columns of [ColumnarJsonSerializer] output, without the state of the balls.
+fn columns<Point: PartialEq + Serialize + 'static>(
+    model: &Model<Point>,
+) -> Result<Columns<Value>, Box<dyn Error>> {
+    let columns = Columns {
+        centers: serialize_model(model)
+            .into_iter()
+            .map(|mut ball| ball.remove("center").unwrap_or_default())
+            .collect(),
+        radii: model
+            .iter_balls()
+            .map(|b| Some(b.radius()).filter(|r| r.is_finite()))
+            .collect(),
+        weights: model.iter_balls().map(|b| b.weight()).collect(),
+        normalization: model.normalization().cloned(),
+        state: None,
+    };
+    Ok(columns)
+}
+
+/// Reads balls back from [ColumnarJsonSerializer] or [checkpoint_columns] output, they can be loaded with [Model::load],
+/// or with [Model::restore] to keep their ids. Balls without state get their rank as id.
+/// Centers are in original units, the normalization of the model, if any, is read by [parse_columnar_normalization].
+pub fn parse_columnar<Point: PartialEq + DeserializeOwned>(
+    input: &str,
+) -> Result<Vec<Ball<Point>>, Box<dyn Error>> {
+    let columns: Columns<Point> = serde_json::from_str(input)?;
+    if columns.radii.len() != columns.centers.len()
+        || columns.weights.len() != columns.centers.len()
+    {
+        return Err("columns do not have the same length".into());
+    }
+    let mut balls: Vec<_> = columns
+        .centers
+        .into_iter()
+        .zip(columns.radii)
+        .zip(columns.weights)
+        .enumerate()
+        .map(|(i, ((center, radius), weight))| {
+            let radius = radius.map_or(f64::INFINITY, |r| r * r);
+            let mut ball = Ball::new(center, radius, weight);
+            ball.id = i as u64;
+            ball
+        })
+        .collect();
+    if let Some(state) = columns.state {
+        state.restore(&mut balls)?;
+    }
+    Ok(balls)
+}
+
+/// Reads the normalization of the model back from [ColumnarJsonSerializer] output,
+/// it can be restored with [Model::with_normalization].
+pub fn parse_columnar_normalization(input: &str) -> Result<Option<Normalization>, Box<dyn Error>> {
+    let columns: Columns<Value> = serde_json::from_str(input)?;
+    Ok(columns.normalization)
+}
+
+/// Writes `(center, weight)` pairs as CSV with a header line, then one line per pair:
+/// center coordinates followed by the weight, see [model::to_weighted_points].
+/// ```
+/// use fluent_data::serializer;
+///
+/// let csv = serializer::weighted_points_csv(&[(vec![1., 2.], 3.)]);
+/// assert_eq!("x0,x1,weight\n1,2,3\n", csv);
+/// assert_eq!(vec![(vec![1., 2.], 3.)], serializer::parse_weighted_csv(&csv).unwrap());
+/// ```
+pub fn weighted_points_csv(points: &[(Vec<f64>, f64)]) -> String {
+    let mut output = String::new();
+    if let Some((center, _)) = points.first() {
+        for i in 0..center.len() {
+            output.push_str(&format!("x{},", i));
+        }
+        output.push_str("weight\n");
+    }
+    for (center, weight) in points {
+        for x in center {
+            output.push_str(&format!("{},", x));
+        }
+        output.push_str(&format!("{}\n", weight));
+    }
+    output
+}
+
+/// Reads `(center, weight)` pairs back from [weighted_points_csv] output,
+/// they can be loaded with [Model::from_weighted_points].
+pub fn parse_weighted_csv(input: &str) -> Result<WeightedPoints, Box<dyn Error>> {
+    input
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut values = line
+                .split(',')
+                .map(|x| x.trim().parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()?;
+            let weight = values.pop().ok_or("empty line")?;
+            Ok((values, weight))
+        })
+        .collect()
+}
+
+/// Writes `(center, weight)` pairs as a NumPy `.npy` array of `f64` with one row per pair:
+/// center coordinates followed by the weight, see [model::to_weighted_points].
+/// Centers must have the same number of coordinates.
+#[cfg(feature = "npy")]
+pub fn weighted_points_npy(points: &[(Vec<f64>, f64)]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let columns = points.first().map_or(0, |(center, _)| center.len() + 1);
+    if points.iter().any(|(center, _)| center.len() + 1 != columns) {
+        return Err("centers do not have the same number of coordinates".into());
+    }
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}",
+        points.len(),
+        columns
+    );
+    // magic string, version and header length take 10 bytes, the data is aligned on 64 bytes
+    let padding = 63 - (10 + header.len()) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+    let mut output = b"\x93NUMPY\x01\x00".to_vec();
+    output.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    output.extend_from_slice(header.as_bytes());
+    for (center, weight) in points {
+        for x in center.iter().chain([weight]) {
+            output.extend_from_slice(&x.to_le_bytes());
+        }
+    }
+    Ok(output)
+}
+
+/// Serialization formats that can be chosen at runtime, e.g. from a command line option.
+/// ```
+/// use fluent_data::{serializer::Format, streamer, Streamer};
+///
+/// let format: Format = "columnar".parse().unwrap();
+/// let (points, write) = streamer::stdio();
+/// let streamer = Streamer::new(points, write).with_serializer(format);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// See [JsonSerializer].
+    Json,
+    /// See [PrettyJsonSerializer].
+    PrettyJson,
+    /// See [ColumnarJsonSerializer].
+    ColumnarJson,
+    /// See [CsvSerializer].
+    Csv,
+    /// See [GeoJsonSerializer].
+    GeoJson,
+    /// See [MsgpackSerializer].
+    Msgpack,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    /// Parses one of `json`, `pretty`, `columnar`, `csv`, `geojson` or `msgpack`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "pretty" => Ok(Format::PrettyJson),
+            "columnar" => Ok(Format::ColumnarJson),
+            "csv" => Ok(Format::Csv),
+            "geojson" => Ok(Format::GeoJson),
+            "msgpack" => Ok(Format::Msgpack),
+            _ => Err(format!("unknown format {}", s)),
+        }
+    }
+}
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for Format {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        match self {
+            Format::Json => JsonSerializer.serialize(model, output),
+            Format::PrettyJson => PrettyJsonSerializer.serialize(model, output),
+            Format::ColumnarJson => ColumnarJsonSerializer.serialize(model, output),
+            Format::Csv => CsvSerializer.serialize(model, output),
+            Format::GeoJson => GeoJsonSerializer.serialize(model, output),
+            Format::Msgpack => MsgpackSerializer.serialize(model, output),
+        }
+    }
+}
+
+/// Writes the model as CSV with a header line, then one line per ball:
+/// center coordinates followed by the radius and the weight.
+/// The radius is left empty when it is not yet defined.
+/// Centers are in original units; the radius of a normalized model is followed by its semi-axes in original units,
+/// see [crate::Algo::with_normalization].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CsvSerializer;
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for CsvSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        let mut header = false;
+        let normalized = model.normalization().is_some();
+        for ball in serialize_model(model) {
+            let center = coordinates(&ball["center"])?;
+            if !header {
+                for i in 0..center.len() {
+                    output.push_str(&format!("x{},", i));
+                }
+                output.push_str("radius,weight");
+                if normalized {
+                    for i in 0..center.len() {
+                        output.push_str(&format!(",r{}", i));
+                    }
+                }
+                output.push('\n');
+                header = true;
+            }
+            for x in center.iter() {
+                output.push_str(&format!("{},", x));
+            }
+            if let Some(radius) = ball["radius"].as_f64() {
+                output.push_str(&radius.to_string());
+            }
+            output.push_str(&format!(",{}", ball["weight"].as_f64().unwrap_or_default()));
+            if normalized {
+                let radii = match ball.get("radii") {
+                    Some(radii) => coordinates(radii)?.iter().map(f64::to_string).collect(),
+                    None => vec![String::new(); center.len()],
+                };
+                for r in radii {
+                    output.push_str(&format!(",{}", r));
+                }
+            }
+            output.push('\n');
+        }
+        Ok(())
+    }
+}
+
+/// Writes the model as a GeoJSON feature collection.
+/// Each ball is a point feature which coordinates are the ball center,
+/// its radius and weight are feature properties.
+/// Centers must have exactly two coordinates, they are in original units;
+/// the radius of a normalized model comes with its semi-axes in original units as `"radii"`, see [crate::Algo::with_normalization].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GeoJsonSerializer;
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for GeoJsonSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        let mut features = vec![];
+        for mut ball in serialize_model(model) {
+            let coordinates = coordinates(&ball["center"])?;
+            if coordinates.len() != 2 {
+                return Err(format!(
+                    "GeoJSON requires 2 dimensional centers, got {}",
+                    coordinates.len()
+                )
+                .into());
+            }
+            let mut properties = json!({ "radius": ball["radius"], "weight": ball["weight"] });
+            if let Some(radii) = ball.remove("radii") {
+                properties["radii"] = radii;
+            }
+            features.push(json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": coordinates },
+                "properties": properties,
+            }));
+        }
+        let collection = json!({ "type": "FeatureCollection", "features": features });
+        serde_json::to_writer(StringWriter(output), &collection)?;
+        Ok(())
+    }
+}
+
+/// Writes the model as base64 encoded MessagePack, with the same layout as [JsonSerializer].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MsgpackSerializer;
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for MsgpackSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        let bytes = rmp_serde::to_vec_named(&serialize_model(model))?;
+        base64::encode_config_buf(bytes, base64::STANDARD, output);
+        Ok(())
+    }
+}
+
+/// Writes the mixture density of a model of 2D real points as a JSON matrix, for quick visualization,
+/// see [model::to_heatmap]. Centers must have exactly two coordinates.
+/// ```
+/// use fluent_data::{Model, model::Ball, serializer::{HeatmapSerializer, ModelSerializer}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![0., 0.], 1., 1.)]);
+/// let heatmap = HeatmapSerializer { bounds: [(-1., 1.), (-1., 1.)], resolution: 2 };
+/// let mut output = String::new();
+/// heatmap.serialize(&model, &mut output).unwrap();
+/// let grid: Vec<Vec<f64>> = serde_json::from_str(&output).unwrap();
+/// assert_eq!(2, grid.len());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct HeatmapSerializer {
+    /// The area covered by the grid.
+    pub bounds: Bounds,
+    /// Number of cells along each axis.
+    pub resolution: usize,
+}
+
+impl ModelSerializer<Vec<f64>> for HeatmapSerializer {
+    fn serialize(
+        &self,
+        model: &Model<Vec<f64>>,
+        output: &mut String,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(ball) = model.iter_balls().find(|b| b.center().len() != 2) {
+            return Err(format!(
+                "heatmap requires 2 dimensional centers, got {}",
+                ball.center().len()
+            )
+            .into());
+        }
+        let grid = model::to_heatmap(model, self.bounds, self.resolution);
+        serde_json::to_writer(StringWriter(output), &grid)?;
+        Ok(())
+    }
+}
+
+/// Writes periodic full snapshots of the model and, in between, delta frames with the balls that changed
+/// since the previous emission, for bandwidth constrained links.
+/// Frames are [Envelope::Snapshot] and [Envelope::Delta] messages, with the same balls as [JsonSerializer];
+/// indices refer to the [Model::iter_balls] order.
+/// Balls are matched with the previous emission by [Ball::id], thus removing a ball does not mark the next ones as changed.
+///
+/// Frames are decoded by [crate::client::DeltaDecoder]. The serializer keeps the last emitted model,
+/// thus an instance must serve a single stream. A serializer resumed after a restart starts with a snapshot.
+/// ```
+/// use fluent_data::{Model, model::Ball, serializer::{DeltaSerializer, ModelSerializer}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![1.], 4., 3.)]);
+/// let delta = DeltaSerializer::new(10);
+/// let mut output = String::new();
+/// delta.serialize(&model, &mut output).unwrap();
+/// assert!(output.starts_with(r#"{"type":"snapshot","seq":0,"ts":"#));
+/// output.clear();
+/// delta.serialize(&model, &mut output).unwrap();
+/// assert_eq!(r#"{"type":"delta","seq":1,"base_seq":0,"changed":[],"removed":[]}"#, output);
+/// ```
+#[derive(Debug)]
+pub struct DeltaSerializer {
+    snapshot_every: u64,
+    state: RefCell<DeltaState>,
+}
+
+/// The last emission of a [DeltaSerializer].
+#[derive(Debug, Default)]
+struct DeltaState {
+    seq: u64,
+    /// The balls with their id, in ascending id order.
+    previous: Vec<(u64, Value)>,
+    /// Whether the next frame is the first one after a restart.
+    restarted: bool,
+}
+
+impl DeltaSerializer {
+    /// Builds a serializer that writes a full snapshot every `snapshot_every` emissions, starting with the first.
+    pub fn new(snapshot_every: usize) -> Self {
+        assert!(snapshot_every > 0, "snapshot period must be positive");
+        Self {
+            snapshot_every: snapshot_every as u64,
+            state: RefCell::new(DeltaState::default()),
+        }
+    }
+
+    /// Numbers the frames after `seq`, the last sequence number written before a restart,
+    /// e.g. restored by [crate::streamer::parse_checkpoint_seq]. The first frame is a snapshot flagged as restarted.
+    pub fn resume_after(self, seq: u64) -> Self {
+        {
+            let mut state = self.state.borrow_mut();
+            state.seq = seq + 1;
+            state.restarted = true;
+        }
+        self
+    }
+}
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for DeltaSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        let balls: Vec<(u64, Value)> = model
+            .iter_balls()
+            .map(|b| b.id())
+            .zip(serialize_model(model).into_iter().map(Value::Object))
+            .collect();
+        let mut state = self.state.borrow_mut();
+        let seq = state.seq;
+        let frame = if seq.is_multiple_of(self.snapshot_every) || state.restarted {
+            Envelope::Snapshot {
+                seq,
+                ts: message::now_ms(),
+                balls: balls.iter().map(|(_, ball)| ball).collect(),
+                restarted: state.restarted,
+            }
+        } else {
+            let previous = |id: &u64| {
+                state
+                    .previous
+                    .binary_search_by_key(id, |(id, _)| *id)
+                    .ok()
+                    .map(|i| &state.previous[i].1)
+            };
+            let changed = balls
+                .iter()
+                .enumerate()
+                .filter(|(_, (id, ball))| previous(id) != Some(ball))
+                .map(|(i, (_, ball))| (i, ball))
+                .collect();
+            let removed = state
+                .previous
+                .iter()
+                .enumerate()
+                .filter(|(_, (id, _))| balls.binary_search_by_key(id, |(id, _)| *id).is_err())
+                .map(|(i, _)| i)
+                .collect();
+            Envelope::Delta {
+                seq,
+                base_seq: seq - 1,
+                changed,
+                removed,
+            }
+        };
+        serde_json::to_writer(StringWriter(output), &frame)?;
+        state.seq += 1;
+        state.previous = balls;
+        state.restarted = false;
+        Ok(())
+    }
+
+    fn last_seq(&self) -> Option<u64> {
+        self.state.borrow().seq.checked_sub(1)
+    }
+
+    /// Writes the balls like [JsonSerializer] and keeps them as the base of the next delta.
+    fn serialize_embedded(
+        &self,
+        model: &Model<Point>,
+        output: &mut String,
+    ) -> Result<(), Box<dyn Error>> {
+        let balls = serialize_model(model);
+        serde_json::to_writer(StringWriter(output), &balls)?;
+        let ids = model.iter_balls().map(|b| b.id());
+        self.state.borrow_mut().previous = ids.zip(balls.into_iter().map(Value::Object)).collect();
+        Ok(())
+    }
+
+    fn frame(&self, body: &str, output: &mut String) -> Result<(), Box<dyn Error>> {
+        let mut state = self.state.borrow_mut();
+        let restarted = std::mem::take(&mut state.restarted);
+        write_composite(state.seq, body, restarted, output)?;
+        state.seq += 1;
+        Ok(())
+    }
+
+    fn heartbeat(&self, output: &mut String) -> Result<(), Box<dyn Error>> {
+        write_heartbeat(self.state.borrow().seq, output)
+    }
+}
+
+/// Writes each model as an [Envelope::Snapshot] message with a sequence number and a timestamp,
+/// or as a bare array of balls like [JsonSerializer] in legacy mode.
+/// ```
+/// use fluent_data::{message::Envelope, Model, model::Ball, serializer::{EnvelopeSerializer, ModelSerializer}, space};
+///
+/// let model = Model::load(space::euclid_dist, vec![Ball::new(vec![1.], 4., 3.)]);
+/// let mut output = String::new();
+/// EnvelopeSerializer::new().serialize(&model, &mut output).unwrap();
+/// let message: Envelope<serde_json::Value> = serde_json::from_str(&output).unwrap();
+/// assert!(matches!(message, Envelope::Snapshot { seq: 0, .. }));
+/// ```
+#[derive(Debug, Default)]
+pub struct EnvelopeSerializer {
+    legacy: bool,
+    seq: Cell<u64>,
+    /// Whether the next message is the first one after a restart.
+    restarted: Cell<bool>,
+}
+
+impl EnvelopeSerializer {
+    /// Builds a serializer that writes snapshot messages, numbered from 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a serializer that writes bare arrays of balls, for consumers of the format prior to envelopes.
+    pub fn legacy() -> Self {
+        Self {
+            legacy: true,
+            ..Default::default()
+        }
+    }
+
+    /// Numbers the messages after `seq`, the last sequence number written before a restart,
+    /// e.g. restored by [crate::streamer::parse_checkpoint_seq]. The first message is flagged as restarted.
+    /// ```
+    /// use fluent_data::{message::Envelope, Model, serializer::{EnvelopeSerializer, ModelSerializer}, space};
+    ///
+    /// let model = Model::<Vec<f64>>::new(space::euclid_dist);
+    /// let mut output = String::new();
+    /// EnvelopeSerializer::new().resume_after(41).serialize(&model, &mut output).unwrap();
+    /// let message: Envelope<serde_json::Value> = serde_json::from_str(&output).unwrap();
+    /// assert!(matches!(message, Envelope::Snapshot { seq: 42, restarted: true, .. }));
+    /// ```
+    pub fn resume_after(self, seq: u64) -> Self {
+        self.seq.set(seq + 1);
+        self.restarted.set(true);
+        self
+    }
+}
+
+impl<Point: PartialEq + Serialize + 'static> ModelSerializer<Point> for EnvelopeSerializer {
+    fn serialize(&self, model: &Model<Point>, output: &mut String) -> Result<(), Box<dyn Error>> {
+        if self.legacy {
+            return JsonSerializer.serialize(model, output);
+        }
+        let seq = self.seq.replace(self.seq.get() + 1);
+        let message = Envelope::Snapshot {
+            seq,
+            ts: message::now_ms(),
+            balls: serialize_model(model),
+            restarted: self.restarted.replace(false),
+        };
+        serde_json::to_writer(StringWriter(output), &message)?;
+        Ok(())
+    }
+
+    fn last_seq(&self) -> Option<u64> {
+        if self.legacy {
+            None
+        } else {
+            self.seq.get().checked_sub(1)
+        }
+    }
+
+    /// Writes the balls like [JsonSerializer], the envelope is added by [ModelSerializer::frame].
+    fn serialize_embedded(
+        &self,
+        model: &Model<Point>,
+        output: &mut String,
+    ) -> Result<(), Box<dyn Error>> {
+        JsonSerializer.serialize(model, output)
+    }
+
+    fn frame(&self, body: &str, output: &mut String) -> Result<(), Box<dyn Error>> {
+        if self.legacy {
+            output.push_str(body);
+            return Ok(());
+        }
+        let seq = self.seq.replace(self.seq.get() + 1);
+        write_composite(seq, body, self.restarted.replace(false), output)
+    }
+
+    fn heartbeat(&self, output: &mut String) -> Result<(), Box<dyn Error>> {
+        if self.legacy {
+            return Ok(());
+        }
+        write_heartbeat(self.seq.get(), output)
+    }
+}
+
+/// Appends an [Envelope::Composite] message that carries the JSON `body`.
+fn write_composite(
+    seq: u64,
+    body: &str,
+    restarted: bool,
+    output: &mut String,
+) -> Result<(), Box<dyn Error>> {
+    let message = Envelope::<Value>::Composite {
+        seq,
+        ts: message::now_ms(),
+        body: serde_json::from_str(body)?,
+        restarted,
+    };
+    serde_json::to_writer(StringWriter(output), &message)?;
+    Ok(())
+}
+
+/// Appends an [Envelope::Heartbeat] message, `seq` is the sequence number of the next message.
+fn write_heartbeat(seq: u64, output: &mut String) -> Result<(), Box<dyn Error>> {
+    let message = Envelope::<Value>::Heartbeat {
+        seq,
+        ts: message::now_ms(),
+    };
+    serde_json::to_writer(StringWriter(output), &message)?;
+    Ok(())
+}
+
+/// Gets the coordinates of a serialized center, which must be a number or an array of numbers.
+fn coordinates(center: &Value) -> Result<Vec<f64>, Box<dyn Error>> {
+    let not_a_number = || format!("center {} is not made of numbers", center);
+    match center {
+        Value::Number(x) => Ok(vec![x.as_f64().ok_or_else(not_a_number)?]),
+        Value::Array(values) => values
+            .iter()
+            .map(|v| v.as_f64().ok_or_else(|| not_a_number().into()))
+            .collect(),
+        _ => Err(not_a_number().into()),
+    }
+}
+
+/// Lets serde write directly into a string buffer.
+struct StringWriter<'a>(&'a mut String);
+
+impl<'a> io::Write for StringWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.0.push_str(s);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        model::{Ball, OutOfRange},
+        serializer::*,
+        space,
+    };
+
+    fn build_model() -> Model<Vec<f64>> {
+        Model::load(
+            space::euclid_dist,
+            vec![
+                Ball::new(vec![3., 5.1], 4., 0.5),
+                Ball::new(vec![1.2, 6.], f64::INFINITY, 0.),
+            ],
+        )
+    }
+
+    fn serialize(serializer: impl ModelSerializer<Vec<f64>>) -> String {
+        let mut output = String::new();
+        serializer.serialize(&build_model(), &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            r#"[{"center":[3.0,5.1],"radius":2.0,"weight":0.5},{"center":[1.2,6.0],"radius":null,"weight":0.0}]"#,
+            serialize(JsonSerializer)
+        );
+    }
+
+    #[test]
+    fn test_pretty_json() {
+        let output = serialize(PrettyJsonSerializer);
+        assert!(output.contains("\n  {\n    \"center\": [\n      3.0,"));
+        let compact: Value = serde_json::from_str(&serialize(JsonSerializer)).unwrap();
+        assert_eq!(compact, serde_json::from_str::<Value>(&output).unwrap());
+    }
+
+    #[test]
+    fn test_columnar() {
+        let model = build_model();
+        let output = serialize(ColumnarJsonSerializer);
+        assert_eq!(
+            r#"{"centers":[[3.0,5.1],[1.2,6.0]],"radii":[2.0,null],"weights":[0.5,0.0]}"#,
+            output
+        );
+        let parsed = Model::load(space::euclid_dist, parse_columnar(&output).unwrap());
+        assert_eq!(model.fingerprint(), parsed.fingerprint());
+        assert!(output.len() < serialize(JsonSerializer).len());
+    }
+
+    #[test]
+    fn test_checkpoint_columns() {
+        let model = build_model();
+        model.freeze_ball(1).unwrap();
+        let mut output = String::new();
+        checkpoint_columns(&model, &mut output).unwrap();
+        let columnar = serialize(ColumnarJsonSerializer);
+        assert!(output.starts_with(&columnar[..columnar.len() - 1]));
+        let restored = Model::restore(space::euclid_dist, parse_columnar(&output).unwrap());
+        for (ball, restored) in model.iter_balls().zip(restored.iter_balls()) {
+            assert_eq!(ball.id(), restored.id());
+            assert_eq!(ball.frozen, restored.frozen);
+            assert_eq!(ball.dist_mean, restored.dist_mean);
+            assert_eq!(ball.recent, restored.recent);
+        }
+        let truncated = output.replace(r#""ids":[0,1]"#, r#""ids":[0]"#);
+        assert!(parse_columnar::<Vec<f64>>(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_columnar_mismatch() {
+        let input = r#"{"centers":[[3.0,5.1],[1.2,6.0]],"radii":[2.0],"weights":[0.5,0.0]}"#;
+        assert!(parse_columnar::<Vec<f64>>(input).is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let format: Format = "columnar".parse().unwrap();
+        assert_eq!(serialize(ColumnarJsonSerializer), serialize(format));
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_csv() {
+        assert_eq!(
+            "x0,x1,radius,weight\n3,5.1,2,0.5\n1.2,6,,0\n",
+            serialize(CsvSerializer)
+        );
+    }
+
+    #[test]
+    fn test_csv_normalized() {
+        let normalization = Normalization::new(vec![0., 0.], vec![10., 100.], OutOfRange::Clamp);
+        let model = Model::load(space::euclid_dist, vec![Ball::new(vec![2., 20.], 0.01, 1.)])
+            .with_normalization(normalization);
+        let mut output = String::new();
+        CsvSerializer.serialize(&model, &mut output).unwrap();
+        assert_eq!("x0,x1,radius,weight,r0,r1\n2,20,0.1,1,1,10\n", output);
+    }
+
+    #[test]
+    fn test_geojson() {
+        let output: Value = serde_json::from_str(&serialize(GeoJsonSerializer)).unwrap();
+        assert_eq!("FeatureCollection", output["type"]);
+        assert_eq!(
+            json!([3.0, 5.1]),
+            output["features"][0]["geometry"]["coordinates"]
+        );
+        assert_eq!(json!(2.0), output["features"][0]["properties"]["radius"]);
+        assert_eq!(json!(0.0), output["features"][1]["properties"]["weight"]);
+    }
+
+    #[test]
+    fn test_geojson_dimension() {
+        let model = Model::load(space::euclid_dist, vec![Ball::new(vec![3.], 4., 0.5)]);
+        let mut output = String::new();
+        assert!(GeoJsonSerializer.serialize(&model, &mut output).is_err());
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let model = Model::load(
+            space::euclid_dist,
+            vec![
+                Ball::new(vec![0., 0.], 1., 1.),
+                Ball::new(vec![0., 0.], f64::INFINITY, 0.),
+            ],
+        );
+        let heatmap = HeatmapSerializer {
+            bounds: [(-3., 3.), (-3., 3.)],
+            resolution: 3,
+        };
+        let mut output = String::new();
+        heatmap.serialize(&model, &mut output).unwrap();
+        let grid: Vec<Vec<f64>> = serde_json::from_str(&output).unwrap();
+        let expected = model::to_heatmap(&model, heatmap.bounds, 3);
+        for (row, expected) in grid.iter().zip(expected.iter()) {
+            for (x, y) in row.iter().zip(expected.iter()) {
+                assert!((x - y).abs() <= 1E-12 * y);
+            }
+        }
+        let flat = Model::load(space::euclid_dist, vec![Ball::new(vec![3.], 4., 0.5)]);
+        assert!(heatmap.serialize(&flat, &mut output).is_err());
+    }
+
+    #[test]
+    fn test_delta() {
+        let delta = DeltaSerializer::new(3);
+        let first = Model::load(space::euclid_dist, vec![Ball::new(vec![3., 5.1], 4., 0.5)]);
+        let mut frames = vec![];
+        for model in [build_model(), build_model(), first, build_model()] {
+            let mut output = String::new();
+            delta.serialize(&model, &mut output).unwrap();
+            frames.push(serde_json::from_str::<Value>(&output).unwrap());
+        }
+        assert_eq!("snapshot", frames[0]["type"]);
+        assert_eq!(2, frames[0]["balls"].as_array().unwrap().len());
+        assert_eq!(
+            json!({"type": "delta", "seq": 1, "base_seq": 0, "changed": [], "removed": []}),
+            frames[1]
+        );
+        assert_eq!(json!([]), frames[2]["changed"]);
+        assert_eq!(json!([1]), frames[2]["removed"]);
+        assert_eq!(2, frames[3]["balls"].as_array().unwrap().len());
+    }
+
+    #[test]
+    fn test_delta_stable_order() {
+        let data = vec![
+            Ball::new(vec![0.], 1., 1.),
+            Ball::new(vec![10.], 1., 1E-3),
+            Ball::new(vec![20.], 1., 1.),
+        ];
+        let mut model = Model::load(space::euclid_dist, data);
+        let delta = DeltaSerializer::new(100);
+        let mut output = String::new();
+        delta.serialize(&model, &mut output).unwrap();
+        model.decay_weights(0., 1E-2);
+        output.clear();
+        delta.serialize(&model, &mut output).unwrap();
+        let frame: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(json!([]), frame["changed"]);
+        assert_eq!(json!([1]), frame["removed"]);
+        let ids: Vec<u64> = model.iter_balls().map(|b| b.id()).collect();
+        assert_eq!(vec![0, 2], ids);
+    }
+
+    #[test]
+    fn test_delta_genuine_changes() {
+        let algo = crate::Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let delta = DeltaSerializer::new(usize::MAX);
+        let mut decoder = crate::client::DeltaDecoder::new();
+        let mut previous: Vec<(u64, Value)> = vec![];
+        // removals of balls which are not the last ones, which used to shift the next balls
+        let mut inner_removals = 0;
+        for i in 0..400 {
+            let x = match i % 40 {
+                n if n < 20 => (n as f64 * 7.3) % 3.,
+                n if i < 200 => 50. + (n as f64 * 3.1) % 2.,
+                n => 25. + (n as f64 * 1.7) % 2.,
+            };
+            algo.fit(&mut model, vec![x, (i % 3) as f64]);
+            let mut output = String::new();
+            delta.serialize(&model, &mut output).unwrap();
+            let mut json = String::new();
+            JsonSerializer.serialize(&model, &mut json).unwrap();
+            let current: Vec<(u64, Value)> = model
+                .iter_balls()
+                .map(|b| b.id())
+                .zip(serde_json::from_str::<Vec<Value>>(&json).unwrap())
+                .collect();
+            assert!(current.windows(2).all(|w| w[0].0 < w[1].0));
+            let balls = decoder.decode(&output).unwrap().unwrap();
+            assert_eq!(
+                current.iter().map(|(_, b)| b).collect::<Vec<_>>(),
+                balls.iter().collect::<Vec<_>>()
+            );
+            if i > 0 {
+                let frame: Value = serde_json::from_str(&output).unwrap();
+                let gone: Vec<usize> = previous
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (id, _))| current.iter().all(|(c, _)| c != id))
+                    .map(|(j, _)| j)
+                    .collect();
+                let differ: Vec<usize> = current
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, ball)| !previous.contains(ball))
+                    .map(|(j, _)| j)
+                    .collect();
+                let changed: Vec<usize> = frame["changed"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|c| c[0].as_u64().unwrap() as usize)
+                    .collect();
+                assert_eq!(json!(gone), frame["removed"]);
+                assert_eq!(differ, changed);
+                if gone.iter().any(|&j| j + 1 < previous.len()) {
+                    inner_removals += 1;
+                }
+            }
+            previous = current;
+        }
+        assert!(inner_removals > 0);
+    }
+
+    #[test]
+    fn test_envelope() {
+        let model = build_model();
+        let serializer = EnvelopeSerializer::new();
+        for seq in 0..2 {
+            let mut output = String::new();
+            serializer.serialize(&model, &mut output).unwrap();
+            match serde_json::from_str::<Envelope<Value>>(&output).unwrap() {
+                Envelope::Snapshot {
+                    seq: s, ts, balls, ..
+                } => {
+                    assert_eq!(seq, s);
+                    assert!(ts > 0);
+                    assert_eq!(
+                        serde_json::from_str::<Value>(&serialize(JsonSerializer)).unwrap(),
+                        json!(balls)
+                    );
+                }
+                _ => panic!("{}", output),
+            }
+        }
+    }
+
+    #[test]
+    fn test_envelope_legacy() {
+        assert_eq!(
+            serialize(JsonSerializer),
+            serialize(EnvelopeSerializer::legacy())
+        );
+        assert_eq!(
+            r#"[{"center":[3.0,5.1],"radius":2.0,"weight":0.5},{"center":[1.2,6.0],"radius":null,"weight":0.0}]"#,
+            serialize(EnvelopeSerializer::legacy())
+        );
+    }
+
+    #[test]
+    fn test_delta_composite() {
+        let model = build_model();
+        let delta = DeltaSerializer::new(10);
+        let mut decoder = crate::client::DeltaDecoder::new();
+        let mut frames = vec![];
+        for framed in [false, true, false] {
+            let mut output = String::new();
+            if framed {
+                let mut body = String::from(r#"{"model":"#);
+                delta.serialize_embedded(&model, &mut body).unwrap();
+                body.push('}');
+                ModelSerializer::<Vec<f64>>::frame(&delta, &body, &mut output).unwrap();
+            } else {
+                delta.serialize(&model, &mut output).unwrap();
+            }
+            assert_eq!(2, decoder.decode(&output).unwrap().unwrap().len());
+            frames.push(serde_json::from_str::<Envelope<Value>>(&output).unwrap());
+        }
+        assert!(matches!(frames[1], Envelope::Composite { seq: 1, .. }));
+        assert!(matches!(
+            frames[2],
+            Envelope::Delta {
+                seq: 2,
+                base_seq: 1,
+                ..
+            }
+        ));
+        let mut output = String::new();
+        ModelSerializer::<Vec<f64>>::heartbeat(&delta, &mut output).unwrap();
+        let heartbeat: Envelope<Value> = serde_json::from_str(&output).unwrap();
+        assert!(matches!(heartbeat, Envelope::Heartbeat { seq: 3, .. }));
+        output.clear();
+        ModelSerializer::<Vec<f64>>::heartbeat(&EnvelopeSerializer::legacy(), &mut output).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_msgpack() {
+        let bytes = base64::decode(serialize(MsgpackSerializer)).unwrap();
+        let decoded: Value = rmp_serde::from_slice(&bytes).unwrap();
+        let json: Value = serde_json::from_str(&serialize(JsonSerializer)).unwrap();
+        assert_eq!(json, decoded);
+    }
+
+    #[test]
+    fn test_weighted_points_round_trip() {
+        let model = build_model();
+        let exported = weighted_points_csv(&model::to_weighted_points(&model));
+        let mut points = parse_weighted_csv(&exported).unwrap();
+        assert_eq!(vec![(vec![3., 5.1], 0.5)], points);
+        points[0].0[0] += 1.;
+        points.push((vec![-2., 0.], 4.));
+        let imported = Model::from_weighted_points(points, space::euclid_dist, 1.);
+        let weights: Vec<f64> = imported.iter_balls().map(|b| b.weight()).collect();
+        assert_eq!(vec![0.5, 4.], weights);
+        assert_eq!(
+            &vec![4., 5.1],
+            imported.iter_balls().next().unwrap().center()
+        );
+        assert_eq!(
+            "x0,x1,weight\n4,5.1,0.5\n-2,0,4\n",
+            weighted_points_csv(&model::to_weighted_points(&imported))
+        );
+    }
+
+    #[cfg(feature = "npy")]
+    #[test]
+    fn test_weighted_points_npy() {
+        let npy = weighted_points_npy(&[(vec![1., 2.], 3.), (vec![4., 5.], 6.)]).unwrap();
+        assert_eq!(b"\x93NUMPY\x01\x00", &npy[..8]);
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!(0, (10 + header_len) % 64);
+        let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 3)"));
+        let data: Vec<f64> = npy[10 + header_len..]
+            .chunks(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(vec![1., 2., 3., 4., 5., 6.], data);
+        assert!(weighted_points_npy(&[(vec![1.], 1.), (vec![1., 2.], 1.)]).is_err());
+    }
+}
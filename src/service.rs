@@ -3,16 +3,58 @@
 //! Use the [backend] function to start the service.
 //! The backend starts listening on port 9001 by default
 //! which can be changed by setting the `PORT`environment variable.
+//!
+//! The last emitted models are served on `GET /model/history?from=<seq>&to=<seq>`.
+//!
+//! The [ServiceConfig] settings can be changed while the service runs by posting a JSON object
+//! to `POST /admin/config`, e.g. `{"emit_every":10}`, or by editing a file given to [watch_config].
+//!
+//! Messages that are not JSON, or that are JSON scalars, are not sent to the algorithm; instead an error frame
+//! `{"error":"<reason>"}` is sent back to the client, which stays connected.
+//! Other messages that are not points, e.g. `{}`, can only be detected once parsed into the point type,
+//! thus the service should be run with [Streamer::run_with_drop_sink](crate::Streamer::run_with_drop_sink),
+//! which skips them instead of stopping.
+//!
+//! On Unix, [backend_uds] serves local clients on a Unix domain socket instead, with newline delimited JSON.
+//!
+//! Browser dashboards can use [backend_sse] instead, which receives points by HTTP POST
+//! and streams models as Server-Sent Events.
+//!
+//! Several pipelines run by a [streamer::Supervisor] are served on their own endpoints by [backend_routed].
+//!
+//! With the `testing` feature, [spawn_for_tests] runs the whole service on an ephemeral port
+//! for end-to-end tests of websocket clients.
 
 use std::{
+    collections::BTreeMap,
     env,
     error::Error,
-    net::{TcpListener, TcpStream},
+    fmt::Display,
+    fs,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    path::PathBuf,
     sync::{
+        atomic::{AtomicBool, Ordering},
         mpsc::{self, Receiver, Sender},
         Arc, Mutex,
     },
     thread,
+    time::{Duration, Instant},
+};
+#[cfg(unix)]
+use std::{
+    io::{BufRead, BufReader},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+};
+#[cfg(feature = "testing")]
+use std::{
+    net::{Shutdown, SocketAddr},
+    thread::JoinHandle,
 };
 
 use tungstenite::{
@@ -21,10 +63,43 @@ use tungstenite::{
     Message, WebSocket,
 };
 
-use crate::streamer;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use url::Url;
+
+#[cfg(feature = "testing")]
+use crate::{algorithm::AlgoConfig, space, Algo, Model, Streamer};
+use crate::{
+    message::Envelope,
+    streamer::{self, BoxedPoints, BoxedWrite, History, StreamError},
+};
 
 type Peers = Arc<Mutex<Vec<WebSocket<TcpStream>>>>;
 
+/// Number of models kept in the history by [backend].
+const HISTORY_CAPACITY: usize = 60;
+
+/// Largest number of models kept in the history that [apply_config] accepts.
+const MAX_HISTORY_CAPACITY: usize = 10_000;
+
+/// Delay between two checks of the file given to [watch_config].
+const CONFIG_POLL: Duration = Duration::from_secs(1);
+
+/// Delay after which [backend_sse] drops a connection that does not send its whole request.
+const SSE_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest head of a plain HTTP request, see [read_request].
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Largest body of a plain HTTP request, see [read_request].
+const MAX_REQUEST_BODY: usize = 1024 * 1024;
+
+/// Delay after which [backend_sse] drops a client that does not read its events.
+const SSE_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Settings that are only read when the service starts.
+const RESTART_KEYS: [&str; 5] = ["port", "bind", "dist", "decay", "threshold"];
+
 /// Starts a backend that accepts data on endpoint ws://0.0.0.0:9001/ws/points
 /// and dispatch models on endpoint ws://0.0.0.0:9001/ws/models.
 /// ```
@@ -37,8 +112,8 @@ type Peers = Arc<Mutex<Vec<WebSocket<TcpStream>>>>;
 ///     let mut model = Model::new(space::euclid_dist);
 ///     let (points, write) = service::backend();
 ///     let streamer = Streamer::new(points, write);
-///     // this will endlessly consume data and produce models...
-///     // Streamer::run(streamer, algo, &mut model)?;
+///     // this will endlessly consume data and produce models, skipping records that are not points...
+///     // Streamer::run_with_drop_sink(streamer, algo, &mut model, |record, reason| eprintln!("{:?}: {}", reason, record))?;
 ///     Ok(())
 /// }
 /// ```
@@ -47,27 +122,591 @@ pub fn backend() -> (
     impl Iterator<Item = Result<String, Box<dyn Error>>>,
     impl FnMut(String) -> Result<(), Box<dyn Error>>,
 ) {
+    backend_with_history(Arc::new(Mutex::new(History::new(HISTORY_CAPACITY))))
+}
+
+/// Same as [backend] but records the dispatched models in the given history,
+/// which is also served on endpoint http://0.0.0.0:9001/model/history?from=<seq>&to=<seq>.
+/// Both `from` and `to` are optional; the response is a JSON array of [streamer::Emission].
+pub fn backend_with_history(history: Arc<Mutex<History>>) -> (BoxedPoints, BoxedWrite) {
+    let config = ServiceConfig {
+        history_capacity: history.lock().unwrap().capacity(),
+        ..Default::default()
+    };
+    backend_with_config(history, Arc::new(Mutex::new(config)))
+}
+
+/// Same as [backend_with_history] with settings which can be changed on endpoint http://0.0.0.0:9001/admin/config,
+/// see [apply_config]. The streamer applies [ServiceConfig::emit_every] when it is built with
+/// [Streamer::with_service_config](crate::Streamer::with_service_config) on the same settings.
+pub fn backend_with_config(
+    history: Arc<Mutex<History>>,
+    config: Arc<Mutex<ServiceConfig>>,
+) -> (BoxedPoints, BoxedWrite) {
     let (point_producer, point_receiver) = mpsc::channel::<String>();
     let (model_producer, model_receiver) = mpsc::channel::<String>();
-    thread::spawn(move || start_server(point_producer, model_receiver));
-    streamer::channels(point_receiver, model_producer)
+    thread::spawn(move || {
+        let port = env::var("PORT").unwrap_or(String::from("9001"));
+        let endpoint = format!("0.0.0.0:{}", port);
+        let server = TcpListener::bind(endpoint).unwrap();
+        start_server(
+            server,
+            point_producer,
+            model_receiver,
+            history,
+            config,
+            None,
+        )
+    });
+    let (points, write) = streamer::channels(point_receiver, model_producer);
+    (Box::new(points), Box::new(write))
+}
+
+/// The point source and the model sink of a pipeline served by [backend_routed].
+pub struct Route {
+    point_receiver: Receiver<String>,
+    model_producer: Sender<String>,
+}
+
+impl Route {
+    /// Gets the point source and the model sink of the pipeline, e.g. to build it in a [streamer::Supervisor].
+    pub fn channels(self) -> (BoxedPoints, BoxedWrite) {
+        let (points, write) = streamer::channels(self.point_receiver, self.model_producer);
+        (Box::new(points), Box::new(write))
+    }
+}
+
+/// The endpoints of a pipeline served by [backend_routed].
+struct Endpoints {
+    point_producer: Sender<String>,
+    peers: Peers,
+}
+
+/// Starts a backend on `listener` for the named pipelines of a [streamer::Supervisor]: the pipeline `<name>` receives points
+/// on endpoint ws://<addr>/ws/<name>/points and dispatches models on endpoint ws://<addr>/ws/<name>/models.
+/// Connections to other endpoints are closed.
+///
+/// Routed sources block until a point is received, thus the supervisor should have a thread per pipeline.
+/// ```no_run
+/// use std::net::TcpListener;
+///
+/// use fluent_data::{algorithm::AlgoConfig, space, Algo, Model, service, Streamer};
+/// use fluent_data::streamer::{Pipeline, Supervisor};
+///
+/// let listener = TcpListener::bind("0.0.0.0:9001").unwrap();
+/// let routes = service::backend_routed(listener, &["north", "south"]);
+/// let mut supervisor = Supervisor::new(AlgoConfig::default(), routes.len());
+/// for (name, route) in routes {
+///     supervisor.add(&name, move |config| {
+///         let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+///         let (points, write) = route.channels();
+///         Pipeline::new(algo, Model::new(space::euclid_dist), Streamer::new(points, write))
+///     });
+/// }
+/// supervisor.run();
+/// ```
+pub fn backend_routed(listener: TcpListener, names: &[&str]) -> BTreeMap<String, Route> {
+    let mut routes = BTreeMap::new();
+    let mut endpoints = BTreeMap::new();
+    for name in names {
+        let (point_producer, point_receiver) = mpsc::channel::<String>();
+        let (model_producer, model_receiver) = mpsc::channel::<String>();
+        let peers: Peers = Arc::new(Mutex::new(vec![]));
+        start_dispatcher(peers.clone(), model_receiver, None);
+        let route = Route {
+            point_receiver,
+            model_producer,
+        };
+        routes.insert(name.to_string(), route);
+        endpoints.insert(
+            name.to_string(),
+            Endpoints {
+                point_producer,
+                peers,
+            },
+        );
+    }
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let (path, websocket) = get_websocket(stream);
+            let endpoint = path
+                .rsplit_once('/')
+                .and_then(|(route, endpoint)| Some((route.rsplit_once("/ws/")?.1, endpoint)))
+                .and_then(|(name, endpoint)| Some((endpoints.get(name)?, endpoint)));
+            match endpoint {
+                Some((endpoints, "points")) => {
+                    handle_point_receiver(websocket, endpoints.point_producer.clone())
+                }
+                Some((endpoints, "models")) => {
+                    handle_model_producer(websocket, endpoints.peers.clone())
+                }
+                _ => {}
+            }
+        }
+    });
+    routes
+}
+
+/// Starts a backend that accepts connections on the Unix domain socket at `path`, for low latency local clients.
+///
+/// Each connection sends points as newline delimited JSON and receives each model on a line.
+/// Lines that are not JSON, or that are JSON scalars, are answered on the same connection with an error message
+/// `{"type":"error","error":"<reason>"}` and are not fitted; blank lines are ignored.
+/// As for [backend], other lines that are not points, e.g. `{}`, are only detected once parsed,
+/// thus the streamer should be run with [Streamer::run_with_drop_sink](crate::Streamer::run_with_drop_sink).
+/// The socket file is removed when the returned writer is dropped, i.e. when the streamer stops.
+/// A socket file left by a process that was killed is removed before binding,
+/// but binding fails if another backend still listens on it or if the path is not a socket.
+/// ```no_run
+/// use std::path::Path;
+///
+/// use fluent_data::{Algo, Model, space, Streamer, service};
+///
+/// let algo = Algo::new(space::euclid_dist, space::real_combine);
+/// let mut model = Model::new(space::euclid_dist);
+/// let (points, write) = service::backend_uds(Path::new("/tmp/fluent_data.sock")).unwrap();
+/// let streamer = Streamer::new(points, write);
+/// Streamer::run_with_drop_sink(streamer, algo, &mut model, |record, reason| eprintln!("{:?}: {}", reason, record)).unwrap();
+/// ```
+#[cfg(unix)]
+pub fn backend_uds(path: &Path) -> Result<(BoxedPoints, BoxedWrite), Box<dyn Error>> {
+    remove_stale_socket(path)?;
+    let listener = UnixListener::bind(path)?;
+    let socket_file = SocketFile(path.to_path_buf());
+    let (point_producer, point_receiver) = mpsc::channel::<String>();
+    let peers: Arc<Mutex<Vec<UnixStream>>> = Arc::new(Mutex::new(vec![]));
+    let server_peers = peers.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => handle_uds_connection(stream, &server_peers, point_producer.clone()),
+                Err(reason) => eprintln!("{}", reason),
+            }
+        }
+    });
+    let points = point_receiver.into_iter().map(Ok);
+    let write = move |model: String| {
+        // the socket file lives as long as the writer
+        let _socket_file = &socket_file;
+        let line = format!("{}\n", model);
+        let mut peers = peers.lock().unwrap();
+        peers.retain_mut(|peer| peer.write_all(line.as_bytes()).is_ok());
+        Ok(())
+    };
+    Ok((Box::new(points), Box::new(write)))
+}
+
+/// Removes the socket file at `path` if no process listens on it anymore.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> Result<(), Box<dyn Error>> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if UnixStream::connect(path).is_ok() {
+                return Err(format!("{}: socket already in use", path.display()).into());
+            }
+            fs::remove_file(path)?;
+            Ok(())
+        }
+        // binding fails on other files
+        _ => Ok(()),
+    }
+}
+
+/// The file of a Unix domain socket, which is removed when dropped.
+#[cfg(unix)]
+struct SocketFile(PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(reason) = fs::remove_file(&self.0) {
+            eprintln!("{}: {}", self.0.display(), reason);
+        }
+    }
+}
+
+/// Registers the connection for receiving models and sends the points it reads to the algorithm.
+#[cfg(unix)]
+fn handle_uds_connection(
+    stream: UnixStream,
+    peers: &Mutex<Vec<UnixStream>>,
+    point_producer: Sender<String>,
+) {
+    match stream.try_clone() {
+        Ok(peer) => peers.lock().unwrap().push(peer),
+        Err(reason) => {
+            eprintln!("{}", reason);
+            return;
+        }
+    }
+    thread::spawn(move || {
+        for (line, record) in BufReader::new(&stream).lines().enumerate() {
+            let record = match record {
+                Ok(record) => record,
+                Err(reason) => {
+                    eprintln!("{}", reason);
+                    break;
+                }
+            };
+            if record.trim().is_empty() {
+                continue;
+            }
+            let (record, error) = check_record(line + 1, record);
+            if let Some(reason) = error {
+                let frame = serde_json::to_string(&Envelope::<Value>::error(reason)).unwrap();
+                if (&stream)
+                    .write_all(format!("{}\n", frame).as_bytes())
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            if let Some(record) = record {
+                if point_producer.send(record).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Starts a backend on `addr` that accepts points posted to `POST /points` and streams models as Server-Sent Events
+/// on `GET /models`, e.g. for browser dashboards.
+///
+/// The body of a post holds one point per line; the answer is `202 Accepted`, or `400 Bad Request` with an error message
+/// `{"type":"error","error":"<reasons>"}` listing the lines that are not JSON, which are not fitted.
+/// Each model is sent to the connected clients as one `data:` event of a `text/event-stream` response.
+/// Responses allow any origin, so that a dashboard served elsewhere can connect.
+///
+/// Each request is handled on its own thread and is dropped if it is not wholly received within 10 seconds.
+/// A request which head exceeds 8 KiB is answered with `431 Request Header Fields Too Large`,
+/// and a request which body exceeds 1 MiB with `413 Payload Too Large`.
+/// Models are sent by a dispatcher thread, which drops the clients that do not read them within 1 second,
+/// so that a stalled client does not hold up the fitting nor the other clients.
+/// Fails if the address cannot be bound.
+/// ```no_run
+/// use fluent_data::{Algo, Model, space, Streamer, service};
+///
+/// let algo = Algo::new(space::euclid_dist, space::real_combine);
+/// let mut model = Model::new(space::euclid_dist);
+/// let (points, write) = service::backend_sse("0.0.0.0:9002").unwrap();
+/// Streamer::run(Streamer::new(points, write), algo, &mut model).unwrap();
+/// ```
+/// Models are then received in the browser with `new EventSource("http://localhost:9002/models")`.
+pub fn backend_sse(addr: impl ToSocketAddrs) -> Result<(BoxedPoints, BoxedWrite), Box<dyn Error>> {
+    Ok(backend_sse_on(TcpListener::bind(addr)?))
+}
+
+/// Same as [backend_sse] on an already bound listener, e.g. on an ephemeral port.
+/// ```no_run
+/// use std::net::TcpListener;
+///
+/// use fluent_data::service;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap();
+/// let (points, write) = service::backend_sse_on(listener);
+/// ```
+pub fn backend_sse_on(listener: TcpListener) -> (BoxedPoints, BoxedWrite) {
+    let (point_producer, point_receiver) = mpsc::channel::<String>();
+    let (model_producer, model_receiver) = mpsc::channel::<String>();
+    let peers: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(vec![]));
+    start_sse_dispatcher(peers.clone(), model_receiver);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let (peers, point_producer) = (peers.clone(), point_producer.clone());
+                    thread::spawn(move || handle_sse_request(stream, &peers, &point_producer));
+                }
+                Err(reason) => eprintln!("{}", reason),
+            }
+        }
+    });
+    let (points, write) = streamer::channels(point_receiver, model_producer);
+    (Box::new(points), Box::new(write))
+}
+
+/// Starts the dispatcher that sends models as Server-Sent Events to the clients of [backend_sse].
+fn start_sse_dispatcher(peers: Arc<Mutex<Vec<TcpStream>>>, model_receiver: Receiver<String>) {
+    thread::spawn(move || {
+        for model in model_receiver {
+            let event = sse_event(&model);
+            let mut peers = peers.lock().unwrap();
+            peers.retain_mut(|peer| peer.write_all(event.as_bytes()).is_ok());
+        }
+    });
+}
+
+/// Formats a model as a Server-Sent Event, each line of the model being a `data:` line.
+fn sse_event(model: &str) -> String {
+    let mut event: String = model
+        .lines()
+        .map(|line| format!("data: {}\n", line))
+        .collect();
+    event.push('\n');
+    event
+}
+
+/// Answers a request of [backend_sse]: registers the client for receiving models or sends the posted points to the algorithm.
+fn handle_sse_request(
+    mut stream: TcpStream,
+    peers: &Mutex<Vec<TcpStream>>,
+    point_producer: &Sender<String>,
+) {
+    let (head, body) = match read_request(&stream, Some(Instant::now() + SSE_READ_TIMEOUT)) {
+        Ok(request) => request,
+        Err(RequestError::Io(reason)) => {
+            eprintln!("{}", reason);
+            return;
+        }
+        Err(reason) => {
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+                reason.status()
+            );
+            if let Err(reason) = stream.write_all(response.as_bytes()) {
+                eprintln!("{}", reason);
+            }
+            return;
+        }
+    };
+    let request_line = head.lines().next().unwrap_or_default();
+    let response = if request_line.starts_with("GET /models ") {
+        let headers = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nAccess-Control-Allow-Origin: *\r\nConnection: keep-alive\r\n\r\n";
+        if stream.set_write_timeout(Some(SSE_WRITE_TIMEOUT)).is_ok()
+            && stream.write_all(headers.as_bytes()).is_ok()
+        {
+            peers.lock().unwrap().push(stream);
+        }
+        return;
+    } else if request_line.starts_with("POST /points ") {
+        let mut errors = vec![];
+        for (line, record) in body.lines().enumerate() {
+            if record.trim().is_empty() {
+                continue;
+            }
+            let (record, error) = check_record(line + 1, String::from(record));
+            errors.extend(error);
+            if let Some(record) = record {
+                if let Err(reason) = point_producer.send(record) {
+                    eprintln!("{}", reason);
+                }
+            }
+        }
+        if errors.is_empty() {
+            String::from("HTTP/1.1 202 Accepted\r\nContent-Length: 0\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n")
+        } else {
+            let reasons: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            let frame =
+                serde_json::to_string(&Envelope::<Value>::error(reasons.join("; "))).unwrap();
+            format!(
+                "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+                frame.len(),
+                frame
+            )
+        }
+    } else {
+        String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+    };
+    if let Err(reason) = stream.write_all(response.as_bytes()) {
+        eprintln!("{}", reason);
+    }
+}
+
+/// The settings of the service that can be changed while it runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceConfig {
+    /// A model is emitted every `emit_every` fitted points, 1 by default,
+    /// see [Streamer::with_service_config](crate::Streamer::with_service_config).
+    pub emit_every: usize,
+    /// Number of models kept in the history, at most 10000.
+    pub history_capacity: usize,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            emit_every: 1,
+            history_capacity: HISTORY_CAPACITY,
+        }
+    }
+}
+
+/// The outcome of [apply_config]: the settings that were applied with their new value,
+/// and the settings that were rejected with the reason. No setting is applied when one is rejected.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ConfigUpdate {
+    pub applied: BTreeMap<String, Value>,
+    pub rejected: BTreeMap<String, String>,
+}
+
+/// Applies the settings of a JSON object, e.g. `{"emit_every":10,"history_capacity":100}`.
+/// Settings that can only be set when the service starts, like `port` or `dist`,
+/// and unknown or invalid settings are rejected. The update is atomic: the settings are applied only if none is rejected.
+/// Fails if `update` is not a JSON object.
+/// ```
+/// use std::sync::Mutex;
+///
+/// use fluent_data::{service::{self, ServiceConfig}, streamer::History};
+///
+/// let config = Mutex::new(ServiceConfig::default());
+/// let history = Mutex::new(History::new(60));
+/// let update = service::apply_config(r#"{"emit_every":5,"port":80}"#, &config, &history).unwrap();
+/// assert!(update.rejected.contains_key("port"));
+/// assert_eq!(1, config.lock().unwrap().emit_every);
+/// service::apply_config(r#"{"emit_every":5}"#, &config, &history).unwrap();
+/// assert_eq!(5, config.lock().unwrap().emit_every);
+/// ```
+pub fn apply_config(
+    update: &str,
+    config: &Mutex<ServiceConfig>,
+    history: &Mutex<History>,
+) -> Result<ConfigUpdate, Box<dyn Error>> {
+    let settings: Map<String, Value> = serde_json::from_str(update)?;
+    let mut config = config.lock().unwrap();
+    let mut updated = config.clone();
+    let mut result = ConfigUpdate::default();
+    for (key, value) in settings {
+        let count = value.as_u64().map(|v| v as usize);
+        let rejection = match (key.as_str(), count) {
+            ("emit_every", Some(count)) if count > 0 => {
+                updated.emit_every = count;
+                None
+            }
+            ("history_capacity", Some(count)) if count <= MAX_HISTORY_CAPACITY => {
+                updated.history_capacity = count;
+                None
+            }
+            ("emit_every", _) => Some(String::from("expected a positive integer")),
+            ("history_capacity", _) => Some(format!(
+                "expected an integer up to {}",
+                MAX_HISTORY_CAPACITY
+            )),
+            (key, _) if RESTART_KEYS.contains(&key) => Some(String::from("requires a restart")),
+            _ => Some(String::from("unknown setting")),
+        };
+        match rejection {
+            Some(reason) => {
+                result.rejected.insert(key, reason);
+            }
+            None => {
+                result.applied.insert(key, value);
+            }
+        }
+    }
+    if !result.rejected.is_empty() {
+        result.applied.clear();
+        return Ok(result);
+    }
+    if updated.history_capacity != config.history_capacity {
+        history
+            .lock()
+            .unwrap()
+            .set_capacity(updated.history_capacity);
+    }
+    *config = updated;
+    Ok(result)
+}
+
+/// Applies the settings of a JSON file with [apply_config], then applies them again each time the file is modified.
+/// Fails if the file cannot be read or if some settings are rejected; later errors are only logged.
+pub fn watch_config(
+    path: PathBuf,
+    config: Arc<Mutex<ServiceConfig>>,
+    history: Arc<Mutex<History>>,
+) -> Result<(), Box<dyn Error>> {
+    let update = apply_config(&fs::read_to_string(&path)?, &config, &history)?;
+    if !update.rejected.is_empty() {
+        let rejected = serde_json::to_string(&update.rejected)?;
+        return Err(format!("{}: rejected settings {}", path.display(), rejected).into());
+    }
+    let mut modified = fs::metadata(&path)?.modified()?;
+    thread::spawn(move || loop {
+        thread::sleep(CONFIG_POLL);
+        match fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(time) if time != modified => {
+                modified = time;
+                let update = fs::read_to_string(&path)
+                    .map_err(|e| e.into())
+                    .and_then(|update| apply_config(&update, &config, &history));
+                match update {
+                    Ok(update) if !update.rejected.is_empty() => {
+                        eprintln!(
+                            "{}: rejected settings {:?}",
+                            path.display(),
+                            update.rejected
+                        )
+                    }
+                    Err(reason) => eprintln!("{}: {}", path.display(), reason),
+                    _ => {}
+                }
+            }
+            Err(reason) => eprintln!("{}: {}", path.display(), reason),
+            _ => {}
+        }
+    });
+    Ok(())
 }
 
 /// Starts the model dispatcher and the websocket server.
-fn start_server(point_producer: Sender<String>, model_receiver: Receiver<String>) {
+fn start_server(
+    server: TcpListener,
+    point_producer: Sender<String>,
+    model_receiver: Receiver<String>,
+    history: Arc<Mutex<History>>,
+    config: Arc<Mutex<ServiceConfig>>,
+    connections: Option<Arc<Connections>>,
+) {
     let peers: Peers = Arc::new(Mutex::new(vec![]));
-    start_dispatcher(peers.clone(), model_receiver);
-    start_websockets(peers.clone(), point_producer);
+    start_dispatcher(peers.clone(), model_receiver, Some(history.clone()));
+    start_websockets(server, peers, point_producer, history, config, connections);
+}
+
+/// Connections of a server that can be stopped, see `spawn_for_tests`.
+#[derive(Default)]
+#[cfg_attr(not(feature = "testing"), allow(dead_code))]
+struct Connections {
+    stopped: AtomicBool,
+    /// The connections of the point senders, which are shut down when the server stops.
+    points: Mutex<Vec<TcpStream>>,
 }
 
 /// Starts the server that will accept websocket connections and listen for points.
-fn start_websockets(peers: Peers, point_producer: Sender<String>) {
-    let port = env::var("PORT").unwrap_or(String::from("9001"));
-    let endpoint = format!("0.0.0.0:{}", port);
-    let server = TcpListener::bind(endpoint).unwrap();
+fn start_websockets(
+    server: TcpListener,
+    peers: Peers,
+    point_producer: Sender<String>,
+    history: Arc<Mutex<History>>,
+    config: Arc<Mutex<ServiceConfig>>,
+    connections: Option<Arc<Connections>>,
+) {
     for stream in server.incoming() {
+        if let Some(connections) = &connections {
+            if connections.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        if let Ok(stream) = &stream {
+            match get_http_request(stream) {
+                Some(HttpRequest::History(path)) => {
+                    handle_history(stream, &path, &history);
+                    continue;
+                }
+                Some(HttpRequest::Config) => {
+                    handle_config(stream, &config, &history);
+                    continue;
+                }
+                None => {}
+            }
+        }
         let (path, websocket) = get_websocket(stream);
         if path.ends_with("/ws/points") {
+            if let Some(connections) = &connections {
+                if let Ok(stream) = websocket.get_ref().try_clone() {
+                    connections.points.lock().unwrap().push(stream);
+                }
+            }
             handle_point_receiver(websocket, point_producer.clone());
         } else if path.ends_with("/ws/models") {
             handle_model_producer(websocket, peers.clone());
@@ -86,6 +725,214 @@ fn get_websocket(stream: Result<TcpStream, std::io::Error>) -> (String, WebSocke
     (path, websocket)
 }
 
+/// A plain HTTP request, as opposed to a websocket handshake.
+enum HttpRequest {
+    /// A model history request with its path.
+    History(String),
+    /// A settings update.
+    Config,
+}
+
+/// Gets the plain HTTP request for the model history or the settings, without consuming the request.
+fn get_http_request(stream: &TcpStream) -> Option<HttpRequest> {
+    let mut buf = [0; 1024];
+    let mut n = stream.peek(&mut buf).ok()?;
+    // the request line may come in several packets
+    while n > 0 && n < buf.len() && !buf[..n].contains(&b'\n') {
+        thread::sleep(Duration::from_millis(1));
+        n = stream.peek(&mut buf).ok()?;
+    }
+    let request = std::str::from_utf8(&buf[..n]).ok()?;
+    if request.starts_with("POST /admin/config ") {
+        return Some(HttpRequest::Config);
+    }
+    let path = request.strip_prefix("GET ")?.split(' ').next()?;
+    if path.starts_with("/model/history") {
+        Some(HttpRequest::History(path.to_string()))
+    } else {
+        None
+    }
+}
+
+/// Answers a model history request.
+fn handle_history(mut stream: &TcpStream, path: &str, history: &Mutex<History>) {
+    let mut buf = [0; 1024];
+    let response = match stream.read(&mut buf) {
+        Ok(_) => get_history_response(path, &history.lock().unwrap()),
+        Err(reason) => format!("HTTP/1.1 400 Bad Request\r\n\r\n{}", reason),
+    };
+    if let Err(reason) = stream.write_all(response.as_bytes()) {
+        eprintln!("{}", reason);
+    }
+}
+
+/// Answers a settings update with the applied and rejected settings, see [ConfigUpdate].
+fn handle_config(mut stream: &TcpStream, config: &Mutex<ServiceConfig>, history: &Mutex<History>) {
+    let response = match read_body(stream) {
+        Ok(body) => get_config_response(&body, config, history),
+        Err(reason) => format!("HTTP/1.1 {}\r\n\r\n{}", reason.status(), reason),
+    };
+    if let Err(reason) = stream.write_all(response.as_bytes()) {
+        eprintln!("{}", reason);
+    }
+}
+
+/// Reads the body of a plain HTTP request, which length is given by the `Content-Length` header.
+fn read_body(stream: &TcpStream) -> Result<String, RequestError> {
+    read_request(stream, None).map(|(_, body)| body)
+}
+
+/// Why a plain HTTP request could not be read, see [read_request].
+#[derive(Debug)]
+enum RequestError {
+    /// The connection failed, was closed, or the request was not received before the deadline.
+    Io(io::Error),
+    /// The head of the request exceeds [MAX_REQUEST_HEAD].
+    HeadTooLarge,
+    /// The body of the request exceeds [MAX_REQUEST_BODY].
+    BodyTooLarge,
+}
+
+impl RequestError {
+    /// The status of the response to the request.
+    fn status(&self) -> &'static str {
+        match self {
+            RequestError::Io(_) => "400 Bad Request",
+            RequestError::HeadTooLarge => "431 Request Header Fields Too Large",
+            RequestError::BodyTooLarge => "413 Payload Too Large",
+        }
+    }
+}
+
+impl From<io::Error> for RequestError {
+    fn from(reason: io::Error) -> Self {
+        RequestError::Io(reason)
+    }
+}
+
+impl Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Io(reason) => write!(f, "{}", reason),
+            RequestError::HeadTooLarge => {
+                write!(f, "the request head exceeds {} bytes", MAX_REQUEST_HEAD)
+            }
+            RequestError::BodyTooLarge => {
+                write!(f, "the request body exceeds {} bytes", MAX_REQUEST_BODY)
+            }
+        }
+    }
+}
+
+/// Reads a plain HTTP request, as its head and its body, of at most [MAX_REQUEST_HEAD] and [MAX_REQUEST_BODY] bytes.
+/// When a deadline is given, the whole request must be received before it.
+fn read_request(
+    mut stream: &TcpStream,
+    deadline: Option<Instant>,
+) -> Result<(String, String), RequestError> {
+    let mut request = vec![];
+    let mut buf = [0; 1024];
+    let mut read = |request: &mut Vec<u8>| -> Result<(), RequestError> {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            }
+            stream.set_read_timeout(Some(remaining))?;
+        }
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        request.extend_from_slice(&buf[..n]);
+        Ok(())
+    };
+    let header_end = loop {
+        read(&mut request)?;
+        match request.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(end) if end + 4 > MAX_REQUEST_HEAD => return Err(RequestError::HeadTooLarge),
+            Some(end) => break end + 4,
+            None if request.len() > MAX_REQUEST_HEAD => return Err(RequestError::HeadTooLarge),
+            None => {}
+        }
+    };
+    let head = String::from_utf8_lossy(&request[..header_end]).into_owned();
+    let length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_REQUEST_BODY {
+        return Err(RequestError::BodyTooLarge);
+    }
+    while request.len() < header_end + length {
+        read(&mut request)?;
+    }
+    let body = String::from_utf8(request[header_end..header_end + length].to_vec())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((head, body))
+}
+
+/// Builds the HTTP response of a settings update: `200 OK` if all settings were applied,
+/// `400 Bad Request` otherwise, in which case none was applied.
+fn get_config_response(
+    body: &str,
+    config: &Mutex<ServiceConfig>,
+    history: &Mutex<History>,
+) -> String {
+    let (status, body) = match apply_config(body, config, history) {
+        Ok(update) if update.rejected.is_empty() => {
+            ("200 OK", serde_json::to_string(&update).unwrap())
+        }
+        Ok(update) => ("400 Bad Request", serde_json::to_string(&update).unwrap()),
+        Err(reason) => ("400 Bad Request", reason.to_string()),
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Builds the HTTP response with the recorded models in the `from..to` sequence range of the query.
+fn get_history_response(path: &str, history: &History) -> String {
+    let range = Url::parse(&format!("http://localhost{}", path))
+        .map_err(|e| e.to_string())
+        .and_then(|url| {
+            let mut from = 0;
+            let mut to = u64::MAX;
+            for (key, value) in url.query_pairs() {
+                let seq = value
+                    .parse::<u64>()
+                    .map_err(|e| format!("{}: {}", key, e))?;
+                match key.as_ref() {
+                    "from" => from = seq,
+                    "to" => to = seq,
+                    _ => return Err(format!("unknown parameter {}", key)),
+                }
+            }
+            Ok((from, to))
+        });
+    match range {
+        Ok((from, to)) => {
+            let emissions: Vec<_> = history.range(from, to).collect();
+            let body = serde_json::to_string(&emissions).unwrap();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+        Err(reason) => format!(
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            reason.len(),
+            reason
+        ),
+    }
+}
+
 /// Registers that the peer ask for receiving models on dispatch.
 fn handle_model_producer(websocket: WebSocket<TcpStream>, peers: Peers) {
     let mut peers = peers.lock().unwrap();
@@ -94,34 +941,51 @@ fn handle_model_producer(websocket: WebSocket<TcpStream>, peers: Peers) {
 
 /// Handles point listening and send them to the algorithm using the `point_producer` channel.
 fn handle_point_receiver(mut websocket: WebSocket<TcpStream>, point_producer: Sender<String>) {
-    thread::spawn(move || loop {
-        let msg = websocket.read_message();
-        match msg {
-            Ok(message) => {
-                if !read_point(message, &point_producer) {
+    thread::spawn(move || {
+        let mut line = 0;
+        loop {
+            let msg = websocket.read_message();
+            line += 1;
+            match msg {
+                Ok(message) => {
+                    if !read_point(message, line, &point_producer, &mut websocket) {
+                        break;
+                    }
+                }
+                Err(reason) => {
+                    eprint!("{}", reason);
                     break;
                 }
-            }
-            Err(reason) => {
-                eprint!("{}", reason);
-                break;
-            }
-        };
+            };
+        }
     });
 }
 
 /// Gets the point and send it to the algorithm.
-fn read_point(message: Message, point_producer: &Sender<String>) -> bool {
+/// Messages that cannot be parsed are answered with an error frame;
+/// `line` is the position of the message in the connection.
+fn read_point(
+    message: Message,
+    line: usize,
+    point_producer: &Sender<String>,
+    websocket: &mut WebSocket<TcpStream>,
+) -> bool {
     match message {
         Message::Text(txt) => {
-            match point_producer.send(txt) {
-                Err(reason) => eprintln!("{:#?}", reason),
-                _ => {}
+            let (record, error) = check_record(line, txt);
+            if let Some(reason) = error {
+                send_error(websocket, &reason.to_string());
+            }
+            if let Some(record) = record {
+                match point_producer.send(record) {
+                    Err(reason) => eprintln!("{:#?}", reason),
+                    _ => {}
+                }
             }
             true
         }
         Message::Binary(_) => {
-            eprintln!("unsupported binary message.");
+            send_error(websocket, "unsupported binary message");
             true
         }
         Message::Close(_) => false,
@@ -129,10 +993,76 @@ fn read_point(message: Message, point_producer: &Sender<String>) -> bool {
     }
 }
 
+/// Checks that a message can be parsed before it is sent to the algorithm; returns the record to send, if any,
+/// and the error to answer, if any.
+/// A record must be a JSON array or object, whether it is a point is only known once parsed by the streamer.
+///
+/// The elements of a batch that are not arrays or objects are removed and reported together, by index, in a single error;
+/// the other elements that are not points are skipped by the streamer, see [streamer::Streamer::with_batches].
+fn check_record(line: usize, record: String) -> (Option<String>, Option<StreamError>) {
+    let elements = match streamer::split_batch(&record) {
+        Some(elements) => elements,
+        None => {
+            return match check_point(line, &record) {
+                Ok(()) => (Some(record), None),
+                Err(reason) => (None, Some(reason)),
+            }
+        }
+    };
+    let mut valid = vec![];
+    let mut errors = vec![];
+    for (index, element) in elements.into_iter().enumerate() {
+        match check_point(line, &element) {
+            Ok(()) => valid.push(element),
+            Err(reason) => errors.push((index, reason)),
+        }
+    }
+    let record = if valid.is_empty() {
+        None
+    } else {
+        Some(format!("[{}]", valid.join(",")))
+    };
+    let error = if errors.is_empty() {
+        None
+    } else {
+        Some(StreamError::BatchError { line, errors })
+    };
+    (record, error)
+}
+
+/// Checks that a record is a JSON array or object.
+fn check_point(line: usize, record: &str) -> Result<(), StreamError> {
+    match streamer::parse_point::<Value>(line, record)? {
+        Value::Array(_) | Value::Object(_) => Ok(()),
+        _ => Err(StreamError::ParseError {
+            line,
+            offset: 0,
+            field: None,
+            message: String::from("expected an array or an object"),
+        }),
+    }
+}
+
+/// Sends an error message `{"type":"error","error":"<reason>"}` to the client, see [Envelope::Error].
+fn send_error(websocket: &mut WebSocket<TcpStream>, reason: &str) {
+    let frame = serde_json::to_string(&Envelope::<Value>::error(reason)).unwrap();
+    if let Err(reason) = websocket.write_message(Message::Text(frame)) {
+        eprintln!("{}", reason);
+    }
+}
+
 /// Starts the dispatcher that will handle peers which asked for receiving models on dispatch.
-fn start_dispatcher(peers: Peers, model_receiver: Receiver<String>) {
+/// Models are recorded in the history, if any.
+fn start_dispatcher(
+    peers: Peers,
+    model_receiver: Receiver<String>,
+    history: Option<Arc<Mutex<History>>>,
+) {
     thread::spawn(move || {
         for msg in model_receiver {
+            if let Some(history) = &history {
+                history.lock().unwrap().push(msg.clone());
+            }
             let mut peers = peers.lock().unwrap();
             peers.retain_mut(|peer| send_model(peer, msg.clone()));
         }
@@ -152,11 +1082,205 @@ fn send_model(peer: &mut WebSocket<TcpStream>, msg: String) -> bool {
     }
 }
 
+/// Options of a service started by [spawn_for_tests].
+#[cfg(feature = "testing")]
+#[derive(Clone, Debug)]
+pub struct TestServiceOptions {
+    /// The configuration of the algorithm, which fits real points.
+    pub config: AlgoConfig,
+    /// Number of models kept in the history.
+    pub history_capacity: usize,
+}
+
+#[cfg(feature = "testing")]
+impl Default for TestServiceOptions {
+    fn default() -> Self {
+        Self {
+            config: AlgoConfig::default(),
+            history_capacity: HISTORY_CAPACITY,
+        }
+    }
+}
+
+/// Starts the full service in the current process on an ephemeral port of the loopback interface,
+/// e.g. for end-to-end tests of websocket clients. Requires the `testing` feature.
+///
+/// The service fits real points with the euclidean distance. It stops when the handle is dropped:
+/// the port is released and the connections of point senders are shut down.
+/// ```
+/// use std::time::Duration;
+///
+/// use fluent_data::service::{self, TestServiceOptions};
+///
+/// let service = service::spawn_for_tests(TestServiceOptions::default());
+/// service.inject("[1.0]");
+/// let models = service.await_emissions(1, Duration::from_secs(5)).unwrap();
+/// assert_eq!(r#"[{"center":[1.0],"radius":null,"weight":0.0}]"#, models[0]);
+/// ```
+#[cfg(feature = "testing")]
+pub fn spawn_for_tests(options: TestServiceOptions) -> TestServiceHandle {
+    let server = TcpListener::bind("127.0.0.1:0").expect("cannot bind an ephemeral port");
+    let addr = server.local_addr().expect("cannot get the bound address");
+    let connections = Arc::new(Connections::default());
+    let history = Arc::new(Mutex::new(History::new(options.history_capacity)));
+    let config = Arc::new(Mutex::new(ServiceConfig {
+        history_capacity: options.history_capacity,
+        ..Default::default()
+    }));
+    let (point_producer, point_receiver) = mpsc::channel::<String>();
+    let (model_producer, model_receiver) = mpsc::channel::<String>();
+    let (emission_producer, emissions) = mpsc::channel::<String>();
+    let server_producer = point_producer.clone();
+    let server_connections = connections.clone();
+    let streamer_config = config.clone();
+    let server_thread = thread::spawn(move || {
+        start_server(
+            server,
+            server_producer,
+            model_receiver,
+            history,
+            config,
+            Some(server_connections),
+        )
+    });
+    let streamer_thread = thread::spawn(move || {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(options.config);
+        let mut model = Model::new(space::euclid_dist);
+        let (points, mut write) = streamer::channels(point_receiver, model_producer);
+        let write = move |model: String| {
+            // the handle may not await emissions
+            let _ = emission_producer.send(model.clone());
+            write(model)
+        };
+        let streamer = Streamer::new(points, write)
+            .with_batches()
+            .with_service_config(streamer_config);
+        if let Err(reason) = Streamer::run_with_drop_sink(streamer, algo, &mut model, |_, _| {}) {
+            eprintln!("{}", reason);
+        }
+    });
+    TestServiceHandle {
+        addr,
+        point_producer: Some(point_producer),
+        emissions,
+        connections,
+        server_thread: Some(server_thread),
+        streamer_thread: Some(streamer_thread),
+    }
+}
+
+/// A service started by [spawn_for_tests], which stops when dropped.
+#[cfg(feature = "testing")]
+pub struct TestServiceHandle {
+    addr: SocketAddr,
+    point_producer: Option<Sender<String>>,
+    emissions: Receiver<String>,
+    connections: Arc<Connections>,
+    server_thread: Option<JoinHandle<()>>,
+    streamer_thread: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "testing")]
+impl TestServiceHandle {
+    /// The address the service is bound to.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The url of the websocket endpoint that receives points.
+    pub fn points_url(&self) -> String {
+        format!("ws://{}/ws/points", self.addr)
+    }
+
+    /// The url of the websocket endpoint that dispatches models.
+    pub fn models_url(&self) -> String {
+        format!("ws://{}/ws/models", self.addr)
+    }
+
+    /// The url of the model history endpoint.
+    pub fn history_url(&self) -> String {
+        format!("http://{}/model/history", self.addr)
+    }
+
+    /// The url of the endpoint that changes the settings of the service, see [apply_config].
+    pub fn config_url(&self) -> String {
+        format!("http://{}/admin/config", self.addr)
+    }
+
+    /// Sends a point record to the algorithm, as if it was received on the points endpoint.
+    pub fn inject(&self, point: &str) {
+        if let Some(point_producer) = &self.point_producer {
+            point_producer.send(point.to_string()).unwrap();
+        }
+    }
+
+    /// Waits for the next `count` models emitted by the service, whichever the endpoint the points came from.
+    /// Returns an error if they are not all emitted within `timeout`.
+    pub fn await_emissions(
+        &self,
+        count: usize,
+        timeout: Duration,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let deadline = Instant::now() + timeout;
+        let mut models = vec![];
+        while models.len() < count {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.emissions.recv_timeout(remaining) {
+                Ok(model) => models.push(model),
+                Err(reason) => {
+                    return Err(format!(
+                        "{} models received out of {}: {}",
+                        models.len(),
+                        count,
+                        reason
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(models)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl Drop for TestServiceHandle {
+    fn drop(&mut self) {
+        self.connections.stopped.store(true, Ordering::SeqCst);
+        // wakes the server up so that it sees it is stopped
+        let _ = TcpStream::connect(self.addr);
+        if let Some(server_thread) = self.server_thread.take() {
+            let _ = server_thread.join();
+        }
+        for stream in self.connections.points.lock().unwrap().drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        self.point_producer.take();
+        if let Some(streamer_thread) = self.streamer_thread.take() {
+            let _ = streamer_thread.join();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::thread;
+    use std::{
+        io::{self, Write},
+        net::{TcpListener, TcpStream},
+        sync::Mutex,
+        thread,
+        time::{Duration, Instant},
+    };
 
-    use crate::{algorithm::Algo, model::Model, service::backend, space, streamer::*};
+    use crate::{
+        algorithm::Algo,
+        model::Model,
+        service::{
+            backend, get_config_response, get_history_response, read_request, RequestError,
+            ServiceConfig,
+        },
+        space,
+        streamer::*,
+    };
     use tungstenite::{connect, Message};
     use url::Url;
 
@@ -186,4 +1310,93 @@ mod tests {
         models_socket.close(None).unwrap();
         points_socket.close(None).unwrap();
     }
+
+    #[test]
+    fn test_history_response() {
+        let mut history = History::new(3);
+        for i in 0..5 {
+            history.push(format!("[{}]", i));
+        }
+        let response = get_history_response("/model/history?from=3", &history);
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK"));
+        let emissions: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(2, emissions.as_array().unwrap().len());
+        assert_eq!(3, emissions[0]["seq"]);
+        assert_eq!("[3]", emissions[0]["model"]);
+        let response = get_history_response("/model/history?from=2&to=3", &history);
+        assert!(response.ends_with(r#""model":"[2]"}]"#));
+        let response = get_history_response("/model/history?from=x", &history);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn test_config_response() {
+        let config = Mutex::new(ServiceConfig::default());
+        let history = Mutex::new(History::new(3));
+        for i in 0..3 {
+            history.lock().unwrap().push(format!("[{}]", i));
+        }
+        let response = get_config_response(
+            r#"{"emit_every":4,"history_capacity":1}"#,
+            &config,
+            &history,
+        );
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        let expected = ServiceConfig {
+            emit_every: 4,
+            history_capacity: 1,
+        };
+        assert_eq!(expected, *config.lock().unwrap());
+        assert_eq!(1, history.lock().unwrap().range(0, u64::MAX).count());
+        let response = get_config_response(
+            r#"{"emit_every":2,"dist":"manhattan","emit_every_ms":5,"history_capacity":100000}"#,
+            &config,
+            &history,
+        );
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 400 Bad Request"));
+        let update: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "applied": {},
+                "rejected": {
+                    "dist": "requires a restart",
+                    "emit_every_ms": "unknown setting",
+                    "history_capacity": "expected an integer up to 10000",
+                },
+            }),
+            update
+        );
+        assert_eq!(expected, *config.lock().unwrap());
+        let response = get_config_response("[1]", &config, &history);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn test_read_request_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // a client that trickles its request
+        thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            for _ in 0..100 {
+                if stream.write_all(b"G").is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let start = Instant::now();
+        let read = read_request(&stream, Some(start + Duration::from_millis(200)));
+        match read {
+            Err(RequestError::Io(reason)) => assert!(matches!(
+                reason.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+            )),
+            _ => panic!("the request should time out"),
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
 }
@@ -1,6 +1,18 @@
 //! This module defines the necessary functions to run the algorithm for data points that belong to R^n.
 //!  - the Euclidian distance function
 //!  - the vectorial barycentre function
+//!
+//! Other distances are provided, like [weighted_euclid_dist], [mahalanobis_dist], [manhattan_dist], [minkowski_dist],
+//! [chebyshev_dist] and [cosine_dist],
+//! and distances can be blended by [blend_dist]. Geographic points are clustered by [haversine_dist]
+//! and [geo_combine].
+//!
+//! It also defines [QuantizedPoint], a representation of R^n points for models with many balls
+//! in high dimension, which stores centers with 8 bits per coordinate, see [Quantization].
+
+use std::mem;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// A point in R^n.
 pub type RealPoint = Vec<f64>;
@@ -16,18 +28,547 @@ pub fn euclid_dist(p1: &RealPoint, p2: &RealPoint) -> f64 {
         .sum()
 }
 
+/// Builds the square of the Euclidian distance in R^n where the square of the difference on each dimension
+/// is multiplied by the weight of the dimension, e.g. to balance features of different scales.
+///
+/// A weight of `1 / s²` makes a difference of `s` on the dimension count as a unit difference.
+/// Panics if the weights are negative, or when computing the distance between points which dimension
+/// differs from the number of weights.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// // temperatures in °C and pressures in Pa
+/// let dist = space::weighted_euclid_dist(vec![1., 1E-4]);
+/// assert_eq!(2., dist(&vec![20., 101300.], &vec![21., 101400.]));
+/// let algo = Algo::new(dist.clone(), space::real_combine);
+/// let model = Model::new(dist);
+/// ```
+pub fn weighted_euclid_dist(weights: Vec<f64>) -> impl Fn(&RealPoint, &RealPoint) -> f64 + Clone {
+    assert!(
+        weights.iter().all(|&w| w >= 0.),
+        "the weights of the dimensions must not be negative"
+    );
+    move |p1: &RealPoint, p2: &RealPoint| {
+        assert!(
+            p1.len() == weights.len() && p2.len() == weights.len(),
+            "points of dimension {} and {} do not match the {} weights",
+            p1.len(),
+            p2.len(),
+            weights.len()
+        );
+        p1.iter()
+            .zip(p2)
+            .zip(weights.iter())
+            .map(|((x1, x2), w)| {
+                let d = x1 - x2;
+                w * d * d
+            })
+            .sum()
+    }
+}
+
+/// Builds the square of the Mahalanobis distance in R^n for the given inverse covariance matrix:
+/// `(p1 - p2)ᵀ.inv_cov.(p1 - p2)`, e.g. for anisotropic clusters which are stretched along some directions.
+///
+/// The matrix should be symmetric positive definite; the identity gives [euclid_dist]
+/// and a diagonal matrix gives [weighted_euclid_dist]. Panics if the matrix is not square,
+/// or when computing the distance between points which dimension differs from the matrix size.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// // clusters stretched along the first diagonal
+/// let dist = space::mahalanobis_dist(vec![vec![1., -0.5], vec![-0.5, 1.]]);
+/// assert_eq!(1., dist(&vec![1., 1.], &vec![0., 0.]));
+/// assert_eq!(3., dist(&vec![1., -1.], &vec![0., 0.]));
+/// let algo = Algo::new(dist.clone(), space::real_combine);
+/// let model = Model::new(dist);
+/// ```
+pub fn mahalanobis_dist(inv_cov: Vec<Vec<f64>>) -> impl Fn(&RealPoint, &RealPoint) -> f64 + Clone {
+    let size = inv_cov.len();
+    assert!(
+        inv_cov.iter().all(|row| row.len() == size),
+        "the inverse covariance matrix must be square"
+    );
+    move |p1: &RealPoint, p2: &RealPoint| {
+        assert!(
+            p1.len() == size && p2.len() == size,
+            "points of dimension {} and {} do not match the {}x{} inverse covariance matrix",
+            p1.len(),
+            p2.len(),
+            size,
+            size
+        );
+        let diff: Vec<f64> = p1.iter().zip(p2).map(|(x1, x2)| x1 - x2).collect();
+        diff.iter()
+            .zip(inv_cov.iter())
+            .map(|(d, row)| d * row.iter().zip(diff.iter()).map(|(m, d)| m * d).sum::<f64>())
+            .sum()
+    }
+}
+
+/// Computes the square of the Euclidian distance in R^n, unless it exceeds `threshold`:
+/// the sum over the dimensions is abandoned as soon as the partial sum exceeds the threshold, and returned.
+/// Thus the result is the exact distance when it is lower than the threshold, and a lower bound of the distance
+/// that is greater than the threshold otherwise.
+///
+/// It is meant for neighborhood searches in high dimension, see [GetNeighborhoodBounded](crate::neighborhood::GetNeighborhoodBounded)
+/// and [Model::with_bounded_dist](crate::Model::with_bounded_dist), where the threshold is the distance
+/// of the farthest neighbor found so far.
+/// ```
+/// use fluent_data::space;
+///
+/// assert_eq!(5., space::euclid_dist_bounded(&vec![1., 2.], &vec![0., 0.], 10.));
+/// assert_eq!(9., space::euclid_dist_bounded(&vec![3., 4.], &vec![0., 0.], 5.));
+/// ```
+pub fn euclid_dist_bounded(p1: &RealPoint, p2: &RealPoint, threshold: f64) -> f64 {
+    let mut sum = 0.;
+    for (x1, x2) in p1.iter().zip(p2) {
+        let d = x1 - x2;
+        sum += d * d;
+        if sum > threshold {
+            break;
+        }
+    }
+    sum
+}
+
+/// Computes the Manhattan distance in R^n, i.e. the sum of the absolute differences of the coordinates,
+/// which is less sensitive to a single outlying coordinate than the Euclidian distance.
+///
+/// Unlike [euclid_dist], the distance is not squared, thus the thresholds of [AlgoConfig](crate::algorithm::AlgoConfig),
+/// which are meant for squared distances, are looser; squaring the distance keeps their meaning.
+/// `minkowski_dist(1.)` is that square, see [minkowski_dist].
+/// Panics if the points do not have the same dimension.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// assert_eq!(5., space::manhattan_dist(&vec![1., 3.], &vec![-1., 6.]));
+/// let algo = Algo::new(space::manhattan_dist, space::real_combine);
+/// let model = Model::new(space::manhattan_dist);
+/// ```
+pub fn manhattan_dist(p1: &RealPoint, p2: &RealPoint) -> f64 {
+    assert_eq!(
+        p1.len(),
+        p2.len(),
+        "points of different dimensions have no Manhattan distance"
+    );
+    p1.iter().zip(p2).map(|(x1, x2)| (x1 - x2).abs()).sum()
+}
+
+/// Computes the square of the Chebyshev distance in R^n, i.e. the largest difference on a dimension,
+/// e.g. for grid-like data where neighborhoods are axis-aligned cubes rather than spheres.
+///
+/// The distance is squared like [euclid_dist], thus the thresholds of [AlgoConfig](crate::algorithm::AlgoConfig)
+/// keep their meaning; centers are combined by [real_combine]. Panics if the points do not have the same dimension.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// assert_eq!(9., space::chebyshev_dist(&vec![1., 3.], &vec![-1., 6.]));
+/// let algo = Algo::new(space::chebyshev_dist, space::real_combine);
+/// let model = Model::new(space::chebyshev_dist);
+/// ```
+pub fn chebyshev_dist(p1: &RealPoint, p2: &RealPoint) -> f64 {
+    assert_eq!(
+        p1.len(),
+        p2.len(),
+        "points of different dimensions have no Chebyshev distance"
+    );
+    let d = p1
+        .iter()
+        .zip(p2)
+        .map(|(x1, x2)| (x1 - x2).abs())
+        .fold(0., f64::max);
+    d * d
+}
+
+/// Builds the square of the Minkowski distance of order `p` in R^n: `(Σ|x1 - x2|^p)^(2/p)`,
+/// e.g. to compare norms without writing a distance for each of them.
+///
+/// The distance is squared like [euclid_dist], which it matches for `p = 2`, thus the thresholds of
+/// [AlgoConfig](crate::algorithm::AlgoConfig) keep their meaning. For `p = 1`, it is the square of
+/// [manhattan_dist], which is not squared. The orders 1 and 2 are computed without powers,
+/// and the infinite order, which is the limit of high orders, by [chebyshev_dist]. Panics if `p` is lower than 1, where the triangle inequality does not hold,
+/// and, whatever the order, if the points do not have the same dimension.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// let dist = space::minkowski_dist(1.);
+/// assert_eq!(25., dist(&vec![1., 3.], &vec![-1., 6.]));
+/// let algo = Algo::new(space::minkowski_dist(3.), space::real_combine);
+/// let model = Model::new(space::minkowski_dist(3.));
+/// ```
+pub fn minkowski_dist(p: f64) -> impl Fn(&RealPoint, &RealPoint) -> f64 + Clone {
+    assert!(p >= 1., "the Minkowski order must be at least 1");
+    move |p1: &RealPoint, p2: &RealPoint| {
+        assert_eq!(
+            p1.len(),
+            p2.len(),
+            "points of different dimensions have no Minkowski distance"
+        );
+        if p == 1. {
+            let d = manhattan_dist(p1, p2);
+            return d * d;
+        }
+        if p == 2. {
+            return euclid_dist(p1, p2);
+        }
+        if p == f64::INFINITY {
+            return chebyshev_dist(p1, p2);
+        }
+        let sum: f64 = p1
+            .iter()
+            .zip(p2)
+            .map(|(x1, x2)| (x1 - x2).abs().powf(p))
+            .sum();
+        sum.powf(2. / p)
+    }
+}
+
+/// Computes the cosine distance between points in R^n: `1 - cos(θ)` where `θ` is the angle between the points,
+/// e.g. to cluster text embeddings by angular similarity.
+/// The distance ranges from 0 for points in the same direction to 2 for opposite points.
+/// The null point has no direction: its distance to any other point is 1, as for orthogonal points,
+/// and its distance to itself is 0, so that a stream of null points still falls into a single ball.
+///
+/// The cosine distance is half the square of the Euclidian distance between the points scaled to unit norm,
+/// thus it can be used where the algorithm expects squared distances. Ball radii are then in the same units:
+/// a ball includes the points which direction is within the angle `θ` of its center, where `1 - cos(θ)` is its
+/// squared radius, see [Ball::radius](crate::model::Ball::radius). Centers are still combined as vectors
+/// by [real_combine], thus their norm does not matter but it shrinks when directions spread.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// assert_eq!(1., space::cosine_dist(&vec![1., 0.], &vec![0., 5.]));
+/// let algo = Algo::new(space::cosine_dist, space::real_combine);
+/// let model = Model::new(space::cosine_dist);
+/// ```
+pub fn cosine_dist(p1: &RealPoint, p2: &RealPoint) -> f64 {
+    let square1: f64 = p1.iter().map(|x| x * x).sum();
+    let square2: f64 = p2.iter().map(|x| x * x).sum();
+    if square1 == 0. && square2 == 0. {
+        return 0.;
+    }
+    if square1 == 0. || square2 == 0. {
+        return 1.;
+    }
+    let dot: f64 = p1.iter().zip(p2).map(|(x1, x2)| x1 * x2).sum();
+    // a single square root makes the distance of a point to itself exactly 0
+    (1. - dot / (square1 * square2).sqrt()).clamp(0., 2.)
+}
+
+/// Mean radius of the Earth in meters, see [haversine_dist].
+pub const EARTH_RADIUS_M: f64 = 6_371_000.;
+
+/// Computes the square of the great-circle distance in meters between geographic points,
+/// which first two coordinates are the latitude and the longitude in degrees; other coordinates are ignored.
+///
+/// The distance is squared like [euclid_dist], thus the thresholds of [AlgoConfig](crate::algorithm::AlgoConfig)
+/// are in square meters. Centers should be combined by [geo_combine].
+/// Panics if a point has less than two coordinates.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// let paris = vec![48.8566, 2.3522];
+/// let london = vec![51.5074, -0.1278];
+/// let d = space::haversine_dist(&paris, &london).sqrt();
+/// assert!((d - 343_500.).abs() < 1000.);
+/// let algo = Algo::new(space::haversine_dist, space::geo_combine);
+/// let model = Model::new(space::haversine_dist);
+/// ```
+pub fn haversine_dist(p1: &RealPoint, p2: &RealPoint) -> f64 {
+    assert!(
+        p1.len() >= 2 && p2.len() >= 2,
+        "geographic points have a latitude and a longitude"
+    );
+    let (lat1, lat2) = (p1[0].to_radians(), p2[0].to_radians());
+    let half_lat = (lat2 - lat1) / 2.;
+    let half_lon = (p2[1] - p1[1]).to_radians() / 2.;
+    let a = half_lat.sin().powi(2) + lat1.cos() * lat2.cos() * half_lon.sin().powi(2);
+    let d = 2. * EARTH_RADIUS_M * a.sqrt().min(1.).asin();
+    d * d
+}
+
+/// Blends two distances: the blended distance is `alpha.d1 + (1 - alpha).d2`, e.g. to balance the magnitude
+/// and the direction of the points. Both distances should be in comparable units, e.g. squared distances
+/// between standardized points.
+/// ```
+/// use fluent_data::{Algo, Model, space};
+///
+/// let dist = space::blend_dist(space::euclid_dist, space::cosine_dist, 0.5);
+/// assert_eq!(0.5, dist(&vec![1., 0.], &vec![2., 0.]));
+/// let algo = Algo::new(space::blend_dist(space::euclid_dist, space::cosine_dist, 0.5), space::real_combine);
+/// let model = Model::new(space::blend_dist(space::euclid_dist, space::cosine_dist, 0.5));
+/// ```
+pub fn blend_dist<Point, Dist1, Dist2>(
+    d1: Dist1,
+    d2: Dist2,
+    alpha: f64,
+) -> impl Fn(&Point, &Point) -> f64 + Clone
+where
+    Dist1: Fn(&Point, &Point) -> f64 + Clone,
+    Dist2: Fn(&Point, &Point) -> f64 + Clone,
+{
+    assert!(
+        (0. ..=1.).contains(&alpha),
+        "the blend factor must lie between 0 and 1"
+    );
+    move |p1: &Point, p2: &Point| alpha * d1(p1, p2) + (1. - alpha) * d2(p1, p2)
+}
+
 /// Computes weighted center in a R^n vector space.
+///
+/// The center is computed as `p1 + t.(p2 - p1)` where `t = w2 / (w1 + w2)`
+/// which neither overflows for large weights nor loses precision when `w1 >> w2`.
 pub fn real_combine(p1: &RealPoint, w1: f64, p2: &RealPoint, w2: f64) -> RealPoint {
-    let w = w1 + w2;
+    let t = 1. / (1. + w1 / w2);
     p1.iter()
         .zip(p2)
-        .map(|(x1, x2)| (x1 * w1 + x2 * w2) / w)
+        .map(|(x1, x2)| x1 + t * (x2 - x1))
         .collect()
 }
 
+/// Computes the weighted center of geographic points, see [haversine_dist].
+///
+/// The latitudes and longitudes are combined as points of the unit sphere, thus the center of points
+/// on both sides of the ±180° meridian stays near it, and the center of points around a pole stays near the pole.
+/// The longitude of the center lies in ]-180°, 180°]. Other coordinates are combined by [real_combine].
+/// Antipodal points of equal weights have no center, the first point is kept.
+/// ```
+/// use fluent_data::space;
+///
+/// let c = space::geo_combine(&vec![0., 179.], 1., &vec![0., -179.], 1.);
+/// assert!((c[1].abs() - 180.).abs() < 1E-9);
+/// ```
+pub fn geo_combine(p1: &RealPoint, w1: f64, p2: &RealPoint, w2: f64) -> RealPoint {
+    let t = 1. / (1. + w1 / w2);
+    let (v1, v2) = (to_unit_vector(p1), to_unit_vector(p2));
+    let v: Vec<f64> = v1
+        .iter()
+        .zip(v2)
+        .map(|(x1, x2)| x1 + t * (x2 - x1))
+        .collect();
+    let mut center = real_combine(p1, w1, p2, w2);
+    let horizontal = v[0].hypot(v[1]);
+    if horizontal.hypot(v[2]) < 1E-12 {
+        center[..2].copy_from_slice(&p1[..2]);
+    } else {
+        center[0] = v[2].atan2(horizontal).to_degrees();
+        center[1] = v[1].atan2(v[0]).to_degrees();
+    }
+    center
+}
+
+/// Converts the latitude and the longitude of a geographic point to a point of the unit sphere.
+fn to_unit_vector(p: &RealPoint) -> [f64; 3] {
+    let (lat, lon) = (p[0].to_radians(), p[1].to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+/// A point in R^n which coordinates may be quantized, see [Quantization].
+///
+/// Points are serialized as arrays of reals, whether they are quantized or not,
+/// and are deserialized as [QuantizedPoint::Exact] points.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuantizedPoint {
+    /// Full precision coordinates, e.g. of an incoming point.
+    Exact(Vec<f64>),
+    /// Coordinates `offset + scale * code`, except the full precision ones.
+    Q8 {
+        codes: Vec<i8>,
+        offset: f64,
+        scale: f64,
+        /// Full precision coordinates by dimension, sorted by dimension.
+        exact: Vec<(usize, f64)>,
+    },
+}
+
+impl QuantizedPoint {
+    /// Gets an iterator over the coordinates, dequantized on the fly.
+    pub fn coords(&self) -> impl Iterator<Item = f64> + '_ {
+        Coords {
+            point: self,
+            dim: 0,
+            exact: 0,
+        }
+    }
+
+    /// Bound of the difference between a coordinate and the coordinate it was quantized from.
+    pub fn max_error(&self) -> f64 {
+        match self {
+            QuantizedPoint::Exact(_) => 0.,
+            QuantizedPoint::Q8 { scale, .. } => scale / 2.,
+        }
+    }
+
+    /// Number of bytes used by the point, including its heap allocations.
+    pub fn size_in_bytes(&self) -> usize {
+        mem::size_of::<Self>()
+            + match self {
+                QuantizedPoint::Exact(coords) => coords.capacity() * mem::size_of::<f64>(),
+                QuantizedPoint::Q8 { codes, exact, .. } => {
+                    codes.capacity() + exact.capacity() * mem::size_of::<(usize, f64)>()
+                }
+            }
+    }
+}
+
+/// Iterator over the coordinates of a [QuantizedPoint].
+struct Coords<'a> {
+    point: &'a QuantizedPoint,
+    dim: usize,
+    exact: usize,
+}
+
+impl Iterator for Coords<'_> {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = match self.point {
+            QuantizedPoint::Exact(coords) => *coords.get(self.dim)?,
+            QuantizedPoint::Q8 {
+                codes,
+                offset,
+                scale,
+                exact,
+            } => {
+                let code = *codes.get(self.dim)?;
+                match exact.get(self.exact) {
+                    Some(&(dim, x)) if dim == self.dim => {
+                        self.exact += 1;
+                        x
+                    }
+                    _ => offset + scale * code as f64,
+                }
+            }
+        };
+        self.dim += 1;
+        Some(x)
+    }
+}
+
+impl Serialize for QuantizedPoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.coords())
+    }
+}
+
+impl<'de> Deserialize<'de> for QuantizedPoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::deserialize(deserializer).map(QuantizedPoint::Exact)
+    }
+}
+
+/// Quantization of ball centers to 8 bits per coordinate.
+///
+/// Each center stores one signed byte per coordinate, and an offset and a scale such that
+/// the quantized coordinates span the range of the center coordinates.
+/// Thus a quantized coordinate differs from the exact one by at most half the scale,
+/// that is `(max - min) / 508` where `min` and `max` are the smallest and largest coordinates of the center.
+/// Centers are dequantized for distance computations and quantized again each time they are updated.
+/// The rounding error of an update is carried over to the next ones, damped by the weight of the ball,
+/// thus the error of a fitted center is typically a few times this bound.
+/// Since the bound depends on the range of the coordinates, a center which coordinates span a large range
+/// is less accurate in its small coordinates. Dimensions that must stay accurate, e.g. because their
+/// range is much smaller than the others, can be kept in full precision.
+///
+/// For `n` dimensions, a quantized center uses `n` bytes plus 64 bytes
+/// instead of `8n` bytes plus 24 bytes, plus 16 bytes per full precision dimension.
+/// ```
+/// use fluent_data::{Algo, Model, space::{self, Q8}};
+///
+/// let quantization = Q8.with_full_precision(&[0]);
+/// let algo = Algo::new(space::quantized_dist, quantization.combine());
+/// let mut model = Model::new(space::quantized_dist);
+/// for p in ["[1.0, 100.0]", "[2.0, 300.0]", "[1.5, 200.0]"] {
+///     algo.fit(&mut model, serde_json::from_str(p).unwrap());
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Quantization {
+    full_precision: Vec<usize>,
+}
+
+/// Quantization of all dimensions to 8 bits.
+pub const Q8: Quantization = Quantization {
+    full_precision: Vec::new(),
+};
+
+impl Quantization {
+    /// Keeps the given dimensions in full precision.
+    pub fn with_full_precision(mut self, dims: &[usize]) -> Self {
+        self.full_precision.extend_from_slice(dims);
+        self.full_precision.sort_unstable();
+        self.full_precision.dedup();
+        self
+    }
+
+    /// Quantizes the coordinates.
+    pub fn quantize(&self, coords: &[f64]) -> QuantizedPoint {
+        let is_exact = |dim: &usize| self.full_precision.binary_search(dim).is_ok();
+        let (min, max) = coords
+            .iter()
+            .enumerate()
+            .filter(|(dim, _)| !is_exact(dim))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), (_, &x)| {
+                (min.min(x), max.max(x))
+            });
+        let (offset, scale) = if min < max {
+            ((min + max) / 2., (max - min) / 254.)
+        } else if min == max {
+            (min, 0.)
+        } else {
+            (0., 0.)
+        };
+        let codes = coords
+            .iter()
+            .enumerate()
+            .map(|(dim, &x)| match is_exact(&dim) || scale == 0. {
+                true => 0,
+                false => ((x - offset) / scale).round().clamp(-127., 127.) as i8,
+            })
+            .collect();
+        let exact = self
+            .full_precision
+            .iter()
+            .filter(|&&dim| dim < coords.len())
+            .map(|&dim| (dim, coords[dim]))
+            .collect();
+        QuantizedPoint::Q8 {
+            codes,
+            offset,
+            scale,
+            exact,
+        }
+    }
+
+    /// Returns the combine function to use with [quantized_dist]:
+    /// it computes the weighted center like [real_combine], then quantizes it.
+    pub fn combine(
+        &self,
+    ) -> impl Fn(&QuantizedPoint, f64, &QuantizedPoint, f64) -> QuantizedPoint + 'static {
+        let quantization = self.clone();
+        move |p1, w1, p2, w2| {
+            let p1: RealPoint = p1.coords().collect();
+            let p2: RealPoint = p2.coords().collect();
+            quantization.quantize(&real_combine(&p1, w1, &p2, w2))
+        }
+    }
+}
+
+/// Computes the square of the Euclidian distance between points which may be quantized.
+pub fn quantized_dist(p1: &QuantizedPoint, p2: &QuantizedPoint) -> f64 {
+    p1.coords()
+        .zip(p2.coords())
+        .map(|(x1, x2)| {
+            let d = x1 - x2;
+            d * d
+        })
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::space::*;
+    use crate::{model::Ball, space::*, Model};
 
     #[test]
     fn test_euclid_dist() {
@@ -37,9 +578,326 @@ mod tests {
         assert_eq!(5., d);
     }
 
+    #[test]
+    fn test_weighted_euclid_dist() {
+        let unit = weighted_euclid_dist(vec![1., 1.]);
+        let (p1, p2) = (vec![1., 3.], vec![-1., 4.]);
+        assert_eq!(euclid_dist(&p1, &p2), unit(&p1, &p2));
+        let scaled = weighted_euclid_dist(vec![0.25, 0.]);
+        assert_eq!(1., scaled(&p1, &p2));
+        assert_eq!(scaled(&p1, &p2), scaled(&p2, &p1));
+    }
+
+    #[test]
+    fn test_weighted_euclid_dist_neighbors() {
+        let centers = [vec![0., 100.], vec![5., 0.], vec![-3., -50.]];
+        let balls = centers
+            .iter()
+            .map(|c| Ball::new(c.clone(), 1., 1.))
+            .collect();
+        let model = Model::load(weighted_euclid_dist(vec![1., 0.]), balls);
+        // the second coordinate is ignored, thus the nearest center is the nearest on the first one
+        for y in [-1000., -50., 0., 1000.] {
+            let nearest: Vec<usize> = model
+                .k_nearest_balls(&vec![1.5, y], 3)
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect();
+            assert_eq!(vec![0, 1, 2], nearest);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "do not match the 2 weights")]
+    fn test_weighted_euclid_dist_dimensions() {
+        weighted_euclid_dist(vec![1., 1.])(&vec![1., 2., 3.], &vec![1., 2., 3.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be negative")]
+    fn test_weighted_euclid_dist_negative() {
+        let _ = weighted_euclid_dist(vec![1., -1.]);
+    }
+
+    #[test]
+    fn test_mahalanobis_dist() {
+        let points = [vec![1., 1.5], vec![-2., 0.3], vec![0., 3.], vec![4.7, -1.]];
+        let identity = mahalanobis_dist(vec![vec![1., 0.], vec![0., 1.]]);
+        let stretched = mahalanobis_dist(vec![vec![4., 0.], vec![0., 0.25]]);
+        for p1 in points.iter() {
+            for p2 in points.iter() {
+                assert_eq!(euclid_dist(p1, p2), identity(p1, p2));
+                let (dx, dy) = (p1[0] - p2[0], p1[1] - p2[1]);
+                let expected = 4. * dx * dx + 0.25 * dy * dy;
+                assert!((expected - stretched(p1, p2)).abs() < 1E-12 * expected.max(1.));
+            }
+        }
+        // a unit step along x counts as 2, along y as 1/2
+        assert_eq!(4., stretched(&vec![1., 0.], &vec![0., 0.]));
+        assert_eq!(0.25, stretched(&vec![0., 1.], &vec![0., 0.]));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be square")]
+    fn test_mahalanobis_dist_square() {
+        let _ = mahalanobis_dist(vec![vec![1., 0.], vec![0.]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "do not match the 2x2")]
+    fn test_mahalanobis_dist_dimensions() {
+        let dist = mahalanobis_dist(vec![vec![1., 0.], vec![0., 1.]]);
+        dist(&vec![1.], &vec![2.]);
+    }
+
+    #[test]
+    fn test_manhattan_dist() {
+        assert_eq!(0., manhattan_dist(&vec![2.], &vec![2.]));
+        assert_eq!(3.5, manhattan_dist(&vec![-1.], &vec![2.5]));
+        let (p1, p2) = (vec![1., -2., 0.5], vec![0., 1., 2.]);
+        assert_eq!(5.5, manhattan_dist(&p1, &p2));
+        assert_eq!(manhattan_dist(&p1, &p2), manhattan_dist(&p2, &p1));
+    }
+
+    #[test]
+    #[should_panic(expected = "different dimensions")]
+    fn test_manhattan_dist_dimensions() {
+        manhattan_dist(&vec![1., 2.], &vec![1., 2., 3.]);
+    }
+
+    #[test]
+    fn test_chebyshev_dist() {
+        assert_eq!(0., chebyshev_dist(&vec![2., -1.], &vec![2., -1.]));
+        let (p1, p2) = (vec![1., -2., 0.5], vec![0., 1., 2.]);
+        assert_eq!(9., chebyshev_dist(&p1, &p2));
+        assert_eq!(chebyshev_dist(&p1, &p2), chebyshev_dist(&p2, &p1));
+        // the largest difference bounds the Euclidean distance
+        assert!(chebyshev_dist(&p1, &p2) <= euclid_dist(&p1, &p2));
+        assert!(euclid_dist(&p1, &p2) <= 3. * chebyshev_dist(&p1, &p2));
+    }
+
+    #[test]
+    #[should_panic(expected = "different dimensions")]
+    fn test_chebyshev_dist_dimensions() {
+        chebyshev_dist(&vec![1., 2.], &vec![1., 2., 3.]);
+    }
+
+    #[test]
+    fn test_minkowski_dist() {
+        let points = [vec![1., 1.], vec![-2., 0.5], vec![0., 3.], vec![4., -1.]];
+        let (euclid, manhattan) = (minkowski_dist(2.), minkowski_dist(1.));
+        for p1 in points.iter() {
+            for p2 in points.iter() {
+                let e = euclid_dist(p1, p2);
+                assert!((e - euclid(p1, p2)).abs() <= 1E-12 * e.max(1.));
+                let m = manhattan_dist(p1, p2);
+                assert!((m * m - manhattan(p1, p2)).abs() <= 1E-12 * m.max(1.));
+            }
+        }
+        // higher orders get closer to the largest coordinate difference
+        let (p1, p2) = (vec![0., 0.], vec![3., 4.]);
+        assert!(minkowski_dist(3.)(&p1, &p2) < 25.);
+        assert!(minkowski_dist(50.)(&p1, &p2) > 16.);
+        let infinite = minkowski_dist(f64::INFINITY);
+        assert_eq!(16., infinite(&p1, &p2));
+        assert_eq!(0., infinite(&p2, &p2));
+        // fractional orders still rank points by their distance along each direction
+        for p in [1.5, 2.5] {
+            let dist = minkowski_dist(p);
+            let ranked: Vec<f64> = (0..10)
+                .map(|i| dist(&p1, &vec![i as f64, 2. * i as f64]))
+                .collect();
+            assert!(ranked.windows(2).all(|w| w[0] < w[1]));
+            assert!(dist(&p1, &vec![1., 1.]) < dist(&p1, &vec![1., 2.]));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 1")]
+    fn test_minkowski_dist_order() {
+        let _ = minkowski_dist(0.5);
+    }
+
+    #[test]
+    fn test_minkowski_dist_dimensions() {
+        for p in [1., 2., 3.] {
+            let dist = minkowski_dist(p);
+            let mismatch = std::panic::catch_unwind(|| dist(&vec![1., 2.], &vec![1.]));
+            assert!(mismatch.is_err());
+        }
+    }
+
+    #[test]
+    fn test_euclid_dist_bounded() {
+        let p1 = vec![1., -2., 0.5, 3.];
+        let p2 = vec![0., 1., 2., -1.];
+        let d = euclid_dist(&p1, &p2);
+        assert_eq!(d, euclid_dist_bounded(&p1, &p2, d));
+        assert_eq!(d, euclid_dist_bounded(&p1, &p2, f64::INFINITY));
+        for threshold in [0., 1., 5., 10., d - 0.1] {
+            let bounded = euclid_dist_bounded(&p1, &p2, threshold);
+            assert!(threshold <= bounded && bounded <= d);
+        }
+        assert_eq!(1., euclid_dist_bounded(&p1, &p2, 0.5));
+    }
+
+    #[test]
+    fn test_cosine_dist() {
+        assert!(cosine_dist(&vec![1., 1.], &vec![3., 3.]) < 1E-12);
+        assert_eq!(0., cosine_dist(&vec![1., 2.], &vec![1., 2.]));
+        assert_eq!(1., cosine_dist(&vec![1., 0.], &vec![0., 5.]));
+        assert!((2. - cosine_dist(&vec![1., 2.], &vec![-1., -2.])).abs() < 1E-12);
+        assert_eq!(1., cosine_dist(&vec![0., 0.], &vec![1., 2.]));
+        assert_eq!(0., cosine_dist(&vec![0., 0.], &vec![0., 0.]));
+        let algo = crate::Algo::new(cosine_dist, real_combine);
+        let mut model = Model::new(cosine_dist);
+        for _ in 0..20 {
+            algo.fit(&mut model, vec![0., 0.]);
+        }
+        assert_eq!(1, model.iter_balls().count());
+        let (p1, p2) = (vec![3., -1., 2.], vec![0.5, 4., 1.]);
+        let unit = |p: &Vec<f64>| {
+            let norm = euclid_dist(p, &vec![0.; 3]).sqrt();
+            p.iter().map(|x| x / norm).collect::<Vec<_>>()
+        };
+        let chord = euclid_dist(&unit(&p1), &unit(&p2));
+        assert!((chord / 2. - cosine_dist(&p1, &p2)).abs() < 1E-12);
+    }
+
+    #[test]
+    fn test_blend_dist() {
+        let points = [vec![1., 1.], vec![-2., 0.5], vec![0., 3.], vec![4., -1.]];
+        let same = blend_dist(euclid_dist, euclid_dist, 0.5);
+        let blend = blend_dist(euclid_dist, cosine_dist, 0.3);
+        for p1 in points.iter() {
+            for p2 in points.iter() {
+                assert!((euclid_dist(p1, p2) - same(p1, p2)).abs() < 1E-12);
+                let (e, c) = (euclid_dist(p1, p2), cosine_dist(p1, p2));
+                let d = blend(p1, p2);
+                assert!(e.min(c) - 1E-12 <= d && d <= e.max(c) + 1E-12);
+                if e != c {
+                    assert!((d - (0.3 * e + 0.7 * c)).abs() < 1E-12);
+                }
+            }
+        }
+        let custom = blend_dist(|_: &Vec<f64>, _: &Vec<f64>| 1., euclid_dist, 1.);
+        assert_eq!(1., custom(&points[0], &points[1]));
+    }
+
     #[test]
     fn test_real_combine() {
         let c = real_combine(&vec![1., -1.2], 1., &vec![2.5, -0.9], 2.);
         assert_eq!(vec![2., -1.], c);
     }
+
+    #[test]
+    fn test_haversine_dist() {
+        assert_eq!(0., haversine_dist(&vec![45., 10.], &vec![45., 10.]));
+        // a degree along the equator, on both sides of the antimeridian
+        let degree = EARTH_RADIUS_M.to_radians();
+        let d = haversine_dist(&vec![0., 179.5], &vec![0., -179.5]).sqrt();
+        assert!((d - degree).abs() < 1E-6);
+        let d = haversine_dist(&vec![0., -180.], &vec![0., 180.]);
+        assert!(d < 1E-6);
+        // near the poles, distant longitudes are close
+        let d = haversine_dist(&vec![89.5, 0.], &vec![89.5, 180.]).sqrt();
+        assert!((d - degree).abs() < 1E-6);
+        let d = haversine_dist(&vec![-90., 0.], &vec![-90., 90.]);
+        assert!(d < 1E-6);
+        let (p1, p2) = (vec![30., 50., 1.], vec![-20., 120., 2.]);
+        assert_eq!(haversine_dist(&p1, &p2), haversine_dist(&p2, &p1));
+        let antipode = haversine_dist(&vec![10., 20.], &vec![-10., -160.]).sqrt();
+        assert!((antipode - EARTH_RADIUS_M * std::f64::consts::PI).abs() < 1.);
+    }
+
+    #[test]
+    fn test_haversine_dist_cities() {
+        let paris = vec![48.8566, 2.3522];
+        let new_york = vec![40.7128, -74.006];
+        let tokyo = vec![35.6762, 139.6503];
+        let sydney = vec![-33.8688, 151.2093];
+        let los_angeles = vec![34.0522, -118.2437];
+        let expected = [
+            (&paris, &new_york, 5_837_000.),
+            (&tokyo, &sydney, 7_826_000.),
+            (&new_york, &los_angeles, 3_936_000.),
+            // across the date line
+            (&tokyo, &los_angeles, 8_815_000.),
+        ];
+        for (p1, p2, meters) in expected {
+            let d = haversine_dist(p1, p2).sqrt();
+            assert!((d - meters).abs() < 0.002 * meters);
+        }
+    }
+
+    #[test]
+    fn test_geo_combine() {
+        // the center of points on both sides of the antimeridian stays on it
+        let c = geo_combine(&vec![10., 170.], 1., &vec![10., -170.], 1.);
+        assert!(c[0] > 10. && c[0] < 11.);
+        assert!((c[1].abs() - 180.).abs() < 1E-9);
+        let c = geo_combine(&vec![0., 178.], 3., &vec![0., -176.], 1.);
+        assert!((c[0]).abs() < 1E-9 && (c[1] - 179.5).abs() < 0.01);
+        // the center of points around a pole stays near the pole
+        let c = geo_combine(&vec![85., 0.], 1., &vec![85., 180.], 1.);
+        assert!((c[0] - 90.).abs() < 1E-9);
+        let c = geo_combine(&vec![-80., 90.], 1., &vec![-80., -90.], 1.);
+        assert!((c[0] + 90.).abs() < 1E-9);
+        // the center lies on the great circle between the points
+        let (p1, p2) = (vec![40., -70., 2.], vec![50., 10., 4.]);
+        let c = geo_combine(&p1, 1., &p2, 1.);
+        let (d1, d2) = (
+            haversine_dist(&p1, &c).sqrt(),
+            haversine_dist(&c, &p2).sqrt(),
+        );
+        assert!((d1 - d2).abs() < 1E-6);
+        assert!((d1 + d2 - haversine_dist(&p1, &p2).sqrt()).abs() < 1E-6);
+        assert_eq!(3., c[2]);
+        let c = geo_combine(&vec![0., 0.], 1., &vec![0., 180.], 1.);
+        assert_eq!(vec![0., 0.], c);
+        // the midpoint of -179° and 179° is not 0°
+        let c = geo_combine(&vec![0., -179.], 1., &vec![0., 179.], 1.);
+        assert!((c[1].abs() - 180.).abs() < 1E-9);
+    }
+
+    #[test]
+    fn test_quantize() {
+        let coords = vec![-3., 0.001, 1.7, 5., 1E-6];
+        let quantization = Q8.with_full_precision(&[4, 1]);
+        let point = quantization.quantize(&coords);
+        assert!(point.max_error() > 0.);
+        let dequantized: Vec<f64> = point.coords().collect();
+        assert_eq!(coords.len(), dequantized.len());
+        for (x, y) in coords.iter().zip(&dequantized) {
+            assert!((x - y).abs() <= point.max_error() + 1E-12);
+        }
+        assert_eq!(coords[1], dequantized[1]);
+        assert_eq!(coords[4], dequantized[4]);
+        assert_eq!(coords[0], dequantized[0]);
+        assert_eq!(coords[3], dequantized[3]);
+        let json = serde_json::to_string(&point).unwrap();
+        let parsed: QuantizedPoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(QuantizedPoint::Exact(dequantized), parsed);
+    }
+
+    #[test]
+    fn test_quantize_constant() {
+        let point = Q8.quantize(&[2., 2., 2.]);
+        assert_eq!(0., point.max_error());
+        assert_eq!(vec![2., 2., 2.], point.coords().collect::<Vec<_>>());
+        assert_eq!(
+            0.,
+            quantized_dist(&point, &QuantizedPoint::Exact(vec![2., 2., 2.]))
+        );
+    }
+
+    #[test]
+    fn test_real_combine_large_weights() {
+        let c = real_combine(&vec![1.], 1E308, &vec![3.], 1E308);
+        assert_eq!(vec![2.], c);
+        let c = real_combine(&vec![0.], 1E300, &vec![1E10], 1E290);
+        assert!((c[0] - 1.).abs() < 1E-9);
+        let c = real_combine(&vec![1.], 1., &vec![2.], 0.);
+        assert_eq!(vec![1.], c);
+    }
 }
@@ -3,20 +3,44 @@
 //! This module also provides the [stdio] function that builds
 //! a point iterator which reads the standard input and a
 //! write closure that writes to the standard output.
+//!
+//! Records that cannot be parsed are reported by a [StreamError::ParseError].
 
 use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, VecDeque},
     error::Error,
-    io,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    mem,
     ops::Deref,
-    sync::mpsc::{Receiver, Sender},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
-    algorithm::Algo,
-    model::{Ball, Model},
+    algorithm::{Algo, AlgoConfig, FitError},
+    model::{Ball, DecayConfig, Model},
+    serializer::{self, JsonSerializer, ModelSerializer},
+    service::ServiceConfig,
 };
-use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "gzip")]
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use rand::{rngs::StdRng, Rng};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Map, Value};
+#[cfg(feature = "gzip")]
+use std::io::Read;
 
 /// Reads data from `In` and writes model to `Out`.
 /// ```
@@ -33,154 +57,4850 @@ use serde_json::{json, Map, Value};
 ///     Ok(())
 /// }
 /// ```
-pub struct Streamer<In, Out>
+pub struct Streamer<In, Out, Ser = JsonSerializer>
 where
     In: Iterator<Item = Result<String, Box<dyn Error>>>,
     Out: FnMut(String) -> Result<(), Box<dyn Error>>,
 {
     points: In,
     write: Out,
+    sampling: Option<AdaptiveSampling>,
+    auto_emit: bool,
+    emit_every: usize,
+    service_config: Option<Arc<Mutex<ServiceConfig>>>,
+    unemitted: usize,
+    report: RunReport,
+    line: usize,
+    phases: Option<Phases>,
+    serializer: Ser,
+    buffer: String,
+    assignments: bool,
+    history: Option<Arc<Mutex<History>>>,
+    reservoir: Option<Reservoir>,
+    admin: bool,
+    soft: Option<f64>,
+    memberships: Vec<(usize, f64)>,
+    watchdog: Option<WatchHandle>,
+    checkpoint: Option<Checkpoint>,
+    ids: bool,
+    id: String,
+    assignment: Option<Assignment>,
+    latency: Option<Latency>,
+    dedup: Option<Dedup>,
+    uid: Option<String>,
+    coverage: Option<Coverage>,
+    batches: bool,
+    adaptive_emit: Option<AdaptiveEmit>,
+    heartbeat: Option<Heartbeat>,
+    /// Whether a model was written since the last pushed record, see [Streamer::push].
+    emitted: bool,
+    /// Why the last point was not fitted, if it was dropped.
+    dropped: Option<DropReason>,
+    /// The dropped records not yet reported, see [Streamer::run_with_drop_sink].
+    drops: Option<Vec<(String, DropReason)>>,
 }
 
-impl<In, Out> Streamer<In, Out>
+/// How the arrival time of points is known, see [Streamer::with_latency].
+#[derive(Clone, Copy, Debug)]
+pub enum Timestamps {
+    /// Points arrive when they are read from `In` source.
+    Ingest,
+    /// Points carry their arrival time, in milliseconds since the clock origin, which the function extracts
+    /// from the record. Points without timestamp arrive when they are read. Records which timestamp is negative
+    /// or not finite are rejected with a [StreamError::ParseError].
+    Producer(fn(&str) -> Option<f64>),
+}
+
+/// Latency measurement state of a [Streamer].
+struct Latency {
+    timestamps: Timestamps,
+    clock: Box<dyn Fn() -> Duration>,
+    /// Arrival of the last record read.
+    arrival: Duration,
+    /// Arrival of the points fitted since the last emission.
+    pending: Vec<Duration>,
+}
+
+/// Rolling coverage state of a [Streamer], see [Streamer::with_coverage].
+struct Coverage {
+    window: usize,
+    floor: f64,
+    /// Whether each of the last points was within the radius of its ball.
+    recent: VecDeque<bool>,
+    inside: usize,
+}
+
+impl Coverage {
+    /// Records whether the last fitted point was within the radius of its ball,
+    /// returns the coverage and whether it is below the floor over a full window.
+    fn record(&mut self, inside: bool) -> (f64, bool) {
+        self.recent.push_back(inside);
+        self.inside += inside as usize;
+        if self.recent.len() > self.window && self.recent.pop_front() == Some(true) {
+            self.inside -= 1;
+        }
+        let coverage = self.inside as f64 / self.recent.len() as f64;
+        (
+            coverage,
+            self.recent.len() == self.window && coverage < self.floor,
+        )
+    }
+}
+
+/// Emission throttling state of a [Streamer], see [Streamer::with_adaptive_emit].
+struct AdaptiveEmit {
+    target: Duration,
+    max_every: usize,
+    /// The number of points per emission chosen after the last write.
+    every: usize,
+    /// The smoothed write time, in seconds, `None` before the first write.
+    write_time: Option<f64>,
+    /// Times the writes.
+    clock: Box<dyn Fn() -> Duration>,
+}
+
+/// Weight of the last write time in the smoothed write time of [Streamer::with_adaptive_emit].
+const WRITE_TIME_SMOOTHING: f64 = 0.3;
+
+/// Heartbeat state of a [Streamer], see [Streamer::with_heartbeat].
+struct Heartbeat {
+    period: Duration,
+    /// When `Out` sink was last written.
+    last: Instant,
+}
+
+/// The time elapsed since the UNIX epoch, a clock for [Streamer::with_latency].
+pub fn unix_clock() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Number of significant bits of the values recorded by a [LatencyHistogram].
+const LATENCY_PRECISION: u32 = 6;
+
+/// A histogram of durations with a bounded relative error, in the manner of HDR histograms.
+///
+/// Durations are counted in microseconds in buckets which width is proportional to their value,
+/// thus percentiles are reported within about 3% of the exact value, by excess. The maximum is exact.
+/// ```
+/// use std::time::Duration;
+///
+/// use fluent_data::streamer::LatencyHistogram;
+///
+/// let mut histogram = LatencyHistogram::default();
+/// for ms in 1..=100 {
+///     histogram.record(Duration::from_millis(ms));
+/// }
+/// let p95 = histogram.percentile(0.95).unwrap();
+/// assert!(p95 >= Duration::from_millis(95) && p95 <= Duration::from_millis(98));
+/// assert_eq!(Some(Duration::from_millis(100)), histogram.max());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    count: u64,
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    /// Counts a duration.
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().min(u64::MAX as u128) as u64;
+        let bucket = latency_bucket(micros);
+        if bucket >= self.counts.len() {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.max = self.max.max(latency);
+    }
+
+    /// Number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The longest recorded duration, if any.
+    pub fn max(&self) -> Option<Duration> {
+        Some(self.max).filter(|_| self.count > 0)
+    }
+
+    /// The duration under which the fraction `q` of the recorded durations lie, e.g. `0.99`, if any.
+    pub fn percentile(&self, q: f64) -> Option<Duration> {
+        let rank = ((q.clamp(0., 1.) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = Duration::from_micros(latency_bucket_upper(bucket));
+                return Some(upper.min(self.max));
+            }
+        }
+        None
+    }
+}
+
+/// The bucket of a value, keeping its [LATENCY_PRECISION] most significant bits.
+fn latency_bucket(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros();
+    if bits <= LATENCY_PRECISION {
+        value as usize
+    } else {
+        let shift = bits - LATENCY_PRECISION;
+        ((shift as usize) << LATENCY_PRECISION) + (value >> shift) as usize
+    }
+}
+
+/// The highest value of a bucket, see [latency_bucket].
+fn latency_bucket_upper(bucket: usize) -> u64 {
+    let shift = (bucket >> LATENCY_PRECISION) as u32;
+    let significant = (bucket & ((1 << LATENCY_PRECISION) - 1)) as u64;
+    if shift == 0 {
+        significant
+    } else {
+        ((significant + 1) << shift) - 1
+    }
+}
+
+/// How often [Streamer::with_checkpoints] writes a checkpoint.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cadence {
+    /// After the given number of fitted points.
+    Points(usize),
+    /// After the first fitted point once the given duration has elapsed since the last checkpoint.
+    Duration(Duration),
+}
+
+/// Checkpoint state of a [Streamer].
+struct Checkpoint {
+    cadence: Cadence,
+    write: BoxedWrite,
+    updates: usize,
+    last: Instant,
+}
+
+/// A bounded set of recently seen point uids, see [Streamer::with_dedup].
+///
+/// When the set is full, the least recently seen uid is forgotten.
+/// ```
+/// use std::time::Duration;
+///
+/// use fluent_data::streamer::Dedup;
+///
+/// let dedup = Dedup::new(10_000).with_ttl(Duration::from_secs(3600));
+/// ```
+pub struct Dedup {
+    capacity: usize,
+    ttl: Option<Duration>,
+    /// The sequence number of the last sighting of each uid.
+    seen: HashMap<String, u64>,
+    /// Sightings from the least recent, entries which sequence number is not the last one of their uid are stale.
+    order: VecDeque<(String, u64, Instant)>,
+    sequence: u64,
+}
+
+impl Dedup {
+    /// Remembers up to `capacity` uids.
+    pub fn new(capacity: usize) -> Self {
+        Dedup {
+            capacity: capacity.max(1),
+            ttl: None,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+            sequence: 0,
+        }
+    }
+
+    /// Forgets uids that were not seen for longer than `ttl`.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Remembers the given uids, from the least recently seen, e.g. restored by [parse_checkpoint_uids].
+    pub fn with_seen(mut self, uids: impl IntoIterator<Item = String>) -> Self {
+        for uid in uids {
+            self.check(&uid);
+        }
+        self
+    }
+
+    /// The remembered uids, from the least recently seen.
+    pub fn uids(&self) -> impl Iterator<Item = &str> {
+        self.order
+            .iter()
+            .filter(|(uid, sequence, _)| self.is_current(uid, *sequence))
+            .map(|(uid, _, _)| uid.as_str())
+    }
+
+    /// Records a sighting of the uid and tells whether it was seen recently.
+    fn check(&mut self, uid: &str) -> bool {
+        let now = Instant::now();
+        if let Some(ttl) = self.ttl {
+            while self
+                .order
+                .front()
+                .is_some_and(|(_, _, seen)| now.duration_since(*seen) >= ttl)
+            {
+                self.forget_least_recent();
+            }
+        }
+        self.sequence += 1;
+        let duplicate = self.seen.insert(uid.to_string(), self.sequence).is_some();
+        self.order.push_back((uid.to_string(), self.sequence, now));
+        while self.seen.len() > self.capacity {
+            self.forget_least_recent();
+        }
+        if self.order.len() > 2 * self.capacity {
+            let seen = &self.seen;
+            self.order
+                .retain(|(uid, sequence, _)| seen.get(uid) == Some(sequence));
+        }
+        duplicate
+    }
+
+    /// Drops the least recent sighting, and its uid unless it was seen again since.
+    fn forget_least_recent(&mut self) {
+        if let Some((uid, sequence, _)) = self.order.pop_front() {
+            if self.is_current(&uid, sequence) {
+                self.seen.remove(&uid);
+            }
+        }
+    }
+
+    /// Tells whether the sighting is the last one of its uid.
+    fn is_current(&self, uid: &str, sequence: u64) -> bool {
+        self.seen.get(uid) == Some(&sequence)
+    }
+}
+
+/// Reads back the uids written in a checkpoint by a [Streamer] with [Dedup],
+/// they can be restored with [Dedup::with_seen].
+pub fn parse_checkpoint_uids(checkpoint: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Uids {
+        #[serde(default)]
+        uids: Vec<String>,
+    }
+    let uids: Uids = serde_json::from_str(checkpoint)?;
+    Ok(uids.uids)
+}
+
+/// Reads back the sequence number of the last model written before a checkpoint by a [Streamer]
+/// which serializer numbers its messages, if any. The numbering is resumed after it with
+/// [crate::serializer::EnvelopeSerializer::resume_after] or [crate::serializer::DeltaSerializer::resume_after].
+pub fn parse_checkpoint_seq(checkpoint: &str) -> Result<Option<u64>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Seq {
+        seq: Option<u64>,
+    }
+    let seq: Seq = serde_json::from_str(checkpoint)?;
+    Ok(seq.seq)
+}
+
+/// Reads back the time at which a checkpoint was written by a [Streamer], if any.
+pub fn parse_checkpoint_time(checkpoint: &str) -> Result<Option<SystemTime>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Time {
+        time: Option<f64>,
+    }
+    let time: Time = serde_json::from_str(checkpoint)?;
+    time.time
+        .map(|secs| {
+            Duration::try_from_secs_f64(secs)
+                .map(|since_epoch| UNIX_EPOCH + since_epoch)
+                .map_err(|_| format!("invalid checkpoint time {}", secs).into())
+        })
+        .transpose()
+}
+
+/// Restores a model from a checkpoint written by a [Streamer], see [Streamer::with_checkpoints].
+///
+/// With a time based `decay`, the restored model is fast-forwarded by the time elapsed since the checkpoint
+/// was written, see [Model::fast_forward], so that a model reloaded after some downtime is not overweighted.
+/// The normalization of a real model, if any, is read with [serializer::parse_columnar_normalization]
+/// and restored with [Model::with_normalization].
+/// ```
+/// use std::time::Duration;
+///
+/// use fluent_data::{model::DecayConfig, space, streamer};
+///
+/// let checkpoint = r#"{"centers":[[1.0]],"radii":[1.0],"weights":[8.0],"time":0.0}"#;
+/// let decay = DecayConfig { factor: 0.5, period: Duration::from_secs(3600), floor: 1E-2 };
+/// let model = streamer::restore_checkpoint(checkpoint, space::euclid_dist, Some(&decay)).unwrap();
+/// assert_eq!(0, model.iter_balls().count());
+/// let model = streamer::restore_checkpoint(checkpoint, space::euclid_dist, None).unwrap();
+/// assert_eq!(1, model.iter_balls().count());
+/// ```
+pub fn restore_checkpoint<Point, Dist>(
+    checkpoint: &str,
+    dist: Dist,
+    decay: Option<&DecayConfig>,
+) -> Result<Model<Point>, Box<dyn Error>>
 where
-    In: Iterator<Item = Result<String, Box<dyn Error>>>,
-    Out: FnMut(String) -> Result<(), Box<dyn Error>>,
+    Point: PartialEq + DeserializeOwned + 'static,
+    Dist: Fn(&Point, &Point) -> f64 + 'static,
 {
-    /// builds a new streamer instance.
-    pub fn new(points: In, write: Out) -> Self {
-        Self { points, write }
+    let balls = serializer::parse_columnar(checkpoint)?;
+    let mut model = Model::restore(dist, balls);
+    if let (Some(decay), Some(time)) = (decay, parse_checkpoint_time(checkpoint)?) {
+        let elapsed = SystemTime::now().duration_since(time).unwrap_or_default();
+        model.fast_forward(elapsed, decay);
+    }
+    Ok(model)
+}
+
+/// Splits a checkpoint of several models, written by [Streamer::run_hierarchical] or an A/B run,
+/// into the checkpoints of each model with their name, `coarse` and `fine` or the configuration names.
+/// Each checkpoint gets the uids, sequence number and time of the whole and is restored with [restore_checkpoint].
+/// ```
+/// use fluent_data::{space, streamer};
+///
+/// let checkpoint = r#"{"models":[{"centers":[[1.0]],"radii":[1.0],"weights":[8.0]}],"names":["fine"],"time":0.0}"#;
+/// let models = streamer::split_checkpoint(checkpoint).unwrap();
+/// assert_eq!("fine", models[0].0);
+/// let model = streamer::restore_checkpoint(&models[0].1, space::euclid_dist, None).unwrap();
+/// assert_eq!(1, model.iter_balls().count());
+/// ```
+pub fn split_checkpoint(checkpoint: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut checkpoint: Map<String, Value> = serde_json::from_str(checkpoint)?;
+    let models = match checkpoint.remove("models") {
+        Some(Value::Array(models)) => models,
+        _ => return Err("the checkpoint has no models".into()),
+    };
+    let names: Vec<String> = match checkpoint.remove("names") {
+        Some(names) => serde_json::from_value(names)?,
+        None => return Err("the checkpoint has no model names".into()),
+    };
+    if names.len() != models.len() {
+        return Err("the checkpoint has not as many names as models".into());
     }
+    names
+        .into_iter()
+        .zip(models)
+        .map(|(name, model)| {
+            let mut model = match model {
+                Value::Object(model) => model,
+                _ => return Err("a model of the checkpoint is not an object".into()),
+            };
+            model.extend(checkpoint.clone());
+            Ok((name, serde_json::to_string(&model)?))
+        })
+        .collect()
+}
 
-    /// Infinitely reads points from `In` source and write model changes to `Out` sink.
-    pub fn run<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
-        mut streamer: Streamer<In, Out>,
-        algo: Algo<Point>,
-        model: &mut Model<Point>,
-    ) -> Result<(), Box<dyn Error>> {
-        for input in streamer.points {
-            let point_str = input?;
-            let point: Point = serde_json::from_str(&point_str)?;
-            algo.fit(model, point);
-            let balls = serialize_model(model);
-            let output = serde_json::to_string(&balls)?;
-            (streamer.write)(output)?;
+/// Layout of the checkpoints written by a [Streamer], see [Streamer::with_checkpoints].
+#[derive(Serialize)]
+struct CheckpointRecord<'a, Models> {
+    /// The columns of a model or [ModelCheckpoints].
+    #[serde(flatten)]
+    models: Models,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uids: Option<Vec<&'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seq: Option<u64>,
+    /// Seconds since the UNIX epoch.
+    time: f64,
+}
+
+/// The models of a checkpoint written by [Streamer::run_hierarchical] or an A/B run, see [split_checkpoint].
+#[derive(Serialize)]
+struct ModelCheckpoints<'a> {
+    models: Vec<serializer::Columns<Value>>,
+    names: Vec<&'a str>,
+}
+
+/// A point record with a unique id, or an object with a point but no uid, see [Streamer::with_dedup].
+#[derive(Deserialize)]
+struct UidRecord {
+    uid: Option<String>,
+    point: Value,
+}
+
+/// Errors raised by a [Streamer].
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamError {
+    /// A record could not be parsed into a point.
+    ParseError {
+        /// Position of the record in `In` source, starting at 1.
+        line: usize,
+        /// Byte offset in the record where parsing failed.
+        offset: usize,
+        /// The field that failed, e.g. `[1]` for the second coordinate, if any.
+        field: Option<String>,
+        /// What went wrong.
+        message: String,
+    },
+    /// A record was longer than the maximum line length, it was skipped.
+    LineTooLong {
+        /// Position of the record in `In` source, starting at 1.
+        line: usize,
+        /// The maximum line length in bytes.
+        max_len: usize,
+    },
+    /// The run was aborted by the watchdog, see [Watchdog::with_abort].
+    Stalled(StallDiagnostic),
+    /// Some elements of a batch record could not be parsed, the other ones were fitted,
+    /// see [Streamer::with_batches] and [RunReport::batch_errors].
+    BatchError {
+        /// Position of the record in `In` source, starting at 1.
+        line: usize,
+        /// The index of each failed element in the batch, with the reason.
+        errors: Vec<(usize, StreamError)>,
+    },
+    /// The transform of a record panicked, the record was skipped, see [map_point].
+    TransformPanicked {
+        /// The record before the transform.
+        record: String,
+        /// The panic message.
+        reason: String,
+    },
+}
+
+/// The accepted shape of a point record.
+const POINT_HINT: &str = "expected a JSON array of numbers, e.g. [1.0,2.5]";
+
+impl Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::ParseError {
+                line,
+                offset,
+                field,
+                message,
+            } => {
+                write!(f, "line {}, offset {}", line, offset)?;
+                if let Some(field) = field {
+                    write!(f, ", field {}", field)?;
+                }
+                write!(f, ": {} ({})", message, POINT_HINT)
+            }
+            StreamError::LineTooLong { line, max_len } => {
+                write!(f, "line {}: longer than {} bytes", line, max_len)
+            }
+            StreamError::Stalled(stall) => write!(
+                f,
+                "stalled while {:?} for {:?} after {} records",
+                stall.phase, stall.stalled_for, stall.processed
+            ),
+            StreamError::BatchError { line, errors } => {
+                write!(f, "line {}: batch", line)?;
+                for (index, error) in errors {
+                    write!(f, "; element {}: {}", index, error)?;
+                }
+                Ok(())
+            }
+            StreamError::TransformPanicked { reason, .. } => {
+                write!(f, "point transform panicked: {}", reason)
+            }
         }
-        Ok(())
     }
 }
 
-fn serialize_model<Point: PartialEq + Serialize + 'static>(
-    model: &Model<Point>,
-) -> Vec<Map<String, Value>> {
-    let balls: Vec<_> = model
-        .iter_balls()
-        .map(|data| serialize_ball(data))
-        .collect();
-    balls
+impl Error for StreamError {}
+
+/// Splits a batch record, a JSON array of point records, into the records of its elements.
+/// Returns `None` if the record is not a batch.
+pub(crate) fn split_batch(record: &str) -> Option<Vec<String>> {
+    let inner = record.trim_start().strip_prefix('[')?;
+    if !inner.trim_start().starts_with('[') {
+        return None;
+    }
+    let elements: Vec<Value> = serde_json::from_str(record).ok()?;
+    Some(elements.iter().map(Value::to_string).collect())
 }
 
-fn serialize_ball<Point: PartialEq + Serialize>(
-    data: impl Deref<Target = Ball<Point>>,
-) -> Map<String, Value> {
-    let mut map = Map::new();
-    map.insert("center".into(), json!(data.center()));
-    map.insert("radius".into(), json!(data.radius()));
-    map.insert("weight".into(), json!(data.weight()));
-    map
+/// Parses a point record; `line` is the position of the record in `In` source.
+///
+/// The happy path is a plain deserialization, diagnostics are computed only when it fails.
+pub(crate) fn parse_point<Point: DeserializeOwned>(
+    line: usize,
+    record: &str,
+) -> Result<Point, StreamError> {
+    serde_json::from_str(record).map_err(|e| {
+        let suffix = format!(" at line {} column {}", e.line(), e.column());
+        let message = e.to_string();
+        let message = message
+            .strip_suffix(&suffix)
+            .unwrap_or(&message)
+            .to_string();
+        let offset = record
+            .split_inclusive('\n')
+            .take(e.line().saturating_sub(1))
+            .map(|l| l.len())
+            .sum::<usize>()
+            + e.column().saturating_sub(1);
+        StreamError::ParseError {
+            line,
+            offset,
+            field: locate_field(record),
+            message,
+        }
+    })
 }
 
-/// Returns point iterator / model writer that use standard in out.
-pub fn stdio() -> (
-    impl Iterator<Item = Result<String, Box<dyn Error>>>,
-    impl FnMut(String) -> Result<(), Box<dyn Error>>,
-) {
-    let points = io::stdin()
-        .lines()
-        .map(|f| -> Result<String, Box<dyn Error>> { Ok(f?) });
-    let write = |model| {
-        println!("{}", model);
-        Ok(())
+/// Parses a record which may start with a string id, e.g. `["a",1.0,2.5]`, see [Streamer::with_point_ids].
+fn parse_tagged_point<Point: DeserializeOwned>(
+    line: usize,
+    record: &str,
+) -> Result<(Option<String>, Point), StreamError> {
+    let tagged = record
+        .trim_start()
+        .strip_prefix('[')
+        .is_some_and(|r| r.trim_start().starts_with('"'));
+    if !tagged {
+        return Ok((None, parse_point(line, record)?));
+    }
+    let mut values: Vec<Value> = parse_point(line, record)?;
+    let id = match values.remove(0) {
+        Value::String(id) => id,
+        _ => return Ok((None, parse_point(line, record)?)),
     };
-    (points, write)
+    let field = values
+        .iter()
+        .position(|v| !v.is_number())
+        .map(|i| format!("[{}]", i + 1));
+    let point =
+        serde_json::from_value(Value::Array(values)).map_err(|e| StreamError::ParseError {
+            line,
+            offset: 0,
+            field,
+            message: e.to_string(),
+        })?;
+    Ok((Some(id), point))
 }
 
-/// Returns point iterator / model writer that use mpsc channels.
-pub fn channels(
-    point_receiver: Receiver<String>,
-    model_producer: Sender<String>,
-) -> (
-    impl Iterator<Item = Result<String, Box<dyn Error>>>,
-    impl FnMut(String) -> Result<(), Box<dyn Error>>,
-) {
-    let points = point_receiver.into_iter().map(|f| Ok(f));
-    let write = move |model| {
-        model_producer.send(model)?;
-        Ok(())
-    };
-    (points, write)
+/// Finds the first field of the record that is not a coordinate.
+fn locate_field(record: &str) -> Option<String> {
+    match serde_json::from_str::<Value>(record).ok()? {
+        Value::Array(values) => values
+            .iter()
+            .position(|v| !v.is_number())
+            .map(|i| format!("[{}]", i)),
+        Value::Object(map) => map.keys().next().map(|k| format!("\"{}\"", k)),
+        _ => None,
+    }
 }
 
-#[cfg(test)]
-mod tests {
+/// Counters collected while running a [Streamer].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunReport {
+    /// Number of points fitted into the model.
+    pub admitted: usize,
+    /// Number of points dropped by adaptive sampling.
+    pub dropped: usize,
+    /// Evaluation of each model when several configurations are run side by side, see [crate::algorithm::ab_run].
+    pub models: Vec<ModelReport>,
+    /// Number of records read from each source when sources are chained, see [chain_sources].
+    pub phases: PhaseReport,
+    /// The threshold selected by the algorithm, see [Algo::with_auto_threshold].
+    pub threshold: Option<f64>,
+    /// Number of points skipped because a user function panicked, see [Algo::with_panic_guard].
+    pub panicked: usize,
+    /// Time from the arrival of the points to the first emission that reflects them, see [Streamer::with_latency].
+    pub latency: LatencyHistogram,
+    /// Number of points skipped because their uid was seen recently, see [Streamer::with_dedup].
+    pub duplicates: usize,
+    /// Fraction of the last points that were within the radius of their ball, see [Streamer::with_coverage].
+    pub coverage: Option<f64>,
+    /// Whether the coverage over a full window is below the floor of [Streamer::with_coverage].
+    pub low_coverage: bool,
+    /// The number of points per emission chosen by [Streamer::with_adaptive_emit] each time it changed,
+    /// with the number of points fitted so far.
+    pub emit_every: Vec<(usize, usize)>,
+    /// Number of administration commands skipped because they could not be applied,
+    /// e.g. for an unknown ball, see [Streamer::with_admin_commands].
+    pub failed_commands: usize,
+    /// The batch records with elements that could not be parsed and were skipped, see [Streamer::with_batches].
+    pub batch_errors: Vec<StreamError>,
+}
 
-    use std::sync::mpsc;
+/// Number of records read from each source of [chain_sources].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PhaseReport {
+    /// Records read from the history source.
+    pub history: usize,
+    /// Records read from the live source.
+    pub live: usize,
+    /// Live records skipped because they were already in the history source.
+    pub skipped: usize,
+}
 
-    use crate::{space, streamer::*};
+/// Shared handle on the counters of [chain_sources], see [Streamer::with_phases].
+#[derive(Clone, Debug, Default)]
+pub struct Phases(Rc<RefCell<PhaseReport>>);
 
-    #[test]
-    fn test_serialize_ball() {
-        let obj = serialize_ball(&Ball::new(vec![3., 5.1], 4.7, 0.999));
-        let json = serde_json::to_string(&obj).unwrap();
-        assert_eq!(
-            r#"{"center":[3.0,5.1],"radius":2.16794833886788,"weight":0.999}"#,
-            json
-        );
+impl Phases {
+    /// Current counters.
+    pub fn get(&self) -> PhaseReport {
+        self.0.borrow().clone()
     }
+}
 
-    #[test]
-    fn test_serialize_model() {
-        let mut model = Model::new(space::euclid_dist);
-        let v = model.add_ball(Ball::new(vec![3., 5.1], 4.7, 0.999), vec![]);
-        model.add_ball(Ball::new(vec![1.2, 6.], 1.3, 3.998), vec![v.as_neighbor()]);
-        let obj = serialize_model(&model);
-        let json = serde_json::to_string(&obj).unwrap();
-        assert_eq!(
-            r#"[{"center":[3.0,5.1],"radius":2.16794833886788,"weight":0.999},{"center":[1.2,6.0],"radius":1.140175425099138,"weight":3.998}]"#,
-            json
-        );
+/// How [chain_sources] switches from the history source to the live source.
+#[derive(Clone, Copy, Debug)]
+pub enum CutoverPolicy {
+    /// Reads the history source fully, then the live source.
+    Drain,
+    /// Reads the history source fully, then the live source, skipping live records which timestamp
+    /// is not later than the last history timestamp. The function extracts the timestamp from a record;
+    /// records without timestamp are never skipped.
+    Timestamp(fn(&str) -> Option<f64>),
+}
+
+/// Shared handle on a bounded uniform random sample of the points read by a [Streamer],
+/// see [Streamer::with_global_reservoir].
+#[derive(Clone, Debug)]
+pub struct Reservoir(Rc<RefCell<Sample>>);
+
+/// Reservoir sampling state.
+#[derive(Debug)]
+struct Sample {
+    capacity: usize,
+    seen: u64,
+    points: Vec<String>,
+    /// Seeded from the algorithm configuration on the first offer.
+    rng: Option<StdRng>,
+}
+
+impl Reservoir {
+    /// Builds an empty reservoir that retains at most `capacity` points.
+    fn new(capacity: usize) -> Self {
+        Self(Rc::new(RefCell::new(Sample {
+            capacity,
+            seen: 0,
+            points: Vec::with_capacity(capacity),
+            rng: None,
+        })))
     }
 
-    #[test]
-    fn test_streamer() {
-        let algo = Algo::new(space::euclid_dist, space::real_combine);
-        let mut model = Model::new(space::euclid_dist);
-        let points = vec![Ok(String::from("[1.0,1.0]"))].into_iter();
-        let mut result = String::new();
-        let write = |s| {
-            result = s;
-            Ok(())
-        };
-        let streamer = Streamer::new(points, write);
-        match Streamer::run(streamer, algo, &mut model) {
-            Ok(()) => assert_eq!(
-                r#"[{"center":[1.0,1.0],"radius":null,"weight":0.0}]"#,
-                result
-            ),
-            Err(_) => panic!(),
-        };
+    /// The sampled points, as they were read from `In` source.
+    pub fn points(&self) -> Vec<String> {
+        self.0.borrow().points.clone()
     }
 
-    #[test]
-    fn test_channels() {
-        let (point_producer, point_receiver) = mpsc::channel();
-        let (model_producer, model_receiver) = mpsc::channel();
-        let (mut points, mut write) = channels(point_receiver, model_producer);
-        point_producer.send(String::from("point")).unwrap();
-        let p = points.next().unwrap().unwrap();
-        assert_eq!("point", p);
-        (write)(String::from("model")).unwrap();
-        let m = model_receiver.recv().unwrap();
-        assert_eq!("model", m);
+    /// Number of points offered to the reservoir so far.
+    pub fn seen(&self) -> u64 {
+        self.0.borrow().seen
+    }
+
+    /// Offers a point: every point seen so far has the same probability to be retained.
+    fn offer(&self, point: &str, rng: impl FnOnce() -> StdRng) {
+        let mut sample = self.0.borrow_mut();
+        sample.seen += 1;
+        if sample.points.len() < sample.capacity {
+            sample.points.push(String::from(point));
+            return;
+        }
+        let seen = sample.seen;
+        let index = sample.rng.get_or_insert_with(rng).gen_range(0..seen) as usize;
+        if index < sample.capacity {
+            sample.points[index] = String::from(point);
+        }
+    }
+}
+
+/// Evaluation metrics of a model fitted by a [Streamer].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModelReport {
+    /// Name of the configuration used to fit the model.
+    pub name: String,
+    /// Number of points that were scored, i.e. that were received when the model was not empty.
+    pub scored: usize,
+    /// Average anomaly score of incoming points before they were fitted, see [Model::anomaly_score].
+    pub mean_anomaly_score: f64,
+}
+
+impl ModelReport {
+    /// Builds an empty report for the named configuration.
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Default::default()
+        }
+    }
+
+    /// Updates the average anomaly score.
+    fn add_score(&mut self, score: f64) {
+        self.scored += 1;
+        self.mean_anomaly_score += (score - self.mean_anomaly_score) / self.scored as f64;
+    }
+}
+
+/// A model emitted by a [Streamer], recorded in a [History].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Emission {
+    /// Sequence number of the emission, starting at 0.
+    pub seq: u64,
+    /// Emission time in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The serialized model.
+    pub model: String,
+}
+
+/// Keeps the last emitted models in memory, evicting the oldest ones beyond capacity.
+/// ```
+/// use fluent_data::streamer::History;
+///
+/// let mut history = History::new(2);
+/// for model in ["a", "b", "c"] {
+///     history.push(model.into());
+/// }
+/// assert!(history.get(0).is_none());
+/// assert_eq!("b", history.get(1).unwrap().model);
+/// assert_eq!("c", history.latest().unwrap().model);
+/// ```
+#[derive(Clone, Debug)]
+pub struct History {
+    capacity: usize,
+    entries: VecDeque<Emission>,
+    next_seq: u64,
+}
+
+impl History {
+    /// Builds an empty history that keeps at most `capacity` models.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            next_seq: 0,
+        }
+    }
+
+    /// Records a model and returns its sequence number.
+    pub fn push(&mut self, model: String) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        if self.capacity == 0 {
+            return seq;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        self.entries.push_back(Emission {
+            seq,
+            timestamp,
+            model,
+        });
+        seq
+    }
+
+    /// The maximum number of models kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the number of models kept, dropping the oldest ones if there are more.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// The last recorded model.
+    pub fn latest(&self) -> Option<&Emission> {
+        self.entries.back()
+    }
+
+    /// The model with the given sequence number, unless it was evicted.
+    pub fn get(&self, seq: u64) -> Option<&Emission> {
+        let first = self.entries.front()?.seq;
+        let index = seq.checked_sub(first)?;
+        self.entries.get(index as usize)
+    }
+
+    /// The recorded models which sequence number is in `from..to`.
+    pub fn range(&self, from: u64, to: u64) -> impl Iterator<Item = &Emission> {
+        self.entries
+            .iter()
+            .skip_while(move |e| e.seq < from)
+            .take_while(move |e| e.seq < to)
+    }
+}
+
+/// Drops points that would be merged into an existing ball in order to keep
+/// the admitted rate near a target.
+struct AdaptiveSampling {
+    target_rate: f64,
+    /// Seeded from the algorithm configuration on the first decision.
+    rng: Option<StdRng>,
+}
+
+impl AdaptiveSampling {
+    /// Decides if a point should be fitted.
+    /// Points that would create a new ball are novel and always admitted,
+    /// others are admitted only if the admitted rate is behind the target.
+    fn admit(&mut self, covered: bool, report: &RunReport, rng: impl FnOnce() -> StdRng) -> bool {
+        if !covered {
+            return true;
+        }
+        let seen = (report.admitted + report.dropped + 1) as f64;
+        let keep = (self.target_rate * seen - report.admitted as f64).clamp(0., 1.);
+        self.rng.get_or_insert_with(rng).gen::<f64>() < keep
+    }
+}
+
+/// Administration command read from the `In` source instead of a point, see [Streamer::with_admin_commands].
+/// Balls are identified by their index in the [Model::iter_balls] order, not by [Ball::id](crate::model::Ball::id),
+/// e.g. `{"cmd":"freeze","index":7}`; indices shift when balls are merged or removed.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Command {
+    /// Freezes a ball, see [Model::freeze_ball].
+    Freeze { index: usize },
+    /// Unfreezes a ball, see [Model::unfreeze_ball].
+    Unfreeze { index: usize },
+}
+
+impl Command {
+    /// Applies the command to the model.
+    pub fn apply<Point: PartialEq + 'static>(
+        &self,
+        model: &Model<Point>,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            Command::Freeze { index } => model.freeze_ball(*index),
+            Command::Unfreeze { index } => model.unfreeze_ball(*index),
+        }
+    }
+}
+
+/// The ball which includes a fitted point, see [Streamer::run_with_hook].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Assignment {
+    /// The id of the point record, or its position in the `In` source starting at 0 if it has none,
+    /// see [Streamer::with_point_ids].
+    pub id: String,
+    /// The index of the ball in the [Model::iter_balls] order.
+    pub ball: usize,
+}
+
+/// Acknowledgement of a record read from the `In` source, identified by its position in the source.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ack {
+    /// The record was processed: it was fitted or dropped by sampling.
+    Ack(usize),
+    /// The record could not be read or parsed.
+    Nack(usize),
+}
+
+/// Why a record was not fitted, see [Streamer::run_with_drop_sink].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DropReason {
+    /// The record is not a point, e.g. it is not JSON or a coordinate is not a number, like `NaN`.
+    Unparsable,
+    /// The record is longer than the maximum line length, see [lines].
+    TooLong,
+    /// The point has a uid seen recently, see [Streamer::with_dedup].
+    Duplicate,
+    /// The point was dropped by adaptive sampling, see [Streamer::with_adaptive_sampling].
+    Sampled,
+    /// The point is outside of the valid region or has another dimension, see [Algo::with_valid_region].
+    OutOfRegion,
+    /// A user function panicked on the point, see [Algo::with_panic_guard] and [map_point].
+    Panicked,
+}
+
+/// Decides if a point should be fitted and updates the report accordingly.
+fn admit<Point: PartialEq + 'static>(
+    sampling: &mut Option<AdaptiveSampling>,
+    report: &mut RunReport,
+    algo: &Algo<Point>,
+    model: &Model<Point>,
+    point: &Point,
+) -> bool {
+    if let Some(sampling) = sampling {
+        if !sampling.admit(algo.is_covered(model, point), report, || algo.rng()) {
+            report.dropped += 1;
+            return false;
+        }
+    }
+    report.admitted += 1;
+    true
+}
+
+impl<In, Out> Streamer<In, Out>
+where
+    In: Iterator<Item = Result<String, Box<dyn Error>>>,
+    Out: FnMut(String) -> Result<(), Box<dyn Error>>,
+{
+    /// builds a new streamer instance.
+    pub fn new(points: In, write: Out) -> Self {
+        Self {
+            points,
+            write,
+            sampling: None,
+            auto_emit: true,
+            emit_every: 1,
+            service_config: None,
+            unemitted: 0,
+            report: RunReport::default(),
+            line: 0,
+            phases: None,
+            serializer: JsonSerializer,
+            buffer: String::new(),
+            assignments: false,
+            history: None,
+            reservoir: None,
+            admin: false,
+            soft: None,
+            memberships: vec![],
+            watchdog: None,
+            checkpoint: None,
+            ids: false,
+            id: String::new(),
+            assignment: None,
+            latency: None,
+            dedup: None,
+            uid: None,
+            coverage: None,
+            batches: false,
+            adaptive_emit: None,
+            heartbeat: None,
+            emitted: false,
+            dropped: None,
+            drops: None,
+        }
+    }
+}
+
+impl<In, Out, Ser> Streamer<In, Out, Ser>
+where
+    In: Iterator<Item = Result<String, Box<dyn Error>>>,
+    Out: FnMut(String) -> Result<(), Box<dyn Error>>,
+{
+    /// Changes the way models are serialized before they are written to `Out` sink, see [crate::serializer].
+    /// ```
+    /// use fluent_data::{serializer::CsvSerializer, streamer, Streamer};
+    ///
+    /// let (points, write) = streamer::stdio();
+    /// let streamer = Streamer::new(points, write).with_serializer(CsvSerializer);
+    /// ```
+    pub fn with_serializer<S>(self, serializer: S) -> Streamer<In, Out, S> {
+        Streamer {
+            points: self.points,
+            write: self.write,
+            sampling: self.sampling,
+            auto_emit: self.auto_emit,
+            emit_every: self.emit_every,
+            service_config: self.service_config,
+            unemitted: self.unemitted,
+            report: self.report,
+            line: self.line,
+            phases: self.phases,
+            serializer,
+            buffer: self.buffer,
+            assignments: self.assignments,
+            history: self.history,
+            reservoir: self.reservoir,
+            admin: self.admin,
+            soft: self.soft,
+            memberships: self.memberships,
+            watchdog: self.watchdog,
+            checkpoint: self.checkpoint,
+            ids: self.ids,
+            id: self.id,
+            assignment: self.assignment,
+            latency: self.latency,
+            dedup: self.dedup,
+            uid: self.uid,
+            coverage: self.coverage,
+            batches: self.batches,
+            adaptive_emit: self.adaptive_emit,
+            heartbeat: self.heartbeat,
+            emitted: self.emitted,
+            dropped: self.dropped,
+            drops: self.drops,
+        }
+    }
+
+    /// Subsamples points which lie inside existing balls, keeping the admitted rate near `target_rate`.
+    ///
+    /// `target_rate` is the fraction of incoming points that should be fitted, between 0 and 1.
+    /// Novel points, i.e. points that would create a new ball, are always admitted;
+    /// thus the actual admitted rate may exceed the target when the stream is not stationary.
+    /// Dropped points do not produce a model.
+    /// Decisions are random, seed the algorithm with [Algo::with_seed] for reproducible runs.
+    ///
+    /// Panics if `target_rate` is not between 0 and 1.
+    /// ```
+    /// use fluent_data::{streamer, Streamer};
+    ///
+    /// let (points, write) = streamer::stdio();
+    /// let streamer = Streamer::new(points, write).with_adaptive_sampling(0.1);
+    /// ```
+    pub fn with_adaptive_sampling(mut self, target_rate: f64) -> Self {
+        assert!(
+            (0. ..=1.).contains(&target_rate),
+            "the target rate must be between 0 and 1"
+        );
+        self.sampling = Some(AdaptiveSampling {
+            target_rate,
+            rng: None,
+        });
+        self
+    }
+
+    /// Disables automatic emissions: instead of writing the model after each point,
+    /// the model is written when [Streamer::emit_now] is called and once more when `In` source is exhausted.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))].into_iter();
+    /// let mut emitted = 0;
+    /// let mut streamer = Streamer::new(points, |_| Ok(emitted += 1)).with_manual_emit();
+    /// while streamer.fit_next(&algo, &mut model).unwrap() {
+    ///     // host scheduling...
+    /// }
+    /// streamer.emit_now(&model).unwrap();
+    /// drop(streamer);
+    /// assert_eq!(1, emitted);
+    /// ```
+    pub fn with_manual_emit(mut self) -> Self {
+        self.auto_emit = false;
+        self
+    }
+
+    /// Writes the model after every `count` points instead of after each point,
+    /// and once more when `In` source is exhausted if points were fitted since the last emission.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = (0..7).map(|i| Ok(format!("[{}]", i)));
+    /// let mut emitted = 0;
+    /// let streamer = Streamer::new(points, |_| Ok(emitted += 1)).with_emit_every(3);
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(3, emitted);
+    /// ```
+    pub fn with_emit_every(mut self, count: usize) -> Self {
+        self.emit_every = count.max(1);
+        self
+    }
+
+    /// Same as [Streamer::with_emit_every] with the [ServiceConfig::emit_every] of a service, which is read before each
+    /// emission so that a change made while the streamer runs, e.g. by [crate::service::apply_config], applies at once.
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use fluent_data::{service::ServiceConfig, Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = (0..7).map(|i| Ok(format!("[{}]", i)));
+    /// let config = Arc::new(Mutex::new(ServiceConfig { emit_every: 3, ..Default::default() }));
+    /// let mut emitted = 0;
+    /// let streamer = Streamer::new(points, |_| Ok(emitted += 1)).with_service_config(config);
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(3, emitted);
+    /// ```
+    pub fn with_service_config(mut self, config: Arc<Mutex<ServiceConfig>>) -> Self {
+        self.service_config = Some(config);
+        self
+    }
+
+    /// The number of fitted points per emission, see [Streamer::with_emit_every].
+    fn emit_every(&self) -> usize {
+        match &self.service_config {
+            Some(config) => config.lock().unwrap().emit_every.max(1),
+            None => self.emit_every,
+        }
+    }
+
+    /// Emits less often when writes to `Out` sink slow down, so that the write time per fitted point stays below
+    /// `target_write_ms` milliseconds instead of building a backlog.
+    ///
+    /// Each write is timed and the number of points per emission is set to the write time divided by the target,
+    /// between [Streamer::with_emit_every], 1 by default, and `max_every`. The write time is smoothed by an exponential
+    /// moving average, so that a single slow write does not throttle emissions, and it recovers within a few fast writes.
+    /// The chosen numbers are recorded in [RunReport::emit_every].
+    ///
+    /// Panics if `target_write_ms` is not a positive number of milliseconds.
+    /// ```
+    /// use fluent_data::{streamer, Streamer};
+    ///
+    /// let (points, write) = streamer::stdio();
+    /// let streamer = Streamer::new(points, write).with_adaptive_emit(5., 100);
+    /// ```
+    pub fn with_adaptive_emit(mut self, target_write_ms: f64, max_every: usize) -> Self {
+        assert!(
+            target_write_ms > 0. && target_write_ms.is_finite(),
+            "the target write time must be a positive number of milliseconds"
+        );
+        let origin = Instant::now();
+        self.adaptive_emit = Some(AdaptiveEmit {
+            target: Duration::from_secs_f64(target_write_ms / 1000.),
+            max_every: max_every.max(1),
+            every: 1,
+            write_time: None,
+            clock: Box::new(move || origin.elapsed()),
+        });
+        self
+    }
+
+    /// Writes a heartbeat message when nothing was written to `Out` sink for `period`, e.g. while points are
+    /// sampled out or between emissions with [Streamer::with_emit_every], so that consumers can tell a quiet stream
+    /// from a dead one. Only serializers that frame their messages write heartbeats, see [ModelSerializer::heartbeat].
+    ///
+    /// The period is checked when a record is read, thus an idle `In` source does not produce heartbeats.
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use fluent_data::{serializer::EnvelopeSerializer, streamer, Streamer};
+    ///
+    /// let (points, write) = streamer::stdio();
+    /// let streamer = Streamer::new(points, write)
+    ///     .with_serializer(EnvelopeSerializer::new())
+    ///     .with_heartbeat(Duration::from_secs(5));
+    /// ```
+    pub fn with_heartbeat(mut self, period: Duration) -> Self {
+        self.heartbeat = Some(Heartbeat {
+            period,
+            last: Instant::now(),
+        });
+        self
+    }
+
+    /// Measures the time from the arrival of each fitted point to the first emission that reflects it,
+    /// see [RunReport::latency] and [Streamer::take_latency].
+    ///
+    /// The arrival is given by the [Timestamps] policy, the emission time by the `clock`,
+    /// e.g. [unix_clock], which gives the time elapsed since an origin common to both.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, streamer::{self, Timestamps}, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))].into_iter();
+    /// let streamer = Streamer::new(points, |_| Ok(())).with_latency(Timestamps::Ingest, streamer::unix_clock);
+    /// let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(2, report.latency.count());
+    /// ```
+    pub fn with_latency(
+        mut self,
+        timestamps: Timestamps,
+        clock: impl Fn() -> Duration + 'static,
+    ) -> Self {
+        self.latency = Some(Latency {
+            timestamps,
+            clock: Box::new(clock),
+            arrival: Duration::ZERO,
+            pending: vec![],
+        });
+        self
+    }
+
+    /// Measures the fraction of the last `window` fitted points that were within the radius of the ball
+    /// which most probably included them before they were fitted, see [RunReport::coverage] and [Ball::contains].
+    ///
+    /// A low coverage suggests that the threshold or the decay do not fit the data:
+    /// [RunReport::low_coverage] tells when the coverage over a full window is below `floor`,
+    /// e.g. for the caller to log a warning, see [Streamer::report].
+    /// The first point, which creates the first ball, is not counted.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = (0..10).map(|i| Ok(format!("[{}]", i % 2)));
+    /// let streamer = Streamer::new(points, |_| Ok(())).with_coverage(5, 0.5);
+    /// let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+    /// assert!(report.coverage.unwrap() > 0.5);
+    /// assert!(!report.low_coverage);
+    /// ```
+    pub fn with_coverage(mut self, window: usize, floor: f64) -> Self {
+        self.coverage = Some(Coverage {
+            window: window.max(1),
+            floor,
+            recent: VecDeque::with_capacity(window),
+            inside: 0,
+        });
+        self
+    }
+
+    /// Gets the latencies measured since the previous call and resets them, e.g. for each reporting interval,
+    /// see [Streamer::with_latency].
+    pub fn take_latency(&mut self) -> LatencyHistogram {
+        mem::take(&mut self.report.latency)
+    }
+
+    /// Reports the counters of chained sources in the run report, see [chain_sources].
+    pub fn with_phases(mut self, phases: Phases) -> Self {
+        self.phases = Some(phases);
+        self
+    }
+
+    /// Wraps each emitted model with the number of points assigned to each ball since the previous emission:
+    /// `{"model":<model>,"assignments":{"<ball index>":<count>,...,"removed":<count>}}`
+    /// where ball indices refer to the emitted model and `removed` counts points assigned to balls
+    /// that were removed since, see [Model::take_assignments]. The serializer must produce JSON.
+    /// Serializers that frame their messages carry this body in an [crate::message::Envelope::Composite] message.
+    /// Keys are sorted as strings, e.g. `"10"` comes before `"2"`.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[1.0]"))].into_iter();
+    /// let mut output = String::new();
+    /// let streamer = Streamer::new(points, |s| Ok(output = s)).with_assignments();
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(
+    ///     r#"{"model":[{"center":[1.0],"radius":null,"weight":0.0}],"assignments":{"0":1,"removed":0}}"#,
+    ///     output
+    /// );
+    /// ```
+    pub fn with_assignments(mut self) -> Self {
+        self.assignments = true;
+        self
+    }
+
+    /// Records each emitted model in the given history.
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use fluent_data::{Algo, Model, space, streamer::History, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))].into_iter();
+    /// let history = Arc::new(Mutex::new(History::new(60)));
+    /// let streamer = Streamer::new(points, |_| Ok(())).with_history(history.clone());
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(1, history.lock().unwrap().latest().unwrap().seq);
+    /// ```
+    pub fn with_history(mut self, history: Arc<Mutex<History>>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Retains a uniform random sample of at most `size` points among all points read from `In` source,
+    /// including points dropped by adaptive sampling, e.g. for periodic full re-fits.
+    /// Records that cannot be parsed are not sampled.
+    /// Decisions are random, seed the algorithm with [Algo::with_seed] for reproducible samples.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = (0..100).map(|i| Ok(format!("[{}]", i)));
+    /// let streamer = Streamer::new(points, |_| Ok(())).with_global_reservoir(10);
+    /// let reservoir = streamer.reservoir().unwrap();
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(10, reservoir.points().len());
+    /// ```
+    pub fn with_global_reservoir(mut self, size: usize) -> Self {
+        self.reservoir = Some(Reservoir::new(size));
+        self
+    }
+
+    /// A handle on the global reservoir, if any, see [Streamer::with_global_reservoir].
+    pub fn reservoir(&self) -> Option<Reservoir> {
+        self.reservoir.clone()
+    }
+
+    /// Accepts point records that start with a string id, e.g. `["a",1.0,2.5]`,
+    /// and wraps each emitted model with the [Assignment] of the last fitted point:
+    /// `{"model":<model>,"assignment":{"id":<id>,"ball":<ball index>}}`.
+    /// Points without id get their position in the `In` source as id. The serializer must produce JSON.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from(r#"["a",1.0]"#))].into_iter();
+    /// let mut output = String::new();
+    /// let streamer = Streamer::new(points, |s| Ok(output = s)).with_point_ids();
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert!(output.ends_with(r#""assignment":{"id":"a","ball":0}}"#));
+    /// ```
+    pub fn with_point_ids(mut self) -> Self {
+        self.ids = true;
+        self
+    }
+
+    /// Accepts batch records, JSON arrays of point records e.g. `[[1.0,2.5],[3.0,0.5]]`.
+    /// The points of a batch are fitted in order and the model is written once at the end of the batch,
+    /// whatever [Streamer::with_emit_every] is set to.
+    ///
+    /// Elements that cannot be parsed are skipped, the other ones are fitted,
+    /// and a [StreamError::BatchError] in [RunReport::batch_errors] reports the index of each failed element.
+    /// Batches are not accepted by default because a point may itself be an array of arrays.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[[1.0],[2.0],[3.0]]"))].into_iter();
+    /// let mut outputs = vec![];
+    /// let streamer = Streamer::new(points, |s| Ok(outputs.push(s))).with_batches();
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(1, outputs.len());
+    /// ```
+    pub fn with_batches(mut self) -> Self {
+        self.batches = true;
+        self
+    }
+
+    /// Wraps each emitted model with the membership probabilities of the last fitted point,
+    /// computed by [Model::soft_predict] before the point is fitted:
+    /// `{"model":<model>,"memberships":[[<ball index>,<probability>],...]}`
+    /// where ball indices refer to the model before the point was fitted. The serializer must produce JSON.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))].into_iter();
+    /// let mut output = String::new();
+    /// let streamer = Streamer::new(points, |s| Ok(output = s)).with_soft_assignments(1.);
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert!(output.ends_with(r#""memberships":[[0,1.0]]}"#));
+    /// ```
+    pub fn with_soft_assignments(mut self, temperature: f64) -> Self {
+        self.soft = Some(temperature);
+        self
+    }
+
+    /// Accepts administration [Command]s in `In` source along with points.
+    /// A command is applied to the model, which is then written to `Out` sink like after a point is fitted.
+    /// A command that cannot be applied, e.g. for an unknown ball, is skipped and counted in [RunReport::failed_commands].
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[1.0]")), Ok(String::from(r#"{"cmd":"freeze","index":0}"#))];
+    /// let streamer = Streamer::new(points.into_iter(), |_| Ok(())).with_admin_commands();
+    /// Streamer::run(streamer, algo, &mut model).unwrap();
+    /// assert!(model.iter_balls().next().unwrap().is_frozen());
+    /// ```
+    pub fn with_admin_commands(mut self) -> Self {
+        self.admin = true;
+        self
+    }
+
+    /// Monitors the progress of the streamer loop with the [Watchdog],
+    /// e.g. to detect a write closure blocked on a dead connection.
+    pub fn with_watchdog(mut self, watchdog: Watchdog) -> Self {
+        self.watchdog = Some(watchdog.start());
+        self
+    }
+
+    /// Writes a checkpoint of the full model to `write` at the given [Cadence], and once more when `In` source is exhausted.
+    ///
+    /// Checkpoints are written in the [crate::serializer::ColumnarJsonSerializer] format with the state of the balls,
+    /// see [crate::serializer::checkpoint_columns], a model is restored with [restore_checkpoint].
+    /// [Streamer::run_hierarchical] and A/B runs write the checkpoints of all their models together,
+    /// which are split with [split_checkpoint].
+    /// With [Streamer::with_dedup], the remembered uids are added in a `uids` field.
+    /// When the serializer numbers its messages, the sequence number of the last written model is added
+    /// in a `seq` field, so that a restarted streamer continues the numbering, see [parse_checkpoint_seq].
+    /// The time at which the checkpoint is written is added in a `time` field, in seconds since the Unix epoch,
+    /// a model is restored and decayed by the elapsed time with [restore_checkpoint].
+    /// The [checkpoint_file] writer replaces a file atomically.
+    /// ```no_run
+    /// use fluent_data::{streamer::{self, Cadence}, Streamer};
+    ///
+    /// let (points, write) = streamer::stdio();
+    /// let streamer = Streamer::new(points, write)
+    ///     .with_checkpoints(Cadence::Points(1000), streamer::checkpoint_file("model.json"));
+    /// ```
+    pub fn with_checkpoints(
+        mut self,
+        cadence: Cadence,
+        write: impl FnMut(String) -> Result<(), Box<dyn Error>> + 'static,
+    ) -> Self {
+        self.checkpoint = Some(Checkpoint {
+            cadence,
+            write: Box::new(write),
+            updates: 0,
+            last: Instant::now(),
+        });
+        self
+    }
+
+    /// Accepts point records with a unique id, `{"uid":<uid>,"point":<point>}`, and skips the points
+    /// which uid was seen recently, e.g. points delivered again by an at-least-once source.
+    /// Skipped points are counted in [RunReport::duplicates]; records without uid, bare points or
+    /// `{"point":<point>}` objects, are always fitted.
+    ///
+    /// The remembered uids are written in checkpoints, see [Streamer::with_checkpoints],
+    /// so that a restarted streamer can restore them with [parse_checkpoint_uids] and [Dedup::with_seen].
+    /// ```
+    /// use fluent_data::{Algo, Model, space, streamer::Dedup, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![r#"{"uid":"a","point":[1.0]}"#, r#"{"uid":"a","point":[1.0]}"#, "[2.0]"];
+    /// let points = points.into_iter().map(|p| Ok(String::from(p)));
+    /// let streamer = Streamer::new(points, |_| Ok(())).with_dedup(Dedup::new(1000));
+    /// let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+    /// assert_eq!(2, report.admitted);
+    /// assert_eq!(1, report.duplicates);
+    /// ```
+    pub fn with_dedup(mut self, dedup: Dedup) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    /// Counters collected so far.
+    pub fn report(&self) -> &RunReport {
+        &self.report
+    }
+
+    /// Reads the next point from `In` source and fits it into the model.
+    /// The model is then written to `Out` sink unless automatic emissions are disabled.
+    /// Returns `false` when `In` source is exhausted.
+    pub fn fit_next<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        algo: &Algo<Point>,
+        model: &mut Model<Point>,
+    ) -> Result<bool, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        let point_str = match self.next_record() {
+            Some(input) => input?,
+            None => return Ok(false),
+        };
+        self.fit_record(algo, model, &point_str)?;
+        Ok(true)
+    }
+
+    /// Fits a point record pushed by the caller, e.g. from an event callback, instead of reading it from `In` source.
+    /// The record is processed as if it was read: the model is written to `Out` sink when an emission is due,
+    /// and it is also returned.
+    /// ```
+    /// use std::{error::Error, iter};
+    ///
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let mut streamer = Streamer::new(iter::empty::<Result<String, Box<dyn Error>>>(), |_| Ok(()));
+    /// let output = streamer.push(&algo, &mut model, String::from("[1.0]")).unwrap();
+    /// assert_eq!(Some(r#"[{"center":[1.0],"radius":null,"weight":0.0}]"#), output.as_deref());
+    /// ```
+    pub fn push<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        algo: &Algo<Point>,
+        model: &mut Model<Point>,
+        point_json: String,
+    ) -> Result<Option<String>, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        self.received();
+        self.emitted = false;
+        self.fit_record(algo, model, &point_json)?;
+        Ok(self.emitted.then(|| self.buffer.clone()))
+    }
+
+    /// Fits a point record, or applies it if it is a command, then measures the drift of the model if it was emitted,
+    /// see [Algo::measure_drift].
+    fn fit_record<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        algo: &Algo<Point>,
+        model: &mut Model<Point>,
+        point_str: &str,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        let emitted = mem::replace(&mut self.emitted, false);
+        let applied = self.apply_record(algo, model, point_str);
+        if self.emitted {
+            algo.measure_drift(model);
+        }
+        self.emitted |= emitted;
+        applied
+    }
+
+    /// Fits a point record, or applies it if it is a command.
+    fn apply_record<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        algo: &Algo<Point>,
+        model: &mut Model<Point>,
+        point_str: &str,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        self.check_stall()?;
+        self.beat::<Point>()?;
+        if let Some(command) = self.command(point_str) {
+            if command.apply(model).is_err() {
+                self.report.failed_commands += 1;
+                return Ok(());
+            }
+            return self.auto_emit(model);
+        }
+        if self.batches {
+            if let Some(elements) = split_batch(point_str) {
+                return self.fit_batch(algo, model, &elements);
+            }
+        }
+        let point: Point = self.parse_record(point_str)?;
+        self.sample(algo, point_str);
+        let fitted = self.fit_point(algo, model, point);
+        self.report_drop(point_str);
+        if fitted {
+            self.auto_emit(model)?;
+        }
+        Ok(())
+    }
+
+    /// Fits the elements of a batch record in order, then writes the model once if any point was fitted.
+    /// Elements that cannot be parsed are skipped and reported together once the batch is processed.
+    fn fit_batch<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        &mut self,
+        algo: &Algo<Point>,
+        model: &mut Model<Point>,
+        elements: &[String],
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        let mut errors = vec![];
+        let mut fitted = 0;
+        for (index, element) in elements.iter().enumerate() {
+            match self.parse_record::<Point>(element) {
+                Ok(point) => {
+                    self.sample(algo, element);
+                    if self.fit_point(algo, model, point) {
+                        fitted += 1;
+                    }
+                    self.report_drop(element);
+                }
+                Err(reason) => {
+                    self.dropped = Some(DropReason::Unparsable);
+                    self.report_drop(element);
+                    errors.push((index, reason));
+                }
+            }
+        }
+        if fitted > 0 {
+            self.unemitted += fitted;
+            if self.auto_emit {
+                self.emit_now(model)?;
+            }
+            self.checkpoint(model, false)?;
+        }
+        if !errors.is_empty() {
+            self.report.batch_errors.push(StreamError::BatchError {
+                line: self.line,
+                errors,
+            });
+        }
+        Ok(())
+    }
+
+    /// Serializes the model and writes it to `Out` sink.
+    pub fn emit_now<Point: PartialEq + Serialize + 'static>(
+        &mut self,
+        model: &Model<Point>,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        self.buffer.clear();
+        if self.assignments || self.soft.is_some() || self.ids {
+            let body = self.wrap(model)?;
+            self.serializer.frame(&body, &mut self.buffer)?;
+        } else {
+            self.serializer.serialize(model, &mut self.buffer)?;
+        }
+        if let Some(history) = &self.history {
+            history.lock().unwrap().push(self.buffer.clone());
+        }
+        let output = self.buffer.clone();
+        self.unemitted = 0;
+        self.emitted = true;
+        let start = self
+            .adaptive_emit
+            .as_ref()
+            .map(|adaptive| (adaptive.clock)());
+        self.write_out(output)?;
+        let emit_every = self.emit_every();
+        if let (Some(adaptive), Some(start)) = (&mut self.adaptive_emit, start) {
+            let elapsed = ((adaptive.clock)().saturating_sub(start)).as_secs_f64();
+            let write_time = match adaptive.write_time {
+                Some(smoothed) => smoothed + WRITE_TIME_SMOOTHING * (elapsed - smoothed),
+                None => elapsed,
+            };
+            adaptive.write_time = Some(write_time);
+            let ratio = write_time / adaptive.target.as_secs_f64();
+            let every =
+                (ratio.ceil() as usize).clamp(emit_every, adaptive.max_every.max(emit_every));
+            if every != adaptive.every {
+                adaptive.every = every;
+                self.report.emit_every.push((self.report.admitted, every));
+            }
+        }
+        if let Some(latency) = &mut self.latency {
+            let now = (latency.clock)();
+            for arrival in latency.pending.drain(..) {
+                self.report.latency.record(now.saturating_sub(arrival));
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the body that embeds the model with the assignments, memberships or ids of the points,
+    /// `{"model":...,"assignments":...,"memberships":...,"assignment":...}`.
+    fn wrap<Point: PartialEq + Serialize + 'static>(
+        &mut self,
+        model: &Model<Point>,
+    ) -> Result<String, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        let mut body = String::from(r#"{"model":"#);
+        self.serializer.serialize_embedded(model, &mut body)?;
+        if self.assignments {
+            let assignments = model.take_assignments();
+            let mut counts: BTreeMap<String, Value> = assignments
+                .balls
+                .iter()
+                .enumerate()
+                .map(|(i, count)| (i.to_string(), json!(count)))
+                .collect();
+            counts.insert("removed".into(), json!(assignments.removed));
+            body.push_str(r#","assignments":"#);
+            body.push_str(&serde_json::to_string(&counts)?);
+        }
+        if self.soft.is_some() {
+            body.push_str(r#","memberships":"#);
+            body.push_str(&serde_json::to_string(&self.memberships)?);
+        }
+        if self.ids {
+            body.push_str(r#","assignment":"#);
+            body.push_str(&serde_json::to_string(&self.assignment)?);
+        }
+        body.push('}');
+        Ok(body)
+    }
+
+    /// Writes to `Out` sink, keeping the watchdog informed.
+    fn write_out(&mut self, output: String) -> Result<(), Box<dyn Error>> {
+        self.enter(StallPhase::Writing);
+        let written = (self.write)(output);
+        self.enter(StallPhase::Fitting);
+        if let Some(heartbeat) = &mut self.heartbeat {
+            heartbeat.last = Instant::now();
+        }
+        written?;
+        Ok(self.check_stall()?)
+    }
+
+    /// Frames a body that embeds models with the serializer, then writes it to `Out` sink.
+    fn write_framed<Point: PartialEq>(&mut self, body: String) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        let mut output = String::new();
+        self.serializer.frame(&body, &mut output)?;
+        self.write_out(output)
+    }
+
+    /// Writes a heartbeat to `Out` sink if nothing was written for the heartbeat period.
+    fn beat<Point: PartialEq>(&mut self) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        let due = match &self.heartbeat {
+            Some(heartbeat) => heartbeat.last.elapsed() >= heartbeat.period,
+            None => false,
+        };
+        if !due {
+            return Ok(());
+        }
+        let mut output = String::new();
+        self.serializer.heartbeat(&mut output)?;
+        if output.is_empty() {
+            return Ok(());
+        }
+        self.write_out(output)
+    }
+
+    /// Records the phase of the streamer loop for the watchdog, if any.
+    fn enter(&self, phase: StallPhase) {
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.enter(phase);
+        }
+    }
+
+    /// Fails with a [StreamError::Stalled] error if the watchdog aborts the run.
+    fn check_stall(&self) -> Result<(), StreamError> {
+        match self.watchdog.as_ref().and_then(|w| w.take_stall()) {
+            Some(stall) => Err(StreamError::Stalled(stall)),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the next record from `In` source and keeps track of its position.
+    fn next_record(&mut self) -> Option<Result<String, Box<dyn Error>>> {
+        self.enter(StallPhase::Reading);
+        let record = self.points.next();
+        self.enter(StallPhase::Fitting);
+        if record.is_some() {
+            self.received();
+        }
+        if let Some(phases) = &self.phases {
+            self.report.phases = phases.get();
+        }
+        record
+    }
+
+    /// Keeps track of the position and arrival of a record which was just received.
+    fn received(&mut self) {
+        if let Some(latency) = &mut self.latency {
+            latency.arrival = (latency.clock)();
+        }
+        self.line += 1;
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.progress();
+        }
+    }
+
+    /// Fits the point into the model unless it is dropped by sampling.
+    /// Returns `true` if the point was fitted. The reason of a drop is kept until it is reported, see [Streamer::report_drop].
+    fn fit_point<Point: PartialEq + 'static>(
+        &mut self,
+        algo: &Algo<Point>,
+        model: &mut Model<Point>,
+        point: Point,
+    ) -> bool {
+        self.dropped = None;
+        if let (Some(dedup), Some(uid)) = (&mut self.dedup, self.uid.take()) {
+            if dedup.check(&uid) {
+                self.report.duplicates += 1;
+                self.dropped = Some(DropReason::Duplicate);
+                return false;
+            }
+        }
+        let admitted = admit(&mut self.sampling, &mut self.report, algo, model, &point);
+        if !admitted {
+            self.dropped = Some(DropReason::Sampled);
+        }
+        if admitted {
+            if let Some(temperature) = self.soft {
+                self.memberships = model.soft_predict(&point, temperature);
+            }
+            let inside = match self.coverage {
+                Some(_) => model.is_within_radius(&point),
+                None => None,
+            };
+            match algo.try_fit(model, point) {
+                Ok(()) => {}
+                Err(FitError::OutOfRegion) => {
+                    self.report.admitted -= 1;
+                    self.dropped = Some(DropReason::OutOfRegion);
+                    return false;
+                }
+                Err(FitError::UserFnPanicked { .. }) => {
+                    self.report.admitted -= 1;
+                    self.report.panicked += 1;
+                    self.dropped = Some(DropReason::Panicked);
+                    return false;
+                }
+            }
+            if let (Some(coverage), Some(inside)) = (&mut self.coverage, inside) {
+                let (coverage, low) = coverage.record(inside);
+                self.report.coverage = Some(coverage);
+                self.report.low_coverage = low;
+            }
+            self.report.threshold = algo.selected_threshold();
+            self.assignment = model.last_fitted_ball().map(|ball| Assignment {
+                id: self.id.clone(),
+                ball,
+            });
+            if let Some(latency) = &mut self.latency {
+                latency.pending.push(latency.arrival);
+            }
+        }
+        admitted
+    }
+
+    /// Keeps the record of the last dropped point with the reason, if drops are reported.
+    fn report_drop(&mut self, record: &str) {
+        if let (Some(drops), Some(reason)) = (&mut self.drops, self.dropped.take()) {
+            drops.push((String::from(record), reason));
+        }
+    }
+
+    /// Parses a point record, and its id if ids are accepted.
+    fn parse_record<Point: DeserializeOwned>(
+        &mut self,
+        record: &str,
+    ) -> Result<Point, StreamError> {
+        self.assignment = None;
+        self.uid = None;
+        let unwrapped;
+        let mut point_record = record;
+        if self.dedup.is_some() && record.trim_start().starts_with('{') {
+            let uid_record: UidRecord = parse_point(self.line, record)?;
+            self.uid = uid_record.uid;
+            unwrapped = uid_record.point.to_string();
+            point_record = &unwrapped;
+        }
+        let (id, point) = if self.ids {
+            parse_tagged_point(self.line, point_record)?
+        } else {
+            (None, parse_point(self.line, point_record)?)
+        };
+        self.id = id.unwrap_or_else(|| (self.line - 1).to_string());
+        if let Some(latency) = &mut self.latency {
+            if let Timestamps::Producer(get_timestamp) = latency.timestamps {
+                if let Some(ms) = get_timestamp(record) {
+                    latency.arrival = Duration::try_from_secs_f64(ms / 1000.).map_err(|_| {
+                        StreamError::ParseError {
+                            line: self.line,
+                            offset: 0,
+                            field: None,
+                            message: format!("invalid timestamp {}", ms),
+                        }
+                    })?;
+                }
+            }
+        }
+        Ok(point)
+    }
+
+    /// Parses the record as an administration command if they are accepted.
+    fn command(&self, record: &str) -> Option<Command> {
+        if self.admin {
+            serde_json::from_str(record).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Offers the point to the global reservoir, if any.
+    fn sample<Point: PartialEq + 'static>(&self, algo: &Algo<Point>, point: &str) {
+        if let Some(reservoir) = &self.reservoir {
+            reservoir.offer(point, || algo.rng());
+        }
+    }
+
+    /// Writes the model to `Out` sink if automatic emissions are enabled,
+    /// then checkpoints it if a checkpoint is due.
+    fn auto_emit<Point: PartialEq + Serialize + 'static>(
+        &mut self,
+        model: &Model<Point>,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        self.unemitted += 1;
+        let every = match &self.adaptive_emit {
+            Some(adaptive) => adaptive.every.max(self.emit_every()),
+            None => self.emit_every(),
+        };
+        if self.auto_emit && self.unemitted >= every {
+            self.emit_now(model)?;
+        }
+        self.checkpoint(model, false)
+    }
+
+    /// Writes the model to `Out` sink when `In` source is exhausted, if automatic emissions are disabled,
+    /// then writes a last checkpoint.
+    fn final_emit<Point: PartialEq + Serialize + 'static>(
+        &mut self,
+        model: &Model<Point>,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        if !self.auto_emit || self.unemitted > 0 {
+            self.emit_now(model)?;
+        }
+        self.checkpoint(model, true)
+    }
+
+    /// Writes a checkpoint of the model if one is due, or if `force` is set.
+    fn checkpoint<Point: PartialEq + Serialize + 'static>(
+        &mut self,
+        model: &Model<Point>,
+        force: bool,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        if !self.checkpoint_due(force) {
+            return Ok(());
+        }
+        self.write_checkpoint::<Point, _>(serializer::state_columns(model)?)
+    }
+
+    /// Writes a checkpoint of several models, `{"models":[<columns>,...],"names":[<name>,...]}`,
+    /// if one is due, or if `force` is set. It is split back into model checkpoints by [split_checkpoint].
+    fn checkpoint_models<Point: PartialEq + Serialize + 'static>(
+        &mut self,
+        models: &[(&str, &Model<Point>)],
+        force: bool,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        if !self.checkpoint_due(force) {
+            return Ok(());
+        }
+        let models = ModelCheckpoints {
+            models: models
+                .iter()
+                .map(|(_, model)| serializer::state_columns(model))
+                .collect::<Result<_, _>>()?,
+            names: models.iter().map(|(name, _)| *name).collect(),
+        };
+        self.write_checkpoint::<Point, _>(models)
+    }
+
+    /// Counts an update and tells whether a checkpoint is due, or if `force` is set whether there are updates.
+    fn checkpoint_due(&mut self, force: bool) -> bool {
+        let checkpoint = match &mut self.checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return false,
+        };
+        if !force {
+            checkpoint.updates += 1;
+        }
+        let due = match checkpoint.cadence {
+            Cadence::Points(points) => checkpoint.updates >= points,
+            Cadence::Duration(duration) => checkpoint.last.elapsed() >= duration,
+        };
+        checkpoint.updates > 0 && (due || force)
+    }
+
+    /// Writes a checkpoint of the models with the remembered uids, the sequence number and the time.
+    fn write_checkpoint<Point: PartialEq, Models: Serialize>(
+        &mut self,
+        models: Models,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        let record = CheckpointRecord {
+            models,
+            uids: self.dedup.as_ref().map(|dedup| dedup.uids().collect()),
+            seq: self.serializer.last_seq(),
+            time: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64(),
+        };
+        let output = serde_json::to_string(&record)?;
+        let checkpoint = match &mut self.checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+        (checkpoint.write)(output)?;
+        checkpoint.updates = 0;
+        checkpoint.last = Instant::now();
+        Ok(())
+    }
+
+    /// Infinitely reads points from `In` source and write model changes to `Out` sink.
+    pub fn run<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        streamer: Streamer<In, Out, Ser>,
+        algo: Algo<Point>,
+        model: &mut Model<Point>,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        Self::run_with_report(streamer, algo, model)?;
+        Ok(())
+    }
+
+    /// Same as [Streamer::run] but returns counters collected while running when `In` source is exhausted.
+    pub fn run_with_report<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        mut streamer: Streamer<In, Out, Ser>,
+        algo: Algo<Point>,
+        model: &mut Model<Point>,
+    ) -> Result<RunReport, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        while streamer.fit_next(&algo, model)? {}
+        streamer.final_emit(model)?;
+        Ok(streamer.report)
+    }
+
+    /// Same as [Streamer::run] but calls `hook` with the [Assignment] of each fitted point, after the model is written.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from(r#"["a",1.0]"#)), Ok(String::from("[2.0]"))].into_iter();
+    /// let streamer = Streamer::new(points, |_| Ok(())).with_point_ids();
+    /// let mut ids = vec![];
+    /// Streamer::run_with_hook(streamer, algo, &mut model, |a| ids.push(a.id.clone())).unwrap();
+    /// assert_eq!(vec!["a", "1"], ids);
+    /// ```
+    pub fn run_with_hook<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        mut streamer: Streamer<In, Out, Ser>,
+        algo: Algo<Point>,
+        model: &mut Model<Point>,
+        mut hook: impl FnMut(&Assignment),
+    ) -> Result<RunReport, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        while streamer.fit_next(&algo, model)? {
+            if let Some(assignment) = streamer.assignment.take() {
+                hook(&assignment);
+            }
+        }
+        streamer.final_emit(model)?;
+        Ok(streamer.report)
+    }
+
+    /// Same as [Streamer::run] but calls `ack` for each record once it is processed.
+    ///
+    /// A record is acknowledged after it is fitted, before the model is written.
+    /// Records that cannot be read or parsed are not acknowledged (nack) and the stream continues.
+    /// ```
+    /// use fluent_data::{Algo, Model, space, streamer::Ack, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = vec![Ok(String::from("[1.0]")), Ok(String::from("oops"))].into_iter();
+    /// let streamer = Streamer::new(points, |_| Ok(()));
+    /// let mut acks = vec![];
+    /// Streamer::run_with_ack(streamer, algo, &mut model, |ack| acks.push(ack)).unwrap();
+    /// assert_eq!(vec![Ack::Ack(0), Ack::Nack(1)], acks);
+    /// ```
+    pub fn run_with_ack<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        mut streamer: Streamer<In, Out, Ser>,
+        algo: Algo<Point>,
+        model: &mut Model<Point>,
+        mut ack: impl FnMut(Ack),
+    ) -> Result<RunReport, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        while let Some(input) = streamer.next_record() {
+            streamer.check_stall()?;
+            streamer.beat::<Point>()?;
+            let record_id = streamer.line - 1;
+            let command = input.as_ref().ok().and_then(|s| streamer.command(s));
+            if let Some(command) = command {
+                match command.apply(model) {
+                    Ok(()) => {
+                        ack(Ack::Ack(record_id));
+                        streamer.auto_emit(model)?;
+                    }
+                    Err(reason) => {
+                        eprintln!("{}", reason);
+                        streamer.report.failed_commands += 1;
+                        ack(Ack::Nack(record_id));
+                    }
+                }
+                continue;
+            }
+            let parsed = input.and_then(|s| Ok((streamer.parse_record::<Point>(&s)?, s)));
+            match parsed {
+                Ok((point, point_str)) => {
+                    streamer.sample(&algo, &point_str);
+                    let fitted = streamer.fit_point(&algo, model, point);
+                    ack(Ack::Ack(record_id));
+                    if fitted {
+                        streamer.auto_emit(model)?;
+                    }
+                }
+                Err(reason) => {
+                    eprintln!("{}", reason);
+                    ack(Ack::Nack(record_id));
+                }
+            }
+        }
+        streamer.final_emit(model)?;
+        Ok(streamer.report)
+    }
+
+    /// Same as [Streamer::run] but calls `sink` with each record that is not fitted and the reason why it was dropped,
+    /// instead of stopping at the first record that cannot be parsed.
+    ///
+    /// The elements of a batch record are reported one by one, see [Streamer::with_batches].
+    /// A record longer than the maximum line length is reported as an empty string, since it was not kept, see [lines].
+    /// ```
+    /// use fluent_data::{Algo, Model, space, streamer::DropReason, Streamer};
+    ///
+    /// let algo = Algo::new(space::euclid_dist, space::real_combine).with_valid_region(vec![0.], vec![10.]);
+    /// let mut model = Model::new(space::euclid_dist);
+    /// let points = ["[1.0]", "oops", "[11.0]"].map(|p| Ok(String::from(p))).into_iter();
+    /// let streamer = Streamer::new(points, |_| Ok(()));
+    /// let mut drops = vec![];
+    /// Streamer::run_with_drop_sink(streamer, algo, &mut model, |record, reason| drops.push((record, reason))).unwrap();
+    /// assert_eq!(
+    ///     vec![(String::from("oops"), DropReason::Unparsable), (String::from("[11.0]"), DropReason::OutOfRegion)],
+    ///     drops
+    /// );
+    /// ```
+    pub fn run_with_drop_sink<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+        mut streamer: Streamer<In, Out, Ser>,
+        algo: Algo<Point>,
+        model: &mut Model<Point>,
+        mut sink: impl FnMut(String, DropReason),
+    ) -> Result<RunReport, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        streamer.drops = Some(vec![]);
+        while let Some(input) = streamer.next_record() {
+            let record = match input {
+                Ok(record) => record,
+                Err(reason) => match reason.downcast_ref::<StreamError>() {
+                    Some(StreamError::LineTooLong { .. }) => {
+                        sink(String::new(), DropReason::TooLong);
+                        continue;
+                    }
+                    Some(StreamError::TransformPanicked { record, .. }) => {
+                        sink(record.clone(), DropReason::Panicked);
+                        continue;
+                    }
+                    _ => return Err(reason),
+                },
+            };
+            let fitted = streamer.fit_record(&algo, model, &record);
+            for (dropped, reason) in streamer.drops.iter_mut().flat_map(|drops| drops.drain(..)) {
+                sink(dropped, reason);
+            }
+            if let Err(reason) = fitted {
+                match reason.downcast_ref::<StreamError>() {
+                    Some(StreamError::ParseError { .. }) => sink(record, DropReason::Unparsable),
+                    _ => return Err(reason),
+                }
+            }
+        }
+        streamer.final_emit(model)?;
+        Ok(streamer.report)
+    }
+
+    /// Fits each point into a coarse and a fine model, e.g. for drilling down from coarse balls to fine ones.
+    /// The coarse model is expected to use a larger threshold than the fine one, see [AlgoConfig::threshold].
+    ///
+    /// Models are written as `{"coarse":[...],"fine":[...],"parents":[...]}` where `parents` gives,
+    /// for each fine ball, the index of the coarse ball that contains its center, see [Model::parents_of].
+    /// Serializers that frame their messages carry this body in an [crate::message::Envelope::Composite] message.
+    /// Adaptive sampling decisions are based on the coarse model.
+    /// ```
+    /// use fluent_data::{algorithm::AlgoConfig, Algo, Model, space, Streamer};
+    ///
+    /// let config: AlgoConfig = "threshold=8".parse().unwrap();
+    /// let coarse_algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+    /// let fine_algo = Algo::new(space::euclid_dist, space::real_combine);
+    /// let (mut coarse_model, mut fine_model) = (Model::new(space::euclid_dist), Model::new(space::euclid_dist));
+    /// let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))].into_iter();
+    /// let streamer = Streamer::new(points, |_| Ok(()));
+    /// Streamer::run_hierarchical(streamer, coarse_algo, &mut coarse_model, fine_algo, &mut fine_model).unwrap();
+    /// ```
+    pub fn run_hierarchical<Point: PartialEq + Clone + Serialize + DeserializeOwned + 'static>(
+        mut streamer: Streamer<In, Out, Ser>,
+        coarse_algo: Algo<Point>,
+        coarse_model: &mut Model<Point>,
+        fine_algo: Algo<Point>,
+        fine_model: &mut Model<Point>,
+    ) -> Result<RunReport, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        while let Some(input) = streamer.next_record() {
+            streamer.check_stall()?;
+            streamer.beat::<Point>()?;
+            let point_str = input?;
+            let point: Point = parse_point(streamer.line, &point_str)?;
+            streamer.sample(&coarse_algo, &point_str);
+            let report = &mut streamer.report;
+            if !admit(
+                &mut streamer.sampling,
+                report,
+                &coarse_algo,
+                coarse_model,
+                &point,
+            ) {
+                continue;
+            }
+            coarse_algo.fit(coarse_model, point.clone());
+            fine_algo.fit(fine_model, point);
+            if streamer.auto_emit {
+                streamer.write_framed(serialize_hierarchy(coarse_model, fine_model)?)?;
+                coarse_algo.measure_drift(coarse_model);
+                fine_algo.measure_drift(fine_model);
+            }
+            streamer.checkpoint_models(&[("coarse", coarse_model), ("fine", fine_model)], false)?;
+        }
+        if !streamer.auto_emit {
+            streamer.write_framed(serialize_hierarchy(coarse_model, fine_model)?)?;
+        }
+        streamer.checkpoint_models(&[("coarse", coarse_model), ("fine", fine_model)], true)?;
+        Ok(streamer.report)
+    }
+
+    /// Fits each point into several models and writes them tagged with their configuration name.
+    /// Adaptive sampling decisions are based on the first model.
+    pub(crate) fn run_ab<Point: PartialEq + Clone + Serialize + DeserializeOwned + 'static>(
+        mut streamer: Streamer<In, Out, Ser>,
+        mut runs: Vec<(Algo<Point>, Model<Point>)>,
+    ) -> Result<RunReport, Box<dyn Error>>
+    where
+        Ser: ModelSerializer<Point>,
+    {
+        if runs.is_empty() {
+            return Err("an A/B run needs at least one configuration".into());
+        }
+        let mut models: Vec<ModelReport> = runs
+            .iter()
+            .map(|(algo, _)| ModelReport::new(algo.config().name.clone()))
+            .collect();
+        while let Some(input) = streamer.next_record() {
+            streamer.check_stall()?;
+            streamer.beat::<Point>()?;
+            let point_str = input?;
+            let point: Point = parse_point(streamer.line, &point_str)?;
+            let (champion, champion_model) = &runs[0];
+            streamer.sample(champion, &point_str);
+            let report = &mut streamer.report;
+            if !admit(
+                &mut streamer.sampling,
+                report,
+                champion,
+                champion_model,
+                &point,
+            ) {
+                continue;
+            }
+            for ((algo, model), model_report) in runs.iter_mut().zip(models.iter_mut()) {
+                if let Some(score) = model.anomaly_score(&point) {
+                    model_report.add_score(score);
+                }
+                algo.fit(model, point.clone());
+            }
+            if streamer.auto_emit {
+                streamer.write_framed(serialize_ab(&runs)?)?;
+                runs.iter()
+                    .for_each(|(algo, model)| algo.measure_drift(model));
+            }
+            streamer.checkpoint_models(&named_models(&runs), false)?;
+        }
+        if !streamer.auto_emit {
+            streamer.write_framed(serialize_ab(&runs)?)?;
+        }
+        streamer.checkpoint_models(&named_models(&runs), true)?;
+        streamer.report.models = models;
+        Ok(streamer.report)
+    }
+}
+
+/// Gets the models tagged with their configuration name.
+fn named_models<Point: PartialEq>(
+    runs: &[(Algo<Point>, Model<Point>)],
+) -> Vec<(&str, &Model<Point>)> {
+    runs.iter()
+        .map(|(algo, model)| (algo.config().name.as_str(), model))
+        .collect()
+}
+
+/// Serializes models tagged with their configuration name.
+fn serialize_ab<Point: PartialEq + Serialize + 'static>(
+    runs: &[(Algo<Point>, Model<Point>)],
+) -> Result<String, Box<dyn Error>> {
+    let output: Vec<_> = runs
+        .iter()
+        .map(|(algo, model)| {
+            let mut map = Map::new();
+            map.insert("name".into(), json!(algo.config().name));
+            map.insert("model".into(), json!(serialize_model(model)));
+            map
+        })
+        .collect();
+    Ok(serde_json::to_string(&output)?)
+}
+
+fn serialize_hierarchy<Point: PartialEq + Serialize + 'static>(
+    coarse: &Model<Point>,
+    fine: &Model<Point>,
+) -> Result<String, Box<dyn Error>> {
+    let output = json!({
+        "coarse": serialize_model(coarse),
+        "fine": serialize_model(fine),
+        "parents": coarse.parents_of(fine),
+    });
+    Ok(serde_json::to_string(&output)?)
+}
+
+pub(crate) fn serialize_model<Point: PartialEq + Serialize + 'static>(
+    model: &Model<Point>,
+) -> Vec<Map<String, Value>> {
+    let mut balls: Vec<_> = model
+        .iter_balls()
+        .map(|data| serialize_ball(data))
+        .collect();
+    if let Some(normalization) = model.normalization() {
+        balls
+            .iter_mut()
+            .for_each(|ball| normalization.denormalize_ball(ball));
+    }
+    balls
+}
+
+fn serialize_ball<Point: PartialEq + Serialize>(
+    data: impl Deref<Target = Ball<Point>>,
+) -> Map<String, Value> {
+    let mut map = Map::new();
+    map.insert("center".into(), json!(data.center()));
+    map.insert("radius".into(), json!(data.radius()));
+    map.insert("weight".into(), json!(data.weight()));
+    if let Some(velocity) = data.velocity() {
+        map.insert("velocity".into(), json!(velocity));
+    }
+    if data.is_frozen() {
+        map.insert("frozen".into(), json!(true));
+    }
+    map
+}
+
+/// Returns point iterator / model writer that use standard in out.
+///
+/// The length of the lines read is not bounded, use [lines] on [io::stdin]
+/// for skipping lines that are too long.
+pub fn stdio() -> (
+    impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    impl FnMut(String) -> Result<(), Box<dyn Error>>,
+) {
+    let points = io::stdin()
+        .lines()
+        .map(|f| -> Result<String, Box<dyn Error>> { Ok(f?) });
+    let write = |model| {
+        println!("{}", model);
+        Ok(())
+    };
+    (points, write)
+}
+
+/// Returns a point iterator that reads the lines of a reader.
+/// Lines longer than `max_len` bytes are skipped and yield a [StreamError::LineTooLong].
+/// ```
+/// use fluent_data::streamer;
+///
+/// let mut points = streamer::lines("[1.0]\n[1.0,2.0,3.0]\n[2.0]".as_bytes(), 8);
+/// assert_eq!("[1.0]", points.next().unwrap().unwrap());
+/// assert!(points.next().unwrap().is_err());
+/// assert_eq!("[2.0]", points.next().unwrap().unwrap());
+/// ```
+pub fn lines<R: BufRead>(reader: R, max_len: usize) -> BoundedLines<R> {
+    BoundedLines {
+        reader,
+        max_len,
+        line: 0,
+    }
+}
+
+/// Iterates over the lines of a reader, see [lines].
+///
+/// A line that is too long is never held in memory: it is discarded
+/// chunk by chunk until the next newline.
+pub struct BoundedLines<R> {
+    reader: R,
+    max_len: usize,
+    line: usize,
+}
+
+impl<R: BufRead> Iterator for BoundedLines<R> {
+    type Item = Result<String, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = vec![];
+        let mut read = 0;
+        let mut too_long = false;
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if available.is_empty() {
+                break;
+            }
+            let newline = available.iter().position(|&b| b == b'\n');
+            let end = newline.unwrap_or(available.len());
+            if !too_long && buf.len() + end > self.max_len {
+                too_long = true;
+                buf = vec![];
+            }
+            if !too_long {
+                buf.extend_from_slice(&available[..end]);
+            }
+            let consumed = newline.map_or(end, |i| i + 1);
+            self.reader.consume(consumed);
+            read += consumed;
+            if newline.is_some() {
+                break;
+            }
+        }
+        if read == 0 {
+            return None;
+        }
+        self.line += 1;
+        if too_long {
+            let err = StreamError::LineTooLong {
+                line: self.line,
+                max_len: self.max_len,
+            };
+            return Some(Err(err.into()));
+        }
+        if buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+        Some(String::from_utf8(buf).map_err(|e| e.into()))
+    }
+}
+
+/// Returns a point iterator that reads the lines of the given file.
+/// ```no_run
+/// use fluent_data::streamer;
+///
+/// let points = streamer::file("points.txt").unwrap();
+/// ```
+pub fn file(
+    path: impl AsRef<Path>,
+) -> Result<impl Iterator<Item = Result<String, Box<dyn Error>>>, Box<dyn Error>> {
+    let points = BufReader::new(File::open(path)?)
+        .lines()
+        .map(|f| -> Result<String, Box<dyn Error>> { Ok(f?) });
+    Ok(points)
+}
+
+/// Returns a checkpoint writer for [Streamer::with_checkpoints] that replaces the given file atomically:
+/// each checkpoint is written to a temporary file next to it, which is flushed to the disk then renamed.
+pub fn checkpoint_file(
+    path: impl AsRef<Path>,
+) -> impl FnMut(String) -> Result<(), Box<dyn Error>> + 'static {
+    let path = path.as_ref().to_path_buf();
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    move |checkpoint| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(checkpoint.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+}
+
+/// Returns point iterator / model writer that use mpsc channels.
+pub fn channels(
+    point_receiver: Receiver<String>,
+    model_producer: Sender<String>,
+) -> (
+    impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    impl FnMut(String) -> Result<(), Box<dyn Error>>,
+) {
+    let points = point_receiver.into_iter().map(|f| Ok(f));
+    let write = move |model| {
+        model_producer.send(model)?;
+        Ok(())
+    };
+    (points, write)
+}
+
+/// Returns a point iterator that snaps each coordinate of the points read from `inner`
+/// to the nearest multiple of the corresponding cell size.
+///
+/// Snapping points to a grid reduces noise and cardinality before clustering.
+/// ```
+/// use fluent_data::streamer;
+///
+/// let points = vec![Ok(String::from("[1.2,-0.4]"))].into_iter();
+/// let mut snapped = streamer::grid_snap(points, vec![1., 0.5]);
+/// assert_eq!("[1.0,-0.5]", snapped.next().unwrap().unwrap());
+/// ```
+pub fn grid_snap(
+    inner: impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    cell_size: Vec<f64>,
+) -> impl Iterator<Item = Result<String, Box<dyn Error>>> {
+    inner.map(move |p| -> Result<String, Box<dyn Error>> {
+        let point: Vec<f64> = serde_json::from_str(&p?)?;
+        if point.len() != cell_size.len() {
+            return Err(format!(
+                "point has {} coordinates but grid has {}",
+                point.len(),
+                cell_size.len()
+            )
+            .into());
+        }
+        let snapped: Vec<f64> = point
+            .iter()
+            .zip(&cell_size)
+            .map(|(x, c)| (x / c).round() * c)
+            .collect();
+        Ok(serde_json::to_string(&snapped)?)
+    })
+}
+
+/// Returns a point iterator that applies the transform `f` to each point of `inner` iterator,
+/// e.g. to append a derived feature before fitting.
+///
+/// Points are transformed one at a time as they are read, within the records that carry them:
+/// points with an id `["a",1.0]`, `{"uid":..,"point":..}` records and the elements of batches.
+/// Other records, e.g. commands or records that are not JSON, are passed through to the streamer.
+/// A record which transform panics is returned as a [StreamError::TransformPanicked] error,
+/// which [Streamer::run_with_drop_sink] reports and skips.
+/// ```
+/// use fluent_data::streamer;
+///
+/// let points = ["[3.0,4.0]", r#"{"uid":"a","point":["b",1.0,2.0]}"#].map(|p| Ok(String::from(p))).into_iter();
+/// let mut mapped = streamer::map_point(points, |p| p.iter().map(|x| x * 2.).collect());
+/// assert_eq!("[6.0,8.0]", mapped.next().unwrap().unwrap());
+/// assert_eq!(r#"{"point":["b",2.0,4.0],"uid":"a"}"#, mapped.next().unwrap().unwrap());
+/// ```
+pub fn map_point(
+    inner: impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    f: impl Fn(Vec<f64>) -> Vec<f64>,
+) -> impl Iterator<Item = Result<String, Box<dyn Error>>> {
+    inner.map(move |p| -> Result<String, Box<dyn Error>> {
+        let record = p?;
+        let value: Value = match serde_json::from_str(&record) {
+            Ok(value) => value,
+            Err(_) => return Ok(record),
+        };
+        match catch_unwind(AssertUnwindSafe(|| map_record(value, &f))) {
+            Ok(Some(mapped)) => Ok(mapped.to_string()),
+            Ok(None) => Ok(record),
+            Err(panic) => Err(StreamError::TransformPanicked {
+                record,
+                reason: panic_reason(panic),
+            }
+            .into()),
+        }
+    })
+}
+
+/// Applies the transform to the points of a record, see [map_point].
+/// Returns `None` if the record carries no point.
+fn map_record(record: Value, f: &impl Fn(Vec<f64>) -> Vec<f64>) -> Option<Value> {
+    match record {
+        Value::Array(values) if values.iter().all(Value::is_number) => {
+            let point = values.iter().map(Value::as_f64).collect::<Option<_>>()?;
+            Some(json!(f(point)))
+        }
+        Value::Array(mut values)
+            if values.first().is_some_and(Value::is_string)
+                && values[1..].iter().all(Value::is_number) =>
+        {
+            let id = values.remove(0);
+            let mut point = match map_record(Value::Array(values), f)? {
+                Value::Array(point) => point,
+                _ => return None,
+            };
+            point.insert(0, id);
+            Some(Value::Array(point))
+        }
+        Value::Array(elements) if elements.first().is_some_and(Value::is_array) => {
+            Some(Value::Array(
+                elements
+                    .into_iter()
+                    .map(|element| map_record(element.clone(), f).unwrap_or(element))
+                    .collect(),
+            ))
+        }
+        Value::Object(mut fields) => {
+            let point = map_record(fields.remove("point")?, f)?;
+            fields.insert("point".into(), point);
+            Some(Value::Object(fields))
+        }
+        _ => None,
+    }
+}
+
+/// Prefix of the compressed models written by [gzip_writer].
+#[cfg(feature = "gzip")]
+pub const GZIP_PREFIX: &str = "gzip:";
+
+/// Wraps a model writer so that it writes each model compressed with gzip, e.g. for bandwidth-constrained clients.
+///
+/// Writers take text, thus the compressed model is base64 encoded and prefixed by [GZIP_PREFIX],
+/// which tells compressed frames from plain ones; [gunzip_frame] decompresses it.
+///
+/// Base64 adds a third to the compressed size, and gzip adds a header of about 20 bytes, thus small models
+/// do not shrink: a JSON model of real points breaks even around 250 bytes, i.e. two or three balls in 2D.
+/// A model which compressed frame would not be shorter is written plain, as [gunzip_frame] expects.
+/// Requires the `gzip` feature.
+/// ```
+/// use fluent_data::streamer;
+///
+/// let mut frames = vec![];
+/// let mut write = streamer::gzip_writer(|frame| {
+///     frames.push(frame);
+///     Ok(())
+/// });
+/// let large = format!("[{}]", vec![r#"{"center":[0.0,0.0],"radius":1.0,"weight":1.0}"#; 10].join(","));
+/// write(large.clone()).unwrap();
+/// write(String::from("[]")).unwrap();
+/// drop(write);
+/// assert!(frames[0].starts_with(streamer::GZIP_PREFIX));
+/// assert_eq!(large, streamer::gunzip_frame(&frames[0]).unwrap());
+/// assert_eq!("[]", frames[1]);
+/// ```
+#[cfg(feature = "gzip")]
+pub fn gzip_writer(
+    mut inner: impl FnMut(String) -> Result<(), Box<dyn Error>>,
+) -> impl FnMut(String) -> Result<(), Box<dyn Error>> {
+    move |model: String| {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(model.as_bytes())?;
+        let compressed = encoder.finish()?;
+        let mut frame = String::from(GZIP_PREFIX);
+        base64::encode_config_buf(compressed, base64::STANDARD, &mut frame);
+        if frame.len() < model.len() {
+            inner(frame)
+        } else {
+            inner(model)
+        }
+    }
+}
+
+/// Decompresses a frame written by [gzip_writer]; frames without the [GZIP_PREFIX] are returned unchanged.
+/// Returns an error if the frame is not valid base64 or gzip, or if the model is not valid UTF-8.
+/// Requires the `gzip` feature.
+#[cfg(feature = "gzip")]
+pub fn gunzip_frame(frame: &str) -> Result<String, Box<dyn Error>> {
+    let encoded = match frame.strip_prefix(GZIP_PREFIX) {
+        Some(encoded) => encoded,
+        None => return Ok(frame.to_string()),
+    };
+    let compressed = base64::decode(encoded)?;
+    let mut model = String::new();
+    GzDecoder::new(compressed.as_slice()).read_to_string(&mut model)?;
+    Ok(model)
+}
+
+/// Returns a point iterator that replays the `history` source to warm the model,
+/// then switches to the `live` source according to the `cutover` policy.
+///
+/// The `live` source is not read until `history` is exhausted.
+/// The returned [Phases] handle counts records per source, see [Streamer::with_phases].
+/// ```
+/// use fluent_data::{Algo, Model, space, streamer::{self, CutoverPolicy}, Streamer};
+///
+/// let algo = Algo::new(space::euclid_dist, space::real_combine);
+/// let mut model = Model::new(space::euclid_dist);
+/// let history = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))].into_iter();
+/// let live = vec![Ok(String::from("[3.0]"))].into_iter();
+/// let (points, phases) = streamer::chain_sources(history, live, CutoverPolicy::Drain);
+/// let streamer = Streamer::new(points, |_| Ok(())).with_phases(phases);
+/// let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+/// assert_eq!(2, report.phases.history);
+/// assert_eq!(1, report.phases.live);
+/// ```
+pub fn chain_sources(
+    history: impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    live: impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    cutover: CutoverPolicy,
+) -> (impl Iterator<Item = Result<String, Box<dyn Error>>>, Phases) {
+    let phases = Phases::default();
+    let points = ChainedSources {
+        history: history.fuse(),
+        live,
+        cutover,
+        last_timestamp: None,
+        phases: phases.clone(),
+    };
+    (points, phases)
+}
+
+/// Point iterator built by [chain_sources].
+struct ChainedSources<History, Live> {
+    history: History,
+    live: Live,
+    cutover: CutoverPolicy,
+    last_timestamp: Option<f64>,
+    phases: Phases,
+}
+
+impl<History, Live> Iterator for ChainedSources<History, Live>
+where
+    History: Iterator<Item = Result<String, Box<dyn Error>>>,
+    Live: Iterator<Item = Result<String, Box<dyn Error>>>,
+{
+    type Item = Result<String, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(record) = self.history.next() {
+            self.phases.0.borrow_mut().history += 1;
+            if let Some(t) = timestamp(self.cutover, &record) {
+                self.last_timestamp = Some(self.last_timestamp.map_or(t, |last| last.max(t)));
+            }
+            return Some(record);
+        }
+        for record in self.live.by_ref() {
+            let mut phases = self.phases.0.borrow_mut();
+            let stale = match (timestamp(self.cutover, &record), self.last_timestamp) {
+                (Some(t), Some(last)) => t <= last,
+                _ => false,
+            };
+            if stale {
+                phases.skipped += 1;
+            } else {
+                phases.live += 1;
+                return Some(record);
+            }
+        }
+        None
+    }
+}
+
+/// Extracts the timestamp of a record if the cutover policy is timestamp based.
+fn timestamp(cutover: CutoverPolicy, record: &Result<String, Box<dyn Error>>) -> Option<f64> {
+    match (cutover, record) {
+        (CutoverPolicy::Timestamp(get_timestamp), Ok(record)) => get_timestamp(record),
+        _ => None,
+    }
+}
+
+/// Returns a point iterator that yields the records of `inner` at the pace given by their timestamps,
+/// e.g. to replay logged points with their real inter-arrival gaps.
+///
+/// `timestamp_of` gets the timestamp of a record in seconds; the gaps between records are divided by `speed`,
+/// thus a speed of 10 replays the stream ten times faster. The pace is kept from the first record on, so that
+/// the time spent fitting is not added to the gaps. A record which timestamp is earlier than a previous one,
+/// or not a number, or which gap is too large to wait for, is yielded without delay, as are errors.
+/// ```
+/// use fluent_data::streamer;
+///
+/// let records = vec![Ok(String::from("[0.0,1.0]")), Ok(String::from("[0.5,2.0]"))];
+/// let timestamp_of = |record: &str| serde_json::from_str::<Vec<f64>>(record).map_or(f64::NAN, |p| p[0]);
+/// let points = streamer::replay_timed(records.into_iter(), timestamp_of, 100.);
+/// assert_eq!(2, points.count());
+/// ```
+pub fn replay_timed(
+    inner: impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    timestamp_of: impl Fn(&str) -> f64,
+    speed: f64,
+) -> impl Iterator<Item = Result<String, Box<dyn Error>>> {
+    assert!(speed > 0., "the replay speed must be positive");
+    TimedReplay {
+        inner,
+        timestamp_of,
+        speed,
+        origin: None,
+        latest: f64::NEG_INFINITY,
+    }
+}
+
+/// Point iterator built by [replay_timed].
+struct TimedReplay<In, Timestamp> {
+    inner: In,
+    timestamp_of: Timestamp,
+    speed: f64,
+    /// The timestamp of the first record and the time it was yielded.
+    origin: Option<(f64, Instant)>,
+    /// The latest timestamp seen.
+    latest: f64,
+}
+
+impl<In, Timestamp> Iterator for TimedReplay<In, Timestamp>
+where
+    In: Iterator<Item = Result<String, Box<dyn Error>>>,
+    Timestamp: Fn(&str) -> f64,
+{
+    type Item = Result<String, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.inner.next()?;
+        let t = match &record {
+            Ok(record) => (self.timestamp_of)(record),
+            Err(_) => return Some(record),
+        };
+        if !t.is_finite() || t <= self.latest {
+            return Some(record);
+        }
+        self.latest = t;
+        let (t0, start) = *self.origin.get_or_insert((t, Instant::now()));
+        let due = Duration::try_from_secs_f64((t - t0) / self.speed)
+            .ok()
+            .and_then(|gap| start.checked_add(gap));
+        let now = Instant::now();
+        if let Some(due) = due.filter(|due| *due > now) {
+            thread::sleep(due - now);
+        }
+        Some(record)
+    }
+}
+
+/// Phase of the streamer loop, see [StallDiagnostic].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StallPhase {
+    /// Waiting for the next record of `In` source.
+    Reading,
+    /// Parsing and fitting a point.
+    Fitting,
+    /// Writing a model to `Out` sink.
+    Writing,
+}
+
+impl StallPhase {
+    fn from_u8(phase: u8) -> Self {
+        match phase {
+            0 => StallPhase::Reading,
+            1 => StallPhase::Fitting,
+            _ => StallPhase::Writing,
+        }
+    }
+}
+
+/// Describes a streamer that made no progress for longer than the [Watchdog] timeout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StallDiagnostic {
+    /// The phase the streamer loop is stuck in.
+    pub phase: StallPhase,
+    /// Time elapsed since the streamer entered the phase.
+    pub stalled_for: Duration,
+    /// Number of records read before the stall.
+    pub processed: usize,
+}
+
+/// Detects a streamer that stops making progress, see [Streamer::with_watchdog].
+///
+/// A thread checks the streamer loop a few times per timeout. When the loop stays in the same phase
+/// for longer than the timeout, the stall callback is called once with a [StallDiagnostic].
+/// Waiting for a record is considered idle input, unless reads are watched too,
+/// e.g. for a file source which never blocks.
+/// ```
+/// use std::time::Duration;
+///
+/// use fluent_data::{streamer, streamer::Watchdog, Streamer};
+///
+/// let (points, write) = streamer::stdio();
+/// let watchdog = Watchdog::new(Duration::from_secs(30), |stall| eprintln!("stalled: {:?}", stall));
+/// let streamer = Streamer::new(points, write).with_watchdog(watchdog);
+/// ```
+pub struct Watchdog {
+    timeout: Duration,
+    on_stall: Box<dyn Fn(StallDiagnostic) + Send>,
+    abort: bool,
+    watch_reads: bool,
+}
+
+impl Watchdog {
+    /// Builds a watchdog which calls `on_stall` when no progress is made within `timeout`.
+    pub fn new(timeout: Duration, on_stall: impl Fn(StallDiagnostic) + Send + 'static) -> Self {
+        Self {
+            timeout,
+            on_stall: Box::new(on_stall),
+            abort: false,
+            watch_reads: false,
+        }
+    }
+
+    /// Stops the run with a [StreamError::Stalled] error once the stalled phase returns.
+    ///
+    /// A phase blocked forever cannot be interrupted: the stall callback may exit the process in that case.
+    pub fn with_abort(mut self) -> Self {
+        self.abort = true;
+        self
+    }
+
+    /// Reports a stall when waiting for a record too, rather than considering the input idle.
+    pub fn with_watched_reads(mut self) -> Self {
+        self.watch_reads = true;
+        self
+    }
+
+    /// Starts the watchdog thread.
+    fn start(self) -> WatchHandle {
+        let state = Arc::new(WatchState {
+            start: Instant::now(),
+            phase: AtomicU8::new(StallPhase::Reading as u8),
+            since: AtomicU64::new(0),
+            processed: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+            stall: Mutex::new(None),
+        });
+        let watched = Arc::clone(&state);
+        let tick = (self.timeout / 4).max(Duration::from_millis(1));
+        let abort = self.abort;
+        thread::spawn(move || {
+            let mut reported = None;
+            while !watched.stopped.load(Ordering::SeqCst) {
+                thread::sleep(tick);
+                let since = watched.since.load(Ordering::SeqCst);
+                let phase = StallPhase::from_u8(watched.phase.load(Ordering::SeqCst));
+                let elapsed = watched.start.elapsed() - Duration::from_nanos(since);
+                let idle = phase == StallPhase::Reading && !self.watch_reads;
+                if idle || elapsed <= self.timeout || reported == Some(since) {
+                    continue;
+                }
+                reported = Some(since);
+                let stall = StallDiagnostic {
+                    phase,
+                    stalled_for: elapsed,
+                    processed: watched.processed.load(Ordering::SeqCst),
+                };
+                if abort {
+                    watched.stall.lock().unwrap().replace(stall.clone());
+                }
+                (self.on_stall)(stall);
+            }
+        });
+        WatchHandle(state)
+    }
+}
+
+/// Progress of the streamer loop, shared with the watchdog thread.
+struct WatchState {
+    start: Instant,
+    phase: AtomicU8,
+    /// Time the current phase was entered, in nanoseconds since `start`.
+    since: AtomicU64,
+    processed: AtomicUsize,
+    stopped: AtomicBool,
+    /// The stall which aborts the run, if any.
+    stall: Mutex<Option<StallDiagnostic>>,
+}
+
+/// Streamer side of a [Watchdog], which stops the watchdog thread when dropped.
+struct WatchHandle(Arc<WatchState>);
+
+impl WatchHandle {
+    /// Records that the streamer loop enters the phase.
+    fn enter(&self, phase: StallPhase) {
+        let since = self.0.start.elapsed().as_nanos() as u64;
+        self.0.since.store(since, Ordering::SeqCst);
+        self.0.phase.store(phase as u8, Ordering::SeqCst);
+    }
+
+    /// Records that a record was read.
+    fn progress(&self) {
+        self.0.processed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Takes the stall that aborts the run, if any.
+    fn take_stall(&self) -> Option<StallDiagnostic> {
+        self.0.stall.lock().unwrap().take()
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.0.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Point source of a [Pipeline].
+pub type BoxedPoints = Box<dyn Iterator<Item = Result<String, Box<dyn Error>>>>;
+
+/// Model sink of a [Pipeline], or checkpoint sink of a [Streamer].
+pub type BoxedWrite = Box<dyn FnMut(String) -> Result<(), Box<dyn Error>>>;
+
+/// An algorithm, a model and a streamer run together by a [Supervisor].
+pub struct Pipeline<Point: PartialEq + 'static> {
+    algo: Algo<Point>,
+    model: Model<Point>,
+    streamer: Streamer<BoxedPoints, BoxedWrite>,
+}
+
+impl<Point: PartialEq + 'static> Pipeline<Point> {
+    /// Builds a pipeline.
+    pub fn new(
+        algo: Algo<Point>,
+        model: Model<Point>,
+        streamer: Streamer<BoxedPoints, BoxedWrite>,
+    ) -> Self {
+        Self {
+            algo,
+            model,
+            streamer,
+        }
+    }
+}
+
+/// How a pipeline run by a [Supervisor] ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PipelineStatus {
+    /// The point source was exhausted.
+    Finished(RunReport),
+    /// The pipeline was stopped by [PipelineControl::shutdown].
+    Shutdown(RunReport),
+    /// The pipeline panicked or returned an error; the reason is given.
+    Failed(String),
+}
+
+/// Final status of each pipeline run by a [Supervisor], in the order they were added.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SupervisorReport {
+    pub pipelines: Vec<(String, PipelineStatus)>,
+}
+
+impl SupervisorReport {
+    /// Gets the status of the named pipeline.
+    pub fn get(&self, name: &str) -> Option<&PipelineStatus> {
+        self.pipelines
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, status)| status)
+    }
+
+    /// Total number of points fitted by the pipelines that did not fail.
+    pub fn admitted(&self) -> usize {
+        self.pipelines
+            .iter()
+            .map(|(_, status)| match status {
+                PipelineStatus::Finished(report) | PipelineStatus::Shutdown(report) => {
+                    report.admitted
+                }
+                PipelineStatus::Failed(_) => 0,
+            })
+            .sum()
+    }
+
+    /// Names of the pipelines that failed.
+    pub fn failed(&self) -> Vec<&str> {
+        self.pipelines
+            .iter()
+            .filter(|(_, status)| matches!(status, PipelineStatus::Failed(_)))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+}
+
+/// Control requests shared between a [PipelineControl] and the supervisor thread.
+#[derive(Debug, Default)]
+struct ControlFlags {
+    paused: AtomicBool,
+    reset: AtomicBool,
+    shutdown: AtomicBool,
+}
+
+/// Controls a pipeline run by a [Supervisor], from any thread.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineControl(Arc<ControlFlags>);
+
+impl PipelineControl {
+    /// Stops reading points until [PipelineControl::resume] is called.
+    pub fn pause(&self) {
+        self.0.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resumes a paused pipeline.
+    pub fn resume(&self) {
+        self.0.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Removes all balls from the model before the next point is fitted.
+    pub fn reset(&self) {
+        self.0.reset.store(true, Ordering::SeqCst);
+    }
+
+    /// Stops the pipeline before the next point is read; its status is [PipelineStatus::Shutdown].
+    pub fn shutdown(&self) {
+        self.0.shutdown.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Builds a pipeline from the configuration template, named after the pipeline.
+type PipelineBuilder<Point> = Box<dyn FnOnce(AlgoConfig) -> Pipeline<Point> + Send>;
+
+/// Runs several independent pipelines in one process on a fixed number of threads.
+///
+/// Algorithms, models and streamers cannot be sent across threads, thus each pipeline is built
+/// by the thread that runs it, from a copy of the configuration template named after the pipeline.
+/// Pipelines of the same thread take turns point by point, thus their sources should not block.
+/// A pipeline that panics is marked failed without affecting the others.
+/// The pipelines are served on their own websocket endpoints by [crate::service::backend_routed].
+/// ```
+/// use fluent_data::{algorithm::AlgoConfig, space, Algo, Model, Streamer};
+/// use fluent_data::streamer::{BoxedPoints, BoxedWrite, Pipeline, PipelineStatus, Supervisor};
+///
+/// let mut supervisor = Supervisor::new(AlgoConfig::default(), 2);
+/// for name in ["north", "south"] {
+///     supervisor.add(name, |config| {
+///         let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+///         let model = Model::new(space::euclid_dist);
+///         let points: BoxedPoints = Box::new((0..10).map(|i| Ok(format!("[{}]", i))));
+///         let write: BoxedWrite = Box::new(|_| Ok(()));
+///         Pipeline::new(algo, model, Streamer::new(points, write))
+///     });
+/// }
+/// let report = supervisor.run();
+/// assert_eq!(20, report.admitted());
+/// ```
+pub struct Supervisor<Point: PartialEq + 'static> {
+    template: AlgoConfig,
+    threads: usize,
+    pipelines: Vec<(String, PipelineControl, PipelineBuilder<Point>)>,
+}
+
+impl<Point: PartialEq + Serialize + DeserializeOwned + 'static> Supervisor<Point> {
+    /// Builds a supervisor that runs pipelines on `threads` threads.
+    pub fn new(template: AlgoConfig, threads: usize) -> Self {
+        assert!(threads > 0, "supervisor needs at least one thread");
+        Self {
+            template,
+            threads,
+            pipelines: vec![],
+        }
+    }
+
+    /// Adds a named pipeline and returns its control handle.
+    /// The pipeline is built when the supervisor runs, by the thread that runs it.
+    pub fn add(
+        &mut self,
+        name: &str,
+        build: impl FnOnce(AlgoConfig) -> Pipeline<Point> + Send + 'static,
+    ) -> PipelineControl {
+        let control = PipelineControl::default();
+        self.pipelines
+            .push((name.to_string(), control.clone(), Box::new(build)));
+        control
+    }
+
+    /// Gets the control handle of the named pipeline.
+    pub fn control(&self, name: &str) -> Option<PipelineControl> {
+        self.pipelines
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, control, _)| control.clone())
+    }
+
+    /// Runs all pipelines until they finish, are shut down or fail.
+    pub fn run(self) -> SupervisorReport {
+        let mut batches: Vec<Vec<_>> = (0..self.threads).map(|_| vec![]).collect();
+        for (i, (name, control, build)) in self.pipelines.into_iter().enumerate() {
+            let mut config = self.template.clone();
+            config.name = name.clone();
+            batches[i % self.threads].push((i, name, control, config, build));
+        }
+        let workers: Vec<_> = batches
+            .into_iter()
+            .map(|batch| {
+                let names: Vec<_> = batch
+                    .iter()
+                    .map(|(i, name, ..)| (*i, name.clone()))
+                    .collect();
+                (names, thread::spawn(move || run_batch(batch)))
+            })
+            .collect();
+        let mut statuses: Vec<_> = workers
+            .into_iter()
+            .flat_map(|(names, worker)| match worker.join() {
+                Ok(statuses) => statuses,
+                // the pipelines of a worker that panicked outside of a pipeline step all failed
+                Err(panic) => {
+                    let reason = panic_reason(panic);
+                    names
+                        .into_iter()
+                        .map(|(i, name)| (i, name, PipelineStatus::Failed(reason.clone())))
+                        .collect()
+                }
+            })
+            .collect();
+        statuses.sort_by_key(|(i, _, _)| *i);
+        SupervisorReport {
+            pipelines: statuses
+                .into_iter()
+                .map(|(_, name, status)| (name, status))
+                .collect(),
+        }
+    }
+}
+
+/// Runs pipelines in turn, point by point, in the current thread.
+fn run_batch<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+    batch: Vec<(
+        usize,
+        String,
+        PipelineControl,
+        AlgoConfig,
+        PipelineBuilder<Point>,
+    )>,
+) -> Vec<(usize, String, PipelineStatus)> {
+    let mut running = vec![];
+    let mut statuses = vec![];
+    for (i, name, control, config, build) in batch {
+        match catch_unwind(AssertUnwindSafe(|| build(config))) {
+            Ok(pipeline) => running.push((i, name, control, pipeline)),
+            Err(panic) => statuses.push((i, name, PipelineStatus::Failed(panic_reason(panic)))),
+        }
+    }
+    while !running.is_empty() {
+        let mut busy = false;
+        let mut index = 0;
+        while index < running.len() {
+            let (_, _, control, pipeline) = &mut running[index];
+            let status = match step(control, pipeline) {
+                Some(status) => status,
+                None => {
+                    busy |= !control.0.paused.load(Ordering::SeqCst);
+                    index += 1;
+                    continue;
+                }
+            };
+            let (i, name, _, _) = running.remove(index);
+            statuses.push((i, name, status));
+        }
+        if !busy {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+    statuses
+}
+
+/// Applies control requests then fits the next point of the pipeline.
+/// Returns the final status when the pipeline ends.
+fn step<Point: PartialEq + Serialize + DeserializeOwned + 'static>(
+    control: &PipelineControl,
+    pipeline: &mut Pipeline<Point>,
+) -> Option<PipelineStatus> {
+    let flags = &control.0;
+    if flags.shutdown.load(Ordering::SeqCst) {
+        return Some(PipelineStatus::Shutdown(pipeline.streamer.report.clone()));
+    }
+    if flags.paused.load(Ordering::SeqCst) {
+        return None;
+    }
+    if flags.reset.swap(false, Ordering::SeqCst) {
+        pipeline.model.clear();
+    }
+    let Pipeline {
+        algo,
+        model,
+        streamer,
+    } = pipeline;
+    let fitted = catch_unwind(AssertUnwindSafe(|| -> Result<bool, Box<dyn Error>> {
+        if streamer.fit_next(algo, model)? {
+            return Ok(true);
+        }
+        streamer.final_emit(model)?;
+        Ok(false)
+    }));
+    match fitted {
+        Ok(Ok(true)) => None,
+        Ok(Ok(false)) => Some(PipelineStatus::Finished(streamer.report.clone())),
+        Ok(Err(reason)) => Some(PipelineStatus::Failed(reason.to_string())),
+        Err(panic) => Some(PipelineStatus::Failed(panic_reason(panic))),
+    }
+}
+
+/// Gets the message of a panic.
+pub(crate) fn panic_reason(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(reason) => *reason,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(reason) => reason.to_string(),
+            Err(_) => String::from("panicked"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{cell::Cell, sync::mpsc};
+
+    use rand::SeedableRng;
+    use rand_distr::{Distribution, Normal};
+
+    use crate::{serializer, space, streamer::*};
+
+    #[test]
+    fn test_serialize_ball() {
+        let obj = serialize_ball(&Ball::new(vec![3., 5.1], 4.7, 0.999));
+        let json = serde_json::to_string(&obj).unwrap();
+        assert_eq!(
+            r#"{"center":[3.0,5.1],"radius":2.16794833886788,"weight":0.999}"#,
+            json
+        );
+    }
+
+    #[test]
+    fn test_serialize_model() {
+        let mut model = Model::new(space::euclid_dist);
+        let v = model.add_ball(Ball::new(vec![3., 5.1], 4.7, 0.999), vec![]);
+        model.add_ball(Ball::new(vec![1.2, 6.], 1.3, 3.998), vec![v.as_neighbor()]);
+        let obj = serialize_model(&model);
+        let json = serde_json::to_string(&obj).unwrap();
+        assert_eq!(
+            r#"[{"center":[3.0,5.1],"radius":2.16794833886788,"weight":0.999},{"center":[1.2,6.0],"radius":1.140175425099138,"weight":3.998}]"#,
+            json
+        );
+    }
+
+    #[test]
+    fn test_streamer() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![Ok(String::from("[1.0,1.0]"))].into_iter();
+        let mut result = String::new();
+        let write = |s| {
+            result = s;
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write);
+        match Streamer::run(streamer, algo, &mut model) {
+            Ok(()) => assert_eq!(
+                r#"[{"center":[1.0,1.0],"radius":null,"weight":0.0}]"#,
+                result
+            ),
+            Err(_) => panic!(),
+        };
+    }
+
+    #[test]
+    fn test_with_serializer() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![Ok(String::from("[1.0,1.0]")), Ok(String::from("[3.0,1.0]"))];
+        let mut result = vec![];
+        let write = |s| {
+            result.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points.into_iter(), write)
+            .with_serializer(serializer::GeoJsonSerializer)
+            .with_manual_emit()
+            .with_serializer(serializer::CsvSerializer);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        assert_eq!(vec!["x0,x1,radius,weight\n3,1,2,1\n"], result);
+    }
+
+    #[test]
+    fn test_serializer_buffer_reuse() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[3.0]"))];
+        let mut result = vec![];
+        let write = |s| {
+            result.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points.into_iter(), write)
+            .with_serializer(serializer::PrettyJsonSerializer);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        assert_eq!(2, result.len());
+        for output in result {
+            let model: Value = serde_json::from_str(&output).unwrap();
+            assert_eq!(1, model.as_array().unwrap().len());
+        }
+    }
+
+    #[test]
+    fn test_envelope_composite() {
+        use crate::{client::DeltaDecoder, message::Envelope, serializer::EnvelopeSerializer};
+
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = (0..4).map(|i| Ok(format!("[{}]", i)));
+        let mut outputs = vec![];
+        let write = |s| {
+            outputs.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write)
+            .with_serializer(EnvelopeSerializer::new())
+            .with_assignments()
+            .with_emit_every(2)
+            .with_heartbeat(Duration::ZERO);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        let messages: Vec<Envelope<Value>> = outputs
+            .iter()
+            .map(|o| serde_json::from_str(o).unwrap())
+            .collect();
+        let kinds: Vec<(&str, u64)> = messages
+            .iter()
+            .map(|m| match m {
+                Envelope::Composite { seq, .. } => ("composite", *seq),
+                Envelope::Heartbeat { seq, .. } => ("heartbeat", *seq),
+                _ => panic!("{:?}", m),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("heartbeat", 0),
+                ("heartbeat", 0),
+                ("composite", 0),
+                ("heartbeat", 1),
+                ("heartbeat", 1),
+                ("composite", 1),
+            ],
+            kinds
+        );
+        let mut decoder = DeltaDecoder::new();
+        let balls = decoder.decode(&outputs[5]).unwrap().unwrap();
+        assert_eq!(model.iter_balls().count(), balls.len());
+        match &messages[5] {
+            Envelope::Composite { body, .. } => {
+                let total: u64 = body["assignments"]
+                    .as_object()
+                    .unwrap()
+                    .values()
+                    .map(|c| c.as_u64().unwrap())
+                    .sum();
+                assert_eq!(2, total);
+            }
+            m => panic!("{:?}", m),
+        }
+    }
+
+    #[test]
+    fn test_assignments() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut points: Vec<_> = normal_points(2., 3., 300, 1).collect();
+        points.extend(normal_points(50., 3., 300, 2));
+        let mut outputs = vec![];
+        let write = |s| {
+            outputs.push(s);
+            Ok(())
+        };
+        let mut streamer = Streamer::new(points.into_iter(), write)
+            .with_manual_emit()
+            .with_assignments();
+        let mut count = 0;
+        while streamer.fit_next(&algo, &mut model).unwrap() {
+            count += 1;
+            if count % 40 == 0 {
+                streamer.emit_now(&model).unwrap();
+            }
+        }
+        drop(streamer);
+        assert_eq!(15, outputs.len());
+        for output in &outputs {
+            let output: Value = serde_json::from_str(output).unwrap();
+            let counts = output["assignments"].as_object().unwrap();
+            assert_eq!(output["model"].as_array().unwrap().len() + 1, counts.len());
+            let total: u64 = counts.values().map(|c| c.as_u64().unwrap()).sum();
+            assert_eq!(40, total);
+        }
+    }
+
+    #[test]
+    fn test_soft_assignments() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = normal_points(2., 3., 100, 1);
+        let mut outputs = vec![];
+        let write = |s| {
+            outputs.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write)
+            .with_assignments()
+            .with_soft_assignments(1.);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        assert_eq!(100, outputs.len());
+        let output: Value = serde_json::from_str(&outputs[99]).unwrap();
+        assert!(output["assignments"].is_object());
+        let memberships = output["memberships"].as_array().unwrap();
+        let total: f64 = memberships.iter().map(|m| m[1].as_f64().unwrap()).sum();
+        assert!((total - 1.).abs() < 1E-9);
+        let first: Value = serde_json::from_str(&outputs[0]).unwrap();
+        assert!(first["memberships"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new(60);
+        for i in 0..100 {
+            assert_eq!(i, history.push(format!("model {}", i)));
+        }
+        assert_eq!(99, history.latest().unwrap().seq);
+        assert!(history.get(39).is_none());
+        assert_eq!("model 40", history.get(40).unwrap().model);
+        assert!(history.get(100).is_none());
+        let seqs: Vec<u64> = history.range(30, 45).map(|e| e.seq).collect();
+        assert_eq!((40..45).collect::<Vec<u64>>(), seqs);
+        assert_eq!(60, history.range(0, u64::MAX).count());
+        assert_eq!(0, history.range(45, 45).count());
+    }
+
+    #[test]
+    fn test_channels() {
+        let (point_producer, point_receiver) = mpsc::channel();
+        let (model_producer, model_receiver) = mpsc::channel();
+        let (mut points, mut write) = channels(point_receiver, model_producer);
+        point_producer.send(String::from("point")).unwrap();
+        let p = points.next().unwrap().unwrap();
+        assert_eq!("point", p);
+        (write)(String::from("model")).unwrap();
+        let m = model_receiver.recv().unwrap();
+        assert_eq!("model", m);
+    }
+
+    #[test]
+    fn test_grid_snap() {
+        let points = vec!["[0.9,2.1]", "[1.1,1.9]", "[1.2,2.2]", "[3.4,2.0]", "[1.0]"]
+            .into_iter()
+            .map(|p| Ok(String::from(p)));
+        let snapped: Vec<_> = grid_snap(points, vec![1., 0.5]).collect();
+        for p in &snapped[0..3] {
+            assert_eq!("[1.0,2.0]", p.as_ref().unwrap());
+        }
+        assert_eq!("[3.0,2.0]", snapped[3].as_ref().unwrap());
+        assert!(snapped[4].is_err());
+    }
+
+    #[test]
+    fn test_map_point() {
+        let points = vec!["[3.0,4.0]", "[1.0]", "[]", "[0.0,0.0]"]
+            .into_iter()
+            .map(|p| Ok(String::from(p)));
+        let with_norm = |mut p: Vec<f64>| {
+            assert!(!p.is_empty(), "empty point");
+            let norm = p.iter().map(|x| x * x).sum::<f64>().sqrt();
+            p.push(norm);
+            p
+        };
+        let mapped: Vec<_> = map_point(points, with_norm).collect();
+        assert_eq!("[3.0,4.0,5.0]", mapped[0].as_ref().unwrap());
+        assert_eq!("[1.0,1.0]", mapped[1].as_ref().unwrap());
+        assert_eq!(
+            "point transform panicked: empty point",
+            mapped[2].as_ref().unwrap_err().to_string()
+        );
+        assert_eq!("[0.0,0.0,0.0]", mapped[3].as_ref().unwrap());
+        let records = [
+            r#"["a",3.0,4.0]"#,
+            r#"{"id":"b","point":[1.0]}"#,
+            r#"[[3.0,4.0],["c",1.0],"oops"]"#,
+            r#"{"command":"remove","ball":0}"#,
+            "oops",
+        ];
+        let mapped: Vec<_> = map_point(records.map(|r| Ok(String::from(r))).into_iter(), with_norm)
+            .map(Result::unwrap)
+            .collect();
+        let expected = [
+            r#"["a",3.0,4.0,5.0]"#,
+            r#"{"id":"b","point":[1.0,1.0]}"#,
+            r#"[[3.0,4.0,5.0],["c",1.0,1.0],"oops"]"#,
+            r#"{"command":"remove","ball":0}"#,
+            "oops",
+        ];
+        assert_eq!(expected.to_vec(), mapped);
+    }
+
+    #[test]
+    fn test_map_point_panic() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = ["[1.0]", "[-1.0]", "[2.0]"].map(|p| Ok(String::from(p)));
+        let points = map_point(points.into_iter(), |p| {
+            assert!(p[0] >= 0., "negative point");
+            p
+        });
+        let streamer = Streamer::new(points, |_| Ok(()));
+        let mut drops = vec![];
+        let report = Streamer::run_with_drop_sink(streamer, algo, &mut model, |record, reason| {
+            drops.push((record, reason))
+        })
+        .unwrap();
+        assert_eq!(vec![(String::from("[-1.0]"), DropReason::Panicked)], drops);
+        assert_eq!(2, report.admitted);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let catalog = [
+            (
+                r#"[1, "2", 3]"#,
+                r#"line 1, offset 6, field [1]: invalid type: string "2", expected f64 (expected a JSON array of numbers, e.g. [1.0,2.5])"#,
+            ),
+            (
+                r#"{"point": [1,2]}"#,
+                r#"line 1, offset 0, field "point": invalid type: map, expected a sequence (expected a JSON array of numbers, e.g. [1.0,2.5])"#,
+            ),
+            (
+                "[1.5, null]",
+                "line 1, offset 9, field [1]: invalid type: null, expected f64 (expected a JSON array of numbers, e.g. [1.0,2.5])",
+            ),
+            (
+                "[1.5, 2",
+                "line 1, offset 6: EOF while parsing a list (expected a JSON array of numbers, e.g. [1.0,2.5])",
+            ),
+            (
+                "oops",
+                "line 1, offset 0: expected value (expected a JSON array of numbers, e.g. [1.0,2.5])",
+            ),
+            (
+                "[1.0,\n 2.0,\n x]",
+                "line 1, offset 13: expected value (expected a JSON array of numbers, e.g. [1.0,2.5])",
+            ),
+        ];
+        for (record, expected) in catalog {
+            let err = parse_point::<Vec<f64>>(1, record).unwrap_err();
+            assert_eq!(expected, err.to_string(), "{}", record);
+        }
+    }
+
+    #[test]
+    fn test_parse_error_line() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec!["[1.0]", "[2.0]", "[\"3\"]"]
+            .into_iter()
+            .map(|p| Ok(String::from(p)));
+        let streamer = Streamer::new(points, |_| Ok(()));
+        let err = Streamer::run(streamer, algo, &mut model).unwrap_err();
+        match err.downcast_ref::<StreamError>() {
+            Some(StreamError::ParseError { line, field, .. }) => {
+                assert_eq!(3, *line);
+                assert_eq!(Some(String::from("[0]")), *field);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_max_line_length() {
+        let input = format!("[1.0]\n{}\n[2.0]\r\n[3.0]", "9".repeat(100_000));
+        let mut points = lines(BufReader::with_capacity(64, input.as_bytes()), 1000);
+        assert_eq!("[1.0]", points.next().unwrap().unwrap());
+        let err = points.next().unwrap().unwrap_err();
+        assert_eq!(
+            Some(&StreamError::LineTooLong {
+                line: 2,
+                max_len: 1000
+            }),
+            err.downcast_ref::<StreamError>()
+        );
+        assert_eq!("[2.0]", points.next().unwrap().unwrap());
+        assert_eq!("[3.0]", points.next().unwrap().unwrap());
+        assert!(points.next().is_none());
+    }
+
+    #[test]
+    fn test_chain_sources_timestamp() {
+        let records = |r: &[&str]| -> Vec<Result<String, Box<dyn Error>>> {
+            r.iter().map(|p| Ok(String::from(*p))).collect()
+        };
+        let history = records(&["[1.0,1.0]", "[2.0,2.0]", "[3.0,3.0]"]);
+        let live = records(&["[2.0,2.0]", "[3.0,3.0]", "[4.0,4.0]", "[5.0,5.0]"]);
+        let first_coordinate = |r: &str| serde_json::from_str::<Vec<f64>>(r).ok().map(|p| p[0]);
+        let (points, phases) = chain_sources(
+            history.into_iter(),
+            live.into_iter(),
+            CutoverPolicy::Timestamp(first_coordinate),
+        );
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut fitted = vec![];
+        let streamer = Streamer::new(points, |_| Ok(())).with_phases(phases);
+        let report =
+            Streamer::run_with_ack(streamer, algo, &mut model, |a| fitted.push(a)).unwrap();
+        assert_eq!(5, fitted.len());
+        assert_eq!(5, report.admitted);
+        assert_eq!(
+            PhaseReport {
+                history: 3,
+                live: 2,
+                skipped: 2
+            },
+            report.phases
+        );
+    }
+
+    #[test]
+    fn test_chain_sources_drain() {
+        let history = vec![Ok(String::from("[2.0]")), Ok(String::from("[3.0]"))];
+        let live = vec![Ok(String::from("[2.0]"))];
+        let (points, phases) =
+            chain_sources(history.into_iter(), live.into_iter(), CutoverPolicy::Drain);
+        let records: Vec<String> = points.map(|r| r.unwrap()).collect();
+        assert_eq!(vec!["[2.0]", "[3.0]", "[2.0]"], records);
+        assert_eq!(1, phases.get().live);
+    }
+
+    #[test]
+    fn test_replay_timed() {
+        let records = [
+            "[100.0,1.0]",
+            "[110.0,2.0]",
+            "[105.0,3.0]",
+            "x",
+            "[110.0,4.0]",
+        ];
+        let timestamp_of =
+            |record: &str| serde_json::from_str::<Vec<f64>>(record).map_or(f64::NAN, |p| p[0]);
+        let points = replay_timed(
+            records.iter().map(|r| Ok(r.to_string())),
+            timestamp_of,
+            100.,
+        );
+        let start = Instant::now();
+        assert_eq!(5, points.count());
+        // the last record is due 100 ms after the first one, it may only be late
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        // a gap too large to wait for does not delay the record
+        let records = ["[0.0]", "[1E300]"].map(|r| Ok(r.to_string()));
+        let points = replay_timed(records.into_iter(), timestamp_of, 1E-9);
+        assert_eq!(2, points.count());
+    }
+
+    #[test]
+    fn test_adaptive_sampling_stationary() {
+        let (full, _) = run_sampled(None, normal_points(2., 3., 20000, 1));
+        let (sampled, report) = run_sampled(Some(0.2), normal_points(2., 3., 20000, 1));
+        assert_eq!(20000, report.admitted + report.dropped);
+        assert!(report.admitted < 6000);
+        let (full_mean, full_std_dev) = mixture_stats(&full);
+        let (sampled_mean, sampled_std_dev) = mixture_stats(&sampled);
+        assert!((full_mean - sampled_mean).abs() < 0.5);
+        assert!((full_std_dev - sampled_std_dev).abs() < 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "between 0 and 1")]
+    fn test_adaptive_sampling_rate() {
+        let (points, write) = stdio();
+        let _ = Streamer::new(points, write).with_adaptive_sampling(f64::NAN);
+    }
+
+    #[test]
+    fn test_adaptive_sampling_novelty() {
+        let mut points: Vec<_> = normal_points(2., 3., 5000, 1).collect();
+        points.extend(normal_points(100., 3., 5, 2));
+        let (model, _) = run_sampled(Some(0.2), points.into_iter());
+        assert!(model.iter_balls().any(|b| b.center()[0] > 90.));
+    }
+
+    #[test]
+    fn test_seeded_replay() {
+        assert_eq!(run_seeded(11), run_seeded(11));
+        assert_ne!(run_seeded(11), run_seeded(12));
+    }
+
+    fn run_seeded(seed: u64) -> Vec<String> {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_seed(seed);
+        let mut model = Model::new(space::euclid_dist);
+        let mut outputs = vec![];
+        let write = |s| {
+            outputs.push(s);
+            Ok(())
+        };
+        let streamer =
+            Streamer::new(normal_points(2., 3., 2000, 1), write).with_adaptive_sampling(0.2);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        outputs
+    }
+
+    #[test]
+    fn test_global_reservoir() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_seed(SAMPLING_SEED);
+        let mut model = Model::new(space::euclid_dist);
+        let points = (0..10000).map(|i| Ok(format!("[{}]", i)));
+        let streamer = Streamer::new(points, |_| Ok(()))
+            .with_manual_emit()
+            .with_global_reservoir(200);
+        let reservoir = streamer.reservoir().unwrap();
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        assert_eq!(10000, reservoir.seen());
+        let sampled: Vec<f64> = reservoir
+            .points()
+            .iter()
+            .map(|p| serde_json::from_str::<Vec<f64>>(p).unwrap()[0])
+            .collect();
+        assert_eq!(200, sampled.len());
+        let mean = sampled.iter().sum::<f64>() / sampled.len() as f64;
+        assert!((mean - 5000.).abs() < 700.);
+        let first_half = sampled.iter().filter(|&&x| x < 5000.).count();
+        assert!((70..130).contains(&first_half));
+    }
+
+    #[test]
+    fn test_admin_commands() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let records = [
+            "[0.0]",
+            "[1.0]",
+            r#"{"cmd":"freeze","index":0}"#,
+            "[0.5]",
+            r#"{"cmd":"freeze","index":3}"#,
+            r#"{"cmd":"unfreeze","index":0}"#,
+        ];
+        let points = records.iter().map(|r| Ok(String::from(*r)));
+        let mut outputs = vec![];
+        let write = |s| {
+            outputs.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write).with_admin_commands();
+        let mut acks = vec![];
+        let report =
+            Streamer::run_with_ack(streamer, algo, &mut model, |ack| acks.push(ack)).unwrap();
+        assert_eq!(1, report.failed_commands);
+        assert_eq!(Ack::Nack(4), acks[4]);
+        assert_eq!(Ack::Ack(5), acks[5]);
+        assert!(outputs[2].contains(r#""frozen":true"#));
+        assert_eq!(outputs[2].replace(r#","frozen":true"#, ""), outputs[1]);
+        assert_eq!(outputs[3].replace(r#","frozen":true"#, ""), outputs[1]);
+        assert!(!outputs[4].contains("frozen"));
+        assert_eq!(5, outputs.len());
+    }
+
+    #[test]
+    fn test_admin_command_unknown_ball() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let records = ["[0.0]", r#"{"cmd":"freeze","index":3}"#, "[1.0]"];
+        let points = records.iter().map(|r| Ok(String::from(*r)));
+        let streamer = Streamer::new(points, |_| Ok(())).with_admin_commands();
+        let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+        assert_eq!(1, report.failed_commands);
+        assert_eq!(2, report.admitted);
+    }
+
+    const SAMPLING_SEED: u64 = 7;
+
+    #[test]
+    fn test_run_hierarchical() {
+        let config: AlgoConfig = "threshold=8".parse().unwrap();
+        let coarse_algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+        let fine_algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut coarse_model = Model::new(space::euclid_dist);
+        let mut fine_model = Model::new(space::euclid_dist);
+        let mut rng = StdRng::seed_from_u64(4);
+        let normal = Normal::new(0., 1.).unwrap();
+        let points: Vec<_> = (0..2000)
+            .map(|i| {
+                let burst = (i / 50) % 6;
+                let center = 100. * (burst % 3) as f64 + 10. * (burst / 3) as f64;
+                Ok(json!(vec![center + normal.sample(&mut rng)]).to_string())
+            })
+            .collect();
+        let mut outputs = vec![];
+        let write = |s| {
+            outputs.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points.into_iter(), write).with_manual_emit();
+        Streamer::run_hierarchical(
+            streamer,
+            coarse_algo,
+            &mut coarse_model,
+            fine_algo,
+            &mut fine_model,
+        )
+        .unwrap();
+        let coarse: Vec<_> = coarse_model.iter_balls().map(|b| b.clone()).collect();
+        let fine: Vec<_> = fine_model.iter_balls().map(|b| b.clone()).collect();
+        assert!(coarse.len() < fine.len());
+        let output: Value = serde_json::from_str(&outputs[0]).unwrap();
+        let parents = coarse_model.parents_of(&fine_model);
+        assert_eq!(json!(parents), output["parents"]);
+        assert_eq!(fine.len(), parents.len());
+        for (ball, parent) in fine.iter().zip(parents) {
+            let parent = &coarse[parent.unwrap()];
+            let dist = space::euclid_dist(&ball.center, &parent.center);
+            assert!(dist < 64. * parent.radius);
+        }
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let stalls = Arc::new(Mutex::new(vec![]));
+        let reported = Arc::clone(&stalls);
+        let watchdog = Watchdog::new(Duration::from_millis(50), move |stall| {
+            reported.lock().unwrap().push(stall)
+        });
+        let points = (0..5).map(|i| Ok(format!("[{}]", i)));
+        let mut writes = 0;
+        let write = |_| {
+            writes += 1;
+            if writes == 3 {
+                thread::sleep(Duration::from_millis(300));
+            }
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write).with_watchdog(watchdog);
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        let stalls = stalls.lock().unwrap();
+        assert_eq!(1, stalls.len());
+        assert_eq!(StallPhase::Writing, stalls[0].phase);
+        assert_eq!(3, stalls[0].processed);
+        assert!(stalls[0].stalled_for > Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_watchdog_abort() {
+        let watchdog = Watchdog::new(Duration::from_millis(50), |_| {}).with_abort();
+        let points = (0..5).map(|i| Ok(format!("[{}]", i)));
+        let write = |model: String| {
+            if model.contains("2.0") {
+                thread::sleep(Duration::from_millis(300));
+            }
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write).with_watchdog(watchdog);
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let err = Streamer::run_with_report(streamer, algo, &mut model).unwrap_err();
+        match err.downcast_ref::<StreamError>() {
+            Some(StreamError::Stalled(stall)) => assert_eq!(StallPhase::Writing, stall.phase),
+            _ => panic!("{}", err),
+        }
+        assert!(model.iter_balls().count() < 5);
+    }
+
+    #[test]
+    fn test_supervisor() {
+        let mut supervisor = Supervisor::new(AlgoConfig::default(), 2);
+        let mut outputs = vec![];
+        for (name, mean) in [("low", -5.), ("high", 5.), ("broken", 0.)] {
+            let output = Arc::new(Mutex::new(vec![]));
+            outputs.push((name, mean, output.clone()));
+            supervisor.add(name, move |config| {
+                let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+                let model = Model::new(space::euclid_dist);
+                let points = normal_points(mean, 1., 500, 3);
+                let points: BoxedPoints = if name == "broken" {
+                    Box::new(
+                        points
+                            .take(100)
+                            .chain(std::iter::from_fn(|| panic!("broken"))),
+                    )
+                } else {
+                    Box::new(points)
+                };
+                let write: BoxedWrite = Box::new(move |s| {
+                    output.lock().unwrap().push(s);
+                    Ok(())
+                });
+                Pipeline::new(algo, model, Streamer::new(points, write).with_manual_emit())
+            });
+        }
+        let report = supervisor.run();
+        assert_eq!(vec!["broken"], report.failed());
+        assert_eq!(
+            Some(&PipelineStatus::Failed("broken".into())),
+            report.get("broken")
+        );
+        assert_eq!(1000, report.admitted());
+        for (_, mean, output) in outputs.iter().take(2) {
+            let algo = Algo::new(space::euclid_dist, space::real_combine);
+            let mut model = Model::new(space::euclid_dist);
+            let mut expected = vec![];
+            let write = |s| {
+                expected.push(s);
+                Ok(())
+            };
+            let streamer =
+                Streamer::new(normal_points(*mean, 1., 500, 3), write).with_manual_emit();
+            Streamer::run(streamer, algo, &mut model).unwrap();
+            assert_eq!(expected, *output.lock().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_supervisor_control() {
+        let mut supervisor = Supervisor::new(AlgoConfig::default(), 1);
+        let (tx, rx) = mpsc::channel();
+        let control = supervisor.add("paused", |config| {
+            let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+            let model = Model::new(space::euclid_dist);
+            let points: BoxedPoints = Box::new(rx.into_iter().map(Ok));
+            let write: BoxedWrite = Box::new(|_| Ok(()));
+            Pipeline::new(algo, model, Streamer::new(points, write))
+        });
+        control.pause();
+        tx.send(String::from("[1]")).unwrap();
+        let handle = thread::spawn(move || supervisor.run());
+        thread::sleep(Duration::from_millis(50));
+        control.shutdown();
+        let report = handle.join().unwrap();
+        assert_eq!(0, report.admitted());
+        assert!(matches!(
+            report.get("paused"),
+            Some(PipelineStatus::Shutdown(_))
+        ));
+    }
+
+    fn run_sampled(
+        target_rate: Option<f64>,
+        points: impl Iterator<Item = Result<String, Box<dyn Error>>>,
+    ) -> (Model<Vec<f64>>, RunReport) {
+        // the model occasionally absorbs outliers into a wide ball, thus seed for reproducibility
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_seed(SAMPLING_SEED);
+        let mut model = Model::new(space::euclid_dist);
+        let write = |_| Ok(());
+        let streamer = Streamer::new(points, write);
+        let streamer = match target_rate {
+            Some(rate) => streamer.with_adaptive_sampling(rate),
+            None => streamer,
+        };
+        let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+        (model, report)
+    }
+
+    fn mixture_stats(model: &Model<Vec<f64>>) -> (f64, f64) {
+        let weight: f64 = model.iter_balls().map(|b| b.weight()).sum();
+        let moment = |f: &dyn Fn(&Ball<Vec<f64>>) -> f64| {
+            model.iter_balls().map(|b| b.weight() * f(&b)).sum::<f64>() / weight
+        };
+        let mean = moment(&|b| b.center()[0]);
+        let square = moment(&|b| b.radius().powi(2) + b.center()[0].powi(2));
+        (mean, (square - mean * mean).sqrt())
+    }
+
+    fn normal_points(
+        mean: f64,
+        std_dev: f64,
+        count: usize,
+        seed: u64,
+    ) -> impl Iterator<Item = Result<String, Box<dyn Error>>> {
+        let normal = Normal::new(mean, std_dev).unwrap();
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count).map(move |_| Ok(json!(vec![normal.sample(&mut rng)]).to_string()))
+    }
+
+    #[test]
+    fn test_run_with_ack() {
+        let queue = vec![
+            Ok(String::from("[1.0]")),
+            Ok(String::from("[1.5]")),
+            Ok(String::from("not a point")),
+            Err("connection lost".into()),
+            Ok(String::from("[2.0]")),
+        ];
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let mut written = 0;
+        let write = |_| {
+            written += 1;
+            Ok(())
+        };
+        let streamer = Streamer::new(queue.into_iter(), write);
+        let mut acks = vec![];
+        let report = Streamer::run_with_ack(streamer, algo, &mut model, |a| acks.push(a)).unwrap();
+        assert_eq!(
+            vec![
+                Ack::Ack(0),
+                Ack::Ack(1),
+                Ack::Nack(2),
+                Ack::Nack(3),
+                Ack::Ack(4)
+            ],
+            acks
+        );
+        assert_eq!(3, report.admitted);
+        assert_eq!(3, written);
+    }
+
+    #[test]
+    fn test_emit_now() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![
+            Ok(String::from("[1.0]")),
+            Ok(String::from("[2.0]")),
+            Ok(String::from("[3.0]")),
+        ];
+        let mut result: Vec<String> = vec![];
+        let write = |s| {
+            result.push(s);
+            Ok(())
+        };
+        let mut streamer = Streamer::new(points.into_iter(), write).with_manual_emit();
+        assert!(streamer.fit_next(&algo, &mut model).unwrap());
+        streamer.emit_now(&model).unwrap();
+        assert!(streamer.fit_next(&algo, &mut model).unwrap());
+        assert!(streamer.fit_next(&algo, &mut model).unwrap());
+        streamer.emit_now(&model).unwrap();
+        assert!(!streamer.fit_next(&algo, &mut model).unwrap());
+        assert_eq!(3, streamer.report().admitted);
+        drop(streamer);
+        assert_eq!(
+            vec![
+                r#"[{"center":[1.0],"radius":null,"weight":0.0}]"#,
+                r#"[{"center":[2.5],"radius":1.0,"weight":2.0}]"#,
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn test_final_emit() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))];
+        let mut result: Vec<String> = vec![];
+        let write = |s| {
+            result.push(s);
+            Ok(())
+        };
+        let streamer = Streamer::new(points.into_iter(), write).with_manual_emit();
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        assert_eq!(
+            vec![r#"[{"center":[2.0],"radius":1.0,"weight":1.0}]"#],
+            result
+        );
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_velocity(0.1);
+        let mut model = Model::new(space::euclid_dist);
+        let points = (0..25).map(|i| Ok(format!("[{}]", (i % 5) as f64)));
+        let checkpoints = Rc::new(RefCell::new(vec![]));
+        let sink = Rc::clone(&checkpoints);
+        let write = move |checkpoint| {
+            sink.borrow_mut().push(checkpoint);
+            Ok(())
+        };
+        let streamer =
+            Streamer::new(points, |_| Ok(())).with_checkpoints(Cadence::Points(10), write);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        let checkpoints = checkpoints.borrow();
+        assert_eq!(3, checkpoints.len());
+        let decay = DecayConfig {
+            factor: 0.5,
+            period: Duration::from_secs(3600),
+            floor: 1E-2,
+        };
+        let restored = restore_checkpoint(
+            checkpoints.last().unwrap(),
+            space::euclid_dist,
+            Some(&decay),
+        )
+        .unwrap();
+        assert_eq!(model.iter_balls().count(), restored.iter_balls().count());
+        for (ball, restored) in model.iter_balls().zip(restored.iter_balls()) {
+            assert_eq!(ball.center(), restored.center());
+            assert!((ball.radius() - restored.radius()).abs() < 1e-9);
+            assert!((ball.weight() - restored.weight()).abs() < 1e-6 * ball.weight());
+            assert_eq!(ball.id(), restored.id());
+            assert_eq!(ball.dist_mean, restored.dist_mean);
+            assert_eq!(ball.dist_var, restored.dist_var);
+            assert_eq!(ball.assigned, restored.assigned);
+            assert_eq!(ball.velocity(), restored.velocity());
+        }
+    }
+
+    #[test]
+    fn test_latency_invalid_timestamp() {
+        // the timestamp is the first coordinate
+        fn timestamp(record: &str) -> Option<f64> {
+            serde_json::from_str::<Vec<f64>>(record)
+                .ok()?
+                .first()
+                .copied()
+        }
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = ["[10.0]", "[-5.0]", "[1e300]", "[20.0]"].map(|p| Ok(String::from(p)));
+        let streamer = Streamer::new(points.into_iter(), |_| Ok(()))
+            .with_latency(Timestamps::Producer(timestamp), || {
+                Duration::from_millis(30)
+            });
+        let mut drops = vec![];
+        let report = Streamer::run_with_drop_sink(streamer, algo, &mut model, |record, reason| {
+            drops.push((record, reason))
+        })
+        .unwrap();
+        assert_eq!(
+            vec![
+                (String::from("[-5.0]"), DropReason::Unparsable),
+                (String::from("[1e300]"), DropReason::Unparsable)
+            ],
+            drops
+        );
+        assert_eq!(2, report.latency.count());
+        assert_eq!(Some(Duration::from_millis(20)), report.latency.max());
+    }
+
+    #[test]
+    fn test_hierarchical_checkpoints() {
+        let config: AlgoConfig = "threshold=8".parse().unwrap();
+        let coarse_algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+        let fine_algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut coarse_model = Model::new(space::euclid_dist);
+        let mut fine_model = Model::new(space::euclid_dist);
+        let points = (0..25).map(|i| Ok(format!("[{}]", (i % 5) as f64 * 3.)));
+        let checkpoints = Rc::new(RefCell::new(vec![]));
+        let sink = Rc::clone(&checkpoints);
+        let write = move |checkpoint| {
+            sink.borrow_mut().push(checkpoint);
+            Ok(())
+        };
+        let streamer =
+            Streamer::new(points, |_| Ok(())).with_checkpoints(Cadence::Points(10), write);
+        Streamer::run_hierarchical(
+            streamer,
+            coarse_algo,
+            &mut coarse_model,
+            fine_algo,
+            &mut fine_model,
+        )
+        .unwrap();
+        let checkpoints = checkpoints.borrow();
+        assert_eq!(3, checkpoints.len());
+        let models = split_checkpoint(checkpoints.last().unwrap()).unwrap();
+        let names: Vec<&str> = models.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["coarse", "fine"], names);
+        for ((_, checkpoint), model) in models.iter().zip([&coarse_model, &fine_model]) {
+            let restored: Model<Vec<f64>> =
+                restore_checkpoint(checkpoint, space::euclid_dist, None).unwrap();
+            assert_eq!(model.iter_balls().count(), restored.iter_balls().count());
+            assert!(parse_checkpoint_time(checkpoint).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn test_latency() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let now = Rc::new(Cell::new(Duration::ZERO));
+        let tick = Rc::clone(&now);
+        let points = (0..10).map(move |i| {
+            tick.set(tick.get() + Duration::from_millis(10));
+            Ok(format!("[{}]", i as f64))
+        });
+        let mut emitted = 0;
+        let write = |_| {
+            emitted += 1;
+            Ok(())
+        };
+        let streamer = Streamer::new(points, write)
+            .with_emit_every(5)
+            .with_latency(Timestamps::Ingest, move || now.get());
+        let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+        assert_eq!(2, emitted);
+        let latency = report.latency;
+        assert_eq!(10, latency.count());
+        assert_eq!(Some(Duration::from_millis(40)), latency.max());
+        let p50 = latency.percentile(0.5).unwrap();
+        assert!(p50 >= Duration::from_millis(20) && p50 <= Duration::from_micros(20_600));
+        let p95 = latency.percentile(0.95).unwrap();
+        assert!(p95 >= Duration::from_millis(30));
+        assert_eq!(None, LatencyHistogram::default().percentile(0.5));
+    }
+
+    #[test]
+    fn test_seq_restart() {
+        use crate::{
+            message::Envelope,
+            serializer::{DeltaSerializer, EnvelopeSerializer},
+        };
+
+        // restores the model from the checkpoint of the previous run, then fits the points
+        fn run<S: ModelSerializer<Vec<f64>>>(
+            checkpoint: &str,
+            serializer: S,
+            points: Vec<f64>,
+        ) -> (Vec<Envelope<Value>>, String) {
+            let algo = Algo::new(space::euclid_dist, space::real_combine);
+            let balls = serializer::parse_columnar(checkpoint).unwrap();
+            let mut model = Model::load(space::euclid_dist, balls);
+            let checkpoints = Rc::new(RefCell::new(vec![]));
+            let sink = Rc::clone(&checkpoints);
+            let mut outputs = vec![];
+            let points = points.into_iter().map(|x| Ok(format!("[{:?}]", x)));
+            let streamer = Streamer::new(points, |s| {
+                outputs.push(s);
+                Ok(())
+            })
+            .with_serializer(serializer)
+            .with_checkpoints(Cadence::Points(2), move |c| {
+                sink.borrow_mut().push(c);
+                Ok(())
+            });
+            Streamer::run(streamer, algo, &mut model).unwrap();
+            let frames = outputs
+                .iter()
+                .map(|o| serde_json::from_str(o).unwrap())
+                .collect();
+            let checkpoint = checkpoints.borrow_mut().pop().unwrap();
+            (frames, checkpoint)
+        }
+
+        let empty = r#"{"centers":[],"radii":[],"weights":[]}"#;
+        assert_eq!(None, parse_checkpoint_seq(empty).unwrap());
+        let (first, checkpoint) = run(empty, EnvelopeSerializer::new(), vec![1., 2., 3.]);
+        let seq = parse_checkpoint_seq(&checkpoint).unwrap().unwrap();
+        assert_eq!(2, seq);
+        let resumed = EnvelopeSerializer::new().resume_after(seq);
+        let (second, _) = run(&checkpoint, resumed, vec![4., 5.]);
+        let frames: Vec<(u64, bool)> = first
+            .iter()
+            .chain(second.iter())
+            .map(|frame| match frame {
+                Envelope::Snapshot { seq, restarted, .. } => (*seq, *restarted),
+                _ => panic!("{:?}", frame),
+            })
+            .collect();
+        assert_eq!(
+            vec![(0, false), (1, false), (2, false), (3, true), (4, false)],
+            frames
+        );
+        let (_, checkpoint) = run(empty, DeltaSerializer::new(10), vec![1., 2., 3.]);
+        let seq = parse_checkpoint_seq(&checkpoint).unwrap().unwrap();
+        let resumed = DeltaSerializer::new(10).resume_after(seq);
+        let (frames, _) = run(&checkpoint, resumed, vec![4., 5.]);
+        assert!(matches!(
+            frames[0],
+            Envelope::Snapshot {
+                seq: 3,
+                restarted: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            frames[1],
+            Envelope::Delta {
+                seq: 4,
+                base_seq: 3,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_dedup_restart() {
+        let record = |uid: usize| {
+            let x = (uid % 4) as f64 * 3. + uid as f64 / 10.;
+            Ok(format!(r#"{{"uid":"{}","point":[{}]}}"#, uid, x))
+        };
+        // each run restores the model and the uids from the checkpoint of the previous one
+        let run = |model: &mut Model<Vec<f64>>, checkpoint: &str, uids: Vec<usize>| {
+            let algo = Algo::new(space::euclid_dist, space::real_combine);
+            let seen = parse_checkpoint_uids(checkpoint).unwrap();
+            let balls = serializer::parse_columnar(checkpoint).unwrap();
+            *model = Model::load(space::euclid_dist, balls);
+            let checkpoints = Rc::new(RefCell::new(vec![]));
+            let sink = Rc::clone(&checkpoints);
+            let streamer = Streamer::new(uids.into_iter().map(record), |_| Ok(()))
+                .with_dedup(Dedup::new(100).with_seen(seen))
+                .with_checkpoints(Cadence::Points(1000), move |c| {
+                    sink.borrow_mut().push(c);
+                    Ok(())
+                });
+            let report = Streamer::run_with_report(streamer, algo, model).unwrap();
+            let checkpoint = checkpoints.borrow_mut().pop().unwrap();
+            (report, checkpoint)
+        };
+        let empty = r#"{"centers":[],"radii":[],"weights":[]}"#;
+        let mut model = Model::new(space::euclid_dist);
+        let (report, checkpoint) = run(&mut model, empty, vec![0, 1, 2, 3, 4, 5, 2, 6]);
+        assert_eq!((7, 1), (report.admitted, report.duplicates));
+        let (report, _) = run(&mut model, &checkpoint, vec![4, 5, 6, 7, 8, 9, 7]);
+        assert_eq!((3, 4), (report.admitted, report.duplicates));
+        let mut expected = Model::new(space::euclid_dist);
+        let (_, checkpoint) = run(&mut expected, empty, (0..7).collect());
+        run(&mut expected, &checkpoint, (7..10).collect());
+        let balls = |model: &Model<Vec<f64>>| {
+            model
+                .iter_balls()
+                .map(|b| (b.center().clone(), b.radius(), b.weight()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(balls(&expected), balls(&model));
+    }
+
+    #[test]
+    fn test_dedup_without_uid() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = [
+            r#"{"uid":"a","point":[1.0]}"#,
+            r#"{"point":[2.0]}"#,
+            r#"{"id":"b","point":[3.0]}"#,
+            r#"{"point":[2.0]}"#,
+            r#"{"uid":"a","point":[1.0]}"#,
+        ];
+        let points = points.map(|p| Ok(String::from(p))).into_iter();
+        let streamer = Streamer::new(points, |_| Ok(())).with_dedup(Dedup::new(10));
+        let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+        assert_eq!(4, report.admitted);
+        assert_eq!(1, report.duplicates);
+    }
+
+    #[test]
+    fn test_dedup_capacity() {
+        let mut dedup = Dedup::new(2);
+        assert!(!dedup.check("a"));
+        assert!(!dedup.check("b"));
+        assert!(dedup.check("a"));
+        assert!(!dedup.check("c"));
+        assert_eq!(vec!["a", "c"], dedup.uids().collect::<Vec<_>>());
+        assert!(!dedup.check("b"));
+        for _ in 0..10 {
+            assert!(dedup.check("b"));
+        }
+        assert!(dedup.order.len() <= 4);
+        let mut dedup = Dedup::new(2).with_ttl(Duration::ZERO);
+        assert!(!dedup.check("a"));
+        assert!(!dedup.check("a"));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let coverage = |points: &[f64]| {
+            let algo = Algo::new(space::euclid_dist, space::real_combine);
+            // the frozen ball of radius 2 includes all the points without changing
+            let ball = Ball::new(vec![0.], 4., 10.).with_frozen(true);
+            let mut model = Model::load(space::euclid_dist, vec![ball]);
+            let points = points.iter().map(|x| Ok(format!("[{:?}]", x)));
+            let streamer = Streamer::new(points, |_| Ok(())).with_coverage(4, 0.3);
+            let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+            assert_eq!(1, model.iter_balls().count());
+            (report.coverage, report.low_coverage)
+        };
+        assert_eq!((None, false), coverage(&[]));
+        assert_eq!((Some(1.), false), coverage(&[2.]));
+        // the window is not full yet
+        assert_eq!((Some(0.), false), coverage(&[2.000001]));
+        assert_eq!((Some(0.5), false), coverage(&[-2., 1., 2.5, -3.]));
+        assert_eq!((Some(0.25), true), coverage(&[-2., 1., 2.5, -3., 2., -3.9]));
+        let ball = Ball::new(vec![0., 0.], 25., 1.);
+        assert!(ball.contains(&vec![3., 4.], space::euclid_dist));
+        assert!(!ball.contains(&vec![3., 4.001], space::euclid_dist));
+    }
+
+    #[test]
+    fn test_adaptive_emit() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = (0..200).map(|i| Ok(format!("[{}]", i % 10)));
+        // writes take 50 ms from the 10th to the 20th emission, on a clock advanced by the writes
+        let now = Rc::new(Cell::new(Duration::ZERO));
+        let clock = now.clone();
+        let mut emissions = 0;
+        let mut streamer = Streamer::new(points, |_| {
+            if (10..20).contains(&emissions) {
+                now.set(now.get() + Duration::from_millis(50));
+            }
+            emissions += 1;
+            Ok(())
+        })
+        .with_adaptive_emit(10., 8);
+        streamer.adaptive_emit.as_mut().unwrap().clock = Box::new(move || clock.get());
+        let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+        let factors: Vec<usize> = report.emit_every.iter().map(|(_, every)| *every).collect();
+        // the smoothed write time ramps up, then down once writes are fast again
+        assert_eq!(vec![2, 3, 4, 5, 4, 3, 2, 1], factors);
+        // the first 10 emissions cover one point each
+        assert_eq!(11, report.emit_every[0].0);
+    }
+
+    #[test]
+    fn test_batches() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let records = [
+            "[1.0]",
+            "[[2.0],[3.0],[4.0]]",
+            "[5.0]",
+            "[6.0]",
+            r#"[[7.0],"x",[8.0],{}]"#,
+        ];
+        let points = records.iter().map(|r| Ok(r.to_string()));
+        let mut outputs = 0;
+        let mut streamer = Streamer::new(points, |_| {
+            outputs += 1;
+            Ok(())
+        })
+        .with_emit_every(2)
+        .with_batches();
+        while streamer.fit_next(&algo, &mut model).unwrap() {}
+        streamer.final_emit(&model).unwrap();
+        assert_eq!(8, streamer.report.admitted);
+        let errors: Vec<String> = streamer
+            .report
+            .batch_errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(1, errors.len());
+        assert!(errors[0]
+            .starts_with("line 5: batch; element 1: line 5, offset 2: invalid type: string"));
+        assert!(errors[0].contains("; element 3: line 5, offset 0: invalid type: map"));
+        drop(streamer);
+        assert_eq!(3, outputs);
+        let points = [Ok(String::from(r#"[[1.0],["a"]]"#))].into_iter();
+        let streamer = Streamer::new(points, |_| Ok(())).with_batches();
+        let report = Streamer::run_with_report(streamer, algo, &mut model).unwrap();
+        assert_eq!(1, report.batch_errors.len());
+        assert_eq!(None, split_batch("[1.0,2.0]"));
+        assert_eq!(None, split_batch(r#"["a",1.0]"#));
+        assert_eq!(
+            Some(vec!["[1.0]".to_string(), r#"["a",2.0]"#.to_string()]),
+            split_batch(r#" [ [1.0], ["a",2.0]]"#)
+        );
+    }
+
+    #[test]
+    fn test_drop_sink() {
+        let combine = |p1: &Vec<f64>, w1: f64, p2: &Vec<f64>, w2: f64| {
+            assert!(p2[0] != 5., "unlucky");
+            space::real_combine(p1, w1, p2, w2)
+        };
+        let algo = Algo::new(space::euclid_dist, combine)
+            .with_valid_region(vec![0.], vec![10.])
+            .with_panic_guard();
+        let mut model = Model::new(space::euclid_dist);
+        let input = [
+            r#"{"uid":"a","point":[1.0]}"#,
+            r#"{"uid":"a","point":[2.0]}"#,
+            "[NaN]",
+            "[1.0,2.0]",
+            "[11.0]",
+            "[2.0,2.0,2.0,2.0,2.0,2.0,2.0,2.0]",
+            r#"[[3.0],"x",[12.0]]"#,
+            "[5.0]",
+            "[4.0]",
+        ]
+        .join("\n");
+        let points = lines(input.as_bytes(), 30);
+        let streamer = Streamer::new(points, |_| Ok(()))
+            .with_dedup(Dedup::new(10))
+            .with_batches();
+        let mut drops = vec![];
+        let report = Streamer::run_with_drop_sink(streamer, algo, &mut model, |record, reason| {
+            drops.push((record, reason))
+        })
+        .unwrap();
+        let expected = [
+            (r#"{"uid":"a","point":[2.0]}"#, DropReason::Duplicate),
+            ("[NaN]", DropReason::Unparsable),
+            ("[1.0,2.0]", DropReason::OutOfRegion),
+            ("[11.0]", DropReason::OutOfRegion),
+            ("", DropReason::TooLong),
+            (r#""x""#, DropReason::Unparsable),
+            ("[12.0]", DropReason::OutOfRegion),
+            ("[5.0]", DropReason::Panicked),
+        ]
+        .map(|(record, reason)| (String::from(record), reason));
+        assert_eq!(expected.to_vec(), drops);
+        // points outside of the valid region are not admitted
+        assert_eq!(3, report.admitted);
+        assert_eq!(1, report.duplicates);
+        assert_eq!(1, report.panicked);
+    }
+
+    #[test]
+    fn test_drift_per_emission() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine).with_drift();
+        let mut model = Model::new(space::euclid_dist);
+        let mut streamer =
+            Streamer::new(std::iter::empty::<Result<String, _>>(), |_| Ok(())).with_emit_every(2);
+        let mut drifts = vec![];
+        for record in ["[0.0]", "[1.0]", "[0.0]", "[1.0]", "[50.0]", "[50.0]"] {
+            streamer
+                .push(&algo, &mut model, record.to_string())
+                .unwrap();
+            drifts.push(algo.drift_since_last());
+        }
+        // the drift changes only when a model is emitted, every other point
+        assert_eq!(drifts[1], drifts[2]);
+        assert_eq!(drifts[3], drifts[4]);
+        assert!(drifts[5] > 10. * drifts[3], "{:?}", drifts);
+    }
+
+    #[test]
+    fn test_push() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let records = ["[1.0]", "[2.0]", "[x]", "[3.0]", "[4.0]", "[5.0]"];
+        let mut outputs = vec![];
+        let mut streamer = Streamer::new(std::iter::empty::<Result<String, _>>(), |output| {
+            outputs.push(output);
+            Ok(())
+        })
+        .with_emit_every(2);
+        let pushed: Vec<_> = records
+            .iter()
+            .map(|r| streamer.push(&algo, &mut model, r.to_string()).ok())
+            .collect();
+        assert_eq!(None, pushed[2]);
+        let returned: Vec<_> = pushed.into_iter().flatten().flatten().collect();
+        assert_eq!(5, streamer.report.admitted);
+        assert_eq!(6, streamer.line);
+        drop(streamer);
+        assert_eq!(outputs, returned);
+        assert_eq!(2, returned.len());
+        let mut expected = Model::new(space::euclid_dist);
+        let points = records.iter().map(|r| Ok(r.to_string()));
+        let mut streamer = Streamer::new(points, |_| Ok(())).with_emit_every(2);
+        while streamer.fit_next(&algo, &mut expected).unwrap_or(true) {}
+        assert_eq!(expected.iter_balls().count(), model.iter_balls().count());
+        assert_eq!(
+            expected
+                .iter_balls()
+                .map(|b| b.center.clone())
+                .collect::<Vec<_>>(),
+            model
+                .iter_balls()
+                .map(|b| b.center.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_file() {
+        let dir =
+            std::env::temp_dir().join(format!("fluent_data_checkpoint_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("model.json");
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![Ok(String::from("[1.0]")), Ok(String::from("[2.0]"))];
+        let streamer = Streamer::new(points.into_iter(), |_| Ok(()))
+            .with_checkpoints(Cadence::Points(1), checkpoint_file(&path));
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        let balls: Vec<Ball<Vec<f64>>> =
+            serializer::parse_columnar(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(vec![2.], *balls[0].center());
+        assert_eq!(
+            vec![path.clone()],
+            fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .collect::<Vec<_>>()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_panic_guard() {
+        let sentinel = vec![666., 666.];
+        let combine = move |p1: &Vec<f64>, w1: f64, p2: &Vec<f64>, w2: f64| {
+            assert!(p2 != &vec![666., 666.], "sentinel");
+            space::real_combine(p1, w1, p2, w2)
+        };
+        let mut rng = StdRng::seed_from_u64(3);
+        let normal = Normal::new(0., 1.).unwrap();
+        let points: Vec<Vec<f64>> = (0..100)
+            .map(|i| {
+                vec![
+                    (i % 2) as f64 * 10. + normal.sample(&mut rng),
+                    normal.sample(&mut rng),
+                ]
+            })
+            .collect();
+        let records = |points: &[Vec<f64>]| -> Vec<Result<String, Box<dyn Error>>> {
+            points
+                .iter()
+                .map(|p| Ok(serde_json::to_string(p).unwrap()))
+                .collect()
+        };
+
+        let mut with_sentinel = points.clone();
+        with_sentinel.insert(50, sentinel);
+        let algo = Algo::new(space::euclid_dist, combine).with_panic_guard();
+        let mut model = Model::new(space::euclid_dist);
+        let mut streamer = Streamer::new(records(&with_sentinel).into_iter(), |_| Ok(()));
+        while streamer.fit_next(&algo, &mut model).unwrap() {}
+        assert_eq!(1, streamer.report().panicked);
+        assert_eq!(100, streamer.report().admitted);
+
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut reference = Model::new(space::euclid_dist);
+        Streamer::run(
+            Streamer::new(records(&points).into_iter(), |_| Ok(())),
+            algo,
+            &mut reference,
+        )
+        .unwrap();
+        let balls = |model: &Model<Vec<f64>>| -> Vec<Ball<Vec<f64>>> {
+            model.iter_balls().map(|b| b.clone()).collect()
+        };
+        assert_eq!(balls(&reference), balls(&model));
+    }
+
+    #[test]
+    fn test_point_ids() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![
+            Ok(String::from(r#"["a",1.0]"#)),
+            Ok(String::from(r#"[ "b", 2.0]"#)),
+            Ok(String::from("[100.0]")),
+            Ok(String::from(r#"["c",100.5]"#)),
+        ];
+        let mut outputs = vec![];
+        let streamer = Streamer::new(points.into_iter(), |s| {
+            outputs.push(s);
+            Ok(())
+        })
+        .with_point_ids();
+        let mut assignments = vec![];
+        Streamer::run_with_hook(streamer, algo, &mut model, |a| assignments.push(a.clone()))
+            .unwrap();
+        let expected = vec![("a", 0), ("b", 0), ("2", 1), ("c", 1)];
+        let actual: Vec<(&str, usize)> = assignments
+            .iter()
+            .map(|a| (a.id.as_str(), a.ball))
+            .collect();
+        assert_eq!(expected, actual);
+        let emitted: Value = serde_json::from_str(&outputs[3]).unwrap();
+        assert_eq!(json!({"id": "c", "ball": 1}), emitted["assignment"]);
+    }
+
+    #[test]
+    fn test_point_ids_parse_error() {
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let points = vec![Ok(String::from(r#"["a",1.0,"x"]"#))];
+        let streamer = Streamer::new(points.into_iter(), |_| Ok(())).with_point_ids();
+        let error = Streamer::run(streamer, algo, &mut model).unwrap_err();
+        match error.downcast_ref::<StreamError>() {
+            Some(StreamError::ParseError { line, field, .. }) => {
+                assert_eq!(1, *line);
+                assert_eq!(Some(String::from("[2]")), *field);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_writer() {
+        let points = (0..20).map(|i| Ok(format!("[{}.0,{}.0]", i % 4, i % 3)));
+        let mut frames = vec![];
+        let streamer = Streamer::new(
+            points,
+            gzip_writer(|frame| {
+                frames.push(frame);
+                Ok(())
+            }),
+        );
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        Streamer::run(streamer, algo, &mut model).unwrap();
+        assert_eq!(20, frames.len());
+        let last = gunzip_frame(frames.last().unwrap()).unwrap();
+        let mut expected = String::new();
+        JsonSerializer.serialize(&model, &mut expected).unwrap();
+        assert_eq!(expected, last);
+        // small models are written plain, larger ones compressed, and no frame is longer than its model
+        assert!(frames[0].starts_with('['));
+        let balls = (0..10)
+            .map(|i| Ball::new(vec![i as f64, 0.], 1., 1.))
+            .collect();
+        let mut large = String::new();
+        JsonSerializer
+            .serialize(&Model::load(space::euclid_dist, balls), &mut large)
+            .unwrap();
+        gzip_writer(|frame| {
+            frames.push(frame);
+            Ok(())
+        })(large.clone())
+        .unwrap();
+        assert!(frames.last().unwrap().starts_with(GZIP_PREFIX));
+        assert_eq!(large, gunzip_frame(frames.last().unwrap()).unwrap());
+        assert!(frames
+            .iter()
+            .all(|f| f.len() <= gunzip_frame(f).unwrap().len()));
+        assert_eq!("[]", gunzip_frame("[]").unwrap());
+        assert!(gunzip_frame("gzip:AAAA").is_err());
     }
 }
@@ -0,0 +1,49 @@
+#![cfg(feature = "bench-utils")]
+
+use std::time::{Duration, Instant};
+
+use fluent_data::{
+    data::{self, FitScenario, Sizes},
+    serializer::{ColumnarJsonSerializer, JsonSerializer, ModelSerializer, MsgpackSerializer},
+    space,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn test_scenarios() {
+    let start = Instant::now();
+    let sizes = Sizes::ci();
+    for &balls in sizes.ball_counts.iter() {
+        let scenario = new_scenario(balls, 2, sizes.points);
+        assert_eq!(balls, scenario.fit(&mut scenario.new_model()));
+        let indexed = new_scenario(balls, 2, sizes.points).with_budget(8);
+        let mut model = indexed.new_model();
+        indexed.fit(&mut model);
+        assert!(model.truncated_searches() > 0);
+    }
+    for &dim in sizes.dims.iter() {
+        let scenario = new_scenario(sizes.sweep_balls, dim, sizes.points);
+        assert_eq!(sizes.sweep_balls, scenario.fit(&mut scenario.new_model()));
+    }
+    let model = data::model_with_balls(&data::centers(sizes.sweep_balls, 2, 42));
+    let mut output = String::new();
+    JsonSerializer.serialize(&model, &mut output).unwrap();
+    ColumnarJsonSerializer
+        .serialize(&model, &mut output)
+        .unwrap();
+    MsgpackSerializer.serialize(&model, &mut output).unwrap();
+    assert!(!output.is_empty());
+    assert!(start.elapsed() < TIMEOUT);
+}
+
+fn new_scenario(balls: usize, dim: usize, points: usize) -> FitScenario {
+    FitScenario::new(
+        space::euclid_dist,
+        space::real_combine,
+        balls,
+        dim,
+        points,
+        42,
+    )
+}
@@ -0,0 +1,185 @@
+//! The public API on models of zero, one and two balls.
+//! A model of one ball is built from a single point, thus its radius is not known yet, or loaded.
+
+use fluent_data::{
+    algorithm::{self, FitTarget},
+    client::DeltaDecoder,
+    model::{self, Ball},
+    neighborhood::Neighborhood,
+    serializer::{
+        ColumnarJsonSerializer, CsvSerializer, DeltaSerializer, EnvelopeSerializer,
+        GeoJsonSerializer, HeatmapSerializer, JsonSerializer, ModelSerializer, MsgpackSerializer,
+        PrettyJsonSerializer,
+    },
+    space, Algo, Model,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Models fitted from 0, 1, 3 and 13 points, which have 0, 1, 1 and 2 balls,
+/// and models loaded with 0, 1 and 2 balls.
+fn build_models() -> Vec<(usize, Model<Vec<f64>>)> {
+    let algo = Algo::new(space::euclid_dist, space::real_combine);
+    let fitted = [0, 1, 3, 13].map(|count| {
+        let mut model = Model::new(space::euclid_dist);
+        for i in 0..count {
+            let x = if i < 3 { i as f64 } else { 100. + i as f64 };
+            algo.fit(&mut model, vec![x, x]);
+        }
+        model
+    });
+    let loaded = [0, 1, 2].map(|count| {
+        let balls = (0..count)
+            .map(|i| Ball::new(vec![10. * i as f64, 0.], 4., 1.))
+            .collect();
+        Model::load(space::euclid_dist, balls)
+    });
+    fitted
+        .into_iter()
+        .chain(loaded)
+        .map(|model| (model.iter_balls().count(), model))
+        .collect()
+}
+
+#[test]
+fn test_model_sizes() {
+    let sizes: Vec<usize> = build_models().iter().map(|(size, _)| *size).collect();
+    assert_eq!(vec![0, 1, 1, 2, 0, 1, 2], sizes);
+}
+
+#[test]
+fn test_predict() {
+    let point = vec![1., 1.];
+    for (size, model) in build_models() {
+        match (size, model.predict(&point)) {
+            (0, Neighborhood::None) | (1, Neighborhood::One(_)) | (2, Neighborhood::Two(_, _)) => {}
+            (size, _) => panic!("unexpected neighborhood for {} balls", size),
+        }
+        assert_eq!(size > 0, model.is_within_radius(&point).is_some());
+        assert_eq!(size > 0, model.anomaly_score(&point).is_some());
+        let probabilities = model.soft_predict(&point, 1.);
+        assert_eq!(size, probabilities.len());
+        if size > 0 {
+            assert!((probabilities.iter().map(|(_, p)| p).sum::<f64>() - 1.).abs() < 1E-9);
+        }
+        assert_eq!(size.min(1), model.soft_predict_nearest(&point, 1., 1).len());
+        assert!(model.soft_predict_nearest(&point, 1., 0).is_empty());
+    }
+}
+
+#[test]
+fn test_queries() {
+    for (size, model) in build_models() {
+        assert_eq!(size, model.separation_profile().len());
+        assert_eq!(size, model.recent_weight_share().len());
+        assert_eq!(size, model.parents_of(&model).len());
+        assert_eq!(size > 0, model.overlapping_with(0, 1.).is_ok());
+        assert!(model.overlapping_with(size, 1.).is_err());
+        assert_eq!(size > 0, model.freeze_ball(0).is_ok());
+        assert_eq!(size > 0, model.unfreeze_ball(0).is_ok());
+        assert!(model.last_fitted_ball().is_none_or(|index| index < size));
+        model.fingerprint();
+        let rebuilt = model.rebuild_with(space::euclid_dist, None);
+        assert_eq!(size, rebuilt.iter_balls().count());
+    }
+}
+
+#[test]
+fn test_model_functions() {
+    let bounds = [(-10., 10.), (-10., 10.)];
+    let mut rng = StdRng::seed_from_u64(0);
+    for (size, model) in build_models() {
+        assert!(model::density_at(&model, &[1., 1.]).is_finite());
+        assert_eq!(4, model::to_heatmap(&model, bounds, 4).len());
+        assert!(model::to_svg(&model, 10, 10, bounds).is_ok());
+        let covered = model::covered_measure(&model, 10, &mut rng);
+        assert!(covered.is_finite() && covered >= 0.);
+        let importance = model::feature_importance(&model);
+        assert!(importance.iter().all(|i| i.is_finite()));
+        let weighted = model.iter_balls().filter(|b| b.weight() > 0.).count();
+        assert!(weighted <= size);
+        assert_eq!(weighted, model::to_weighted_points(&model).len());
+        let dendrogram = model::dendrogram(&model, space::euclid_dist);
+        assert_eq!(weighted > 0, dendrogram.is_some());
+    }
+}
+
+#[test]
+fn test_merge() {
+    for index in 0..build_models().len() {
+        for (other_size, other) in build_models() {
+            let (size, mut model) = build_models().remove(index);
+            model.merge(&other);
+            assert_eq!(size + other_size, model.iter_balls().count());
+        }
+    }
+    let algo = Algo::new(space::euclid_dist, space::real_combine);
+    for (size, mut model) in build_models() {
+        algo.merge_overlapping(&mut model);
+        assert!(model.iter_balls().count() <= size);
+    }
+}
+
+#[test]
+fn test_fit() {
+    let algo = Algo::new(space::euclid_dist, space::real_combine);
+    for (size, mut model) in build_models() {
+        let preview = algo.preview_fit(&model, &vec![1., 1.]);
+        assert_eq!(size == 0, preview.target == FitTarget::NewBall);
+        algo.fit(&mut model, vec![1., 1.]);
+        algo.fit(&mut model, vec![1., 1.]);
+        assert!(model.iter_balls().count() >= size.max(1));
+        algo.fast_forward(&mut model, 1_000_000);
+        assert_eq!(0, model.iter_balls().count());
+    }
+    let holdout = vec![vec![0., 0.]];
+    algorithm::tune_split_threshold(
+        &[],
+        &holdout,
+        &[4.],
+        space::euclid_dist,
+        space::real_combine,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_serializers() {
+    let serializers: Vec<Box<dyn ModelSerializer<Vec<f64>>>> = vec![
+        Box::new(JsonSerializer),
+        Box::new(PrettyJsonSerializer),
+        Box::new(ColumnarJsonSerializer),
+        Box::new(CsvSerializer),
+        Box::new(GeoJsonSerializer),
+        Box::new(MsgpackSerializer),
+        Box::new(HeatmapSerializer {
+            bounds: [(-10., 10.), (-10., 10.)],
+            resolution: 4,
+        }),
+        Box::new(EnvelopeSerializer::new()),
+        Box::new(EnvelopeSerializer::legacy()),
+    ];
+    for (size, model) in build_models() {
+        for serializer in serializers.iter() {
+            let mut output = String::new();
+            serializer.serialize(&model, &mut output).unwrap();
+            assert!(!output.contains("null") || size > 0);
+        }
+        let mut output = String::new();
+        JsonSerializer.serialize(&model, &mut output).unwrap();
+        if size == 0 {
+            assert_eq!("[]", output);
+        }
+    }
+}
+
+#[test]
+fn test_deltas() {
+    let serializer = DeltaSerializer::new(10);
+    let mut client = DeltaDecoder::new();
+    for (size, model) in build_models() {
+        let mut output = String::new();
+        serializer.serialize(&model, &mut output).unwrap();
+        let balls = client.decode(&output).unwrap().unwrap();
+        assert_eq!(size, balls.len());
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    env, fs,
+    net::TcpStream,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use serde_json::Value;
+use tungstenite::{connect, stream::MaybeTlsStream, Message, WebSocket};
+use url::Url;
+
+const PORT: &str = "9002";
+
+#[test]
+fn test_input_then_service() {
+    let input = env::temp_dir().join(format!("fluent_data_input_{}.txt", std::process::id()));
+    fs::write(&input, get_clusters()).unwrap();
+    let mut service = Command::new(env!("CARGO_BIN_EXE_fluent_data"))
+        .arg("--input")
+        .arg(&input)
+        .arg("--service")
+        .env("PORT", PORT)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut models_socket = connect_retry("ws/models");
+    let mut points_socket = connect_retry("ws/points");
+    points_socket
+        .write_message(Message::Text(String::from("[50.0]")))
+        .unwrap();
+    let model = models_socket.read_message().unwrap().into_text().unwrap();
+    service.kill().unwrap();
+    service.wait().unwrap();
+    fs::remove_file(&input).unwrap();
+    let balls: Vec<Value> = serde_json::from_str(&model).unwrap();
+    let has_ball = |x: f64| {
+        balls
+            .iter()
+            .any(|b| (b["center"][0].as_f64().unwrap() - x).abs() < 5.)
+    };
+    assert!(has_ball(-50.));
+    assert!(has_ball(50.));
+}
+
+/// Two clusters of points around -50 and 50, one point per line.
+fn get_clusters() -> String {
+    [-50., 50.]
+        .iter()
+        .flat_map(|c| (0..100).map(move |i| format!("[{}]\n", c + ((i * 7) % 10) as f64 / 10.)))
+        .collect()
+}
+
+/// Connects to the given endpoint, waiting for the service to start.
+fn connect_retry(endpoint: &str) -> WebSocket<MaybeTlsStream<TcpStream>> {
+    let url = Url::parse(&format!("ws://localhost:{}/{}", PORT, endpoint)).unwrap();
+    for _ in 0..100 {
+        if let Ok((socket, _)) = connect(url.clone()) {
+            return socket;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("Can't connect")
+}
@@ -0,0 +1,78 @@
+use std::{env, net::TcpStream, thread, time::Duration};
+
+use fluent_data::{service, space, Algo, Model, Streamer};
+use serde_json::Value;
+use tungstenite::{connect, stream::MaybeTlsStream, Message, WebSocket};
+use url::Url;
+
+const PORT: &str = "9003";
+
+#[test]
+fn test_malformed_point() {
+    thread::spawn(start);
+    let mut models_socket = connect_retry("ws/models");
+    let mut points_socket = connect_retry("ws/points");
+    send(&mut points_socket, "[1.0,1.0]");
+    read(&mut models_socket);
+    send(&mut points_socket, "oops");
+    let error: Value = serde_json::from_str(&read(&mut points_socket)).unwrap();
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("line 2, offset 0"));
+    send(&mut points_socket, "{}");
+    send(&mut points_socket, r#""x""#);
+    let error: Value = serde_json::from_str(&read(&mut points_socket)).unwrap();
+    assert!(error["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("line 4, offset 0"));
+    send(&mut points_socket, "[3.0,3.0]");
+    assert_eq!(
+        expected(&["[1.0,1.0]", "[3.0,3.0]"]),
+        read(&mut models_socket)
+    );
+}
+
+fn start() {
+    env::set_var("PORT", PORT);
+    let algo = Algo::new(space::euclid_dist, space::real_combine);
+    let mut model = Model::new(space::euclid_dist);
+    let (points, write) = service::backend();
+    let streamer = Streamer::new(points, write);
+    Streamer::run_with_drop_sink(streamer, algo, &mut model, |_, _| {}).unwrap();
+}
+
+/// The last model fitted from the given points.
+fn expected(points: &[&str]) -> String {
+    let algo = Algo::new(space::euclid_dist, space::real_combine);
+    let mut model = Model::new(space::euclid_dist);
+    let points = points.iter().map(|p| Ok(String::from(*p)));
+    let mut last = String::new();
+    let streamer = Streamer::new(points, |m| {
+        last = m;
+        Ok(())
+    });
+    Streamer::run(streamer, algo, &mut model).unwrap();
+    last
+}
+
+fn send(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, text: &str) {
+    socket.write_message(Message::Text(text.into())).unwrap();
+}
+
+fn read(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> String {
+    socket.read_message().unwrap().into_text().unwrap()
+}
+
+/// Connects to the given endpoint, waiting for the service to start.
+fn connect_retry(endpoint: &str) -> WebSocket<MaybeTlsStream<TcpStream>> {
+    let url = Url::parse(&format!("ws://localhost:{}/{}", PORT, endpoint)).unwrap();
+    for _ in 0..100 {
+        if let Ok((socket, _)) = connect(url.clone()) {
+            return socket;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("Can't connect")
+}
@@ -0,0 +1,70 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use fluent_data::{
+    algorithm::AlgoConfig,
+    service, space,
+    streamer::{Pipeline, Supervisor},
+    Algo, Model, Streamer,
+};
+use tungstenite::{connect, stream::MaybeTlsStream, Message, WebSocket};
+use url::Url;
+
+#[test]
+fn test_routed_pipelines() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let routes = service::backend_routed(listener, &["north", "south"]);
+    let mut supervisor = Supervisor::new(AlgoConfig::default(), routes.len());
+    for (name, route) in routes {
+        supervisor.add(&name, move |config| {
+            let algo = Algo::new(space::euclid_dist, space::real_combine).with_config(config);
+            let (points, write) = route.channels();
+            Pipeline::new(
+                algo,
+                Model::new(space::euclid_dist),
+                Streamer::new(points, write),
+            )
+        });
+    }
+    std::thread::spawn(move || supervisor.run());
+    let mut north_models = connect_to(addr, "ws/north/models");
+    let mut south_models = connect_to(addr, "ws/south/models");
+    let mut north_points = connect_to(addr, "ws/north/points");
+    let mut south_points = connect_to(addr, "ws/south/points");
+    // wait for the model peers to be registered
+    send(&mut north_points, "[1.0]");
+    read(&mut north_models);
+    send(&mut south_points, "[-1.0]");
+    read(&mut south_models);
+    send(&mut north_points, "[2.0]");
+    assert_eq!(expected(&["[1.0]", "[2.0]"]), read(&mut north_models));
+    send(&mut south_points, "[-2.0]");
+    assert_eq!(expected(&["[-1.0]", "[-2.0]"]), read(&mut south_models));
+}
+
+/// The last model fitted from the given points.
+fn expected(points: &[&str]) -> String {
+    let algo = Algo::new(space::euclid_dist, space::real_combine);
+    let mut model = Model::new(space::euclid_dist);
+    let points = points.iter().map(|p| Ok(String::from(*p)));
+    let mut last = String::new();
+    let streamer = Streamer::new(points, |m| {
+        last = m;
+        Ok(())
+    });
+    Streamer::run(streamer, algo, &mut model).unwrap();
+    last
+}
+
+fn connect_to(addr: SocketAddr, endpoint: &str) -> WebSocket<MaybeTlsStream<TcpStream>> {
+    let url = Url::parse(&format!("ws://{}/{}", addr, endpoint)).unwrap();
+    connect(url).unwrap().0
+}
+
+fn send(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, text: &str) {
+    socket.write_message(Message::Text(text.into())).unwrap();
+}
+
+fn read(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> String {
+    socket.read_message().unwrap().into_text().unwrap()
+}
@@ -1,5 +1,9 @@
 use fluent_data::{service, space, Algo, Model, Streamer};
-use std::thread;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
 use tungstenite::{connect, Message};
 use url::Url;
 
@@ -47,3 +51,66 @@ fn collect() -> Vec<String> {
     models_socket.close(None).unwrap();
     results
 }
+
+#[test]
+fn test_sse() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = &listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (points, write) = service::backend_sse_on(listener);
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        Streamer::run(Streamer::new(points, write), algo, &mut model).unwrap();
+    });
+    // a client that sends nothing does not hold up the others
+    let _silent = TcpStream::connect(addr).unwrap();
+    let mut events = TcpStream::connect(addr).unwrap();
+    events
+        .write_all(b"GET /models HTTP/1.1\r\nHost: localhost\r\nAccept: text/event-stream\r\n\r\n")
+        .unwrap();
+    let mut events = BufReader::new(events);
+    let mut headers = String::new();
+    while !headers.ends_with("\r\n\r\n") {
+        events.read_line(&mut headers).unwrap();
+    }
+    assert!(headers.starts_with("HTTP/1.1 200 OK"));
+    assert!(headers.contains("Content-Type: text/event-stream"));
+    assert!(headers.contains("Access-Control-Allow-Origin: *"));
+
+    assert!(post(addr, "[2.5,-1.0]\n").starts_with("HTTP/1.1 202"));
+    let mut event = String::new();
+    events.read_line(&mut event).unwrap();
+    assert_eq!(
+        "data: [{\"center\":[2.5,-1.0],\"radius\":null,\"weight\":0.0}]\n",
+        event
+    );
+    event.clear();
+    events.read_line(&mut event).unwrap();
+    assert_eq!("\n", event);
+
+    let response = post(addr, "oops\n");
+    assert!(response.starts_with("HTTP/1.1 400"));
+    assert!(response.contains(r#""type":"error""#));
+
+    let too_long = "POST /points HTTP/1.1\r\nContent-Length: 2000000\r\n\r\n";
+    assert!(request(addr, too_long).starts_with("HTTP/1.1 413"));
+    let too_many = format!("GET /models HTTP/1.1\r\nX-Padding: {}", "a".repeat(9000));
+    assert!(request(addr, &too_many).starts_with("HTTP/1.1 431"));
+}
+
+fn post(addr: &str, body: &str) -> String {
+    let request = format!(
+        "POST /points HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    self::request(addr, &request)
+}
+
+fn request(addr: &str, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
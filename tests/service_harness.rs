@@ -0,0 +1,179 @@
+#![cfg(feature = "testing")]
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use fluent_data::{
+    algorithm::AlgoConfig,
+    service::{self, TestServiceHandle, TestServiceOptions},
+};
+use serde_json::{json, Value};
+use tungstenite::{connect, Message};
+use url::Url;
+
+#[path = "./utilities.rs"]
+mod utilities;
+use utilities::{assert_results, get_point_iter};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[test]
+fn test_injected_points() {
+    let service = service::spawn_for_tests(TestServiceOptions::default());
+    for point in get_point_iter(10000) {
+        service.inject(&point.unwrap());
+    }
+    assert_results(service.await_emissions(10000, TIMEOUT).unwrap());
+}
+
+#[test]
+fn test_websockets() {
+    let service = service::spawn_for_tests(TestServiceOptions::default());
+    let (mut models_socket, _) = connect(Url::parse(&service.models_url()).unwrap()).unwrap();
+    let (mut points_socket, _) = connect(Url::parse(&service.points_url()).unwrap()).unwrap();
+    points_socket
+        .write_message(Message::Text("[1.0,1.0]".into()))
+        .unwrap();
+    let model = models_socket.read_message().unwrap().into_text().unwrap();
+    assert_eq!(
+        r#"[{"center":[1.0,1.0],"radius":null,"weight":0.0}]"#,
+        model
+    );
+    assert_eq!(vec![model], service.await_emissions(1, TIMEOUT).unwrap());
+}
+
+#[test]
+fn test_error_frame() {
+    let service = service::spawn_for_tests(TestServiceOptions::default());
+    let (mut points_socket, _) = connect(Url::parse(&service.points_url()).unwrap()).unwrap();
+    points_socket
+        .write_message(Message::Text("oops".into()))
+        .unwrap();
+    let frame = points_socket.read_message().unwrap().into_text().unwrap();
+    let frame: Value = serde_json::from_str(&frame).unwrap();
+    assert_eq!("error", frame["type"]);
+    points_socket
+        .write_message(Message::Text("[2.0]".into()))
+        .unwrap();
+    let models = service.await_emissions(1, TIMEOUT).unwrap();
+    assert_eq!(
+        r#"[{"center":[2.0],"radius":null,"weight":0.0}]"#,
+        models[0]
+    );
+}
+
+#[test]
+fn test_batches() {
+    let service = service::spawn_for_tests(TestServiceOptions::default());
+    let (mut points_socket, _) = connect(Url::parse(&service.points_url()).unwrap()).unwrap();
+    for message in ["[1.0]", r#"[[2.0],"x",[3.0],1]"#, "[[4.0],[5.0]]", "[6.0]"] {
+        points_socket
+            .write_message(Message::Text(message.into()))
+            .unwrap();
+    }
+    let frame = points_socket.read_message().unwrap().into_text().unwrap();
+    let frame: Value = serde_json::from_str(&frame).unwrap();
+    let error = frame["error"].as_str().unwrap();
+    assert!(error.starts_with("line 2: batch; element 1: "));
+    assert!(error.contains("; element 3: "));
+    service.await_emissions(4, TIMEOUT).unwrap();
+    assert!(service
+        .await_emissions(1, Duration::from_millis(200))
+        .is_err());
+    // an element that is an array but not a point is skipped by the streamer, which keeps running
+    for message in [r#"[[7.0],["a"]]"#, "[8.0]"] {
+        points_socket
+            .write_message(Message::Text(message.into()))
+            .unwrap();
+    }
+    service.await_emissions(2, TIMEOUT).unwrap();
+}
+
+#[test]
+fn test_history() {
+    let options = TestServiceOptions {
+        config: "decay=0.5".parse::<AlgoConfig>().unwrap(),
+        history_capacity: 2,
+    };
+    let service = service::spawn_for_tests(options);
+    for point in ["[1.0]", "[2.0]", "[3.0]"] {
+        service.inject(point);
+    }
+    service.await_emissions(3, TIMEOUT).unwrap();
+    let mut stream = TcpStream::connect(service.addr()).unwrap();
+    let path = service
+        .history_url()
+        .replace(&format!("http://{}", service.addr()), "");
+    write!(stream, "GET {}?from=2 HTTP/1.1\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK"));
+    let emissions: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(1, emissions.as_array().unwrap().len());
+    assert_eq!(2, emissions[0]["seq"]);
+}
+
+#[test]
+fn test_shutdown() {
+    let service = service::spawn_for_tests(TestServiceOptions::default());
+    let addr = service.addr();
+    let (mut points_socket, _) = connect(Url::parse(&service.points_url()).unwrap()).unwrap();
+    points_socket
+        .write_message(Message::Text("[1.0]".into()))
+        .unwrap();
+    service.await_emissions(1, TIMEOUT).unwrap();
+    drop(service);
+    assert!(points_socket.read_message().is_err());
+    TcpListener::bind(addr).unwrap();
+}
+
+#[test]
+fn test_config_update() {
+    let service = service::spawn_for_tests(TestServiceOptions::default());
+    let (mut models_socket, _) = connect(Url::parse(&service.models_url()).unwrap()).unwrap();
+    service.inject("[1.0]");
+    let model = models_socket.read_message().unwrap().into_text().unwrap();
+    assert_eq!(r#"[{"center":[1.0],"radius":null,"weight":0.0}]"#, model);
+    let (head, update) = post_config(&service, r#"{"emit_every":2,"port":80}"#);
+    assert!(head.starts_with("HTTP/1.1 400 Bad Request"));
+    assert_eq!(json!({}), update["applied"]);
+    assert_eq!("requires a restart", update["rejected"]["port"]);
+    let (head, update) = post_config(&service, r#"{"emit_every":2}"#);
+    assert!(head.starts_with("HTTP/1.1 200 OK"));
+    assert_eq!(2, update["applied"]["emit_every"]);
+    for point in ["[2.0]", "[3.0]", "[4.0]", "[5.0]"] {
+        service.inject(point);
+    }
+    let emitted = service.await_emissions(3, TIMEOUT).unwrap();
+    for expected in &emitted[1..] {
+        let model = models_socket.read_message().unwrap().into_text().unwrap();
+        assert_eq!(expected, &model);
+    }
+    assert!(service
+        .await_emissions(1, Duration::from_millis(100))
+        .is_err());
+}
+
+/// Posts settings to the admin endpoint of the service, returns the response head and body.
+fn post_config(service: &TestServiceHandle, body: &str) -> (String, Value) {
+    let mut stream = TcpStream::connect(service.addr()).unwrap();
+    let path = service
+        .config_url()
+        .replace(&format!("http://{}", service.addr()), "");
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.to_string(), serde_json::from_str(body).unwrap())
+}
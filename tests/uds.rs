@@ -0,0 +1,71 @@
+#![cfg(unix)]
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    process,
+    sync::mpsc,
+    thread,
+};
+
+use fluent_data::{service, space, Algo, Model, Streamer};
+use serde_json::Value;
+
+#[test]
+fn test_round_trip() {
+    let path = env::temp_dir().join(format!("fluent_data_{}.sock", process::id()));
+    let (ready, bound) = mpsc::channel();
+    let socket = path.clone();
+    thread::spawn(move || {
+        let (points, write) = service::backend_uds(&socket).unwrap();
+        ready.send(()).unwrap();
+        let algo = Algo::new(space::euclid_dist, space::real_combine);
+        let mut model = Model::new(space::euclid_dist);
+        let streamer = Streamer::new(points, write);
+        Streamer::run_with_drop_sink(streamer, algo, &mut model, |_, _| {}).unwrap();
+    });
+    bound.recv().unwrap();
+    let mut stream = UnixStream::connect(&path).unwrap();
+    let mut models = BufReader::new(stream.try_clone().unwrap()).lines();
+    stream.write_all(b"oops\n").unwrap();
+    let frame: Value = serde_json::from_str(&models.next().unwrap().unwrap()).unwrap();
+    assert_eq!("error", frame["type"]);
+    stream.write_all(b"{}\n[1.0,1.0]\n").unwrap();
+    assert_eq!(
+        r#"[{"center":[1.0,1.0],"radius":null,"weight":0.0}]"#,
+        models.next().unwrap().unwrap()
+    );
+    // the streamer never stops, thus the writer never removes the socket file
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_socket_file_removed() {
+    let path = env::temp_dir().join(format!("fluent_data_{}_removed.sock", process::id()));
+    let (_points, write) = service::backend_uds(&path).unwrap();
+    assert!(path.exists());
+    assert!(service::backend_uds(&path).is_err());
+    drop(write);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_stale_socket_removed() {
+    let path = env::temp_dir().join(format!("fluent_data_{}_stale.sock", process::id()));
+    // a killed process leaves its socket file behind
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+    let (_points, write) = service::backend_uds(&path).unwrap();
+    drop(write);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_regular_file_kept() {
+    let path = env::temp_dir().join(format!("fluent_data_{}_regular.sock", process::id()));
+    fs::write(&path, "data").unwrap();
+    assert!(service::backend_uds(&path).is_err());
+    assert_eq!("data", fs::read_to_string(&path).unwrap());
+    fs::remove_file(&path).unwrap();
+}